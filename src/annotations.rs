@@ -0,0 +1,64 @@
+//! Notes attached to differences, kept in a JSON sidecar file so they survive
+//! between sessions. A note is keyed by the entry's path and change kind; one
+//! whose entry no longer differs that way is orphaned and listed separately.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::atomic;
+use crate::report::ChangeKind;
+
+/// Where notes are kept when `--annotations` isn't given: next to the inputs.
+pub const DEFAULT_FILE: &str = "diffrs-notes.json";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Annotation {
+    pub path: String,
+    pub kind: String,
+    pub note: String,
+}
+
+#[derive(Clone, Default)]
+pub struct Annotations {
+    pub file: PathBuf,
+    notes: Vec<Annotation>,
+}
+
+impl Annotations {
+    /// Reads `file`; a missing file is an empty set of notes.
+    pub fn load(file: PathBuf) -> Result<Self> {
+        let notes = match std::fs::read_to_string(&file) {
+            Ok(contents) => serde_json::from_str(&contents).with_context(|| file.display().to_string())?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err).with_context(|| file.display().to_string()),
+        };
+        Ok(Self { file, notes })
+    }
+
+    pub fn get(&self, path: &str, kind: ChangeKind) -> Option<&str> {
+        self.notes.iter().find(|a| a.path == path && a.kind == kind.name()).map(|a| a.note.as_str())
+    }
+
+    /// Sets or, with an empty `note`, removes a note, and saves the file.
+    pub fn set(&mut self, path: &str, kind: ChangeKind, note: &str) -> Result<()> {
+        self.notes.retain(|a| !(a.path == path && a.kind == kind.name()));
+        if !note.trim().is_empty() {
+            self.notes.push(Annotation { path: path.to_string(), kind: kind.name().to_string(), note: note.trim().to_string() });
+        }
+        let contents = serde_json::to_string_pretty(&self.notes)?;
+        atomic::write(&self.file, contents + "\n")
+    }
+
+    /// Notes whose path and kind aren't among `live` differences.
+    pub fn orphaned(&self, live: &[(String, ChangeKind)]) -> Vec<&Annotation> {
+        self.notes
+            .iter()
+            .filter(|a| !live.iter().any(|(path, kind)| *path == a.path && kind.name() == a.kind))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+}
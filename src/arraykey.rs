@@ -0,0 +1,244 @@
+//! `--array [<array path>=]<strategy>`: how the elements of arrays are
+//! paired. `ordered` aligns them in order, `set` pairs them by their whole
+//! value, comparing the arrays as unordered collections, `multiset` counts
+//! how often each value occurs on each side and shows the difference in
+//! those counts, one row per value (`+ "admin" (×1)`), and `key:<field>`
+//! pairs arrays of objects by a field's value, for arrays whose order
+//! differs between the sides. Without a path the strategy applies to every
+//! array; `--array users.*.roles=key:name` (or `users[].roles[]=key:name`)
+//! to one, the most specific pattern winning. `--array-key [<path>=]<field>`
+//! is `--array [<path>=]key:<field>`.
+//!
+//! `--unordered-arrays` (`S`) makes `set` the strategy of every array no
+//! rule covers.
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::ignore;
+
+#[derive(Clone, PartialEq)]
+pub enum Strategy {
+    Ordered,
+    Set,
+    /// Elements by value with their number of occurrences.
+    Multiset,
+    /// Pairs elements by the dotted field within each.
+    Key(String),
+}
+
+impl Strategy {
+    /// Whether arrays with the same elements in another order are equal.
+    pub fn unordered(&self) -> bool {
+        matches!(self, Self::Set | Self::Multiset)
+    }
+
+    fn parse(strategy: &str) -> Result<Self> {
+        Ok(match strategy.trim() {
+            "ordered" => Self::Ordered,
+            "set" => Self::Set,
+            "multiset" => Self::Multiset,
+            key => match key.strip_prefix("key:").map(str::trim) {
+                Some(field) if !field.is_empty() && !field.split('.').any(str::is_empty) => Self::Key(field.to_string()),
+                Some(_) => bail!("'{strategy}' doesn't name a field"),
+                None => bail!("unknown array strategy '{strategy}' (expected ordered, set, multiset or key:<field>)"),
+            },
+        })
+    }
+}
+
+#[derive(Clone)]
+struct ArrayKey {
+    /// As `--array` takes it, for the `O` panel and the flags it exports.
+    spec: String,
+    /// Dotted array path pattern (`*` for any key or index); `None` for
+    /// every array.
+    pattern: Option<Vec<String>>,
+    strategy: Strategy,
+}
+
+#[derive(Clone, Default)]
+pub struct ArrayKeys {
+    keys: Vec<ArrayKey>,
+}
+
+impl ArrayKeys {
+    /// Consumes `arg` (and its value from `iter`) if it is `--array` or
+    /// `--array-key`.
+    pub fn parse_arg<'a>(&mut self, arg: &str, iter: &mut impl Iterator<Item = &'a String>) -> Result<bool> {
+        let spec = match arg {
+            "--array" => iter.next().ok_or_else(|| anyhow!("{arg} needs [<array path>=]<strategy>"))?.clone(),
+            "--array-key" => {
+                let spec = iter.next().ok_or_else(|| anyhow!("{arg} needs [<array path>=]<field>"))?;
+                match spec.rsplit_once('=') {
+                    Some((path, field)) => format!("{path}=key:{field}"),
+                    None => format!("key:{spec}"),
+                }
+            }
+            _ => return Ok(false),
+        };
+        self.keys.push(parse(&spec).map_err(|err| anyhow!("{arg}: {err}"))?);
+        Ok(true)
+    }
+
+    /// Rules from specs as `--array` takes them.
+    pub fn from_specs(specs: &[String]) -> Result<Self> {
+        Ok(Self { keys: specs.iter().map(|spec| parse(spec)).collect::<Result<_>>()? })
+    }
+
+    pub fn specs(&self) -> Vec<String> {
+        self.keys.iter().map(|key| key.spec.clone()).collect()
+    }
+
+    /// The strategy for the array at `array_path` (indices as numbers): by
+    /// the rule for it, else `set` if `unordered`, else `ordered`.
+    pub fn strategy(&self, array_path: &[String], unordered: bool) -> Strategy {
+        match self.strategy_for(array_path) {
+            Some(strategy) => strategy.clone(),
+            None if unordered => Strategy::Set,
+            None => Strategy::Ordered,
+        }
+    }
+
+    /// The strategy of the rule for the array at `array_path`, if any. A
+    /// rule for that array beats one for every array, and among rules for it
+    /// the pattern with the fewest `*` wins, then the last given.
+    fn strategy_for(&self, array_path: &[String]) -> Option<&Strategy> {
+        let scoped = self
+            .keys
+            .iter()
+            .enumerate()
+            .filter_map(|(order, key)| Some((key.pattern.as_ref()?, order, key)))
+            .filter(|(pattern, _, _)| ignore::path_matches(pattern, array_path))
+            .max_by_key(|(pattern, order, _)| (pattern.iter().filter(|segment| *segment != "*").count(), *order));
+        scoped.map(|(_, _, key)| key).or_else(|| self.keys.iter().rev().find(|key| key.pattern.is_none())).map(|key| &key.strategy)
+    }
+
+    /// The field that pairs the elements of the array at `array_path`, if
+    /// its strategy is `key:<field>`.
+    pub fn field_for(&self, array_path: &[String]) -> Option<&str> {
+        match self.strategy_for(array_path) {
+            Some(Strategy::Key(field)) => Some(field),
+            _ => None,
+        }
+    }
+
+    /// Whether a rule compares some arrays as sets or multisets.
+    pub fn has_sets(&self) -> bool {
+        self.keys.iter().any(|key| key.strategy.unordered())
+    }
+}
+
+/// Pairs the elements of `left` and `right` that have the same `key`:
+/// `(left index, right index, unkeyed)` in the order of `right`, each element
+/// only on the left placed after the one before it there. Elements without a
+/// key are paired by position with one that lacks it too, and flagged
+/// `unkeyed`.
+pub fn pair(left: &[Value], right: &[Value], key: impl Fn(&Value) -> Option<String>) -> Vec<(Option<usize>, Option<usize>, bool)> {
+    let left_keys: Vec<Option<String>> = left.iter().map(&key).collect();
+    let mut by_key: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (i, key) in left_keys.iter().enumerate().rev() {
+        if let Some(key) = key {
+            by_key.entry(key.clone()).or_default().push(i);
+        }
+    }
+    let mut used = vec![false; left.len()];
+    let mut rows = Vec::new();
+    for (j, element) in right.iter().enumerate() {
+        let element_key = key(element);
+        let i = match &element_key {
+            Some(element_key) => by_key.get_mut(element_key).and_then(Vec::pop),
+            None => Some(j).filter(|&i| i < left.len() && left_keys[i].is_none()),
+        };
+        if let Some(i) = i {
+            used[i] = true;
+        }
+        rows.push((i, Some(j), element_key.is_none()));
+    }
+
+    // Each unpaired left element follows the nearest paired one before it.
+    let mut following: BTreeMap<Option<usize>, Vec<usize>> = BTreeMap::new();
+    let mut anchor = None;
+    for (i, used) in used.into_iter().enumerate() {
+        if used {
+            anchor = Some(i);
+        } else {
+            following.entry(anchor).or_default().push(i);
+        }
+    }
+    let removed = |anchor: Option<usize>| {
+        following.get(&anchor).into_iter().flatten().map(|&i| (Some(i), None, left_keys[i].is_none())).collect::<Vec<_>>()
+    };
+    let mut aligned = removed(None);
+    for row in rows {
+        aligned.push(row);
+        if row.0.is_some() {
+            aligned.extend(removed(row.0));
+        }
+    }
+    aligned
+}
+
+/// Where each distinct value occurs in `left` and `right`: its indices on
+/// each side, values in the order they first appear on the right and then
+/// on the left.
+pub fn tally(left: &[Value], right: &[Value]) -> Vec<[Vec<usize>; 2]> {
+    let mut order = Vec::new();
+    let mut tallies: BTreeMap<String, [Vec<usize>; 2]> = BTreeMap::new();
+    for (side, items) in [(1, right), (0, left)] {
+        for (index, item) in items.iter().enumerate() {
+            let value = canonical(item);
+            tallies
+                .entry(value.clone())
+                .or_insert_with(|| {
+                    order.push(value);
+                    Default::default()
+                })[side]
+                .push(index);
+        }
+    }
+    order.into_iter().map(|value| tallies.remove(&value).expect("tallied")).collect()
+}
+
+/// The value `element` has for the dotted `field`, as JSON; `None` if it has
+/// none.
+pub fn field_key(element: &Value, field: &str) -> Option<String> {
+    let value = field.split('.').try_fold(element, |value, key| value.as_object()?.get(key))?;
+    Some(value.to_string()).filter(|_| !value.is_null())
+}
+
+/// `value` as JSON with the elements of every array in it sorted, so values
+/// that differ only in the order of array elements come out the same.
+pub fn canonical(value: &Value) -> String {
+    match value {
+        Value::Array(items) => {
+            let mut items: Vec<String> = items.iter().map(canonical).collect();
+            items.sort();
+            format!("[{}]", items.join(","))
+        }
+        Value::Object(map) => {
+            let entries: Vec<String> = map.iter().map(|(key, value)| format!("{}:{}", Value::from(key.as_str()), canonical(value))).collect();
+            format!("{{{}}}", entries.join(","))
+        }
+        scalar => scalar.to_string(),
+    }
+}
+
+/// Checks a spec and splits it into its array path and strategy.
+fn parse(spec: &str) -> Result<ArrayKey> {
+    let (pattern, strategy) = match spec.rsplit_once('=') {
+        Some((path, strategy)) => {
+            // `users[].roles[]` is `users.*.roles`.
+            let path = path.trim().trim_end_matches("[]").replace("[].", ".*.");
+            let segments: Vec<String> = path.split('.').map(str::to_string).collect();
+            if segments.iter().any(String::is_empty) {
+                return Err(anyhow!("'{spec}' has an empty path segment"));
+            }
+            (Some(segments), strategy)
+        }
+        None => (None, spec),
+    };
+    let strategy = Strategy::parse(strategy).map_err(|err| anyhow!("'{spec}': {err}"))?;
+    Ok(ArrayKey { spec: spec.trim().to_string(), pattern, strategy })
+}
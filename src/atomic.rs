@@ -0,0 +1,182 @@
+//! Writes the files diffrs produces for the user (exports, notes, history)
+//! so that a crash or a full disk mid-write never leaves a truncated file:
+//! the contents go to a temporary file in the destination directory, are
+//! synced, and then renamed over the target in one step.
+
+use anyhow::{anyhow, Result};
+use std::fs::{self, Metadata};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// How many links `write` follows before taking the path as it is.
+const MAX_LINKS: usize = 40;
+
+/// Replaces `path` with `contents`, atomically. An existing file keeps its
+/// permissions; a read-only one is refused. A link is written through, to
+/// the file it names. On failure `path` is left as it was.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    // Renaming over a link would replace the link and leave its file stale.
+    let target = resolve(path);
+    let dir = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let existing = fs::metadata(&target).ok();
+    if existing.as_ref().is_some_and(|metadata| metadata.permissions().readonly()) {
+        return Err(anyhow!("{}: the file is read-only", path.display()));
+    }
+    let temp = temp_file(path, dir, contents.as_ref(), existing.as_ref())?;
+    match temp.persist(&target) {
+        Ok(_) => Ok(()),
+        // A file mounted on its own, as a bind mount, is on another device
+        // than its directory and can't be renamed over: the synced copy is
+        // copied onto it instead, which isn't atomic but starts only once
+        // the contents are safely written.
+        Err(err) if err.error.kind() == io::ErrorKind::CrossesDevices => {
+            fs::copy(err.file.path(), &target).map_err(|err| failed(path, err))?;
+            fs::OpenOptions::new().write(true).open(&target).and_then(|file| file.sync_all()).map_err(|err| failed(path, err))?;
+            Ok(())
+        }
+        Err(err) => Err(failed(path, err.error)),
+    }
+}
+
+/// The file `path` names once links are followed; where it would be for a
+/// link to nothing yet.
+fn resolve(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    for _ in 0..MAX_LINKS {
+        match fs::read_link(&path) {
+            // A relative link is relative to its directory; joining an
+            // absolute one replaces the directory.
+            Ok(target) => path = path.parent().map_or_else(|| target.clone(), |dir| dir.join(&target)),
+            Err(_) => break,
+        }
+    }
+    path
+}
+
+/// A synced temporary file in `dir` holding `contents`, with the permissions
+/// of the file it will replace, if any. Removed again if dropped.
+fn temp_file(path: &Path, dir: &Path, contents: &[u8], existing: Option<&Metadata>) -> Result<NamedTempFile> {
+    let name = path.file_name().map_or_else(|| "diffrs".into(), |name| name.to_string_lossy());
+    let mut temp = tempfile::Builder::new()
+        .prefix(&format!(".{name}."))
+        .suffix(".tmp")
+        .tempfile_in(dir)
+        .map_err(|err| failed(path, err))?;
+    temp.write_all(contents).map_err(|err| failed(path, err))?;
+    if let Some(metadata) = existing {
+        temp.as_file().set_permissions(metadata.permissions()).map_err(|err| failed(path, err))?;
+    }
+    temp.as_file().sync_all().map_err(|err| failed(path, err))?;
+    Ok(temp)
+}
+
+/// Names the cause of a failed write plainly where it is a common one.
+fn failed(path: &Path, err: io::Error) -> anyhow::Error {
+    let cause = match err.kind() {
+        io::ErrorKind::PermissionDenied => "permission denied",
+        io::ErrorKind::StorageFull => "the disk is full",
+        io::ErrorKind::ReadOnlyFilesystem => "the file system is read-only",
+        io::ErrorKind::NotFound => "the directory doesn't exist",
+        _ => return anyhow::Error::new(err).context(format!("{}: not written", path.display())),
+    };
+    anyhow!("{}: not written, {cause}", path.display())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    /// The names in `dir`, so a failed write can be seen to leave no
+    /// temporary file behind.
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn replaces_the_file_and_keeps_its_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.json");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        write(&path, "new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        assert_eq!(names(dir.path()), ["notes.json"]);
+    }
+
+    #[test]
+    fn refuses_a_read_only_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.csv");
+        fs::write(&path, "original").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o444)).unwrap();
+        let err = write(&path, "new").unwrap_err();
+        assert!(err.to_string().ends_with("the file is read-only"), "{err}");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "original");
+        assert_eq!(names(dir.path()), ["export.csv"]);
+    }
+
+    #[test]
+    fn leaves_the_file_in_an_unwritable_directory_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("patch.json");
+        fs::write(&path, "original").unwrap();
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o555)).unwrap();
+        // Permissions don't bind the superuser, so there is no failure to see.
+        let unwritable = fs::File::create(dir.path().join("probe")).is_err();
+        if unwritable {
+            let err = write(&path, "new").unwrap_err();
+            assert!(err.to_string().ends_with("permission denied"), "{err}");
+            assert_eq!(fs::read_to_string(&path).unwrap(), "original");
+        }
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn leaves_no_temporary_file_when_the_rename_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        // A directory can't be replaced by a file.
+        let path = dir.path().join("history");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("entry"), "kept").unwrap();
+        assert!(write(&path, "new").is_err());
+        assert_eq!(fs::read_to_string(path.join("entry")).unwrap(), "kept");
+        assert_eq!(names(dir.path()), ["history"]);
+    }
+
+    #[test]
+    fn reports_a_missing_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing").join("out.json");
+        let err = write(&path, "new").unwrap_err();
+        assert!(err.to_string().ends_with("the directory doesn't exist"), "{err}");
+    }
+
+    #[test]
+    fn writes_through_a_link() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("real")).unwrap();
+        let file = dir.path().join("real").join("notes.json");
+        fs::write(&file, "old").unwrap();
+        let link = dir.path().join("notes.json");
+        symlink("real/notes.json", &link).unwrap();
+        write(&link, "new").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "new");
+        assert_eq!(names(&dir.path().join("real")), ["notes.json"]);
+
+        // A link to a file not there yet creates it.
+        let dangling = dir.path().join("export.csv");
+        symlink(dir.path().join("real").join("export.csv"), &dangling).unwrap();
+        write(&dangling, "rows").unwrap();
+        assert!(fs::symlink_metadata(&dangling).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(dir.path().join("real").join("export.csv")).unwrap(), "rows");
+    }
+}
@@ -0,0 +1,114 @@
+//! Named bookmarks on diff rows. A mark remembers the row's document path,
+//! so it survives re-diffs that shift rows around; rows without a known path
+//! (line-diff output) fall back to the row number.
+
+use crossterm::event::KeyCode;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Clear, List, ListState},
+};
+use std::collections::BTreeMap;
+
+use crate::{centered_rect, display};
+
+#[derive(Clone)]
+pub struct Mark {
+    pub path: Option<String>,
+    pub row: usize,
+}
+
+impl Mark {
+    pub fn describe(&self) -> String {
+        match &self.path {
+            Some(path) => display::sanitize(path).into_owned(),
+            None => format!("line {}", self.row + 1),
+        }
+    }
+
+    /// The row this mark points at in a diff with the given row paths, and
+    /// whether it was found by path rather than by falling back to its row.
+    pub fn resolve(&self, paths: &[Option<String>]) -> (usize, bool) {
+        if let Some(path) = &self.path {
+            if let Some(row) = paths.iter().position(|p| p.as_ref() == Some(path)) {
+                return (row, true);
+            }
+        }
+        (self.row.min(paths.len().saturating_sub(1)), self.path.is_none())
+    }
+}
+
+pub enum PopupAction {
+    None,
+    Jump(char),
+    Close,
+}
+
+#[derive(Default)]
+pub struct Bookmarks {
+    marks: BTreeMap<char, Mark>,
+    /// Selected entry while the bookmarks popup is open.
+    popup: Option<usize>,
+}
+
+impl Bookmarks {
+    /// Sets `letter`, returning the mark it replaced.
+    pub fn set(&mut self, letter: char, mark: Mark) -> Option<Mark> {
+        self.marks.insert(letter, mark)
+    }
+
+    pub fn get(&self, letter: char) -> Option<&Mark> {
+        self.marks.get(&letter)
+    }
+
+    pub fn popup_open(&self) -> bool {
+        self.popup.is_some()
+    }
+
+    pub fn toggle_popup(&mut self) {
+        self.popup = if self.popup.is_some() { None } else { Some(0) };
+    }
+
+    pub fn handle_popup_key(&mut self, code: KeyCode) -> PopupAction {
+        let Some(selected) = self.popup else {
+            return PopupAction::None;
+        };
+        let last = self.marks.len().saturating_sub(1);
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => self.popup = Some(selected.saturating_sub(1)),
+            KeyCode::Down | KeyCode::Char('j') => self.popup = Some((selected + 1).min(last)),
+            KeyCode::Char('d') | KeyCode::Delete => {
+                if let Some(letter) = self.marks.keys().nth(selected).copied() {
+                    self.marks.remove(&letter);
+                }
+                self.popup = Some(selected.min(self.marks.len().saturating_sub(1)));
+            }
+            KeyCode::Enter => {
+                if let Some(letter) = self.marks.keys().nth(selected).copied() {
+                    self.popup = None;
+                    return PopupAction::Jump(letter);
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('B') => {
+                self.popup = None;
+                return PopupAction::Close;
+            }
+            _ => {}
+        }
+        PopupAction::None
+    }
+
+    pub fn render_popup(&self, f: &mut Frame, area: Rect) {
+        let popup = centered_rect(area, 60, 50);
+        f.render_widget(Clear, popup);
+        let items: Vec<String> = if self.marks.is_empty() {
+            vec!["no bookmarks yet: press m and a letter on a diff row".to_string()]
+        } else {
+            self.marks.iter().map(|(letter, mark)| format!("{letter}  {}", mark.describe())).collect()
+        };
+        let list = List::new(items)
+            .block(Block::bordered().title("Bookmarks ([enter] jump, [d] delete, [esc] close)"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(self.popup.filter(|_| !self.marks.is_empty()));
+        f.render_stateful_widget(list, popup, &mut state);
+    }
+}
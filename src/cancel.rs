@@ -0,0 +1,51 @@
+//! Cooperative cancellation for long-running diffs.
+
+use signal_hook::consts::SIGINT;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// How many visited nodes pass between polls of the token.
+const CHECK_INTERVAL: usize = 1024;
+
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Cancels the token on Ctrl+C, for diffs run without the TUI, whose raw
+    /// mode reads Ctrl+C as a key instead. A second Ctrl+C before the diff
+    /// stops exits at once, with status 130.
+    pub fn cancel_on_interrupt(&self) -> io::Result<()> {
+        // Registered first, so it sees the flag as the first Ctrl+C left it.
+        signal_hook::flag::register_conditional_shutdown(SIGINT, 130, Arc::clone(&self.0))?;
+        signal_hook::flag::register(SIGINT, Arc::clone(&self.0))?;
+        Ok(())
+    }
+}
+
+/// Counts the nodes a traversal visits and polls the token every
+/// `CHECK_INTERVAL` of them, so the hot loop stays cheap.
+pub struct Checkpoint<'a> {
+    token: &'a CancelToken,
+    visited: usize,
+}
+
+impl<'a> Checkpoint<'a> {
+    pub fn new(token: &'a CancelToken) -> Self {
+        Self { token, visited: 0 }
+    }
+
+    /// Records one visited node and returns whether the traversal should stop.
+    pub fn tick(&mut self) -> bool {
+        self.visited += 1;
+        self.visited.is_multiple_of(CHECK_INTERVAL) && self.token.is_cancelled()
+    }
+}
@@ -0,0 +1,216 @@
+//! What the terminal can do, detected from the environment at startup. A
+//! limited terminal gets a degraded UI (basic colors, no mouse) or, without an
+//! alternate screen, a printed summary instead of escape-sequence noise.
+
+use anyhow::{bail, Result};
+use ratatui::{buffer::Buffer, style::Color};
+use std::io::IsTerminal;
+
+/// How many colors the terminal can show.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorDepth {
+    Mono,
+    Basic8,
+    Basic16,
+    Indexed256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// `--color-depth mono|8|16|256|truecolor`.
+    pub fn parse(value: &str) -> Result<Self> {
+        Ok(match value {
+            "mono" | "0" | "2" => Self::Mono,
+            "8" => Self::Basic8,
+            "16" => Self::Basic16,
+            "256" => Self::Indexed256,
+            "truecolor" | "24bit" => Self::TrueColor,
+            other => bail!("--color-depth: unknown depth '{other}' (expected mono, 8, 16, 256 or truecolor)"),
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mono => "mono",
+            Self::Basic8 => "8 colors",
+            Self::Basic16 => "16 colors",
+            Self::Indexed256 => "256 colors",
+            Self::TrueColor => "truecolor",
+        }
+    }
+}
+
+pub struct Capabilities {
+    /// `$TERM`, or empty when unset.
+    pub term: String,
+    pub alternate_screen: bool,
+    pub mouse: bool,
+    pub colors: ColorDepth,
+}
+
+impl Capabilities {
+    /// Detects from `$TERM`, `$COLORTERM` and `$NO_COLOR`, and whether stdout
+    /// is a terminal at all.
+    pub fn detect() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let tty = std::io::stdout().is_terminal();
+        Self::from_env(var("TERM").as_deref(), var("COLORTERM").as_deref(), var("NO_COLOR").is_some(), tty)
+    }
+
+    fn from_env(term: Option<&str>, colorterm: Option<&str>, no_color: bool, tty: bool) -> Self {
+        let term = term.unwrap_or_default();
+        // Hardware-style terminals: no alternate screen, no xterm mouse reporting.
+        let dumb = term.is_empty() || term == "dumb" || term.starts_with("vt") || term == "ansi";
+        let colors = if no_color || (dumb && term != "ansi") {
+            ColorDepth::Mono
+        } else if matches!(colorterm, Some("truecolor" | "24bit")) || term.ends_with("-direct") {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Indexed256
+        } else if term == "ansi" || term == "linux" || term.ends_with("-8color") {
+            ColorDepth::Basic8
+        } else {
+            ColorDepth::Basic16
+        };
+        Self {
+            term: term.to_string(),
+            alternate_screen: tty && !dumb,
+            mouse: tty && !dumb && term != "linux",
+            colors,
+        }
+    }
+
+    /// For `--debug`.
+    pub fn describe(&self) -> String {
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+        format!(
+            "terminal: TERM={}\n  alternate screen: {}\n  mouse: {}\n  colors: {}\n",
+            if self.term.is_empty() { "(unset)" } else { &self.term },
+            yes_no(self.alternate_screen),
+            yes_no(self.mouse),
+            self.colors.name()
+        )
+    }
+
+    /// Rewrites every cell's colors to ones the terminal can show.
+    pub fn degrade(&self, buffer: &mut Buffer) {
+        if self.colors == ColorDepth::TrueColor {
+            return;
+        }
+        for cell in &mut buffer.content {
+            cell.fg = map_color(cell.fg, self.colors);
+            cell.bg = map_color(cell.bg, self.colors);
+        }
+    }
+}
+
+/// The 16 basic colors with their usual xterm RGB values, the first 8 being
+/// the ones an 8-color terminal has.
+const BASIC: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// The nearest color `depth` can show.
+fn map_color(color: Color, depth: ColorDepth) -> Color {
+    match (color, depth) {
+        (Color::Reset, _) | (_, ColorDepth::TrueColor) => color,
+        (_, ColorDepth::Mono) => Color::Reset,
+        (Color::Rgb(r, g, b), ColorDepth::Indexed256) => {
+            // The nearest step of the 6×6×6 color cube.
+            let step = |v: u8| if v < 48 { 0 } else if v < 115 { 1 } else { (v - 35) / 40 };
+            Color::Indexed(16 + 36 * step(r) + 6 * step(g) + step(b))
+        }
+        (_, ColorDepth::Indexed256) => color,
+        _ => {
+            let basic = match depth {
+                ColorDepth::Basic8 => &BASIC[..8],
+                _ => &BASIC[..],
+            };
+            let Some(rgb) = rgb(color) else {
+                return color;
+            };
+            let distance = |(r, g, b): (u8, u8, u8)| {
+                let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+                d(r, rgb.0) + d(g, rgb.1) + d(b, rgb.2)
+            };
+            basic.iter().min_by_key(|(_, candidate)| distance(*candidate)).map_or(color, |(basic, _)| *basic)
+        }
+    }
+}
+
+/// The RGB value of a named, indexed or RGB color.
+fn rgb(color: Color) -> Option<(u8, u8, u8)> {
+    match color {
+        Color::Rgb(r, g, b) => Some((r, g, b)),
+        Color::Indexed(index @ 0..=15) => Some(BASIC[usize::from(index)].1),
+        Color::Indexed(index @ 16..=231) => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+            let index = index - 16;
+            Some((level(index / 36), level(index / 6 % 6), level(index % 6)))
+        }
+        Color::Indexed(index) => {
+            let gray = 8 + (index - 232) * 10;
+            Some((gray, gray, gray))
+        }
+        named => BASIC.iter().find(|(basic, _)| *basic == named).map(|(_, rgb)| *rgb),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colors(term: Option<&str>, colorterm: Option<&str>) -> &'static str {
+        Capabilities::from_env(term, colorterm, false, true).colors.name()
+    }
+
+    #[test]
+    fn colors_follow_term_and_colorterm() {
+        assert_eq!(colors(Some("xterm-256color"), None), "256 colors");
+        assert_eq!(colors(Some("xterm-256color"), Some("truecolor")), "truecolor");
+        assert_eq!(colors(Some("xterm-direct"), None), "truecolor");
+        assert_eq!(colors(Some("xterm"), None), "16 colors");
+        assert_eq!(colors(Some("linux"), None), "8 colors");
+        assert_eq!(colors(Some("ansi"), None), "8 colors");
+        assert_eq!(colors(Some("dumb"), Some("truecolor")), "mono");
+        assert_eq!(colors(None, None), "mono");
+        assert_eq!(Capabilities::from_env(Some("xterm-256color"), Some("truecolor"), true, true).colors.name(), "mono");
+    }
+
+    #[test]
+    fn limited_terminals_lose_the_screen_and_mouse() {
+        let xterm = Capabilities::from_env(Some("xterm"), None, false, true);
+        assert!(xterm.alternate_screen && xterm.mouse);
+        let console = Capabilities::from_env(Some("linux"), None, false, true);
+        assert!(console.alternate_screen && !console.mouse);
+        for term in [None, Some("dumb"), Some("vt100"), Some("ansi")] {
+            let limited = Capabilities::from_env(term, None, false, true);
+            assert!(!limited.alternate_screen && !limited.mouse, "{term:?}");
+        }
+        let piped = Capabilities::from_env(Some("xterm"), None, false, false);
+        assert!(!piped.alternate_screen && !piped.mouse);
+    }
+
+    #[test]
+    fn colors_degrade_to_the_nearest() {
+        assert_eq!(map_color(Color::Rgb(250, 10, 10), ColorDepth::Basic16), Color::LightRed);
+        assert_eq!(map_color(Color::LightRed, ColorDepth::Basic8), Color::Red);
+        assert_eq!(map_color(Color::Rgb(255, 0, 0), ColorDepth::Indexed256), Color::Indexed(196));
+        assert_eq!(map_color(Color::Green, ColorDepth::Mono), Color::Reset);
+    }
+}
@@ -0,0 +1,128 @@
+//! `diffrs check <left> <right>`: compares two documents without the TUI,
+//! prints the change summary and exits non-zero when the failure policy says
+//! so. Meant for CI pipelines. The diff is the TUI's, under the same options,
+//! and `.diffrsignore` files next to either input apply. `--json` and `--paths-only` print the changes for
+//! scripts instead, addressed by JSON Pointer, and `--by-kind` grouped by
+//! kind for people; `--stats` prints only the counts.
+
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+
+use crate::cli::Cli;
+use crate::history::{self, History};
+use crate::ignore;
+use crate::normalize::Normalizers;
+use crate::redact;
+use crate::repair;
+use crate::report::{ChangeKind, FailPolicy};
+use crate::{compare_inputs, comparison_title, read_inputs, report_limit, CancelToken, Comparison};
+
+/// Runs the check and returns the process exit code. Options other than the
+/// failure policy and the output go through `Cli::parse`, so the check
+/// compares as the TUI and `--stats` do.
+pub fn run(args: &[String]) -> Result<i32> {
+    let mut policy = FailPolicy::new(vec![ChangeKind::Added, ChangeKind::Removed, ChangeKind::Changed, ChangeKind::TypeChanged]);
+    let mut rest = Vec::new();
+    let (mut json, mut paths_only) = (false, false);
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if policy.parse_arg(arg, &mut iter)? {
+            continue;
+        }
+        match arg.as_str() {
+            "--json" => json = true,
+            "--paths-only" => paths_only = true,
+            _ => rest.push(arg.clone()),
+        }
+    }
+    let cli = Cli::parse(&rest)?;
+    // Everything but the inputs and the output format shapes the comparison.
+    let shapes = |arg: &&String| !cli.paths.contains(&PathBuf::from(arg.as_str())) && !matches!(arg.as_str(), "--json" | "--paths-only" | "--by-kind" | "--stats");
+    let options: Vec<String> = args.iter().filter(shapes).cloned().collect();
+    if [json, paths_only, cli.by_kind].iter().filter(|on| **on).count() > 1 {
+        bail!("--json, --paths-only and --by-kind are different outputs: pass one");
+    }
+    if cli.stats && (json || paths_only) {
+        bail!("--stats is a text output: it does not go with --json or --paths-only");
+    }
+    let [left_path, right_path] = cli.paths.as_slice() else {
+        bail!("usage: diffrs check <left> <right> [--fail-on kinds] [--max-changes N] [diffrs options…] [--json | --paths-only | --by-kind | --stats]");
+    };
+
+    let rules = cli.ignore.rules(&[ignore::input_dir(left_path), ignore::input_dir(right_path)])?;
+    if cli.ignore.show {
+        print!("{}", rules.describe());
+        return Ok(0);
+    }
+    let normalizers = Normalizers::locale_numbers(&cli.locale_numbers)?;
+
+    let inputs = read_inputs([left_path, right_path], cli.error_on_missing)?;
+    let absent = inputs.each_ref().map(Option::is_none);
+    let names = [left_path, right_path].map(|path| path.display().to_string());
+    let contents = inputs.map(Option::unwrap_or_default);
+    let redactor = cli.redact.redactor()?;
+    let cancel = CancelToken::default();
+    cancel.cancel_on_interrupt()?;
+    let Some(Comparison { left, right, rows, identical, suppressed, redactions, repairs }) =
+        compare_inputs(&cli, &names, &contents, &redactor, &rules, &normalizers, &cancel)?
+    else {
+        eprintln!("diffrs: check cancelled");
+        return Ok(130);
+    };
+    let summary = &rows.summary;
+    if json {
+        let mut report = summary.to_json(&names[0], &names[1]);
+        if repairs.iter().any(|repairs| !repairs.is_empty()) {
+            report["repaired"] = repair::to_json(&repairs);
+        }
+        println!("{report:#}");
+    } else if paths_only {
+        for change in &summary.changes {
+            println!("{}", change.pointer);
+        }
+        repair::warn(&repairs);
+    } else {
+        let title = comparison_title([&names[0], &names[1]], absent);
+        // --stats alone prints just its line, as it does without `check`.
+        if cli.stats {
+            match suppressed.len() {
+                0 => println!("{title}: {}", rows.stats),
+                n => println!("{title}: {}, {n} ignored", rows.stats),
+            }
+        }
+        if !cli.stats || cli.by_kind {
+            if identical {
+                println!("{title}: documents are identical");
+            } else if cli.by_kind {
+                summary.print_by_kind(&title, report_limit(&cli));
+            } else {
+                summary.print(&title, report_limit(&cli));
+            }
+            if !suppressed.is_empty() {
+                println!("  ({} path(s) suppressed by ignore rules)", suppressed.len());
+            }
+            if let Some(note) = redact::note(redactions) {
+                println!("  ({note})");
+            }
+        }
+        repair::print(&repairs);
+    }
+    if let Some(history) = History::open(cli.no_history, "check", &options) {
+        let absolute = |path: &PathBuf| std::path::absolute(path).unwrap_or_else(|_| path.clone()).display().to_string();
+        let sources = [absolute(left_path), absolute(right_path)];
+        let rerun = sources.iter().cloned().chain(options).collect();
+        history.record(sources, summary, history::leaf_total(&left, &right), Some(rerun));
+    }
+
+    if let Some(reason) = policy.evaluate(&[summary]) {
+        // Kept off stdout when that is for scripts to read.
+        if json || paths_only {
+            eprintln!("diffrs: failed: {reason}");
+        } else {
+            println!("diffrs: failed: {reason}");
+        }
+        return Ok(1);
+    }
+    Ok(0)
+}
@@ -0,0 +1,525 @@
+//! Command-line options for the interactive diff view.
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::arraykey::ArrayKeys;
+use crate::capabilities::ColorDepth;
+use crate::compare::{MatchRule, Structure, UnicodeForm};
+use crate::context::ContextFields;
+use crate::duplicates::DuplicateKeys;
+use crate::external::ExternalRule;
+use crate::fetch::FetchArgs;
+use crate::har::HarArgs;
+use crate::ignore::IgnoreArgs;
+use crate::redact::RedactArgs;
+use crate::select::Selection;
+use crate::{annotations, compare, depth, tail};
+
+/// Arrays longer than this are summarized unless overridden on the command line.
+pub const DEFAULT_ARRAY_SUMMARY_THRESHOLD: usize = 1000;
+
+/// Added or removed subtrees larger than this many bytes, about what fits
+/// on two rows of a pane, are summarized.
+pub const DEFAULT_SUBTREE_THRESHOLD: usize = 128;
+
+/// Diff entries shown before the rest are left to a footer.
+pub const DEFAULT_MAX_ENTRIES: usize = 1000;
+
+/// How input files are interpreted.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    /// By extension (`.yaml`/`.yml` or `.json`), else by content: JSON when it
+    /// starts with `{` or `[`, YAML otherwise.
+    Auto,
+    Json,
+    Yaml,
+    /// Plain text, compared line by line without parsing. Both sides or neither.
+    Text,
+}
+
+impl Format {
+    fn parse(arg: &str, value: Option<&String>) -> Result<Self> {
+        Ok(match value.map(String::as_str) {
+            Some("auto") => Self::Auto,
+            Some("json") => Self::Json,
+            Some("yaml") => Self::Yaml,
+            Some("text") => Self::Text,
+            Some(other) => bail!("{arg}: unknown format '{other}' (expected auto, json, yaml or text)"),
+            None => bail!("{arg} needs a format"),
+        })
+    }
+
+    /// The format `contents`, read from `path`, is parsed as: `Auto` becomes
+    /// `Json` or `Yaml`.
+    pub fn resolve(self, path: &Path, contents: &str) -> Self {
+        if self != Self::Auto {
+            return self;
+        }
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => Self::Yaml,
+            Some("json" | "ndjson" | "jsonl") => Self::Json,
+            _ => match contents.trim_start().chars().next() {
+                None | Some('{' | '[') => Self::Json,
+                Some(_) => Self::Yaml,
+            },
+        }
+    }
+
+    /// For pane titles.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Json => "JSON",
+            Self::Yaml => "YAML",
+            Self::Text => "text",
+        }
+    }
+}
+
+/// What happens to a displayed diff when a pane's content changes.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnChange {
+    /// `--on-change refresh`: diff again.
+    Refresh,
+    /// `--on-change stale`: dim the diff until `d` is pressed.
+    Stale,
+}
+
+/// Which actions `--read-only` takes away.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ReadOnly {
+    Off,
+    /// `--read-only`: no editing, clearing or file-writing exports.
+    Hard,
+    /// `--read-only=soft`: no editing or clearing; exports ask for confirmation.
+    Soft,
+}
+
+pub struct Cli {
+    /// `-f`: load `./left.json` and `./right.json` on startup.
+    pub load_files: bool,
+    pub array_summary_threshold: usize,
+    /// `--timings`: print a per-phase performance breakdown to stderr on exit.
+    pub timings: bool,
+    /// `--max-depth-parse`: deepest nesting accepted when parsing input.
+    pub max_depth_parse: usize,
+    /// `--duplicate-keys`: what a key repeated within a JSON object is read
+    /// as, see `duplicates`.
+    pub duplicate_keys: DuplicateKeys,
+    /// `--format`, or `--left-format`/`--right-format` for one side.
+    pub formats: [Format; 2],
+    /// `--no-mouse`: start with mouse capture off so native selection works.
+    pub no_mouse: bool,
+    pub redact: RedactArgs,
+    pub read_only: ReadOnly,
+    pub on_change: OnChange,
+    /// `--tail <left> <right>`: follow two NDJSON files as they grow.
+    pub tail: Option<(PathBuf, PathBuf)>,
+    /// `--apply-patch <document> <patch>`: diff a document against itself
+    /// with a JSON Patch applied.
+    pub apply_patch: Option<(PathBuf, PathBuf)>,
+    /// `--base <file>`: the common ancestor for a three-way comparison.
+    pub base: Option<PathBuf>,
+    /// `--record-key`: pair tailed records by this (dotted) field instead of arrival order.
+    pub record_key: Option<String>,
+    /// `--tail-pairs`: how many recent pairs tail mode keeps.
+    pub tail_pairs: usize,
+    pub ignore: IgnoreArgs,
+    /// `--context-fields`: fields that name the elements of an array.
+    pub context: ContextFields,
+    /// `--array` and `--array-key`: how the elements of arrays are paired.
+    pub array_keys: ArrayKeys,
+    /// `--unordered-arrays`: compare arrays as unordered collections.
+    pub unordered_arrays: bool,
+    /// `--key-order`: keys written in another order count as moved.
+    pub key_order: bool,
+    /// `--compare`: the comparators that decide what else is equal.
+    pub comparators: Vec<String>,
+    /// `--equal-if` rules, `[name:]pattern=regex`.
+    pub equal_rules: Vec<String>,
+    /// `--compare-with` rules, `pattern=command`.
+    pub external_rules: Vec<String>,
+    /// `--compare-units <path>`: compare durations and sizes at `path` as quantities.
+    pub unit_paths: Vec<String>,
+    /// `--compare-urls <path>`: compare URLs at `path` once normalized.
+    pub url_paths: Vec<String>,
+    /// `--tolerance`: numbers at most this far apart are equal.
+    pub tolerance: Option<f64>,
+    /// `--exact-numbers`: an integer and a float of the same value differ.
+    pub exact_numbers: bool,
+    /// `--normalize-unicode[=<form>]`: compare strings and keys normalized.
+    pub unicode_form: Option<UnicodeForm>,
+    /// `--compare-timestamps[=<seconds>]`: timestamps at most this many
+    /// milliseconds apart are equal.
+    pub timestamps: Option<u64>,
+    /// `--select <path>`: the subtree of both documents to compare.
+    pub select: Option<String>,
+    /// `--only-differences`: start with unchanged entries hidden from the diff.
+    pub only_differences: bool,
+    /// `--full-paths`: name each diff row by its whole path, not its key.
+    pub full_paths: bool,
+    /// `--no-markers`: leave out the `+`/`-`/`~` before each diff row.
+    pub no_markers: bool,
+    /// `--percent-change`: show a changed number's change as a percentage.
+    pub percent_change: bool,
+    /// `--stats`: print how many values differ instead of starting the TUI.
+    pub stats: bool,
+    /// `--by-kind`: print the changes grouped by kind instead of starting the TUI.
+    pub by_kind: bool,
+    /// `--changed-paths`: print each differing path, by kind, instead of
+    /// starting the TUI.
+    pub changed_paths: bool,
+    /// `--structure[=lengths]`: compare the documents' shape, not their values.
+    pub structure: Option<Structure>,
+    /// `--schema`: compare schemas inferred from the documents.
+    pub schema: bool,
+    /// `--detect-renames`: show a key removed and one added with equal
+    /// values as a rename.
+    pub detect_renames: bool,
+    /// `--locale-number <path>=<locale>`: parse locale-formatted numeric strings at `path`;
+    /// the locale may be given as its decimal and grouping separators.
+    pub locale_numbers: Vec<String>,
+    /// `--decode-base64 <path>`: compare the base64-encoded JSON at `path` decoded.
+    pub decode_base64: Vec<String>,
+    /// `--epoch-seconds`/`--epoch-millis`: paths whose numbers are Unix timestamps.
+    pub epoch_seconds: Vec<String>,
+    pub epoch_millis: Vec<String>,
+    /// `--relative-times`: start with relative-time annotations shown.
+    pub relative_times: bool,
+    /// `--type-tags`: start with each value's type shown after it.
+    pub type_tags: bool,
+    /// `--annotations`: the sidecar file notes on differences are kept in.
+    pub annotations: PathBuf,
+    pub har: HarArgs,
+    pub fetch: FetchArgs,
+    /// `--expand-depth`: levels of nesting expanded in the diff before any differ.
+    pub expand_depth: usize,
+    /// `--max-depth`: levels of nesting the diff descends into; deeper
+    /// objects and arrays are collapsed with a count of their changes.
+    pub diff_depth: Option<usize>,
+    /// `--truncate`: columns a value may take in the diff and the printed
+    /// reports before its middle is cut out; 0 never cuts. By default, three
+    /// rows of the pane, or of the terminal for printed reports.
+    pub truncate: Option<usize>,
+    /// `--subtree-threshold`: bytes over which an added or removed object or
+    /// array is summarized in one row; 0 never summarizes.
+    pub subtree_threshold: usize,
+    /// `--max-entries`: diff entries shown before the rest are counted in a
+    /// footer; 0 shows them all.
+    pub max_entries: usize,
+    /// `--summarize-subtrees`: summarize them in exports too, rather than
+    /// writing them out in full.
+    pub summarize_subtrees: bool,
+    /// `--no-history`: don't record comparisons in the history log.
+    pub no_history: bool,
+    /// `--error-on-missing`: fail when an input file doesn't exist, rather
+    /// than compare it as absent (`--treat-missing-as-empty`, the default).
+    pub error_on_missing: bool,
+    /// `--lenient`: repair invalid string escapes in JSON instead of failing.
+    pub lenient: bool,
+    /// `--no-wizard`: start on empty panes rather than the start screen.
+    pub no_wizard: bool,
+    /// `--debug`: print the detected terminal capabilities to stderr on startup.
+    pub debug: bool,
+    /// `--force-tui`: start the TUI even where the terminal seems unable to show it.
+    pub force_tui: bool,
+    /// `--color-depth`: overrides the detected number of colors.
+    pub color_depth: Option<ColorDepth>,
+    /// The inputs named on the command line, which only `diffrs check` takes.
+    pub paths: Vec<PathBuf>,
+}
+
+impl Cli {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut cli = Self {
+            load_files: false,
+            array_summary_threshold: DEFAULT_ARRAY_SUMMARY_THRESHOLD,
+            timings: false,
+            max_depth_parse: depth::DEFAULT_MAX_DEPTH,
+            duplicate_keys: DuplicateKeys::Warn,
+            formats: [Format::Auto; 2],
+            no_mouse: false,
+            redact: RedactArgs::default(),
+            read_only: ReadOnly::Off,
+            on_change: OnChange::Refresh,
+            tail: None,
+            apply_patch: None,
+            base: None,
+            record_key: None,
+            tail_pairs: tail::DEFAULT_TAIL_PAIRS,
+            ignore: IgnoreArgs::default(),
+            context: ContextFields::default(),
+            array_keys: ArrayKeys::default(),
+            unordered_arrays: false,
+            key_order: false,
+            comparators: Vec::new(),
+            equal_rules: Vec::new(),
+            external_rules: Vec::new(),
+            unit_paths: Vec::new(),
+            url_paths: Vec::new(),
+            tolerance: None,
+            exact_numbers: false,
+            unicode_form: None,
+            timestamps: None,
+            select: None,
+            only_differences: false,
+            full_paths: false,
+            no_markers: false,
+            percent_change: false,
+            stats: false,
+            by_kind: false,
+            changed_paths: false,
+            structure: None,
+            schema: false,
+            detect_renames: false,
+            locale_numbers: Vec::new(),
+            decode_base64: Vec::new(),
+            epoch_seconds: Vec::new(),
+            epoch_millis: Vec::new(),
+            relative_times: false,
+            type_tags: false,
+            annotations: PathBuf::from(annotations::DEFAULT_FILE),
+            har: HarArgs::default(),
+            fetch: FetchArgs::default(),
+            expand_depth: 1,
+            diff_depth: None,
+            truncate: None,
+            subtree_threshold: DEFAULT_SUBTREE_THRESHOLD,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            summarize_subtrees: false,
+            no_history: false,
+            no_wizard: false,
+            lenient: false,
+            error_on_missing: false,
+            debug: false,
+            force_tui: false,
+            color_depth: None,
+            paths: Vec::new(),
+        };
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if cli.ignore.parse_arg(arg, &mut iter)?
+                || cli.har.parse_arg(arg, &mut iter)?
+                || cli.fetch.parse_arg(arg, &mut iter)?
+                || cli.context.parse_arg(arg, &mut iter)?
+                || cli.array_keys.parse_arg(arg, &mut iter)?
+                || cli.redact.parse_arg(arg, &mut iter)?
+            {
+                continue;
+            }
+            match arg.as_str() {
+                "-f" => cli.load_files = true,
+                "--timings" => cli.timings = true,
+                "--no-mouse" => cli.no_mouse = true,
+                "--relative-times" => cli.relative_times = true,
+                "--type-tags" => cli.type_tags = true,
+                "--no-history" => cli.no_history = true,
+                "--no-wizard" => cli.no_wizard = true,
+                "--lenient" => cli.lenient = true,
+                "--unordered-arrays" => cli.unordered_arrays = true,
+                "--key-order" => cli.key_order = true,
+                "--exact-numbers" => cli.exact_numbers = true,
+                "--normalize-unicode" => cli.unicode_form = Some(UnicodeForm::Nfc),
+                "--compare-timestamps" => cli.timestamps = Some(0),
+                flag if flag.starts_with("--compare-timestamps=") => {
+                    let seconds = &flag["--compare-timestamps=".len()..];
+                    cli.timestamps = Some(compare::parse_skew(seconds).map_err(|err| anyhow!("--compare-timestamps: {err}"))?);
+                }
+                flag if flag.starts_with("--normalize-unicode=") => {
+                    let form = &flag["--normalize-unicode=".len()..];
+                    cli.unicode_form = Some(UnicodeForm::parse(form).map_err(|err| anyhow!("--normalize-unicode: {err}"))?);
+                }
+                "--ignore-case" | "--ignore-whitespace" | "--coerce-numbers" => {
+                    let name = match arg.as_str() {
+                        "--ignore-case" => "case-insensitive",
+                        "--ignore-whitespace" => "whitespace",
+                        _ => "numeric-strings",
+                    };
+                    if !cli.comparators.iter().any(|enabled| enabled == name) {
+                        cli.comparators.push(name.to_string());
+                    }
+                }
+                "--only-differences" => cli.only_differences = true,
+                "--full-paths" => cli.full_paths = true,
+                "--no-markers" => cli.no_markers = true,
+                "--percent-change" => cli.percent_change = true,
+                "--stats" => cli.stats = true,
+                "--by-kind" => cli.by_kind = true,
+                "--changed-paths" => cli.changed_paths = true,
+                "--structure" => cli.structure = Some(Structure::Types),
+                "--schema" => cli.schema = true,
+                "--detect-renames" => cli.detect_renames = true,
+                flag if flag.starts_with("--structure=") => {
+                    let mode = &flag["--structure=".len()..];
+                    cli.structure = Some(Structure::parse(mode).map_err(|err| anyhow!("--structure: {err}"))?);
+                }
+                "--summarize-subtrees" => cli.summarize_subtrees = true,
+                "--error-on-missing" => cli.error_on_missing = true,
+                "--treat-missing-as-empty" => cli.error_on_missing = false,
+                "--debug" => cli.debug = true,
+                "--force-tui" => cli.force_tui = true,
+                "--read-only" | "--read-only=hard" => cli.read_only = ReadOnly::Hard,
+                "--read-only=soft" => cli.read_only = ReadOnly::Soft,
+                "--array-summary-threshold" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a number"))?;
+                    cli.array_summary_threshold = value
+                        .parse()
+                        .map_err(|_| anyhow!("{arg}: '{value}' is not a number"))?;
+                }
+                "--expand-depth" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a number"))?;
+                    cli.expand_depth = value
+                        .parse()
+                        .map_err(|_| anyhow!("{arg}: '{value}' is not a number"))?;
+                }
+                "--max-depth" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a number"))?;
+                    cli.diff_depth = match value.parse() {
+                        Ok(0) => bail!("{arg} must be at least 1"),
+                        Ok(depth) => Some(depth),
+                        Err(_) => bail!("{arg}: '{value}' is not a number"),
+                    };
+                }
+                "--truncate" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a number"))?;
+                    cli.truncate = Some(
+                        value
+                            .parse()
+                            .map_err(|_| anyhow!("{arg}: '{value}' is not a number"))?,
+                    );
+                }
+                "--subtree-threshold" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a number of bytes"))?;
+                    cli.subtree_threshold = value
+                        .parse()
+                        .map_err(|_| anyhow!("{arg}: '{value}' is not a number"))?;
+                }
+                "--max-entries" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a number"))?;
+                    cli.max_entries = value
+                        .parse()
+                        .map_err(|_| anyhow!("{arg}: '{value}' is not a number"))?;
+                }
+                "--duplicate-keys" => {
+                    let policy = iter.next().ok_or_else(|| anyhow!("{arg} needs warn, first, last or error"))?;
+                    cli.duplicate_keys = DuplicateKeys::parse(policy).map_err(|err| anyhow!("{arg}: {err}"))?;
+                }
+                "--max-depth-parse" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a number"))?;
+                    cli.max_depth_parse = value
+                        .parse()
+                        .map_err(|_| anyhow!("{arg}: '{value}' is not a number"))?;
+                }
+                "--tail" => {
+                    let mut path = || iter.next().map(PathBuf::from).ok_or_else(|| anyhow!("{arg} needs a left and a right file"));
+                    cli.tail = Some((path()?, path()?));
+                }
+                "--apply-patch" => {
+                    let mut path = || iter.next().map(PathBuf::from).ok_or_else(|| anyhow!("{arg} needs a document and a patch"));
+                    cli.apply_patch = Some((path()?, path()?));
+                }
+                "--base" => {
+                    cli.base = Some(iter.next().map(PathBuf::from).ok_or_else(|| anyhow!("{arg} needs a file"))?);
+                }
+                "--compare" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a comparator name"))?;
+                    compare::check(value).map_err(|err| anyhow!("{arg}: {err}"))?;
+                    cli.comparators.push(value.clone());
+                }
+                "--equal-if" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a rule like uuid:**.id=^[0-9a-f-]{{36}}$"))?;
+                    MatchRule::parse(value).map_err(|err| anyhow!("{arg}: {err:#}"))?;
+                    cli.equal_rules.push(value.clone());
+                }
+                "--compare-with" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a rule like 'geometry.*=./compare_geo.sh'"))?;
+                    ExternalRule::parse(value).map_err(|err| anyhow!("{arg}: {err:#}"))?;
+                    cli.external_rules.push(value.clone());
+                }
+                "--compare-units" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a path pattern"))?;
+                    if value.split('.').any(str::is_empty) {
+                        bail!("{arg}: '{value}' has an empty path segment");
+                    }
+                    cli.unit_paths.push(value.clone());
+                }
+                "--compare-urls" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a path pattern"))?;
+                    if value.split('.').any(str::is_empty) {
+                        bail!("{arg}: '{value}' has an empty path segment");
+                    }
+                    cli.url_paths.push(value.clone());
+                }
+                "--select" => {
+                    let path = iter.next().ok_or_else(|| anyhow!("{arg} needs a path like $.spec.containers"))?;
+                    Selection::parse(path).map_err(|err| anyhow!("{arg}: {err}"))?;
+                    cli.select = Some(path.clone());
+                }
+                "--tolerance" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a number"))?;
+                    cli.tolerance = Some(compare::parse_tolerance(value).map_err(|err| anyhow!("{arg}: {err}"))?);
+                }
+                "--locale-number" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs <path>=<locale>"))?;
+                    cli.locale_numbers.push(value.clone());
+                }
+                "--decode-base64" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a path pattern"))?;
+                    if value.split('.').any(str::is_empty) {
+                        bail!("{arg}: '{value}' has an empty path segment");
+                    }
+                    cli.decode_base64.push(value.clone());
+                }
+                "--epoch-seconds" | "--epoch-millis" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a path pattern"))?.clone();
+                    if arg == "--epoch-seconds" {
+                        cli.epoch_seconds.push(value);
+                    } else {
+                        cli.epoch_millis.push(value);
+                    }
+                }
+                "--annotations" => {
+                    cli.annotations = iter.next().map(PathBuf::from).ok_or_else(|| anyhow!("{arg} needs a file"))?;
+                }
+                "--record-key" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a field name"))?;
+                    cli.record_key = Some(value.clone());
+                }
+                "--tail-pairs" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs a number"))?;
+                    cli.tail_pairs = value
+                        .parse()
+                        .map_err(|_| anyhow!("{arg}: '{value}' is not a number"))?;
+                }
+                "--color-depth" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("{arg} needs mono, 8, 16, 256 or truecolor"))?;
+                    cli.color_depth = Some(ColorDepth::parse(value)?);
+                }
+                "--on-change" => {
+                    cli.on_change = match iter.next().map(String::as_str) {
+                        Some("refresh") => OnChange::Refresh,
+                        Some("stale") => OnChange::Stale,
+                        _ => bail!("{arg} needs refresh or stale"),
+                    }
+                }
+                "--format" => cli.formats = [Format::parse(arg, iter.next())?; 2],
+                "--left-format" => cli.formats[0] = Format::parse(arg, iter.next())?,
+                "--right-format" => cli.formats[1] = Format::parse(arg, iter.next())?,
+                other if other.starts_with('-') => bail!("unknown option '{other}'"),
+                path => cli.paths.push(PathBuf::from(path)),
+            }
+        }
+
+        if (cli.formats[0] == Format::Text) != (cli.formats[1] == Format::Text) {
+            bail!("text format compares lines, so it applies to both sides: use --format text");
+        }
+        if cli.base.is_some() && cli.formats[0] == Format::Text {
+            bail!("--base compares documents, not lines of text");
+        }
+        Ok(cli)
+    }
+}
@@ -0,0 +1,753 @@
+//! Comparators decide what "equal" means for a pair of values. The diff
+//! consults them in order wherever the two sides differ: the first with an
+//! opinion decides, and values none has an opinion on are equal if they are
+//! the same JSON. Objects and arrays nobody decides on are equal when all
+//! their members are, by the same rules.
+//!
+//! `--compare <name>` enables one shipped with diffrs, see `NAMES`;
+//! `--tolerance <epsilon>`, and `--unordered-arrays` or `--array <path>=set`,
+//! are ones too. Integers
+//! and floats of the same value are equal unless `--exact-numbers` is given.
+//!
+//! `--compare-timestamps` compares RFC 3339 timestamps as the instants they
+//! name, so `2024-01-02T03:04:05Z` and `2024-01-02T05:04:05+02:00` match.
+//!
+//! `--compare-units <pattern>` compares durations and sizes at paths
+//! matching the glob as the quantities they are, so `1500ms` and `1.5s`, or
+//! `1Gi` and `1073741824`, match.
+//!
+//! `--compare-urls <pattern>` compares URLs at paths matching the glob as
+//! they are once normalized, so `https://EXAMPLE.com:443/a/../b?y=2&x=1`
+//! and `https://example.com/b?x=1&y=2` match.
+//!
+//! `--equal-if [name:]pattern=regex` makes strings at paths matching the
+//! glob `pattern` (see `ignore`) equal when both match `regex`: any two
+//! UUIDs, say. One side matching and the other not is still a difference.
+//!
+//! `--compare-with pattern=command` leaves values at matching paths to a
+//! command of the user's, see `external`.
+//!
+//! `--normalize-unicode` compares strings, and pairs object keys, by their
+//! Unicode normalization form (NFC unless another is given), so decomposed
+//! and composed text match.
+//!
+//! `--structure[=lengths]` compares documents by their shape alone: the
+//! same keys, nested the same way, holding values of the same types, and
+//! with `lengths` arrays of the same lengths. It decides every pair, so the
+//! comparators after it never get a say.
+
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::collections::BTreeMap;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::coverage::type_name;
+use crate::arraykey::ArrayKeys;
+use crate::decimal::{self, Decimal};
+use crate::{arraykey, ignore, timefmt};
+use crate::diff::DiffOptions;
+use crate::external::ExternalRule;
+
+/// The comparators `--compare` enables. Under `case-insensitive`
+/// (`--ignore-case`, `I`), strings that differ only in case are equal; under
+/// `whitespace` (`--ignore-whitespace`, `w`), those that differ only in
+/// whitespace; under `numeric-strings` (`--coerce-numbers`, `N`), a string
+/// holding a JSON number and that number.
+pub const NAMES: [&str; 3] = ["case-insensitive", "whitespace", "numeric-strings"];
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnicodeForm {
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+impl UnicodeForm {
+    pub fn parse(form: &str) -> Result<Self> {
+        Ok(match form.to_lowercase().as_str() {
+            "nfc" => Self::Nfc,
+            "nfd" => Self::Nfd,
+            "nfkc" => Self::Nfkc,
+            "nfkd" => Self::Nfkd,
+            _ => bail!("'{form}' is not a normalization form (nfc, nfd, nfkc or nfkd)"),
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Nfc => "nfc",
+            Self::Nfd => "nfd",
+            Self::Nfkc => "nfkc",
+            Self::Nfkd => "nfkd",
+        }
+    }
+
+    pub fn normalize(self, text: &str) -> String {
+        match self {
+            Self::Nfc => text.nfc().collect(),
+            Self::Nfd => text.nfd().collect(),
+            Self::Nfkc => text.nfkc().collect(),
+            Self::Nfkd => text.nfkd().collect(),
+        }
+    }
+
+    /// The members of two objects, paired by their normalized keys in the
+    /// order of those: each side's key and value. A key normalized like
+    /// another on its side stays apart, under its own spelling.
+    pub fn pair_members<'v>(self, left: &'v Map<String, Value>, right: &'v Map<String, Value>) -> Vec<[Option<(&'v String, &'v Value)>; 2]> {
+        let mut members: BTreeMap<String, [Option<(&String, &Value)>; 2]> = BTreeMap::new();
+        for (side, map) in [left, right].into_iter().enumerate() {
+            for member in map {
+                let normalized = self.normalize(member.0);
+                let key = if members.get(&normalized).is_some_and(|pair| pair[side].is_some()) { member.0.clone() } else { normalized };
+                members.entry(key).or_default()[side] = Some(member);
+            }
+        }
+        members.into_values().collect()
+    }
+}
+
+/// What `--structure` compares: the types of values, or those and the
+/// lengths of arrays too.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Structure {
+    Types,
+    Lengths,
+}
+
+impl Structure {
+    pub fn parse(mode: &str) -> Result<Self> {
+        Ok(match mode.to_lowercase().as_str() {
+            "types" => Self::Types,
+            "lengths" => Self::Lengths,
+            _ => bail!("'{mode}' is not a structure mode (types or lengths)"),
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Types => "types",
+            Self::Lengths => "lengths",
+        }
+    }
+
+    /// `value` with every scalar replaced by the name of its type:
+    /// `{"port": 8080}` is `{"port": "number"}`. Without `Lengths` an array
+    /// is the distinct shapes of its elements, in no particular order, so
+    /// `[1, 2, 3]` is `["number"]`.
+    pub fn shape(self, value: &Value) -> Value {
+        match value {
+            Value::Object(map) => Value::Object(map.iter().map(|(key, value)| (key.clone(), self.shape(value))).collect()),
+            Value::Array(items) => {
+                let mut shapes: Vec<Value> = items.iter().map(|item| self.shape(item)).collect();
+                if self == Self::Types {
+                    shapes.sort_by_cached_key(Value::to_string);
+                    shapes.dedup();
+                }
+                Value::Array(shapes)
+            }
+            scalar => Value::String(type_name(scalar).to_string()),
+        }
+    }
+}
+
+impl ValueComparator for Structure {
+    fn equal(&self, _path: &str, left: &Value, right: &Value) -> Option<bool> {
+        Some(self.shape(left) == self.shape(right))
+    }
+}
+
+pub trait ValueComparator: Send + Sync {
+    /// Whether the values at `path` (as the rows record it, `servers[2].port`)
+    /// are equal, or `None` to leave it to the next comparator.
+    fn equal(&self, path: &str, left: &Value, right: &Value) -> Option<bool>;
+
+    /// Shown next to `left` and `right`, values this comparator found equal.
+    fn note(&self, _left: &Value, _right: &Value) -> Option<String> {
+        None
+    }
+
+    /// Why this comparator couldn't decide on the values at `path`, when it
+    /// should have.
+    fn failure(&self, _path: &str) -> Option<String> {
+        None
+    }
+
+    /// Every path it couldn't decide on, and why.
+    fn failures(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+}
+
+#[derive(Default)]
+pub struct Comparators {
+    chain: Vec<Box<dyn ValueComparator>>,
+    /// Pairs object keys by this form.
+    unicode: Option<UnicodeForm>,
+}
+
+impl Comparators {
+    /// The comparators `options` enable: `--structure`, `--equal-if` and
+    /// `--compare-with` rules, numbers by
+    /// value, the numeric tolerance, numeric strings, timestamps, quantities,
+    /// strings folded as `--compare` and `--normalize-unicode` say, then
+    /// unordered arrays.
+    pub fn new(options: &DiffOptions) -> Self {
+        let mut comparators = Self { unicode: options.unicode_form, ..Self::default() };
+        if let Some(structure) = options.structure {
+            comparators.push(structure);
+        }
+        // The rules were checked when they were set.
+        for rule in options.equal_rules.iter().filter_map(|spec| MatchRule::parse(spec).ok()) {
+            comparators.push(rule);
+        }
+        for rule in options.external_rules.iter().filter_map(|spec| ExternalRule::parse(spec).ok()) {
+            comparators.push(rule);
+        }
+        comparators.push(Numbers { exact: options.exact_numbers });
+        if let Some(epsilon) = options.tolerance {
+            comparators.push(Tolerance(epsilon));
+        }
+        let enabled = |name: &str| options.comparators.iter().any(|enabled| enabled == name);
+        if enabled("numeric-strings") {
+            comparators.push(NumericStrings { exact: options.exact_numbers, tolerance: options.tolerance });
+        }
+        if let Some(skew) = options.timestamps {
+            comparators.push(Timestamps(skew));
+        }
+        if !options.unit_paths.is_empty() {
+            comparators.push(Quantities(options.unit_paths.clone()));
+        }
+        if !options.url_paths.is_empty() {
+            comparators.push(Urls(options.url_paths.clone()));
+        }
+        let strings = Strings { case: enabled("case-insensitive"), whitespace: enabled("whitespace"), unicode: options.unicode_form };
+        if strings.case || strings.whitespace || strings.unicode.is_some() {
+            comparators.push(strings);
+        }
+        // The rules were checked when they were set.
+        let rules = ArrayKeys::from_specs(&options.array_keys).unwrap_or_default();
+        if options.unordered_arrays || rules.has_sets() {
+            comparators.push(ArraySets { rules, unordered: options.unordered_arrays });
+        }
+        comparators
+    }
+
+    /// Adds `comparator` to the end of the chain.
+    pub fn push(&mut self, comparator: impl ValueComparator + 'static) {
+        self.chain.push(Box::new(comparator));
+    }
+
+    pub fn equal(&self, path: &str, left: &Value, right: &Value) -> bool {
+        if self.chain.is_empty() || left == right {
+            return left == right;
+        }
+        if let Some(equal) = self.chain.iter().find_map(|comparator| comparator.equal(path, left, right)) {
+            return equal;
+        }
+        match (left, right) {
+            (Value::Object(left_map), Value::Object(right_map)) if self.unicode.is_some() => {
+                let members = self.unicode.expect("checked").pair_members(left_map, right_map);
+                members.iter().all(|pair| match pair {
+                    [Some((key, value)), Some((_, other))] => {
+                        value == other || self.equal(&if path.is_empty() { key.to_string() } else { format!("{path}.{key}") }, value, other)
+                    }
+                    _ => false,
+                })
+            }
+            (Value::Object(left_map), Value::Object(right_map)) => {
+                // Equal members are skipped before their paths are built.
+                left_map.len() == right_map.len()
+                    && left_map.iter().all(|(key, value)| {
+                        right_map.get(key).is_some_and(|other| {
+                            value == other || self.equal(&if path.is_empty() { key.clone() } else { format!("{path}.{key}") }, value, other)
+                        })
+                    })
+            }
+            (Value::Array(left_items), Value::Array(right_items)) => {
+                left_items.len() == right_items.len()
+                    && left_items.iter().zip(right_items).enumerate().all(|(index, (l, r))| l == r || self.equal(&format!("{path}[{index}]"), l, r))
+            }
+            _ => false,
+        }
+    }
+
+    /// Why a comparator that should have decided on the values at `path`
+    /// couldn't.
+    pub fn failure(&self, path: &str) -> Option<String> {
+        self.chain.iter().find_map(|comparator| comparator.failure(path))
+    }
+
+    /// Every such failure, by path.
+    pub fn failures(&self) -> BTreeMap<String, String> {
+        self.chain.iter().flat_map(|comparator| comparator.failures()).collect()
+    }
+
+    /// The note of the comparator that decided `left` and `right` at `path`
+    /// are equal, if it has one.
+    pub fn note(&self, path: &str, left: &Value, right: &Value) -> Option<String> {
+        let decided = self.chain.iter().find_map(|comparator| comparator.equal(path, left, right).map(|equal| (comparator, equal)));
+        decided.filter(|&(_, equal)| equal).and_then(|(comparator, _)| comparator.note(left, right))
+    }
+}
+
+/// An `--equal-if` rule: strings at paths matching `pattern` are equal
+/// when both match `regex`.
+pub struct MatchRule {
+    name: String,
+    pattern: String,
+    regex: Regex,
+}
+
+impl MatchRule {
+    /// Parses `[name:]pattern=regex`; the name defaults to the pattern.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (target, regex) = spec.split_once('=').ok_or_else(|| anyhow!("'{spec}' is not [name:]pattern=regex"))?;
+        let (name, pattern) = match target.split_once(':') {
+            Some((name, pattern)) => (name.trim(), pattern.trim()),
+            None => (target.trim(), target.trim()),
+        };
+        if pattern.is_empty() || pattern.split('.').any(str::is_empty) {
+            bail!("'{spec}' has an empty path segment");
+        }
+        let regex = Regex::new(regex).with_context(|| format!("'{spec}'"))?;
+        Ok(Self { name: name.to_string(), pattern: pattern.to_string(), regex })
+    }
+}
+
+impl ValueComparator for MatchRule {
+    fn equal(&self, path: &str, left: &Value, right: &Value) -> Option<bool> {
+        let (Value::String(l), Value::String(r)) = (left, right) else {
+            return None;
+        };
+        (ignore::glob_matches_path(&self.pattern, path) && self.regex.is_match(l) && self.regex.is_match(r)).then_some(true)
+    }
+
+    fn note(&self, _left: &Value, _right: &Value) -> Option<String> {
+        Some(format!("≈ matched rule: {}", self.name))
+    }
+}
+
+/// `n` if it is an integer, exactly.
+fn integer(n: &Number) -> Option<i128> {
+    n.as_i64().map(i128::from).or_else(|| n.as_u64().map(i128::from))
+}
+
+/// Parses a `--tolerance` epsilon: a finite number, not negative.
+pub fn parse_tolerance(value: &str) -> Result<f64> {
+    match value.trim().parse::<f64>() {
+        Ok(epsilon) if epsilon.is_finite() && epsilon >= 0.0 => Ok(epsilon),
+        _ => bail!("'{value}' is not a non-negative number"),
+    }
+}
+
+/// Checks a `--compare` name.
+pub fn check(name: &str) -> Result<()> {
+    if !NAMES.contains(&name) {
+        bail!("unknown comparator '{name}' (expected one of {})", NAMES.join(", "));
+    }
+    Ok(())
+}
+
+/// Numbers of the same value are equal, compared digit by digit rather
+/// than as floats (see `decimal`): floats written differently, `1.0`,
+/// `1.00` and `1e0`, and unless `exact`, an integer and a float, `1` and
+/// `1.0` or `1e2` and `100`. So `9007199254740993` isn't taken for
+/// `9007199254740992.0`, nor `0.1000000000000000000000001` for `0.1`.
+struct Numbers {
+    exact: bool,
+}
+
+impl ValueComparator for Numbers {
+    fn equal(&self, _path: &str, left: &Value, right: &Value) -> Option<bool> {
+        let (Value::Number(l), Value::Number(r)) = (left, right) else {
+            return None;
+        };
+        if self.exact {
+            return decimal::same_number(left, right).then_some(true);
+        }
+        (Decimal::of(l)? == Decimal::of(r)?).then_some(true)
+    }
+
+    fn note(&self, _left: &Value, _right: &Value) -> Option<String> {
+        Some("≈ same number".to_string())
+    }
+}
+
+/// Numbers at most this far apart are equal, integers and floats alike.
+/// Integers are compared exactly, as converting those over 2^53 to floats
+/// would round them.
+struct Tolerance(f64);
+
+impl ValueComparator for Tolerance {
+    fn equal(&self, _path: &str, left: &Value, right: &Value) -> Option<bool> {
+        let (Value::Number(l), Value::Number(r)) = (left, right) else {
+            return None;
+        };
+        let difference = match (integer(l), integer(r)) {
+            (Some(l), Some(r)) => (l - r).unsigned_abs() as f64,
+            _ => (l.as_f64()? - r.as_f64()?).abs(),
+        };
+        (difference <= self.0).then_some(true)
+    }
+
+    fn note(&self, _left: &Value, _right: &Value) -> Option<String> {
+        Some("≈ within tolerance".to_string())
+    }
+}
+
+/// A string that is exactly a JSON number, with nothing around it, is
+/// compared as that number against a number: `"42"` matches `42`, and
+/// `"1.0"` matches `1` as numbers do, but `"42 "`, `"+42"` and `"0x2a"`
+/// are strings. The tolerance applies as to numbers; the string is only
+/// read, the panes show it as written.
+struct NumericStrings {
+    exact: bool,
+    tolerance: Option<f64>,
+}
+
+impl ValueComparator for NumericStrings {
+    fn equal(&self, path: &str, left: &Value, right: &Value) -> Option<bool> {
+        let (text, number) = match (left, right) {
+            (Value::String(text), number @ Value::Number(_)) | (number @ Value::Number(_), Value::String(text)) => (text, number),
+            _ => return None,
+        };
+        if text.trim() != text {
+            return None;
+        }
+        let parsed = Value::Number(serde_json::from_str::<Number>(text).ok()?);
+        let equal = Numbers { exact: self.exact }.equal(path, &parsed, number) == Some(true)
+            || self.tolerance.is_some_and(|epsilon| Tolerance(epsilon).equal(path, &parsed, number) == Some(true));
+        equal.then_some(true)
+    }
+
+    fn note(&self, _left: &Value, _right: &Value) -> Option<String> {
+        Some("≈ numeric string".to_string())
+    }
+}
+
+/// Strings that are both RFC 3339 timestamps are equal if they name
+/// instants at most this many milliseconds apart. Anything short of a full
+/// date, time and offset, such as `2024-01-02` or a version, is a string.
+struct Timestamps(u64);
+
+impl ValueComparator for Timestamps {
+    fn equal(&self, _path: &str, left: &Value, right: &Value) -> Option<bool> {
+        let (Value::String(l), Value::String(r)) = (left, right) else {
+            return None;
+        };
+        let skew = timefmt::parse_rfc3339(l)?.abs_diff(timefmt::parse_rfc3339(r)?);
+        (skew <= self.0).then_some(true)
+    }
+
+    fn note(&self, left: &Value, right: &Value) -> Option<String> {
+        let instant = |value: &Value| value.as_str().and_then(timefmt::parse_rfc3339);
+        Some(if instant(left) == instant(right) { "≈ same instant" } else { "≈ instants within tolerance" }.to_string())
+    }
+}
+
+/// Parses a `--compare-timestamps` tolerance in seconds to milliseconds.
+pub fn parse_skew(seconds: &str) -> Result<u64> {
+    match seconds.trim().trim_end_matches('s').parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => Ok((seconds * 1000.0).round() as u64),
+        _ => bail!("'{seconds}' is not a number of seconds"),
+    }
+}
+
+/// Durations and sizes at paths matching one of the patterns (see
+/// `ignore`) are equal if they are the same quantity: `"1500ms"` and
+/// `"1.5s"`, or `"1Gi"`, `"1GiB"` and `"1073741824"`. Durations take `ms`,
+/// `s`, `m` (minutes) and `h`; sizes `k`, `M`, `G` and `T` in powers of
+/// 1000 and `Ki`, `Mi`, `Gi` and `Ti` in powers of 1024, each with or
+/// without a trailing `B`, and `B` alone. Case is kept, so `m` is minutes
+/// and `M` millions of bytes. A plain number is a number of bytes against a
+/// size; a duration needs its unit on both sides. Only the nominated paths
+/// are read, so a word like `"yes"` elsewhere is never taken for seconds.
+struct Quantities(Vec<String>);
+
+#[derive(Clone, Copy)]
+enum Quantity {
+    /// In milliseconds.
+    Duration(f64),
+    /// In bytes.
+    Size(f64),
+    Plain(f64),
+}
+
+impl Quantity {
+    fn read(value: &Value) -> Option<Self> {
+        let text = match value {
+            Value::Number(n) => return n.as_f64().map(Self::Plain),
+            Value::String(text) => text,
+            _ => return None,
+        };
+        let (number, unit) = text.split_at(text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len()));
+        if number.starts_with('.') || number.ends_with('.') {
+            return None;
+        }
+        let number: f64 = number.parse().ok()?;
+        let unit = unit.strip_prefix(' ').unwrap_or(unit);
+        let duration = match unit {
+            "ms" => Some(1.0),
+            "s" => Some(1e3),
+            "m" => Some(6e4),
+            "h" => Some(3.6e6),
+            _ => None,
+        };
+        if let Some(scale) = duration {
+            return Some(Self::Duration(number * scale));
+        }
+        let scale = match unit.strip_suffix('B').unwrap_or(unit) {
+            "" if unit.is_empty() => return Some(Self::Plain(number)),
+            "" => 1.0,
+            "k" => 1e3,
+            "M" => 1e6,
+            "G" => 1e9,
+            "T" => 1e12,
+            "Ki" => 1024.0,
+            "Mi" => 1024.0 * 1024.0,
+            "Gi" => 1024.0 * 1024.0 * 1024.0,
+            "Ti" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+            _ => return None,
+        };
+        Some(Self::Size(number * scale))
+    }
+}
+
+impl ValueComparator for Quantities {
+    fn equal(&self, path: &str, left: &Value, right: &Value) -> Option<bool> {
+        if !self.0.iter().any(|pattern| ignore::glob_matches_path(pattern, path)) {
+            return None;
+        }
+        let (l, r) = match (Quantity::read(left)?, Quantity::read(right)?) {
+            (Quantity::Duration(l), Quantity::Duration(r)) => (l, r),
+            (Quantity::Size(l) | Quantity::Plain(l), Quantity::Size(r)) | (Quantity::Size(l), Quantity::Plain(r)) => (l, r),
+            _ => return None,
+        };
+        // Scaling a fraction can leave a trace of rounding.
+        ((l - r).abs() <= l.abs().max(r.abs()) * 1e-12).then_some(true)
+    }
+
+    fn note(&self, _left: &Value, _right: &Value) -> Option<String> {
+        Some("≈ equal quantity".to_string())
+    }
+}
+
+/// `--compare-urls`: strings at paths matching any of the patterns are
+/// equal if they are the same URL once normalized as RFC 3986 describes:
+/// the scheme and host lowercased, a default port dropped, `.` and `..`
+/// segments resolved, escapes of unreserved characters decoded and other
+/// escapes uppercased, and query parameters sorted by name, those of one
+/// name kept in their order. A string that isn't an absolute URL with a
+/// host part, such as a `mailto:` address or a relative path, is left to
+/// the comparators after.
+struct Urls(Vec<String>);
+
+impl ValueComparator for Urls {
+    fn equal(&self, path: &str, left: &Value, right: &Value) -> Option<bool> {
+        if !self.0.iter().any(|pattern| ignore::glob_matches_path(pattern, path)) {
+            return None;
+        }
+        let (Value::String(l), Value::String(r)) = (left, right) else {
+            return None;
+        };
+        (normalize_url(l)? == normalize_url(r)?).then_some(true)
+    }
+
+    fn note(&self, _left: &Value, _right: &Value) -> Option<String> {
+        Some("≈ same URL".to_string())
+    }
+}
+
+/// `text` as `Urls` compares it, or `None` if it isn't such a URL.
+fn normalize_url(text: &str) -> Option<String> {
+    if text.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return None;
+    }
+    let (scheme, rest) = text.split_once(':')?;
+    let mut scheme_chars = scheme.chars();
+    if !scheme_chars.next()?.is_ascii_alphabetic() || !scheme_chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')) {
+        return None;
+    }
+    let scheme = scheme.to_ascii_lowercase();
+    let rest = rest.strip_prefix("//")?;
+    let (rest, fragment) = match rest.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (rest, None),
+    };
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, query)) => (rest, Some(query)),
+        None => (rest, None),
+    };
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let (userinfo, host_port) = match authority.rsplit_once('@') {
+        Some((userinfo, host_port)) => (Some(userinfo), host_port),
+        None => (None, authority),
+    };
+    // An IPv6 address has colons of its own.
+    let port_at = match host_port.starts_with('[') {
+        true => host_port.find(']')? + 1,
+        false => host_port.find(':').unwrap_or(host_port.len()),
+    };
+    let (host, port) = host_port.split_at(port_at);
+    let port = match port.strip_prefix(':') {
+        None if port.is_empty() => None,
+        Some("") => None,
+        Some(digits) if digits.bytes().all(|b| b.is_ascii_digit()) => Some(digits.parse::<u16>().ok()?),
+        _ => return None,
+    };
+    let default_port = match scheme.as_str() {
+        "http" | "ws" => Some(80),
+        "https" | "wss" => Some(443),
+        "ftp" => Some(21),
+        _ => None,
+    };
+    let mut url = format!("{scheme}://");
+    if let Some(userinfo) = userinfo {
+        url.push_str(&format!("{}@", normalize_escapes(userinfo)?));
+    }
+    url.push_str(&normalize_escapes(host)?.to_lowercase());
+    if let Some(port) = port.filter(|port| Some(*port) != default_port) {
+        url.push_str(&format!(":{port}"));
+    }
+    match remove_dot_segments(&normalize_escapes(path)?) {
+        // For the schemes with a default port, no path is the root.
+        path if path.is_empty() && default_port.is_some() => url.push('/'),
+        path => url.push_str(&path),
+    }
+    if let Some(query) = query.filter(|query| !query.is_empty()) {
+        let mut parameters = Vec::new();
+        for parameter in query.split('&').filter(|parameter| !parameter.is_empty()) {
+            parameters.push(normalize_escapes(parameter)?);
+        }
+        let name = |parameter: &String| parameter.split('=').next().unwrap_or_default().to_string();
+        parameters.sort_by_key(name);
+        url.push_str(&format!("?{}", parameters.join("&")));
+    }
+    if let Some(fragment) = fragment {
+        url.push_str(&format!("#{}", normalize_escapes(fragment)?));
+    }
+    Some(url)
+}
+
+/// `text` with escapes of unreserved characters decoded and the others'
+/// hex digits uppercased; `None` if an escape is malformed.
+fn normalize_escapes(text: &str) -> Option<String> {
+    let mut normalized = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find('%') {
+        normalized.push_str(&rest[..at]);
+        let hex = rest.get(at + 1..at + 3).filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))?;
+        let byte = u8::from_str_radix(hex, 16).ok()?;
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            normalized.push(char::from(byte));
+        } else {
+            normalized.push_str(&format!("%{}", hex.to_ascii_uppercase()));
+        }
+        rest = &rest[at + 3..];
+    }
+    normalized.push_str(rest);
+    Some(normalized)
+}
+
+/// `path` with its `.` and `..` segments resolved, as RFC 3986 does it: a
+/// `..` above the root stays at the root.
+fn remove_dot_segments(path: &str) -> String {
+    if path.is_empty() {
+        return String::new();
+    }
+    let parts: Vec<&str> = path.split('/').skip(1).collect();
+    let mut segments = Vec::new();
+    for (index, part) in parts.iter().enumerate() {
+        match *part {
+            "." | ".." => {
+                if *part == ".." {
+                    segments.pop();
+                }
+                // A path ending in one still names a directory.
+                if index + 1 == parts.len() {
+                    segments.push("");
+                }
+            }
+            part => segments.push(part),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+/// Strings equal once folded: with `unicode`, normalized to that form, so
+/// a decomposed `"café"` matches a composed one; with `case`, uppercased and
+/// then lowercased by Unicode's rules, so `"ACTIVE"` matches `"active"`,
+/// `"ÉTÉ"` `"été"` and `"STRASSE"` `"Straße"`; with `whitespace`, trimmed
+/// and with every run of whitespace inside, newlines included, made one
+/// space. Only values are folded; keys are paired by `unicode` alone.
+#[derive(Clone, Copy)]
+struct Strings {
+    case: bool,
+    whitespace: bool,
+    unicode: Option<UnicodeForm>,
+}
+
+impl Strings {
+    fn fold(self, text: &str) -> String {
+        let text = match self.unicode {
+            Some(form) => form.normalize(text),
+            None => text.to_string(),
+        };
+        let text = if self.whitespace { text.split_whitespace().collect::<Vec<_>>().join(" ") } else { text };
+        if self.case {
+            text.to_uppercase().to_lowercase()
+        } else {
+            text
+        }
+    }
+}
+
+impl ValueComparator for Strings {
+    fn equal(&self, _path: &str, left: &Value, right: &Value) -> Option<bool> {
+        let (Value::String(l), Value::String(r)) = (left, right) else {
+            return None;
+        };
+        (self.fold(l) == self.fold(r)).then_some(true)
+    }
+
+    /// The foldings the strings needed: those without which they differ.
+    fn note(&self, left: &Value, right: &Value) -> Option<String> {
+        let (Value::String(l), Value::String(r)) = (left, right) else {
+            return None;
+        };
+        let equal_without = |strings: Strings| strings.fold(l) == strings.fold(r);
+        let needed: Vec<&str> = [
+            (self.case && !equal_without(Strings { case: false, ..*self }), "case"),
+            (self.whitespace && !equal_without(Strings { whitespace: false, ..*self }), "whitespace"),
+            (self.unicode.is_some() && !equal_without(Strings { unicode: None, ..*self }), "Unicode form"),
+        ]
+        .into_iter()
+        .filter_map(|(needed, name)| needed.then_some(name))
+        .collect();
+        (!needed.is_empty()).then(|| format!("≈ differs in {}", needed.join(" and ")))
+    }
+}
+
+/// Arrays compared as sets or multisets, by their `--array` rule or under
+/// `--unordered-arrays`, are equal if they differ only in the order of their
+/// elements, at any depth.
+struct ArraySets {
+    rules: ArrayKeys,
+    unordered: bool,
+}
+
+impl ValueComparator for ArraySets {
+    fn equal(&self, path: &str, left: &Value, right: &Value) -> Option<bool> {
+        let (Value::Array(_), Value::Array(_)) = (left, right) else {
+            return None;
+        };
+        let set = self.rules.strategy(&ignore::path_segments(path), self.unordered).unordered();
+        (set && arraykey::canonical(left) == arraykey::canonical(right)).then_some(true)
+    }
+}
@@ -0,0 +1,63 @@
+//! `--context-fields <array>=<field>,...`: labels the elements of an array by
+//! identifying fields rather than their index, so a change reported inside
+//! `orders[137]` says which order it is: `orders[id=A-1009, customer=acme]`.
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use crate::ignore;
+
+#[derive(Clone, Default)]
+pub struct ContextFields {
+    /// Dotted array path patterns (`*` for any key or index) and the fields
+    /// that identify their elements.
+    specs: Vec<(Vec<String>, Vec<String>)>,
+}
+
+impl ContextFields {
+    /// Consumes `arg` (and its value from `iter`) if it is `--context-fields`.
+    pub fn parse_arg<'a>(&mut self, arg: &str, iter: &mut impl Iterator<Item = &'a String>) -> Result<bool> {
+        if arg != "--context-fields" {
+            return Ok(false);
+        }
+        let spec = iter.next().ok_or_else(|| anyhow!("{arg} needs <array path>=<field>,..."))?;
+        let (path, fields) = spec
+            .split_once('=')
+            .filter(|(path, fields)| !path.is_empty() && !fields.is_empty())
+            .ok_or_else(|| anyhow!("{arg}: expected <array path>=<field>,..., got '{spec}'"))?;
+        self.specs.push((
+            path.split('.').map(str::to_string).collect(),
+            fields.split(',').map(|field| field.trim().to_string()).filter(|field| !field.is_empty()).collect(),
+        ));
+        Ok(true)
+    }
+
+    /// The bracketed label for element `index` of the array at `array_path`
+    /// (dot-joined segments, indices as numbers): `[id=A-1009, customer=acme]`
+    /// from whichever side has each field, or `[index]` when the array has no
+    /// context fields or the element none of them.
+    pub fn label(&self, array_path: &[String], index: &str, left: Option<&Value>, right: Option<&Value>) -> String {
+        let Some((_, fields)) = self.specs.iter().find(|(pattern, _)| ignore::path_matches(pattern, array_path)) else {
+            return format!("[{index}]");
+        };
+        fn lookup<'v>(element: Option<&'v Value>, field: &str) -> Option<&'v Value> {
+            field.split('.').try_fold(element?, |value, key| value.get(key)).filter(|value| !value.is_null())
+        }
+        let parts: Vec<String> = fields
+            .iter()
+            .filter_map(|field| {
+                let value = lookup(left, field).or_else(|| lookup(right, field))?;
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                Some(format!("{field}={value}"))
+            })
+            .collect();
+        if parts.is_empty() {
+            format!("[{index}]")
+        } else {
+            format!("[{}]", parts.join(", "))
+        }
+    }
+}
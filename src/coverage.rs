@@ -0,0 +1,296 @@
+//! Key-coverage statistics for arrays of records: for every field path, how
+//! many records on each side contain it and which type dominates there.
+
+use anyhow::Result;
+use crossterm::event::KeyCode;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Row, Table, TableState},
+};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::{atomic, display};
+
+#[derive(Clone, PartialEq)]
+pub enum Segment {
+    Key(String),
+    /// Every element of an array.
+    Each,
+}
+
+pub fn path_label(segments: &[Segment]) -> String {
+    let mut label = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Key(key) => {
+                if !label.is_empty() {
+                    label.push('.');
+                }
+                label.push_str(key);
+            }
+            Segment::Each => label.push_str("[]"),
+        }
+    }
+    label
+}
+
+#[derive(Default)]
+struct SideStats {
+    records: usize,
+    types: BTreeMap<&'static str, usize>,
+}
+
+impl SideStats {
+    /// The most common type at this path, or `None` if no record has it.
+    fn dominant_type(&self) -> Option<&'static str> {
+        self.types.iter().max_by_key(|(_, count)| **count).map(|(name, _)| *name)
+    }
+
+    fn type_label(&self) -> &'static str {
+        self.dominant_type().unwrap_or("-")
+    }
+}
+
+struct CoverageRow {
+    segments: Vec<Segment>,
+    left: SideStats,
+    right: SideStats,
+}
+
+pub struct Coverage {
+    rows: Vec<CoverageRow>,
+    left_total: usize,
+    right_total: usize,
+    selected: usize,
+    sort_by_path: bool,
+}
+
+impl Coverage {
+    /// Builds the table when both roots are arrays; non-object records
+    /// simply contribute no fields.
+    pub fn build(left: &Value, right: &Value) -> Option<Self> {
+        let (left_records, right_records) = (left.as_array()?, right.as_array()?);
+        let mut rows: Vec<CoverageRow> = Vec::new();
+        for (records, is_left) in [(left_records, true), (right_records, false)] {
+            for record in records {
+                let mut seen = Vec::new();
+                collect_paths(record, &mut Vec::new(), &mut seen);
+                for (segments, type_name) in seen {
+                    let index = match rows.iter().position(|row| row.segments == segments) {
+                        Some(index) => index,
+                        None => {
+                            rows.push(CoverageRow { segments, left: SideStats::default(), right: SideStats::default() });
+                            rows.len() - 1
+                        }
+                    };
+                    let stats = if is_left { &mut rows[index].left } else { &mut rows[index].right };
+                    stats.records += 1;
+                    *stats.types.entry(type_name).or_default() += 1;
+                }
+            }
+        }
+        if rows.is_empty() {
+            return None;
+        }
+        let mut coverage = Self {
+            rows,
+            left_total: left_records.len(),
+            right_total: right_records.len(),
+            selected: 0,
+            sort_by_path: false,
+        };
+        coverage.sort();
+        Some(coverage)
+    }
+
+    fn percent(count: usize, total: usize) -> f64 {
+        if total == 0 {
+            0.0
+        } else {
+            count as f64 * 100.0 / total as f64
+        }
+    }
+
+    fn delta(&self, row: &CoverageRow) -> f64 {
+        Self::percent(row.right.records, self.right_total) - Self::percent(row.left.records, self.left_total)
+    }
+
+    fn sort(&mut self) {
+        if self.sort_by_path {
+            self.rows.sort_by_key(|row| path_label(&row.segments));
+        } else {
+            let deltas: Vec<(String, f64)> =
+                self.rows.iter().map(|row| (path_label(&row.segments), self.delta(row).abs())).collect();
+            let mut order: Vec<usize> = (0..self.rows.len()).collect();
+            order.sort_by(|&a, &b| deltas[b].1.total_cmp(&deltas[a].1).then_with(|| deltas[a].0.cmp(&deltas[b].0)));
+            let mut rows: Vec<Option<CoverageRow>> = self.rows.drain(..).map(Some).collect();
+            self.rows = order.into_iter().map(|i| rows[i].take().unwrap()).collect();
+        }
+    }
+
+    /// The path of the selected row, for filtering the main view.
+    pub fn selected_path(&self) -> Option<&[Segment]> {
+        self.rows.get(self.selected).map(|row| row.segments.as_slice())
+    }
+
+    /// Returns whether the key was consumed by the coverage view.
+    pub fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(self.rows.len().saturating_sub(1)),
+            KeyCode::Char('o') => {
+                self.sort_by_path = !self.sort_by_path;
+                self.sort();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn cells(&self, row: &CoverageRow) -> [String; 6] {
+        [
+            path_label(&row.segments),
+            format!("{} ({:.1}%)", row.left.records, Self::percent(row.left.records, self.left_total)),
+            format!("{} ({:.1}%)", row.right.records, Self::percent(row.right.records, self.right_total)),
+            format!("{:+.1}%", self.delta(row)),
+            row.left.type_label().to_string(),
+            row.right.type_label().to_string(),
+        ]
+    }
+
+    pub fn write_csv(&self, path: &Path) -> Result<()> {
+        let mut csv = String::from("path,left_count,left_percent,right_count,right_percent,delta_percent,left_type,right_type\n");
+        for row in &self.rows {
+            let label = path_label(&row.segments);
+            let label = if label.contains([',', '"', '\n']) { format!("\"{}\"", label.replace('"', "\"\"")) } else { label };
+            csv.push_str(&format!(
+                "{label},{},{:.2},{},{:.2},{:.2},{},{}\n",
+                row.left.records,
+                Self::percent(row.left.records, self.left_total),
+                row.right.records,
+                Self::percent(row.right.records, self.right_total),
+                self.delta(row),
+                row.left.type_label(),
+                row.right.type_label(),
+            ));
+        }
+        atomic::write(path, csv)
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        let rows: Vec<Value> = self
+            .rows
+            .iter()
+            .map(|row| {
+                json!({
+                    "path": path_label(&row.segments),
+                    "left": { "count": row.left.records, "percent": Self::percent(row.left.records, self.left_total), "type": row.left.dominant_type() },
+                    "right": { "count": row.right.records, "percent": Self::percent(row.right.records, self.right_total), "type": row.right.dominant_type() },
+                    "delta_percent": self.delta(row),
+                })
+            })
+            .collect();
+        let report = json!({ "left_records": self.left_total, "right_records": self.right_total, "fields": rows });
+        atomic::write(path, serde_json::to_string_pretty(&report)?)
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect) {
+        let header = Row::new(["path", "left", "right", "Δ presence", "left type", "right type"]).bold();
+        let rows = self.rows.iter().map(|row| {
+            let cells = self.cells(row);
+            let style = match self.delta(row) {
+                d if d < 0.0 => Style::default().fg(Color::Red),
+                d if d > 0.0 => Style::default().fg(Color::Green),
+                _ if row.left.dominant_type() != row.right.dominant_type() => Style::default().fg(Color::Yellow),
+                _ => Style::default(),
+            };
+            Row::new(cells.map(|cell| display::sanitize(&cell).into_owned())).style(style)
+        });
+        let widths = [
+            Constraint::Min(20),
+            Constraint::Length(16),
+            Constraint::Length(16),
+            Constraint::Length(11),
+            Constraint::Length(10),
+            Constraint::Length(10),
+        ];
+        let sort = if self.sort_by_path { "path" } else { "presence delta" };
+        let title = format!(
+            "Key coverage: {} left / {} right records, sorted by {sort}",
+            self.left_total, self.right_total
+        );
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = TableState::default().with_selected(Some(self.selected));
+        f.render_stateful_widget(table, area, &mut state);
+    }
+}
+
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Records each field path present in `value` once, with the type seen first.
+fn collect_paths(value: &Value, prefix: &mut Vec<Segment>, seen: &mut Vec<(Vec<Segment>, &'static str)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                prefix.push(Segment::Key(key.clone()));
+                if !seen.iter().any(|(segments, _)| segments == prefix) {
+                    seen.push((prefix.clone(), type_name(child)));
+                }
+                collect_paths(child, prefix, seen);
+                prefix.pop();
+            }
+        }
+        Value::Array(items) => {
+            prefix.push(Segment::Each);
+            for item in items {
+                collect_paths(item, prefix, seen);
+            }
+            prefix.pop();
+        }
+        _ => {}
+    }
+}
+
+/// Projects every record onto `segments`, so the main diff shows only that
+/// field: `{"path": value}` where present and `{}` where absent.
+pub fn project_records(records: &Value, segments: &[Segment]) -> Value {
+    let label = path_label(segments);
+    let project = |record: &Value| {
+        let mut map = Map::new();
+        if let Some(value) = lookup(record, segments) {
+            map.insert(label.clone(), value);
+        }
+        Value::Object(map)
+    };
+    match records {
+        Value::Array(items) => Value::Array(items.iter().map(project).collect()),
+        other => project(other),
+    }
+}
+
+fn lookup(value: &Value, segments: &[Segment]) -> Option<Value> {
+    let Some((first, rest)) = segments.split_first() else {
+        return Some(value.clone());
+    };
+    match first {
+        Segment::Key(key) => lookup(value.as_object()?.get(key)?, rest),
+        Segment::Each => {
+            let found: Vec<Value> = value.as_array()?.iter().filter_map(|item| lookup(item, rest)).collect();
+            (!found.is_empty()).then_some(Value::Array(found))
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Numbers compared and subtracted exactly. serde_json keeps each number's
+//! digits as written (its `arbitrary_precision` feature), so nothing goes
+//! through a float on the way: `0.1000000000000000000000001` isn't `0.1`,
+//! and `9007199254740993` isn't `9007199254740992`. Numbers written
+//! differently but of the same value, `1.0`, `1.00` and `1e0`, are equal
+//! by these rules, though no longer by `==`.
+
+use serde_json::{Number, Value};
+
+/// A number's exact value: `0.digits × 10^exponent`, the digits with no
+/// zeros leading or trailing, none for zero.
+#[derive(PartialEq)]
+pub struct Decimal {
+    negative: bool,
+    digits: String,
+    exponent: i64,
+}
+
+impl Decimal {
+    pub fn of(n: &Number) -> Option<Self> {
+        let text = n.to_string();
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.as_str()),
+        };
+        let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => (mantissa, exponent.strip_prefix('+').unwrap_or(exponent).parse::<i64>().ok()?),
+            None => (unsigned, 0),
+        };
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let all = format!("{integer}{fraction}");
+        let digits = all.trim_start_matches('0');
+        let exponent = exponent.checked_add(i64::try_from(integer.len()).ok()?)?.checked_sub(i64::try_from(all.len() - digits.len()).ok()?)?;
+        let digits = digits.trim_end_matches('0');
+        if digits.is_empty() {
+            return Some(Self { negative: false, digits: String::new(), exponent: 0 });
+        }
+        Some(Self { negative, digits: digits.to_string(), exponent })
+    }
+}
+
+/// Whether `n` is written as an integer, as opposed to `1.0` or `1e2`.
+pub fn is_integer(n: &Number) -> bool {
+    n.as_i64().is_some() || n.as_u64().is_some()
+}
+
+/// Whether `left` and `right` are numbers of the same value written the
+/// same way but for zeros or an exponent, as `1.0` and `1.00` are: both
+/// integers or both not.
+pub fn same_number(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(l), Value::Number(r)) => is_integer(l) == is_integer(r) && Decimal::of(l).is_some_and(|l| Decimal::of(r) == Some(l)),
+        _ => false,
+    }
+}
+
+/// `right - left` to `precision` decimals, signed, if both are written
+/// without an exponent and fit 38 digits at that precision: `+0.3` for
+/// `0.1 → 0.4`.
+pub fn difference(left: &Number, right: &Number, precision: usize) -> Option<String> {
+    let scaled = |n: &Number| -> Option<i128> {
+        let text = n.to_string();
+        if text.contains(['e', 'E']) {
+            return None;
+        }
+        let (integer, fraction) = text.split_once('.').unwrap_or((&text, ""));
+        if fraction.len() > precision {
+            return None;
+        }
+        format!("{integer}{fraction:0<precision$}").parse().ok()
+    };
+    let difference = scaled(right)?.checked_sub(scaled(left)?)?;
+    let sign = if difference < 0 { '-' } else { '+' };
+    let digits = format!("{:0>width$}", difference.unsigned_abs(), width = precision + 1);
+    Some(match precision {
+        0 => format!("{sign}{digits}"),
+        _ => {
+            let (integer, fraction) = digits.split_at(digits.len() - precision);
+            format!("{sign}{integer}.{fraction}")
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(text: &str) -> Value {
+        serde_json::from_str(text).unwrap()
+    }
+
+    fn decimal(text: &str) -> Decimal {
+        match number(text) {
+            Value::Number(n) => Decimal::of(&n).unwrap(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn difference_of(left: &str, right: &str, precision: usize) -> Option<String> {
+        match (number(left), number(right)) {
+            (Value::Number(l), Value::Number(r)) => difference(&l, &r, precision),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn integers_past_a_float_stay_apart() {
+        assert!(!same_number(&number("9007199254740993"), &number("9007199254740992")));
+        assert!(same_number(&number("9007199254740993"), &number("9007199254740993")));
+        let huge = "123456789012345678901234567890";
+        assert!(same_number(&number(huge), &number(huge)));
+        assert!(decimal(huge) != decimal("123456789012345678901234567891"));
+        assert_eq!(difference_of("9007199254740992", "9007199254740993", 0).as_deref(), Some("+1"));
+    }
+
+    #[test]
+    fn long_fractions_stay_apart() {
+        assert!(!same_number(&number("0.1000000000000000000000001"), &number("0.1")));
+        assert_eq!(difference_of("0.1", "0.1000000000000000000000001", 25).as_deref(), Some("+0.0000000000000000000000001"));
+        assert_eq!(difference_of("0.1", "0.4", 1).as_deref(), Some("+0.3"));
+        assert_eq!(difference_of("2.50", "-1", 2).as_deref(), Some("-3.50"));
+    }
+
+    #[test]
+    fn zeros_and_exponents_write_the_same_value() {
+        assert!(decimal("1.0") == decimal("1.00"));
+        assert!(decimal("1.0") == decimal("1e0"));
+        assert!(decimal("150") == decimal("1.5E+2"));
+        assert!(decimal("0.015") == decimal("15e-3"));
+        assert!(decimal("-0.0") == decimal("0"));
+        assert!(same_number(&number("1.0"), &number("1.00")));
+        assert!(same_number(&number("1.0"), &number("1e0")));
+        // An integer and a fraction of the same value are written apart.
+        assert!(!same_number(&number("1"), &number("1.0")));
+    }
+
+    #[test]
+    fn differences_need_plain_numbers_at_the_precision() {
+        assert_eq!(difference_of("1e2", "100", 0), None);
+        assert_eq!(difference_of("0.125", "0.5", 2), None);
+        assert_eq!(difference_of("1", "1", 2).as_deref(), Some("+0.00"));
+    }
+}
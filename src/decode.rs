@@ -0,0 +1,125 @@
+//! `--decode-base64 <path>`: strings at paths matching the pattern (see
+//! `ignore`) that hold base64-encoded JSON, such as the values of a
+//! Kubernetes secret or the payload of a JWT, are replaced by the JSON they
+//! encode before comparison, so they diff field by field instead of as two
+//! opaque strings. Standard and URL-safe base64 are read, padded or not.
+//! Where either side isn't base64-encoded JSON both are compared as the
+//! strings they are, with a note saying why. Redaction comes later in the
+//! pipeline, so the decoded values are redacted like any other.
+
+use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::ignore;
+
+pub enum Decoding {
+    Decoded,
+    /// Left as strings, for this reason.
+    Failed(String),
+}
+
+/// What became of the strings at each matching path, by dotted path.
+pub type Decoded = BTreeMap<String, Decoding>;
+
+/// The JSON that `text` encodes in base64, or why it doesn't.
+fn decode(text: &str) -> Result<Value, &'static str> {
+    let text = text.trim().trim_end_matches('=');
+    let engine = if text.contains(['-', '_']) { URL_SAFE_NO_PAD } else { STANDARD_NO_PAD };
+    let bytes = engine.decode(text).map_err(|_| "not base64")?;
+    let text = String::from_utf8(bytes).map_err(|_| "not UTF-8 once decoded")?;
+    serde_json::from_str(&text).map_err(|_| "not JSON once decoded")
+}
+
+/// Replaces the base64-encoded JSON strings of `left` and `right` at paths
+/// matching one of `patterns` by what they encode, and says where.
+pub fn apply(patterns: &[String], left: &mut Value, right: &mut Value) -> Decoded {
+    let mut decoded = Decoded::new();
+    if !patterns.is_empty() {
+        let patterns: Vec<Vec<String>> = patterns.iter().map(|pattern| pattern.split('.').map(str::to_string).collect()).collect();
+        apply_in(&patterns, [Some(left), Some(right)], &mut Vec::new(), &mut decoded);
+    }
+    decoded
+}
+
+fn apply_in(patterns: &[Vec<String>], sides: [Option<&mut Value>; 2], path: &mut Vec<String>, decoded: &mut Decoded) {
+    let strings = sides.each_ref().map(|side| matches!(side, Some(Value::String(_))));
+    if strings.contains(&true) && patterns.iter().any(|pattern| ignore::path_matches(pattern, path)) {
+        let results = sides.each_ref().map(|side| match side {
+            Some(Value::String(text)) => Some(decode(text)),
+            _ => None,
+        });
+        let failed = ["left", "right"].iter().zip(&results).find_map(|(side, result)| match result {
+            Some(Err(reason)) => Some(format!("the {side} is {reason}")),
+            _ => None,
+        });
+        match failed {
+            Some(reason) => {
+                decoded.insert(path.join("."), Decoding::Failed(reason));
+            }
+            None => {
+                for (side, result) in sides.into_iter().zip(results) {
+                    if let (Some(side), Some(Ok(value))) = (side, result) {
+                        *side = value;
+                    }
+                }
+                decoded.insert(path.join("."), Decoding::Decoded);
+            }
+        }
+        return;
+    }
+    match sides {
+        [Some(Value::Object(left)), Some(Value::Object(right))] => {
+            for (key, child) in left.iter_mut() {
+                path.push(key.clone());
+                apply_in(patterns, [Some(child), right.get_mut(key)], path, decoded);
+                path.pop();
+            }
+            for (key, child) in right.iter_mut().filter(|(key, _)| !left.contains_key(*key)) {
+                path.push(key.clone());
+                apply_in(patterns, [None, Some(child)], path, decoded);
+                path.pop();
+            }
+        }
+        [Some(Value::Array(left)), Some(Value::Array(right))] => {
+            let mut right = right.iter_mut();
+            for (index, child) in left.iter_mut().enumerate() {
+                path.push(index.to_string());
+                apply_in(patterns, [Some(child), right.next()], path, decoded);
+                path.pop();
+            }
+            for (index, child) in right.enumerate() {
+                path.push((left.len() + index).to_string());
+                apply_in(patterns, [None, Some(child)], path, decoded);
+                path.pop();
+            }
+        }
+        [left, right] => {
+            for (side, value) in [left, right].into_iter().enumerate() {
+                let children: Vec<(String, &mut Value)> = match value {
+                    Some(Value::Object(map)) => map.iter_mut().map(|(key, child)| (key.clone(), child)).collect(),
+                    Some(Value::Array(items)) => items.iter_mut().enumerate().map(|(index, child)| (index.to_string(), child)).collect(),
+                    _ => continue,
+                };
+                for (key, child) in children {
+                    path.push(key);
+                    let mut sides = [None, None];
+                    sides[side] = Some(child);
+                    apply_in(patterns, sides, path, decoded);
+                    path.pop();
+                }
+            }
+        }
+    }
+}
+
+/// ` (decoded from base64)` for the row at dotted `path` if its strings were
+/// decoded, or the note saying why they weren't.
+pub fn note(decoded: &Decoded, path: &str) -> String {
+    match decoded.get(path) {
+        Some(Decoding::Decoded) => " (decoded from base64)".to_string(),
+        Some(Decoding::Failed(reason)) => format!(" ⚠ compared as strings: {reason}"),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,53 @@
+//! Nesting-depth guard for JSON input.
+//!
+//! serde_json's recursion limit is disabled so documents may nest deeper than
+//! its fixed 128 levels; instead a cheap iterative pre-scan enforces our own
+//! limit, and every thread that parses, walks or drops documents is given a
+//! stack sized for that limit.
+
+use anyhow::{bail, Result};
+
+pub const DEFAULT_MAX_DEPTH: usize = 1024;
+
+const BASE_STACK: usize = 8 * 1024 * 1024;
+const STACK_PER_LEVEL: usize = 4 * 1024;
+
+/// Fails with the position of the first bracket that nests deeper than `max_depth`.
+pub fn check(contents: &str, max_depth: usize) -> Result<()> {
+    let (mut depth, mut line, mut column) = (0usize, 1, 0);
+    let (mut in_string, mut escaped) = (false, false);
+
+    for byte in contents.bytes() {
+        column += 1;
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max_depth {
+                    bail!("maximum nesting depth {max_depth} exceeded at line {line}, column {column}");
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            b'\n' => {
+                line += 1;
+                column = 0;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Stack size for threads handling documents nested up to `max_depth` levels.
+pub fn stack_size(max_depth: usize) -> usize {
+    BASE_STACK.saturating_add(max_depth.saturating_mul(STACK_PER_LEVEL))
+}
@@ -0,0 +1,1274 @@
+//! The comparison behind the diff view: the entries of the two documents in
+//! the order they are shown, how each differs, and which objects and arrays
+//! are expanded. `render_diff` in main turns a `DiffResult` into the panes'
+//! rows; filters, exports and statistics can work on the entries instead.
+//!
+//! `DiffOptions` are the settings that shape the comparison, as the command
+//! line sets them, the `O` panel edits them and the TUI's toggles flip them.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::arraykey::{self, ArrayKeys, Strategy};
+use crate::cancel::{CancelToken, Checkpoint};
+use crate::cli::{Cli, OnChange, DEFAULT_ARRAY_SUMMARY_THRESHOLD, DEFAULT_MAX_ENTRIES, DEFAULT_SUBTREE_THRESHOLD};
+use crate::compare::{Comparators, Structure, UnicodeForm};
+use crate::context::ContextFields;
+use crate::depth;
+use crate::decode::Decoded;
+use crate::duplicates::DuplicateKeys;
+use crate::hash::Hashes;
+use crate::history;
+use crate::ignore::IgnoreArgs;
+use crate::keyorder::KeyOrders;
+use crate::linediff;
+use crate::normalize::Notes;
+use crate::redact::Redaction;
+use crate::report::ChangeKind;
+use crate::timefmt::TimeAnnotator;
+
+/// The settings that shape a comparison. Unset fields of a serialized copy
+/// take their defaults, and the builder methods change one from there:
+/// `DiffOptions::default().ignore_key("timestamp").max_depth(5)`.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiffOptions {
+    /// Arrays with more elements than this on either side are summarized.
+    pub array_summary_threshold: usize,
+    /// Levels of nesting shown expanded; containers that differ always are.
+    pub expand_depth: usize,
+    /// `--max-depth`: objects and arrays nested deeper than this many levels
+    /// are collapsed even if they differ, with a count of their changes.
+    pub diff_depth: Option<usize>,
+    /// Values wider than this many columns are cut in the middle; 0 never,
+    /// `None` three rows of a pane.
+    pub truncate: Option<usize>,
+    /// Added or removed objects and arrays over this many bytes are shown as
+    /// a one-row summary until expanded; 0 never.
+    pub subtree_threshold: usize,
+    /// Entries shown before the rest are left to a footer; 0 shows them all.
+    /// Only affects what is shown of the diff, which is always made whole.
+    pub max_entries: usize,
+    /// Only affects how string values are displayed.
+    pub decode_escapes: bool,
+    pub relative_times: bool,
+    /// Each value is followed by its type, and an object's or array's size.
+    pub type_tags: bool,
+    pub lenient: bool,
+    pub on_change: OnChange,
+    pub ignore_files: bool,
+    pub ignores: Vec<String>,
+    pub masks: Vec<String>,
+    /// `--ignore-key` names, ignored at any depth.
+    pub ignored_keys: Vec<String>,
+    /// `--array` specs, `--array-key` ones written as `key:<field>`.
+    pub array_keys: Vec<String>,
+    /// Compares arrays as unordered collections.
+    pub unordered_arrays: bool,
+    /// Keys present on both sides with equal values, written in another
+    /// order, are moved; the panes keep the order keys were written in.
+    pub key_order: bool,
+    /// `--compare` names, see `compare::NAMES`.
+    pub comparators: Vec<String>,
+    /// `--equal-if` rules, see `compare::MatchRule`.
+    pub equal_rules: Vec<String>,
+    /// `--compare-with` rules, see `external::ExternalRule`.
+    pub external_rules: Vec<String>,
+    /// `--compare-units` patterns: durations and sizes there are compared as
+    /// quantities.
+    pub unit_paths: Vec<String>,
+    /// `--compare-urls` patterns: URLs there are compared normalized.
+    pub url_paths: Vec<String>,
+    /// Numbers at most this far apart are equal.
+    pub tolerance: Option<f64>,
+    /// An integer and a float of the same value differ.
+    pub exact_numbers: bool,
+    /// Strings and keys are compared in this normalization form.
+    pub unicode_form: Option<UnicodeForm>,
+    /// Timestamps at most this many milliseconds apart are equal.
+    pub timestamps: Option<u64>,
+    /// Only the shape of the documents is compared, and shown.
+    pub structure: Option<Structure>,
+    /// Schemas inferred from the documents are compared instead of them.
+    pub schema: bool,
+    /// A key on the left only and one on the right only with equal values
+    /// are shown as one renamed key.
+    pub detect_renames: bool,
+    /// `--decode-base64` patterns: base64-encoded JSON strings there are
+    /// compared decoded.
+    pub decode_base64: Vec<String>,
+    /// `--select`: only this subtree of both documents is compared.
+    pub select: Option<String>,
+    /// Names each row by its whole path rather than its key.
+    pub full_paths: bool,
+    /// Prefixes each row with its change marker, `+`, `-`, `~` or `!`.
+    pub markers: bool,
+    /// A changed number's change is shown as a percentage, not a ratio.
+    pub percent_change: bool,
+    /// Documents nested deeper than this are rejected.
+    pub max_depth: usize,
+    /// What a key repeated within a JSON object is read as.
+    pub duplicate_keys: DuplicateKeys,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            array_summary_threshold: DEFAULT_ARRAY_SUMMARY_THRESHOLD,
+            expand_depth: 1,
+            diff_depth: None,
+            truncate: None,
+            subtree_threshold: DEFAULT_SUBTREE_THRESHOLD,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            decode_escapes: false,
+            relative_times: false,
+            type_tags: false,
+            lenient: false,
+            on_change: OnChange::Refresh,
+            ignore_files: true,
+            ignores: Vec::new(),
+            ignored_keys: Vec::new(),
+            masks: Vec::new(),
+            array_keys: Vec::new(),
+            unordered_arrays: false,
+            key_order: false,
+            comparators: Vec::new(),
+            equal_rules: Vec::new(),
+            external_rules: Vec::new(),
+            unit_paths: Vec::new(),
+            url_paths: Vec::new(),
+            tolerance: None,
+            exact_numbers: false,
+            unicode_form: None,
+            timestamps: None,
+            structure: None,
+            schema: false,
+            detect_renames: false,
+            decode_base64: Vec::new(),
+            select: None,
+            full_paths: false,
+            markers: true,
+            percent_change: false,
+            max_depth: depth::DEFAULT_MAX_DEPTH,
+            duplicate_keys: DuplicateKeys::Warn,
+        }
+    }
+}
+
+impl DiffOptions {
+    pub fn from_cli(cli: &Cli) -> Self {
+        let options = Self::default()
+            .array_summary_threshold(cli.array_summary_threshold)
+            .expand_depth(cli.expand_depth)
+            .diff_depth(cli.diff_depth)
+            .truncate(cli.truncate)
+            .subtree_threshold(cli.subtree_threshold)
+            .max_entries(cli.max_entries)
+            .relative_times(cli.relative_times)
+            .type_tags(cli.type_tags)
+            .lenient(cli.lenient)
+            .on_change(cli.on_change)
+            .ignore_files(!cli.ignore.no_file)
+            .unordered_arrays(cli.unordered_arrays)
+            .key_order(cli.key_order)
+            .tolerance(cli.tolerance)
+            .exact_numbers(cli.exact_numbers)
+            .unicode_form(cli.unicode_form)
+            .timestamps(cli.timestamps)
+            .structure(cli.structure)
+            .schema(cli.schema)
+            .detect_renames(cli.detect_renames)
+            .select(cli.select.clone())
+            .full_paths(cli.full_paths)
+            .markers(!cli.no_markers)
+            .percent_change(cli.percent_change)
+            .max_depth(cli.max_depth_parse)
+            .duplicate_keys(cli.duplicate_keys);
+        let options = cli.ignore.ignores.iter().fold(options, |options, pattern| options.ignore_key(pattern));
+        let options = cli.ignore.keys.iter().fold(options, |options, name| options.ignore_key_name(name));
+        let options = cli.ignore.masks.iter().fold(options, |options, pattern| options.mask_key(pattern));
+        let options = cli.comparators.iter().fold(options, |options, name| options.comparator(name));
+        let options = cli.equal_rules.iter().fold(options, |options, rule| options.equal_if(rule));
+        let options = cli.external_rules.iter().fold(options, |options, rule| options.compare_with(rule));
+        let options = cli.unit_paths.iter().fold(options, |options, pattern| options.compare_units(pattern));
+        let options = cli.url_paths.iter().fold(options, |options, pattern| options.compare_urls(pattern));
+        let options = cli.decode_base64.iter().fold(options, |options, pattern| options.decode_base64(pattern));
+        cli.array_keys.specs().iter().fold(options, |options, spec| options.array_key(spec))
+    }
+
+    pub fn array_summary_threshold(mut self, threshold: usize) -> Self {
+        self.array_summary_threshold = threshold;
+        self
+    }
+
+    pub fn expand_depth(mut self, depth: usize) -> Self {
+        self.expand_depth = depth;
+        self
+    }
+
+    pub fn diff_depth(mut self, depth: Option<usize>) -> Self {
+        self.diff_depth = depth;
+        self
+    }
+
+    pub fn truncate(mut self, limit: Option<usize>) -> Self {
+        self.truncate = limit;
+        self
+    }
+
+    pub fn subtree_threshold(mut self, threshold: usize) -> Self {
+        self.subtree_threshold = threshold;
+        self
+    }
+
+    pub fn max_entries(mut self, limit: usize) -> Self {
+        self.max_entries = limit;
+        self
+    }
+
+    pub fn relative_times(mut self, on: bool) -> Self {
+        self.relative_times = on;
+        self
+    }
+
+    pub fn type_tags(mut self, on: bool) -> Self {
+        self.type_tags = on;
+        self
+    }
+
+    pub fn lenient(mut self, on: bool) -> Self {
+        self.lenient = on;
+        self
+    }
+
+    pub fn on_change(mut self, on_change: OnChange) -> Self {
+        self.on_change = on_change;
+        self
+    }
+
+    pub fn ignore_files(mut self, on: bool) -> Self {
+        self.ignore_files = on;
+        self
+    }
+
+    /// Adds an `--ignore` pattern.
+    pub fn ignore_key(mut self, pattern: &str) -> Self {
+        self.ignores.push(pattern.to_string());
+        self
+    }
+
+    /// Adds an `--ignore-key` name.
+    pub fn ignore_key_name(mut self, name: &str) -> Self {
+        self.ignored_keys.push(name.to_string());
+        self
+    }
+
+    /// Adds a `--mask` pattern.
+    pub fn mask_key(mut self, pattern: &str) -> Self {
+        self.masks.push(pattern.to_string());
+        self
+    }
+
+    /// Adds an `--array` spec.
+    pub fn array_key(mut self, spec: &str) -> Self {
+        self.array_keys.push(spec.to_string());
+        self
+    }
+
+    /// Enables a comparator shipped with diffrs, by its `--compare` name.
+    pub fn comparator(mut self, name: &str) -> Self {
+        self.comparators.push(name.to_string());
+        self
+    }
+
+    /// Adds an `--equal-if` rule.
+    pub fn equal_if(mut self, rule: &str) -> Self {
+        self.equal_rules.push(rule.to_string());
+        self
+    }
+
+    /// Adds a `--compare-with` rule.
+    pub fn compare_with(mut self, rule: &str) -> Self {
+        self.external_rules.push(rule.to_string());
+        self
+    }
+
+    /// Adds a `--compare-units` pattern.
+    pub fn compare_units(mut self, pattern: &str) -> Self {
+        self.unit_paths.push(pattern.to_string());
+        self
+    }
+
+    /// Adds a `--compare-urls` pattern.
+    pub fn compare_urls(mut self, pattern: &str) -> Self {
+        self.url_paths.push(pattern.to_string());
+        self
+    }
+
+    pub fn tolerance(mut self, epsilon: Option<f64>) -> Self {
+        self.tolerance = epsilon;
+        self
+    }
+
+    pub fn exact_numbers(mut self, on: bool) -> Self {
+        self.exact_numbers = on;
+        self
+    }
+
+    pub fn unicode_form(mut self, form: Option<UnicodeForm>) -> Self {
+        self.unicode_form = form;
+        self
+    }
+
+    pub fn timestamps(mut self, skew: Option<u64>) -> Self {
+        self.timestamps = skew;
+        self
+    }
+
+    pub fn structure(mut self, structure: Option<Structure>) -> Self {
+        self.structure = structure;
+        self
+    }
+
+    pub fn schema(mut self, on: bool) -> Self {
+        self.schema = on;
+        self
+    }
+
+    pub fn detect_renames(mut self, on: bool) -> Self {
+        self.detect_renames = on;
+        self
+    }
+
+    /// Adds a `--decode-base64` pattern.
+    pub fn decode_base64(mut self, pattern: &str) -> Self {
+        self.decode_base64.push(pattern.to_string());
+        self
+    }
+
+    pub fn select(mut self, path: Option<String>) -> Self {
+        self.select = path;
+        self
+    }
+
+    pub fn unordered_arrays(mut self, on: bool) -> Self {
+        self.unordered_arrays = on;
+        self
+    }
+
+    pub fn key_order(mut self, on: bool) -> Self {
+        self.key_order = on;
+        self
+    }
+
+    pub fn full_paths(mut self, on: bool) -> Self {
+        self.full_paths = on;
+        self
+    }
+
+    pub fn markers(mut self, on: bool) -> Self {
+        self.markers = on;
+        self
+    }
+
+    pub fn percent_change(mut self, on: bool) -> Self {
+        self.percent_change = on;
+        self
+    }
+
+    pub fn duplicate_keys(mut self, policy: DuplicateKeys) -> Self {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// The `--ignore`, `--mask`, `--ignore-key` and `--no-ignore-file`
+    /// options these were built from, to build the rules again.
+    pub fn ignore_args(&self) -> IgnoreArgs {
+        IgnoreArgs {
+            no_file: !self.ignore_files,
+            ignores: self.ignores.clone(),
+            masks: self.masks.clone(),
+            keys: self.ignored_keys.clone(),
+            show: false,
+        }
+    }
+
+    /// The command-line flags that set these options, leaving out defaults.
+    pub fn flags(&self) -> Vec<String> {
+        let defaults = Self::default();
+        let mut flags = Vec::new();
+        let mut number = |flag: &str, value: usize, default: usize| {
+            if value != default {
+                flags.extend([flag.to_string(), value.to_string()]);
+            }
+        };
+        number("--array-summary-threshold", self.array_summary_threshold, defaults.array_summary_threshold);
+        number("--expand-depth", self.expand_depth, defaults.expand_depth);
+        number("--subtree-threshold", self.subtree_threshold, defaults.subtree_threshold);
+        number("--max-entries", self.max_entries, defaults.max_entries);
+        number("--max-depth-parse", self.max_depth, defaults.max_depth);
+        if let Some(depth) = self.diff_depth {
+            flags.extend(["--max-depth".to_string(), depth.to_string()]);
+        }
+        if self.duplicate_keys != defaults.duplicate_keys {
+            flags.extend(["--duplicate-keys".to_string(), self.duplicate_keys.name().to_string()]);
+        }
+        if let Some(truncate) = self.truncate {
+            flags.extend(["--truncate".to_string(), truncate.to_string()]);
+        }
+        if let Some(path) = &self.select {
+            flags.extend(["--select".to_string(), path.clone()]);
+        }
+        if let Some(epsilon) = self.tolerance {
+            flags.extend(["--tolerance".to_string(), epsilon.to_string()]);
+        }
+        match self.unicode_form {
+            Some(UnicodeForm::Nfc) => flags.push("--normalize-unicode".to_string()),
+            Some(form) => flags.push(format!("--normalize-unicode={}", form.name())),
+            None => {}
+        }
+        match self.structure {
+            Some(Structure::Types) => flags.push("--structure".to_string()),
+            Some(Structure::Lengths) => flags.push("--structure=lengths".to_string()),
+            None => {}
+        }
+        match self.timestamps {
+            Some(0) => flags.push("--compare-timestamps".to_string()),
+            Some(skew) => flags.push(format!("--compare-timestamps={}", skew as f64 / 1000.0)),
+            None => {}
+        }
+        for (set, flag) in [
+            (self.relative_times, "--relative-times"),
+            (self.type_tags, "--type-tags"),
+            (self.lenient, "--lenient"),
+            (self.unordered_arrays, "--unordered-arrays"),
+            (self.key_order, "--key-order"),
+            (self.schema, "--schema"),
+            (self.detect_renames, "--detect-renames"),
+            (self.full_paths, "--full-paths"),
+            (!self.markers, "--no-markers"),
+            (self.percent_change, "--percent-change"),
+            (self.exact_numbers, "--exact-numbers"),
+            (self.on_change == OnChange::Stale, "--on-change stale"),
+            (!self.ignore_files, "--no-ignore-file"),
+        ] {
+            if set {
+                flags.extend(flag.split(' ').map(str::to_string));
+            }
+        }
+        let lists = [("--ignore", &self.ignores), ("--mask", &self.masks), ("--ignore-key", &self.ignored_keys), ("--array", &self.array_keys), ("--compare", &self.comparators), ("--equal-if", &self.equal_rules), ("--compare-with", &self.external_rules), ("--compare-units", &self.unit_paths), ("--compare-urls", &self.url_paths), ("--decode-base64", &self.decode_base64)];
+        for (flag, patterns) in lists {
+            for pattern in patterns {
+                flags.extend([flag.to_string(), pattern.clone()]);
+            }
+        }
+        flags
+    }
+
+    /// `flags` as a shell command line.
+    pub fn command_line(&self) -> String {
+        let quote = |arg: &String| {
+            if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_.,/=".contains(c)) {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        };
+        ["diffrs".to_string()].iter().chain(&self.flags()).map(quote).collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// How the documents are compared and their rows laid out.
+pub struct DiffView<'a> {
+    pub options: &'a DiffOptions,
+    /// Original text of values the normalizers rewrote, shown next to them.
+    pub notes: &'a [Notes; 2],
+    /// Annotates timestamps with relative times when set.
+    pub times: Option<&'a TimeAnnotator>,
+    /// Containers the user expanded (`true`) or collapsed, by row path.
+    pub folds: &'a BTreeMap<String, bool>,
+    /// Labels array elements by their identifying fields.
+    pub context: &'a ContextFields,
+    /// Pairs array elements by a field rather than by alignment.
+    pub array_keys: &'a ArrayKeys,
+    /// Decide which differing values are equal all the same.
+    pub comparators: &'a Comparators,
+    /// `options.truncate`, resolved against the panes' width.
+    pub truncate: usize,
+    /// Dotted paths of values `--lenient` repaired on either side.
+    pub repaired: &'a BTreeSet<String>,
+    /// Where `--decode-base64` decoded strings, or didn't.
+    pub decoded: &'a Decoded,
+    /// Tell differing subtrees of the documents apart without walking them.
+    pub hashes: &'a Hashes,
+    /// With `--key-order`, the order keys were written in; empty otherwise.
+    pub key_orders: &'a KeyOrders,
+    /// Secrets of the documents, compared as they are and shown as tags.
+    pub redaction: &'a Redaction,
+    pub now: i64,
+}
+
+pub enum DiffResult<'v> {
+    /// Entries in display order, each expanded object or array followed by
+    /// its children.
+    Entries(Vec<DiffEntry<'v>>),
+    /// Documents that can't be compared key by key, to diff line by line.
+    Lines(&'v Value, &'v Value),
+}
+
+pub struct DiffEntry<'v> {
+    /// The path the rows record (`servers[2].port`), which `view.folds` is
+    /// keyed by; empty for the root.
+    pub path: String,
+    /// The same path split, indices as numbers.
+    pub segments: Vec<String>,
+    /// `None` where both sides are equal.
+    pub kind: Option<ChangeKind>,
+    pub left: Option<&'v Value>,
+    pub right: Option<&'v Value>,
+    /// How an array element was paired; `None` for an object member.
+    pub element: Option<Element>,
+    pub shape: Shape,
+    /// The right side's key, where keys paired by their Unicode form are
+    /// spelled differently; the path has the left side's.
+    pub right_key: Option<String>,
+    /// With `--key-order`, for a member equal on both sides but written in
+    /// another place among their shared keys: its position on each side.
+    pub key_moved: Option<[usize; 2]>,
+    /// For an object or array shown collapsed though it differs, or because
+    /// it is below `--max-depth`, how many changes it holds; `Some(0)` if
+    /// it is equal.
+    pub hidden_changes: Option<usize>,
+}
+
+#[derive(Clone, Copy)]
+pub enum Shape {
+    /// An object or array shown expanded; its children follow.
+    Expanded,
+    /// A value, or an object or array shown collapsed.
+    Value,
+    /// An array summarized by the counts of its elements; only the elements
+    /// that differ follow.
+    Summary(Counts),
+    /// An element of a summarized array that isn't an object or array on
+    /// both sides.
+    SummaryElement,
+}
+
+/// A summarized array's elements by how they differ.
+#[derive(Clone, Copy, Default)]
+pub struct Counts {
+    pub changed: usize,
+    pub added: usize,
+    pub removed: usize,
+    pub moved: usize,
+    pub equal: usize,
+}
+
+/// How many values differ, by kind, and how many are equal: the one-line
+/// summary of a diff in the status bar and from `--stats`. A value added or
+/// removed counts once however big it is; an equal object or array counts
+/// its leaves, and an equal element of a summarized array counts once.
+#[derive(Clone, Copy, Default)]
+pub struct Stats {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    pub type_changed: usize,
+    pub renamed: usize,
+    pub moved: usize,
+    pub unchanged: usize,
+}
+
+impl Stats {
+    pub fn count(entries: &[DiffEntry]) -> Self {
+        let mut stats = Self::default();
+        for entry in entries {
+            match entry.shape {
+                Shape::Expanded => {}
+                Shape::Summary(counts) => stats.unchanged += counts.equal,
+                Shape::Value | Shape::SummaryElement => match entry.kind {
+                    Some(ChangeKind::Moved) if entry.hidden_changes.is_some_and(|changes| changes > 0) => {
+                        stats.moved += entry.hidden_changes.unwrap_or_default()
+                    }
+                    Some(_) if entry.hidden_changes.is_some_and(|changes| changes > 0) => {
+                        stats.changed += entry.hidden_changes.unwrap_or_default()
+                    }
+                    Some(kind) => stats.record(kind),
+                    None => stats.unchanged += entry.left.or(entry.right).map_or(0, history::leaves),
+                },
+            }
+        }
+        stats
+    }
+
+    /// The rows of a line diff, a line removed and one added on the same row
+    /// counting as one change.
+    pub fn count_lines(kinds: &[Option<ChangeKind>]) -> Self {
+        let mut stats = Self::default();
+        for kind in kinds {
+            match kind {
+                Some(kind) => stats.record(*kind),
+                None => stats.unchanged += 1,
+            }
+        }
+        stats
+    }
+
+    fn record(&mut self, kind: ChangeKind) {
+        match kind {
+            ChangeKind::Added => self.added += 1,
+            ChangeKind::Removed => self.removed += 1,
+            ChangeKind::Changed => self.changed += 1,
+            ChangeKind::TypeChanged => self.type_changed += 1,
+            ChangeKind::Renamed => self.renamed += 1,
+            ChangeKind::Moved => self.moved += 1,
+        }
+    }
+
+    pub fn identical(&self) -> bool {
+        self.added + self.removed + self.changed + self.type_changed + self.renamed + self.moved == 0
+    }
+}
+
+/// `3 added, 1 removed, 7 changed, 212 unchanged`, with type changes when
+/// there are any, or `identical documents`.
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.identical() {
+            return f.write_str("identical documents");
+        }
+        write!(f, "{} added, {} removed, {} changed", self.added, self.removed, self.changed)?;
+        if self.type_changed > 0 {
+            write!(f, ", {} type-changed", self.type_changed)?;
+        }
+        if self.renamed > 0 {
+            write!(f, ", {} renamed", self.renamed)?;
+        }
+        if self.moved > 0 {
+            write!(f, ", {} moved", self.moved)?;
+        }
+        write!(f, ", {} unchanged", self.unchanged)
+    }
+}
+
+/// Where an array element is on each side once the arrays are aligned.
+#[derive(Clone, Copy)]
+pub struct Element {
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    /// For an element removed in one place and added, unchanged, in another:
+    /// its index on the left. It is one element, on both sides.
+    pub moved: Option<usize>,
+    /// Paired by position because it lacks the array's key field.
+    pub unkeyed: bool,
+    /// In a multiset, how many times the value occurs on both sides, or how
+    /// many more times on the side it is on.
+    pub count: Option<usize>,
+}
+
+impl Element {
+    /// The index the element's row goes by: its index on the right, or on
+    /// the left if it was removed.
+    pub fn index(self) -> usize {
+        self.right.or(self.left).expect("an element is on one side at least")
+    }
+
+    /// Shown after the element's value; `key` is the array's key field.
+    pub fn note(self, key: Option<&str>) -> String {
+        let moved = match (self.moved, self.right, self.count) {
+            (Some(from), Some(to), _) => format!(" ⇄ moved from [{from}] to [{to}]"),
+            (_, _, Some(count)) => format!(" (×{count})"),
+            _ => String::new(),
+        };
+        match key {
+            Some(key) if self.unkeyed => format!("{moved} ⚠ no {key}, matched by position"),
+            _ => moved,
+        }
+    }
+}
+
+/// Returns `None` if `cancel` fires mid-walk. Objects are compared key by
+/// key, nested objects and arrays expanded down to `view.options.expand_depth`
+/// levels (and wherever they differ); arrays longer than the summary threshold, and
+/// root arrays whose elements are paired by key or value, are summarized,
+/// with only their differing elements as entries. Other root arrays are
+/// compared element by element, as nested ones are. Scalar roots, and roots
+/// of different types, are left to a line diff.
+pub fn compare<'v>(left: &'v Value, right: &'v Value, view: &DiffView, cancel: &CancelToken) -> Option<DiffResult<'v>> {
+    let mut entries = Vec::new();
+    let mut checkpoint = Checkpoint::new(cancel);
+
+    if let (Some(left_map), Some(right_map)) = (left.as_object(), right.as_object()) {
+        let moved = view.key_orders.moved(left_map, right_map);
+        for (key, values, right_key) in members(view, left_map, right_map) {
+            let first = entries.len();
+            push_entry(&mut entries, &mut checkpoint, view, &mut vec![key.clone()], key, values, None)?;
+            name_right_key(view, &mut entries[first], right_key);
+            mark_moved(&mut entries[first], moved.get(key).copied());
+        }
+    } else if let (Some(left_items), Some(right_items)) = (left.as_array(), right.as_array()) {
+        let paired = view.array_keys.strategy(&[], view.options.unordered_arrays) != Strategy::Ordered;
+        if paired || left_items.len().max(right_items.len()) > view.options.array_summary_threshold {
+            summarize_array(&mut entries, &mut checkpoint, view, &mut Vec::new(), "", (left, right), None)?;
+        } else {
+            // Element by element, aligned as the elements of nested arrays are.
+            for element in align_elements(left_items, right_items, view, &[], &mut checkpoint)? {
+                let index = element.index();
+                let values = (element.left.map(|i| &left_items[i]), element.right.map(|j| &right_items[j]));
+                push_entry(&mut entries, &mut checkpoint, view, &mut vec![index.to_string()], &format!("[{index}]"), values, Some(element))?;
+            }
+        }
+    } else {
+        return Some(DiffResult::Lines(left, right));
+    }
+
+    Some(DiffResult::Entries(entries))
+}
+
+/// Pushes the entry for one value, then its children's if it is an expanded
+/// object or array. `segments` is the entry's document path and `path` the
+/// same path as the rows record it.
+fn push_entry<'v>(
+    entries: &mut Vec<DiffEntry<'v>>,
+    checkpoint: &mut Checkpoint,
+    view: &DiffView,
+    segments: &mut Vec<String>,
+    path: &str,
+    (left, right): (Option<&'v Value>, Option<&'v Value>),
+    element: Option<Element>,
+) -> Option<()> {
+    if checkpoint.tick() {
+        return None;
+    }
+    let level = segments.len() - 1;
+    let (left_value, right_value) = (left.unwrap_or(&Value::Null), right.unwrap_or(&Value::Null));
+    if let (Some(left_items), Some(right_items)) = (left_value.as_array(), right_value.as_array()) {
+        if left_items.len().max(right_items.len()) > view.options.array_summary_threshold {
+            return summarize_array(entries, checkpoint, view, segments, path, (left_value, right_value), element);
+        }
+    }
+    let differs = change_kind(view, path, left, right);
+    let kind = element_kind(element, differs);
+    let container =
+        matches!((left_value, right_value), (Value::Object(_), Value::Object(_)) | (Value::Array(_), Value::Array(_)));
+    let below_limit = view.options.diff_depth.is_some_and(|depth| level >= depth);
+    let expanded = container
+        && view.folds.get(path).copied().unwrap_or(!below_limit && (level + 1 < view.options.expand_depth || differs.is_some()));
+    let shape = if expanded { Shape::Expanded } else { Shape::Value };
+    let hidden_changes = match (container && !expanded, differs) {
+        (true, Some(_)) => Some(nested_changes(view, checkpoint, segments, path, (left_value, right_value))?),
+        (true, None) if below_limit => Some(0),
+        _ => None,
+    };
+    entries.push(DiffEntry {
+        path: path.to_string(),
+        segments: segments.clone(),
+        kind,
+        left,
+        right,
+        element,
+        shape,
+        right_key: None,
+        key_moved: None,
+        hidden_changes,
+    });
+    if !expanded {
+        return Some(());
+    }
+
+    type Child<'v> = (String, String, (Option<&'v Value>, Option<&'v Value>), Option<Element>, Option<&'v String>);
+    let moved = match (left_value, right_value) {
+        (Value::Object(left_map), Value::Object(right_map)) => view.key_orders.moved(left_map, right_map),
+        _ => BTreeMap::new(),
+    };
+    let children: Vec<Child> = match (left_value, right_value) {
+        (Value::Object(left_map), Value::Object(right_map)) => members(view, left_map, right_map)
+            .into_iter()
+            .map(|(child, values, right_key)| (child.clone(), format!("{path}.{child}"), values, None, right_key))
+            .collect(),
+        (Value::Array(left_items), Value::Array(right_items)) => align_elements(left_items, right_items, view, segments, checkpoint)?
+            .into_iter()
+            .map(|element| {
+                let index = element.index();
+                let values = (element.left.map(|i| &left_items[i]), element.right.map(|j| &right_items[j]));
+                (index.to_string(), format!("{path}[{index}]"), values, Some(element), None)
+            })
+            .collect(),
+        _ => unreachable!(),
+    };
+    for (child, child_path, values, element, right_key) in children {
+        let positions = element.is_none().then(|| moved.get(&child).copied()).flatten();
+        segments.push(child);
+        let first = entries.len();
+        push_entry(entries, checkpoint, view, segments, &child_path, values, element)?;
+        name_right_key(view, &mut entries[first], right_key);
+        mark_moved(&mut entries[first], positions);
+        segments.pop();
+    }
+    Some(())
+}
+
+/// The members of two objects in key order: the key, each side's value, and
+/// the right side's key where it is spelled differently, as keys paired by
+/// their Unicode normalization form or as renames can be.
+type Member<'v> = (&'v String, (Option<&'v Value>, Option<&'v Value>), Option<&'v String>);
+
+fn members<'v>(view: &DiffView, left: &'v Map<String, Value>, right: &'v Map<String, Value>) -> Vec<Member<'v>> {
+    let members = paired_members(view, left, right);
+    if view.options.detect_renames {
+        pair_renames(members)
+    } else {
+        members
+    }
+}
+
+fn paired_members<'v>(view: &DiffView, left: &'v Map<String, Value>, right: &'v Map<String, Value>) -> Vec<Member<'v>> {
+    match view.options.unicode_form {
+        Some(form) => form
+            .pair_members(left, right)
+            .into_iter()
+            .map(|[l, r]| {
+                let key = l.or(r).expect("a member is on one side at least").0;
+                (key, (l.map(|(_, value)| value), r.map(|(_, value)| value)), r.map(|(key, _)| key).filter(|right_key| *right_key != key))
+            })
+            .collect(),
+        None => {
+            let keys: BTreeSet<&String> = left.keys().chain(right.keys()).collect();
+            keys.into_iter().map(|key| (key, (left.get(key), right.get(key)), None)).collect()
+        }
+    }
+}
+
+/// `members` with each member on the left only paired with one on the right
+/// only whose value is equal, where no other member on either side has that
+/// value: two removed keys with the same value stay removed.
+fn pair_renames(members: Vec<Member>) -> Vec<Member> {
+    let removed: Vec<(&String, &Value)> = members.iter().filter_map(|(key, values, _)| match values {
+        (Some(value), None) => Some((*key, *value)),
+        _ => None,
+    }).collect();
+    let added: Vec<(&String, &Value)> = members.iter().filter_map(|(key, values, _)| match values {
+        (None, Some(value)) => Some((*key, *value)),
+        _ => None,
+    }).collect();
+    let mut renames = BTreeMap::new();
+    for (old_key, value) in &removed {
+        let mut same_removed = removed.iter().filter(|(_, other)| other == value);
+        let mut same_added = added.iter().filter(|(_, other)| other == value);
+        if let (Some(_), None, Some(&(new_key, new_value)), None) = (same_removed.next(), same_removed.next(), same_added.next(), same_added.next()) {
+            renames.insert(*old_key, (new_key, new_value));
+        }
+    }
+    let new_keys: BTreeSet<&String> = renames.values().map(|(new_key, _)| *new_key).collect();
+    members
+        .into_iter()
+        .filter(|(key, values, _)| !(values.0.is_none() && new_keys.contains(key)))
+        .map(|(key, values, right_key)| match renames.get(key) {
+            Some(&(new_key, new_value)) => (key, (values.0, Some(new_value)), Some(new_key)),
+            None => (key, values, right_key),
+        })
+        .collect()
+}
+
+/// Sets the entry's right-side key, and makes it a rename if the key is not
+/// just spelled in another Unicode form.
+fn name_right_key(view: &DiffView, entry: &mut DiffEntry, right_key: Option<&String>) {
+    entry.right_key = right_key.cloned();
+    if entry.segments.last().is_some_and(|key| renamed(view, key, right_key)) {
+        entry.kind = Some(ChangeKind::Renamed);
+    }
+}
+
+/// Makes the entry of a member equal on both sides but written elsewhere
+/// among their keys a move, from and to `positions`.
+fn mark_moved(entry: &mut DiffEntry, positions: Option<[usize; 2]>) {
+    if let (Some(positions), None | Some(ChangeKind::Moved)) = (positions, entry.kind) {
+        entry.kind = Some(ChangeKind::Moved);
+        entry.key_moved = Some(positions);
+    }
+}
+
+fn renamed(view: &DiffView, key: &str, right_key: Option<&String>) -> bool {
+    right_key.is_some_and(|right_key| view.options.unicode_form.is_none_or(|form| form.normalize(key) != form.normalize(right_key)))
+}
+
+/// Pushes a summary of the arrays `left` and `right`, paired as
+/// `align_elements` does, and then only their differing elements; those that
+/// are objects or arrays on both sides are compared key by key, like any
+/// entry. `path` is the array's path, empty for the root.
+fn summarize_array<'v>(
+    entries: &mut Vec<DiffEntry<'v>>,
+    checkpoint: &mut Checkpoint,
+    view: &DiffView,
+    segments: &mut Vec<String>,
+    path: &str,
+    (left, right): (&'v Value, &'v Value),
+    element: Option<Element>,
+) -> Option<()> {
+    let (left_items, right_items) = match (left, right) {
+        (Value::Array(left_items), Value::Array(right_items)) => (left_items, right_items),
+        _ => unreachable!(),
+    };
+    let mut counts = Counts::default();
+    let mut differing = Vec::new();
+    for element in align_elements(left_items, right_items, view, segments, checkpoint)? {
+        let element_path = format!("{path}[{}]", element.index());
+        match (element.left, element.right, element.moved) {
+            (Some(_), Some(_), Some(_)) => counts.moved += 1,
+            (Some(i), Some(j), None) if change_kind(view, &element_path, Some(&left_items[i]), Some(&right_items[j])).is_none() => {
+                counts.equal += 1;
+                continue;
+            }
+            (Some(_), Some(_), None) => counts.changed += 1,
+            (Some(_), None, _) => counts.removed += 1,
+            (None, Some(_), _) => counts.added += 1,
+            (None, None, _) => unreachable!(),
+        }
+        differing.push(element);
+    }
+    let kind = (!differing.is_empty()).then_some(ChangeKind::Changed);
+    let shape = Shape::Summary(counts);
+    entries.push(DiffEntry {
+        path: path.to_string(),
+        segments: segments.clone(),
+        kind,
+        left: Some(left),
+        right: Some(right),
+        element,
+        shape,
+        right_key: None,
+        key_moved: None,
+        hidden_changes: None,
+    });
+
+    for element in differing {
+        let (left_item, right_item) = (element.left.map(|i| &left_items[i]), element.right.map(|j| &right_items[j]));
+        let element_path = format!("{path}[{}]", element.index());
+        segments.push(element.index().to_string());
+        if let (Some(Value::Object(_)), Some(Value::Object(_))) | (Some(Value::Array(_)), Some(Value::Array(_))) =
+            (left_item, right_item)
+        {
+            push_entry(entries, checkpoint, view, segments, &element_path, (left_item, right_item), Some(element))?;
+        } else {
+            let kind = element_kind(Some(element), change_kind(view, &element_path, left_item, right_item));
+            entries.push(DiffEntry {
+                path: element_path,
+                segments: segments.clone(),
+                kind,
+                left: left_item,
+                right: right_item,
+                element: Some(element),
+                shape: Shape::SummaryElement,
+                right_key: None,
+                key_moved: None,
+                hidden_changes: None,
+            });
+        }
+        segments.pop();
+    }
+    Some(())
+}
+
+/// How many values differ within two objects or arrays at `path`, counted
+/// as the entries of an expanded diff would be: a member or element on one
+/// side only once, however big. Returns `None` if cancelled.
+fn nested_changes(
+    view: &DiffView,
+    checkpoint: &mut Checkpoint,
+    segments: &mut Vec<String>,
+    path: &str,
+    (left, right): (&Value, &Value),
+) -> Option<usize> {
+    if change_kind(view, path, Some(left), Some(right)).is_none() {
+        return Some(0);
+    }
+    let mut changes = 0;
+    match (left, right) {
+        (Value::Object(left_map), Value::Object(right_map)) => {
+            let moved = view.key_orders.moved(left_map, right_map);
+            for (key, values, right_key) in members(view, left_map, right_map) {
+                changes += match values {
+                    _ if renamed(view, key, right_key) => 1,
+                    (Some(l), Some(r)) => {
+                        segments.push(key.clone());
+                        let nested = nested_changes(view, checkpoint, segments, &format!("{path}.{key}"), (l, r));
+                        segments.pop();
+                        nested?.max(usize::from(moved.contains_key(key)))
+                    }
+                    _ => 1,
+                };
+            }
+        }
+        (Value::Array(left_items), Value::Array(right_items)) => {
+            for element in align_elements(left_items, right_items, view, segments, checkpoint)? {
+                changes += match (element.left, element.right) {
+                    _ if element.moved.is_some() => 1,
+                    (Some(i), Some(j)) => {
+                        segments.push(element.index().to_string());
+                        let element_path = format!("{path}[{}]", element.index());
+                        let nested = nested_changes(view, checkpoint, segments, &element_path, (&left_items[i], &right_items[j]));
+                        segments.pop();
+                        nested?
+                    }
+                    _ => 1,
+                };
+            }
+        }
+        _ => changes = 1,
+    }
+    Some(changes)
+}
+
+/// `ChangeKind::between`, except that values `view.comparators` find equal
+/// at `path` are, and that equal objects and arrays with keys written in
+/// another order inside them are moved. Subtrees whose hashes differ aren't
+/// compared.
+pub fn change_kind(view: &DiffView, path: &str, left: Option<&Value>, right: Option<&Value>) -> Option<ChangeKind> {
+    let kind = match (left, right) {
+        (Some(l), Some(r)) if std::mem::discriminant(l) == std::mem::discriminant(r) && view.hashes.differ(l, r) => Some(ChangeKind::Changed),
+        _ => ChangeKind::between(left, right),
+    };
+    match (kind, left, right) {
+        (Some(ChangeKind::Changed | ChangeKind::TypeChanged), Some(l), Some(r)) if view.comparators.equal(path, l, r) => {
+            view.key_orders.differ(l, r).then_some(ChangeKind::Moved)
+        }
+        (None, Some(l), Some(r)) if view.key_orders.differ(l, r) => Some(ChangeKind::Moved),
+        (kind, _, _) => kind,
+    }
+}
+
+/// Aligns the elements of two arrays by their longest common subsequence, so
+/// an insertion doesn't shift every element after it into a change. Between
+/// equal elements, a removed and an added element of the same type are paired
+/// as one modified element (objects only if they share a field's value), and
+/// an element removed in one place and added, unchanged, in another becomes
+/// one moved element, at its new position; only where no other element
+/// removed or added is equal to it, so identical elements are never guessed
+/// at. That is the `ordered` strategy; for the array at `array_path` a key
+/// field or the `set` strategy pairs elements by the field's value or their
+/// own instead, see `arraykey::pair`. Returns `None` if cancelled.
+fn align_elements(
+    left: &[Value],
+    right: &[Value],
+    view: &DiffView,
+    array_path: &[String],
+    checkpoint: &mut Checkpoint,
+) -> Option<Vec<Element>> {
+    let keyed = |pairs: Vec<(Option<usize>, Option<usize>, bool)>| {
+        pairs.into_iter().map(|(left, right, unkeyed)| Element { left, right, moved: None, unkeyed, count: None }).collect()
+    };
+    match view.array_keys.strategy(array_path, view.options.unordered_arrays) {
+        Strategy::Key(field) => return Some(keyed(arraykey::pair(left, right, |element| arraykey::field_key(element, &field)))),
+        Strategy::Set => return Some(keyed(arraykey::pair(left, right, |element| Some(arraykey::canonical(element))))),
+        Strategy::Multiset => return Some(multiset_elements(left, right)),
+        Strategy::Ordered => {}
+    }
+    let similar = |l: &Value, r: &Value| match (l, r) {
+        (Value::Object(l), Value::Object(r)) => l.iter().any(|(key, value)| r.get(key) == Some(value)),
+        _ => std::mem::discriminant(l) == std::mem::discriminant(r),
+    };
+    let (left_hashed, right_hashed) = (view.hashes.elements(left), view.hashes.elements(right));
+    let mut elements: Vec<Element> = linediff::align(&left_hashed, &right_hashed, |l, r| similar(l.0, r.0), checkpoint)?
+        .into_iter()
+        .map(|(left, right)| Element { left, right, moved: None, unkeyed: false, count: None })
+        .collect();
+    // Each removed and added element by its value, with its position.
+    let mut removed: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    let mut added: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (position, element) in elements.iter().enumerate() {
+        if checkpoint.tick() {
+            return None;
+        }
+        match (element.left, element.right) {
+            (Some(i), None) => removed.entry(left[i].to_string()).or_default().push(position),
+            (None, Some(j)) => added.entry(right[j].to_string()).or_default().push(position),
+            _ => {}
+        }
+    }
+    let mut origins = BTreeSet::new();
+    for (value, positions) in &removed {
+        if let ([from], Some([to])) = (positions.as_slice(), added.get(value).map(Vec::as_slice)) {
+            let i = elements[*from].left;
+            elements[*to].left = i;
+            elements[*to].moved = i;
+            origins.insert(*from);
+        }
+    }
+    Some(elements.into_iter().enumerate().filter(|(position, _)| !origins.contains(position)).map(|(_, element)| element).collect())
+}
+
+/// One element for each distinct value of two arrays compared as multisets,
+/// for the occurrences on both sides, and one more for those on one side
+/// only: `"admin"` twice on the left and three times on the right is an
+/// equal element counted twice and an added one counted once.
+fn multiset_elements(left: &[Value], right: &[Value]) -> Vec<Element> {
+    let mut elements = Vec::new();
+    for [on_left, on_right] in arraykey::tally(left, right) {
+        let common = on_left.len().min(on_right.len());
+        let element = |left: Option<usize>, right: Option<usize>, count: usize| Element { left, right, moved: None, unkeyed: false, count: Some(count) };
+        if common > 0 {
+            elements.push(element(Some(on_left[0]), Some(on_right[0]), common));
+        }
+        if on_left.len() > common {
+            elements.push(element(Some(on_left[common]), None, on_left.len() - common));
+        }
+        if on_right.len() > common {
+            elements.push(element(None, Some(on_right[common]), on_right.len() - common));
+        }
+    }
+    elements
+}
+
+/// The kind of change an element shows: moved, or else `differs`.
+fn element_kind(element: Option<Element>, differs: Option<ChangeKind>) -> Option<ChangeKind> {
+    match element {
+        Some(Element { moved: Some(_), .. }) => Some(ChangeKind::Moved),
+        _ => differs,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Runs `f` with a view of `left` and `right` under `options` and the
+    /// `--array` rules `arrays`, as `compare_json_files` builds it.
+    pub fn with_view<R>(options: &DiffOptions, arrays: &[&str], [left, right]: [&Value; 2], f: impl FnOnce(&DiffView) -> R) -> R {
+        let mut array_keys = ArrayKeys::default();
+        let arrays: Vec<String> = arrays.iter().map(|spec| spec.to_string()).collect();
+        for spec in &arrays {
+            let flag = "--array".to_string();
+            array_keys.parse_arg(&flag, &mut std::iter::once(spec)).expect("valid --array rule");
+        }
+        let view = DiffView {
+            options,
+            notes: &[Notes::new(), Notes::new()],
+            times: None,
+            folds: &BTreeMap::new(),
+            context: &ContextFields::default(),
+            array_keys: &array_keys,
+            comparators: &Comparators::new(options),
+            truncate: 0,
+            repaired: &BTreeSet::new(),
+            decoded: &Decoded::new(),
+            hashes: &Hashes::of(&[left, right]),
+            key_orders: &KeyOrders::default(),
+            redaction: &Redaction::default(),
+            now: 0,
+        };
+        f(&view)
+    }
+
+    /// `compare`'s entries as `(path, kind)`, `=` for equal ones.
+    fn entries(options: &DiffOptions, arrays: &[&str], left: &Value, right: &Value) -> Vec<(String, &'static str)> {
+        with_view(options, arrays, [left, right], |view| match compare(left, right, view, &CancelToken::default()) {
+            Some(DiffResult::Entries(entries)) => entries.iter().map(|entry| (entry.path.clone(), entry.kind.map_or("=", ChangeKind::name))).collect(),
+            Some(DiffResult::Lines(..)) => panic!("compared line by line"),
+            None => panic!("cancelled"),
+        })
+    }
+
+    fn pairs(entries: &[(&str, &'static str)]) -> Vec<(String, &'static str)> {
+        entries.iter().map(|&(path, kind)| (path.to_string(), kind)).collect()
+    }
+
+    #[test]
+    fn objects_compare_key_by_key() {
+        let left = json!({"name": "a", "port": 80, "old": true, "tags": ["x"]});
+        let right = json!({"name": "a", "port": "80", "new": null, "tags": ["x", "y"]});
+        let expected = [("name", "="), ("new", "added"), ("old", "removed"), ("port", "type-changed"), ("tags", "changed"), ("tags[0]", "="), ("tags[1]", "added")];
+        assert_eq!(entries(&DiffOptions::default(), &[], &left, &right), pairs(&expected));
+    }
+
+    #[test]
+    fn nested_objects_expand_where_they_differ() {
+        let left = json!({"db": {"host": "h", "port": 1}, "cache": {"ttl": 5}});
+        let right = json!({"db": {"host": "h", "port": 2}, "cache": {"ttl": 5}});
+        let expected = [("cache", "="), ("db", "changed"), ("db.host", "="), ("db.port", "changed")];
+        assert_eq!(entries(&DiffOptions::default().expand_depth(0), &[], &left, &right), pairs(&expected));
+    }
+
+    #[test]
+    fn root_arrays_compare_element_by_element() {
+        let left = json!([1, 2, 3]);
+        let right = json!([1, 5, 3, 4]);
+        let expected = [("[0]", "="), ("[1]", "changed"), ("[2]", "="), ("[3]", "added")];
+        assert_eq!(entries(&DiffOptions::default(), &[], &left, &right), pairs(&expected));
+    }
+
+    #[test]
+    fn keyed_elements_pair_by_their_key() {
+        let left = json!({"users": [{"id": 1, "n": "a"}, {"id": 2, "n": "b"}]});
+        let right = json!({"users": [{"id": 2, "n": "b"}, {"id": 1, "n": "c"}]});
+        let changed = entries(&DiffOptions::default(), &["key:id"], &left, &right);
+        assert!(changed.contains(&("users[1].n".to_string(), "changed")), "{changed:?}");
+        assert!(!changed.iter().any(|(_, kind)| *kind == "added" || *kind == "removed"), "{changed:?}");
+    }
+
+    #[test]
+    fn comparators_and_renames_shape_the_kinds() {
+        let left = json!({"price": 1.0, "old_name": "x"});
+        let right = json!({"price": 1.004, "new_name": "x"});
+        let options = DiffOptions::default().tolerance(Some(0.01)).detect_renames(true);
+        assert_eq!(entries(&options, &[], &left, &right), pairs(&[("old_name", "renamed"), ("price", "=")]));
+    }
+
+    #[test]
+    fn scalar_roots_are_left_to_a_line_diff() {
+        let (left, right) = (json!("a"), json!({"a": 1}));
+        let lines = with_view(&DiffOptions::default(), &[], [&left, &right], |view| matches!(compare(&left, &right, view, &CancelToken::default()), Some(DiffResult::Lines(..))));
+        assert!(lines);
+    }
+
+    #[test]
+    fn a_cancelled_comparison_stops() {
+        let left = Value::Object((0..5000).map(|i| (i.to_string(), json!(i))).collect());
+        let right = json!({});
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        let result = with_view(&DiffOptions::default(), &[], [&left, &right], |view| compare(&left, &right, view, &cancel).is_none());
+        assert!(result);
+    }
+
+    #[test]
+    fn options_build_from_the_defaults() {
+        let options = DiffOptions::default().ignore_key("meta.*").max_depth(5).tolerance(Some(0.5)).unordered_arrays(true);
+        assert_eq!(options.ignores, ["meta.*"]);
+        assert_eq!(options.max_depth, 5);
+        assert_eq!(options.tolerance, Some(0.5));
+        assert!(options.unordered_arrays);
+        assert!(options.markers);
+        assert_eq!(options.expand_depth, DiffOptions::default().expand_depth);
+    }
+
+    #[test]
+    fn options_survive_a_round_trip() {
+        let options = DiffOptions::default().array_key("key:id").comparator("case").select(Some("spec".to_string())).markers(false);
+        let text = serde_json::to_string(&options).unwrap();
+        assert!(serde_json::from_str::<DiffOptions>(&text).unwrap() == options);
+
+        // Unset fields take their defaults.
+        let partial: DiffOptions = serde_json::from_str(r#"{"lenient": true}"#).unwrap();
+        assert!(partial == DiffOptions::default().lenient(true));
+    }
+
+    #[test]
+    fn flags_leave_out_the_defaults() {
+        assert!(DiffOptions::default().flags().is_empty());
+        assert_eq!(DiffOptions::default().command_line(), "diffrs");
+
+        let options = DiffOptions::default().expand_depth(3).key_order(true).markers(false).ignore_key("a b").on_change(OnChange::Stale);
+        assert_eq!(options.flags(), ["--expand-depth", "3", "--key-order", "--no-markers", "--on-change", "stale", "--ignore", "a b"]);
+        assert_eq!(options.command_line(), "diffrs --expand-depth 3 --key-order --no-markers --on-change stale --ignore 'a b'");
+        assert_eq!(DiffOptions::default().equal_if("it's").command_line(), r"diffrs --equal-if 'it'\''s'");
+    }
+}
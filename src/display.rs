@@ -0,0 +1,471 @@
+//! Display-only sanitizing of string content. Control and invisible
+//! formatting characters are shown as visible `\uXXXX` escapes so they can't
+//! corrupt the layout; comparisons never see any of this.
+
+use ratatui::text::{Line, Span};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::decimal;
+use crate::report::ChangeKind;
+use crate::timings;
+
+/// Characters that render as nothing (or reorder text) and so would hide
+/// differences: zero-width spaces/joiners, bidi controls and the BOM.
+fn is_invisible(c: char) -> bool {
+    matches!(c, '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{2064}' | '\u{2066}'..='\u{2069}' | '\u{FEFF}')
+}
+
+/// Replaces control and invisible characters with `\uXXXX` escapes and
+/// expands tabs, so the result occupies exactly the width it appears to.
+pub fn sanitize(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(|c| c.is_control() || is_invisible(c)) {
+        return Cow::Borrowed(text);
+    }
+    let mut sanitized = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        match c {
+            '\t' => sanitized.push_str("    "),
+            c if c.is_control() || is_invisible(c) => sanitized.push_str(&format!("\\u{:04x}", c as u32)),
+            c => sanitized.push(c),
+        }
+    }
+    Cow::Owned(sanitized)
+}
+
+/// `sanitize` applied line by line, keeping the line breaks themselves.
+pub fn sanitize_lines(text: &str) -> String {
+    text.lines().map(sanitize).collect::<Vec<_>>().join("\n")
+}
+
+/// Shown for the side a key is absent from, unlike a present `null`.
+pub const MISSING: &str = "<missing>";
+
+/// Compact JSON for display, sanitized. With `decode`, literal `\uXXXX`
+/// sequences inside string values are shown as the characters they encode.
+pub fn render_value(value: &Value, decode: bool) -> String {
+    let text = if decode { decode_strings(value).to_string() } else { value.to_string() };
+    sanitize(&text).into_owned()
+}
+
+/// Pretty-printed JSON for the original-content panes, sanitized like `render_value`.
+pub fn render_pretty(value: &Value, decode: bool) -> String {
+    let pretty = if decode {
+        serde_json::to_string_pretty(&decode_strings(value))
+    } else {
+        serde_json::to_string_pretty(value)
+    };
+    sanitize_lines(&pretty.unwrap_or_default())
+}
+
+fn decode_strings(value: &Value) -> Value {
+    match value {
+        Value::String(s) => Value::String(decode_literal_escapes(s)),
+        Value::Array(items) => Value::Array(items.iter().map(decode_strings).collect()),
+        Value::Object(map) => Value::Object(map.iter().map(|(k, v)| (k.clone(), decode_strings(v))).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Decodes literal `\uXXXX` sequences (including surrogate pairs) that
+/// encode printable characters; anything else is left as written.
+pub fn decode_literal_escapes(s: &str) -> String {
+    let hex_at = |rest: &str| -> Option<u32> {
+        let digits = rest.strip_prefix("\\u")?.get(..4)?;
+        u32::from_str_radix(digits, 16).ok()
+    };
+
+    let mut decoded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("\\u") {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(unit) = hex_at(rest) else {
+            decoded.push_str("\\u");
+            rest = &rest[2..];
+            continue;
+        };
+        let (c, consumed) = match unit {
+            0xD800..=0xDBFF => match hex_at(&rest[6..]) {
+                Some(low @ 0xDC00..=0xDFFF) => (char::from_u32(0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00)), 12),
+                _ => (None, 6),
+            },
+            _ => (char::from_u32(unit), 6),
+        };
+        match c {
+            Some(c) if !c.is_control() && !is_invisible(c) => decoded.push(c),
+            _ => decoded.push_str(&rest[..consumed]),
+        }
+        rest = &rest[consumed..];
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// A rendered value cut down to its start and end.
+pub struct Truncated {
+    /// `start…end [1,842 more chars]`.
+    pub text: String,
+    /// The byte range of the original left out.
+    pub hidden: Range<usize>,
+}
+
+/// `text` cut to about `limit` columns around a middle ellipsis, with the
+/// size of what was left out; `None` if it fits or `limit` is 0. Cuts fall
+/// between graphemes, so no character loses its combining marks and no wide
+/// character is split, and count display width, so wide characters take two.
+pub fn truncate_middle(text: &str, limit: usize) -> Option<Truncated> {
+    // Bytes bound display width from above, so most values are settled here.
+    if limit == 0 || text.len() <= limit || fits(text, limit) {
+        return None;
+    }
+    // Only the graphemes kept are measured, from each end, so a value of
+    // megabytes is cut as quickly as one of a few hundred bytes.
+    let keep = limit / 2;
+    let (mut start, mut width) = (0, 0);
+    for (index, grapheme) in text.grapheme_indices(true) {
+        width += grapheme.width();
+        if width > keep {
+            break;
+        }
+        start = index + grapheme.len();
+    }
+    let (mut end, mut width) = (text.len(), 0);
+    for (index, grapheme) in text.grapheme_indices(true).rev() {
+        width += grapheme.width();
+        if width > keep || index < start {
+            break;
+        }
+        end = index;
+    }
+    let hidden = start..end;
+    if hidden.is_empty() {
+        return None;
+    }
+    Some(Truncated {
+        text: format!(
+            "{}…{} [{} more chars]",
+            &text[..hidden.start],
+            &text[hidden.end..],
+            group_thousands(text[hidden.clone()].chars().count())
+        ),
+        hidden,
+    })
+}
+
+/// Formats `1234567` as `1,234,567`.
+pub fn group_thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Whether `text` is at most `limit` columns wide, measuring no further
+/// than that.
+fn fits(text: &str, limit: usize) -> bool {
+    let mut width = 0;
+    text.chars().all(|c| {
+        width += c.width().unwrap_or(0);
+        width <= limit
+    })
+}
+
+/// Splits `line` into lines at most `width` columns wide, by display width
+/// and between graphemes, keeping span and line styles. Line breaks inside
+/// spans are dropped. Always at least one line.
+pub fn wrap_line(line: &Line<'static>, width: usize) -> Vec<Line<'static>> {
+    let width = width.max(1);
+    let mut lines = vec![Line::default().style(line.style)];
+    let mut used = 0;
+    for span in &line.spans {
+        let mut piece = String::new();
+        for grapheme in span.content.graphemes(true) {
+            if grapheme == "\n" || grapheme == "\r\n" {
+                continue;
+            }
+            let columns = grapheme.width();
+            if used + columns > width && used > 0 {
+                let last = lines.last_mut().expect("at least one line");
+                last.spans.push(Span::styled(std::mem::take(&mut piece), span.style));
+                lines.push(Line::default().style(line.style));
+                used = 0;
+            }
+            piece.push_str(grapheme);
+            used += columns;
+        }
+        if !piece.is_empty() {
+            lines.last_mut().expect("at least one line").spans.push(Span::styled(piece, span.style));
+        }
+    }
+    lines
+}
+
+/// `parent` extended by an object `key` (`server.timeout`) or by an array
+/// element's bracketed label (`endpoints[3]`). Keys that are empty or hold
+/// dots, spaces, brackets or quotes are quoted: `labels."app.kubernetes.io/name"`.
+pub fn join_path(parent: &str, key: &str, element: bool) -> String {
+    if element {
+        return format!("{parent}{key}");
+    }
+    let quoted = if key.is_empty() || key.contains(['.', ' ', '[', ']', '"']) {
+        Value::from(key).to_string()
+    } else {
+        key.to_string()
+    };
+    if parent.is_empty() {
+        quoted
+    } else {
+        format!("{parent}.{quoted}")
+    }
+}
+
+/// ` (str)`, ` (num)`, ` (bool)`, ` (null)`, ` (obj:12 keys)` or ` (arr:5)`
+/// after a value, for telling `"true"` from `true` at a glance.
+pub fn type_tag(value: &Value) -> String {
+    match value {
+        Value::Null => " (null)".to_string(),
+        Value::Bool(_) => " (bool)".to_string(),
+        Value::Number(_) => " (num)".to_string(),
+        Value::String(_) => " (str)".to_string(),
+        Value::Array(items) => format!(" (arr:{})", items.len()),
+        Value::Object(map) if map.len() == 1 => " (obj:1 key)".to_string(),
+        Value::Object(map) => format!(" (obj:{} keys)", map.len()),
+    }
+}
+
+/// `{object, 48 keys, 2 nested objects, 4 nested arrays, 3.1 KB}` or
+/// `[array, 12 items, 880 B]` for an object or array over `threshold` bytes
+/// as compact JSON, `{added object, ...}` for one that `change` added or
+/// removed; `None` for a smaller one, a scalar, or a `threshold` of 0.
+pub fn subtree_summary(value: &Value, threshold: usize, change: Option<ChangeKind>) -> Option<String> {
+    let (open, kind, count, noun, close) = match value {
+        Value::Object(map) => ('{', "object", map.len(), "key", '}'),
+        Value::Array(items) => ('[', "array", items.len(), "item", ']'),
+        _ => return None,
+    };
+    if threshold == 0 {
+        return None;
+    }
+    let size = serde_json::to_vec(value).map_or(0, |json| json.len());
+    if size <= threshold {
+        return None;
+    }
+    let counted = |count: usize, noun: &str| format!("{count} {noun}{}", if count == 1 { "" } else { "s" });
+    let change = change.map_or_else(String::new, |change| format!("{} ", change.name()));
+    let mut parts = vec![format!("{change}{kind}"), counted(count, noun)];
+    let [objects, arrays] = nested_containers(value);
+    if objects > 0 {
+        parts.push(counted(objects, "nested object"));
+    }
+    if arrays > 0 {
+        parts.push(counted(arrays, "nested array"));
+    }
+    parts.push(timings::format_bytes(size));
+    Some(format!("{open}{}{close}", parts.join(", ")))
+}
+
+/// How many objects and arrays there are inside `value`, at any depth.
+fn nested_containers(value: &Value) -> [usize; 2] {
+    let children: Box<dyn Iterator<Item = &Value>> = match value {
+        Value::Object(map) => Box::new(map.values()),
+        Value::Array(items) => Box::new(items.iter()),
+        _ => return [0, 0],
+    };
+    children.fold([0, 0], |[objects, arrays], child| {
+        let [nested_objects, nested_arrays] = nested_containers(child);
+        [objects + nested_objects + usize::from(child.is_object()), arrays + nested_arrays + usize::from(child.is_array())]
+    })
+}
+
+/// The byte range where `left` and `right` differ, on character boundaries of
+/// each: `(start, left end, right end)`.
+pub fn changed_region(left: &str, right: &str) -> (usize, usize, usize) {
+    let prefix: usize = left.chars().zip(right.chars()).take_while(|(l, r)| l == r).map(|(c, _)| c.len_utf8()).sum();
+    let suffix: usize = left[prefix..]
+        .chars()
+        .rev()
+        .zip(right[prefix..].chars().rev())
+        .take_while(|(l, r)| l == r)
+        .map(|(c, _)| c.len_utf8())
+        .sum();
+    (prefix, left.len() - suffix, right.len() - suffix)
+}
+
+/// Mostly control characters or replacement characters: better read as hex.
+pub fn is_binaryish(s: &str) -> bool {
+    let total = s.chars().count();
+    let odd = s.chars().filter(|c| (c.is_control() && !c.is_whitespace()) || *c == '\u{FFFD}').count();
+    total > 0 && odd * 10 >= total * 3
+}
+
+/// Classic 16-bytes-per-row hex dump of the UTF-8 bytes of `s`.
+pub fn hex_dump(s: &str) -> String {
+    let mut dump = String::new();
+    for (row, chunk) in s.as_bytes().chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+        let ascii: String = chunk.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+        dump.push_str(&format!("{:08x}  {:<47}  |{ascii}|\n", row * 16, hex.join(" ")));
+    }
+    dump
+}
+
+/// The change between two numbers as `("+8", "×3.0")`, or `None` unless
+/// both are numbers and the change isn't zero. The difference keeps no more decimals than the inputs
+/// have, so `0.1 → 0.4` is `+0.3`, and is exact unless they are written
+/// with an exponent or have too many digits; the ratio is `n/a` from zero.
+pub fn numeric_delta(left: &Value, right: &Value) -> Option<(String, String)> {
+    let (Value::Number(l), Value::Number(r)) = (left, right) else {
+        return None;
+    };
+    // Integers past `i64` are still exact as `u64`.
+    let integer = |n: &serde_json::Number| n.as_i64().map(i128::from).or_else(|| n.as_u64().map(i128::from));
+    let absolute = match (integer(l), integer(r)) {
+        (Some(l), Some(r)) => format!("{:+}", r - l),
+        _ => {
+            let decimals = |n: &serde_json::Number| {
+                let text = n.to_string();
+                match text.split_once(['e', 'E']) {
+                    Some(_) => 6,
+                    None => text.split_once('.').map_or(0, |(_, fraction)| fraction.len()),
+                }
+            };
+            let precision = decimals(l).max(decimals(r));
+            match decimal::difference(l, r, precision) {
+                Some(difference) => difference,
+                None => {
+                    let difference = r.as_f64()? - l.as_f64()?;
+                    if !difference.is_finite() {
+                        return Some(("n/a".to_string(), "n/a".to_string()));
+                    }
+                    format!("{difference:+.precision$}")
+                }
+            }
+        }
+    };
+    // Equal numbers written differently, such as `1` and `1.0`, or a
+    // difference too small for the decimals shown: nothing to show.
+    if absolute.trim_start_matches(['+', '-']).chars().all(|c| c == '0' || c == '.') {
+        return None;
+    }
+    Some((absolute, ratio(l, r)?))
+}
+
+/// `×3.0`, or `n/a` from zero.
+fn ratio(l: &serde_json::Number, r: &serde_json::Number) -> Option<String> {
+    let ratio = r.as_f64()? / l.as_f64()?;
+    Some(if ratio.is_finite() {
+        let ratio = format!("{ratio:.2}");
+        let ratio = ratio.trim_end_matches('0');
+        format!("×{ratio}{}", if ratio.ends_with('.') { "0" } else { "" })
+    } else {
+        "n/a".to_string()
+    })
+}
+
+/// The change from `left` to `right` as a percentage of `left`, `None` unless
+/// both are numbers or from zero. The difference is `numeric_delta`'s exact
+/// one where there is one, so numbers too close for `f64` to tell apart
+/// still change by a little.
+pub fn percent_change(left: &Value, right: &Value) -> Option<f64> {
+    let (l, r) = (left.as_f64()?, right.as_f64()?);
+    let difference = numeric_delta(left, right).and_then(|(absolute, _)| absolute.parse::<f64>().ok()).unwrap_or(r - l);
+    let percent = difference / l.abs() * 100.0;
+    percent.is_finite().then_some(percent)
+}
+
+/// `+50%`, `-12.5%`: one decimal at most. A change too small for that is
+/// `<0.1%` or `>-0.1%`, never `+0%` or a lost sign.
+pub fn format_percent(percent: f64) -> String {
+    let formatted = format!("{percent:+.1}");
+    let formatted = formatted.strip_suffix(".0").unwrap_or(&formatted);
+    match formatted {
+        "+0" | "-0" if percent > 0.0 => "<0.1%".to_string(),
+        "+0" | "-0" if percent < 0.0 => ">-0.1%".to_string(),
+        "+0" | "-0" => "+0%".to_string(),
+        _ => format!("{formatted}%"),
+    }
+}
+
+/// A difference from `numeric_delta` as a JSON number, for scripts. An
+/// integer difference too large for 64 bits stays exact as its decimal text.
+pub fn delta_value(absolute: &str) -> Option<Value> {
+    let text = absolute.trim_start_matches('+');
+    if let Ok(n) = text.parse::<i64>() {
+        return Some(Value::from(n));
+    }
+    if let Ok(n) = text.parse::<u64>() {
+        return Some(Value::from(n));
+    }
+    if text.bytes().all(|b| b.is_ascii_digit() || b == b'-') {
+        return Some(Value::String(text.to_string()));
+    }
+    serde_json::from_str::<serde_json::Number>(text).ok().map(Value::Number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::{Color, Style};
+
+    fn texts(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(|line| line.spans.iter().map(|span| span.content.as_ref()).collect()).collect()
+    }
+
+    #[test]
+    fn wide_characters_wrap_by_their_width() {
+        let line = Line::from("日本語のテキスト");
+        let lines = wrap_line(&line, 5);
+        assert_eq!(texts(&lines), ["日本", "語の", "テキ", "スト"]);
+        assert!(lines.iter().all(|line| line.width() <= 5));
+    }
+
+    #[test]
+    fn emoji_stay_whole() {
+        let line = Line::from("ab😀😀e\u{301}");
+        assert_eq!(texts(&wrap_line(&line, 3)), ["ab", "😀", "😀e\u{301}"]);
+        // A flag is two code points, one grapheme.
+        assert_eq!(texts(&wrap_line(&Line::from("a🇯🇵"), 2)), ["a", "🇯🇵"]);
+    }
+
+    #[test]
+    fn spans_keep_their_styles_across_lines() {
+        let red = Style::default().fg(Color::Red);
+        let line = Line::from(vec![Span::raw("key: "), Span::styled("値値値", red)]);
+        let lines = wrap_line(&line, 6);
+        assert_eq!(texts(&lines), ["key: ", "値値値"]);
+        assert_eq!(lines[1].spans[0].style, red);
+        assert_eq!(texts(&wrap_line(&Line::default(), 4)), [""]);
+    }
+
+    #[test]
+    fn truncation_counts_columns_and_keeps_graphemes() {
+        assert!(truncate_middle("日本語", 6).is_none());
+        let cut = truncate_middle("日本語のテキスト", 6).unwrap();
+        assert_eq!(cut.text, "日…ト [6 more chars]");
+        let cut = truncate_middle("e\u{301}e\u{301}e\u{301}e\u{301}e\u{301}", 2).unwrap();
+        assert_eq!(cut.text, "e\u{301}…e\u{301} [6 more chars]");
+    }
+
+    #[test]
+    fn tiny_changes_keep_their_sign() {
+        let number = |text: &str| serde_json::from_str::<Value>(text).unwrap();
+        let percent = |l: &str, r: &str| percent_change(&number(l), &number(r)).map(format_percent);
+        assert_eq!(percent("12345678901234567890", "12345678901234567891").as_deref(), Some("<0.1%"));
+        assert_eq!(percent("12345678901234567891", "12345678901234567890").as_deref(), Some(">-0.1%"));
+        assert_eq!(percent("0.1", "0.1000000000000000001").as_deref(), Some("<0.1%"));
+        assert_eq!(percent("1000", "999.9999").as_deref(), Some(">-0.1%"));
+        assert_eq!(percent("4", "6").as_deref(), Some("+50%"));
+        assert_eq!(percent("8", "7").as_deref(), Some("-12.5%"));
+        assert_eq!(percent("1", "1.0").as_deref(), Some("+0%"));
+        assert_eq!(percent("0", "1"), None);
+    }
+}
@@ -0,0 +1,185 @@
+//! `diffrs doctor`: checks the environment diffrs runs in, for setting up
+//! and for bug reports. Every check prints pass, warn or fail, with a remedy
+//! for the last two; the exit code is 1 if anything failed.
+
+use anyhow::{bail, Result};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use crate::annotations::{self, Annotations};
+use crate::capabilities::{Capabilities, ColorDepth};
+use crate::history;
+use crate::ignore::IgnoreArgs;
+use crate::wizard;
+
+/// Smallest terminal the two panes and their key hints are usable in.
+const MIN_SIZE: (u16, u16) = (60, 12);
+
+#[derive(PartialEq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+struct Report {
+    failed: bool,
+}
+
+impl Report {
+    /// Prints one check; `remedy` is shown unless it passed.
+    fn check(&mut self, status: Status, what: &str, remedy: &str) {
+        let label = match status {
+            Status::Pass => "pass",
+            Status::Warn => "warn",
+            Status::Fail => "fail",
+        };
+        println!("[{label}] {what}");
+        if status != Status::Pass && !remedy.is_empty() {
+            println!("       → {remedy}");
+        }
+        self.failed |= status == Status::Fail;
+    }
+}
+
+/// Returns the exit code.
+pub fn run(args: &[String]) -> Result<i32> {
+    if let Some(arg) = args.first() {
+        bail!("unknown doctor option '{arg}' (usage: diffrs doctor)");
+    }
+    let mut report = Report { failed: false };
+    println!("diffrs {}", env!("CARGO_PKG_VERSION"));
+
+    // Terminal.
+    let capabilities = Capabilities::detect();
+    let term = if capabilities.term.is_empty() { "(unset)" } else { capabilities.term.as_str() };
+    match crossterm::terminal::size() {
+        Ok((width, height)) if width >= MIN_SIZE.0 && height >= MIN_SIZE.1 => {
+            report.check(Status::Pass, &format!("terminal: {width}×{height}, TERM={term}"), "")
+        }
+        Ok((width, height)) => report.check(
+            Status::Warn,
+            &format!("terminal: {width}×{height}, TERM={term}"),
+            &format!("enlarge the window to at least {}×{} so both panes fit", MIN_SIZE.0, MIN_SIZE.1),
+        ),
+        Err(_) => report.check(Status::Warn, &format!("terminal: size unknown, TERM={term}"), "run diffrs doctor in the terminal diffrs will run in"),
+    }
+    if capabilities.alternate_screen {
+        report.check(Status::Pass, "alternate screen: yes", "");
+    } else if std::io::stdout().is_terminal() {
+        report.check(Status::Fail, "alternate screen: no, diffrs prints a summary instead of the TUI", "set TERM to your terminal's type (e.g. xterm-256color), or pass --force-tui");
+    } else {
+        report.check(Status::Warn, "alternate screen: stdout is not a terminal", "run diffrs doctor without redirecting its output to check the terminal");
+    }
+    report.check(
+        if capabilities.mouse { Status::Pass } else { Status::Warn },
+        &format!("mouse: {}", if capabilities.mouse { "yes" } else { "no" }),
+        "the keyboard works everywhere; a terminal with xterm mouse reporting adds clicking and scrolling",
+    );
+    report.check(
+        if capabilities.colors == ColorDepth::Mono { Status::Warn } else { Status::Pass },
+        &format!("colors: {}", capabilities.colors.name()),
+        "unset NO_COLOR, or set TERM or --color-depth, to tell additions from removals by color",
+    );
+
+    // Editor: run as a single program name, as the a/b keys do.
+    let editor = std::env::var("EDITOR").ok().filter(|editor| !editor.is_empty());
+    let program = editor.clone().unwrap_or_else(|| "vim".to_string());
+    let source = if editor.is_some() { "$EDITOR" } else { "default, $EDITOR unset" };
+    match find_program(&program) {
+        Some(path) => report.check(Status::Pass, &format!("editor: {program} ({source}) → {}", path.display()), ""),
+        None if program.contains(' ') => report.check(
+            Status::Fail,
+            &format!("editor: '{program}' ({source}) is not a program"),
+            "diffrs runs $EDITOR without splitting arguments: point it at a program or a wrapper script",
+        ),
+        None => report.check(Status::Fail, &format!("editor: {program} ({source}) not found"), "set EDITOR to an installed editor, e.g. EDITOR=nano"),
+    }
+    if let Some(visual) = std::env::var("VISUAL").ok().filter(|visual| Some(visual) != editor.as_ref()) {
+        report.check(Status::Warn, &format!("$VISUAL is {visual}, which diffrs doesn't use"), "set EDITOR as well to edit panes with it");
+    }
+
+    // Clipboard, for pasting on the start screen.
+    match wizard::CLIPBOARD_TOOLS.iter().find_map(|(tool, _)| find_program(tool).map(|_| tool)) {
+        Some(tool) => report.check(Status::Pass, &format!("clipboard: {tool}"), ""),
+        None => report.check(Status::Warn, "clipboard: none of wl-paste, xclip, xsel or pbpaste found", "install one to paste on the start screen"),
+    }
+
+    // Configuration read from the working directory.
+    match IgnoreArgs::default().rules(&[Path::new(".")]) {
+        Ok(rules) if rules.files.is_empty() => report.check(Status::Pass, "ignore files: no .diffrsignore found", ""),
+        Ok(rules) => {
+            let files: Vec<String> = rules.files.iter().map(|file| file.display().to_string()).collect();
+            report.check(Status::Pass, &format!("ignore files: {}", files.join(", ")), "");
+        }
+        Err(err) => report.check(Status::Fail, &format!("ignore files: {err:#}"), "fix the file's permissions, or pass --no-ignore-file"),
+    }
+    let notes = PathBuf::from(annotations::DEFAULT_FILE);
+    match Annotations::load(notes.clone()) {
+        Ok(_) if !notes.exists() => report.check(Status::Pass, &format!("notes: {} (not created yet)", notes.display()), ""),
+        Ok(_) => report.check(Status::Pass, &format!("notes: {}", notes.display()), ""),
+        Err(err) => report.check(
+            Status::Fail,
+            &format!("notes: {err:#}"),
+            "fix or remove the file (each note needs path, kind and note), or pass --annotations",
+        ),
+    }
+
+    // History.
+    match history::history_file() {
+        Some(file) => {
+            let dir = file.parent().unwrap_or(Path::new("."));
+            match writable(dir) {
+                Ok(()) => report.check(Status::Pass, &format!("history: {}", file.display()), ""),
+                Err(err) => report.check(
+                    Status::Warn,
+                    &format!("history: {} is not writable ({err})", dir.display()),
+                    "set XDG_DATA_HOME to a writable directory, or pass --no-history",
+                ),
+            }
+        }
+        None => report.check(Status::Warn, "history: no data directory", "set XDG_DATA_HOME or HOME, or pass --no-history"),
+    }
+
+    report.check(Status::Pass, "build: no optional features", "");
+    Ok(i32::from(report.failed))
+}
+
+/// Where `program` runs from: itself if it is a path, else the first match
+/// on `$PATH`; `None` unless it is an executable file.
+fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains(['/', std::path::MAIN_SEPARATOR]) {
+        return Some(PathBuf::from(program)).filter(|path| is_executable(path));
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?).flat_map(|dir| candidates(&dir, program)).find(|path| is_executable(path))
+}
+
+/// The files `program` names in `dir`: on Windows also with each of
+/// `$PATHEXT`'s extensions, as the shell would try them.
+fn candidates(dir: &Path, program: &str) -> Vec<PathBuf> {
+    let mut candidates = vec![dir.join(program)];
+    if cfg!(windows) {
+        let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+        candidates.extend(extensions.split(';').filter(|extension| !extension.is_empty()).map(|extension| dir.join(format!("{program}{extension}"))));
+    }
+    candidates
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Without an executable bit, any file can be run.
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Whether a file can be created in `dir`, or in the nearest existing
+/// directory above it that diffrs would create it in.
+fn writable(dir: &Path) -> std::io::Result<()> {
+    let existing = dir.ancestors().find(|dir| dir.is_dir()).unwrap_or(Path::new("."));
+    tempfile::tempfile_in(existing).map(drop)
+}
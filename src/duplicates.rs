@@ -0,0 +1,261 @@
+//! `--duplicate-keys`: what to do with an object that has the same key more
+//! than once. serde_json keeps the last value without a word, so a file with
+//! two conflicting `"env"` blocks would diff as if it had one. A scan of the
+//! text finds each repeated key with its document path and position; by
+//! default they are listed (the `W` panel) and the last value is kept, as
+//! before. `first` keeps the first value instead, `last` keeps the last
+//! without listing, and `error` refuses the document.
+
+use anyhow::{bail, Result};
+use serde::de::{DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::cli::Format;
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateKeys {
+    /// Keep the last value, and list the duplicates.
+    #[default]
+    Warn,
+    First,
+    Last,
+    Error,
+}
+
+impl DuplicateKeys {
+    pub fn parse(policy: &str) -> Result<Self> {
+        Ok(match policy.to_lowercase().as_str() {
+            "warn" => Self::Warn,
+            "first" => Self::First,
+            "last" => Self::Last,
+            "error" => Self::Error,
+            _ => bail!("'{policy}' is not a duplicate key policy (warn, first, last or error)"),
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Warn => "warn",
+            Self::First => "first",
+            Self::Last => "last",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// A key written again in the same object.
+pub struct Duplicate {
+    /// Dotted document path of the object, empty for the root.
+    pub path: String,
+    pub key: String,
+    /// Where it is repeated, and where it first appears.
+    pub line: usize,
+    pub column: usize,
+    pub first: (usize, usize),
+}
+
+/// `duplicate key "env" in services at line 12, column 5 (first at line 3,
+/// column 5)`.
+impl fmt::Display for Duplicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = if self.path.is_empty() { "(root)" } else { &self.path };
+        write!(
+            f,
+            "duplicate key {:?} in {path} at line {}, column {} (first at line {}, column {})",
+            self.key, self.line, self.column, self.first.0, self.first.1
+        )
+    }
+}
+
+enum Level {
+    /// Each key seen so far, with where it was first written.
+    Object { keys: BTreeMap<String, (usize, usize)>, key: Option<String>, expecting_key: bool },
+    Array(usize),
+}
+
+/// The keys repeated within an object anywhere in the JSON `contents`, in
+/// the order they appear.
+pub fn find(contents: &str) -> Vec<Duplicate> {
+    let mut duplicates = Vec::new();
+    let mut stack: Vec<Level> = Vec::new();
+    // Top-level values, for newline-delimited input.
+    let (mut records, mut root_done) = (0, false);
+    let (mut line, mut line_start) = (1, 0);
+    let mut chars = contents.char_indices();
+
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '\n' => {
+                line += 1;
+                line_start = offset + 1;
+            }
+            '{' | '[' => {
+                if stack.is_empty() && root_done {
+                    records += 1;
+                }
+                stack.push(if c == '{' {
+                    Level::Object { keys: BTreeMap::new(), key: None, expecting_key: true }
+                } else {
+                    Level::Array(0)
+                });
+            }
+            '}' | ']' => {
+                stack.pop();
+                root_done |= stack.is_empty();
+            }
+            ',' => match stack.last_mut() {
+                Some(Level::Array(index)) => *index += 1,
+                Some(Level::Object { expecting_key, .. }) => *expecting_key = true,
+                None => {}
+            },
+            ':' => {
+                if let Some(Level::Object { expecting_key, .. }) = stack.last_mut() {
+                    *expecting_key = false;
+                }
+            }
+            '"' => {
+                if stack.is_empty() && root_done {
+                    records += 1;
+                }
+                let column = offset - line_start + 1;
+                let start = offset + 1;
+                let mut end = contents.len();
+                while let Some((offset, c)) = chars.next() {
+                    match c {
+                        '"' => {
+                            end = offset;
+                            break;
+                        }
+                        '\\' => {
+                            chars.next();
+                        }
+                        _ => {}
+                    }
+                }
+                let path = path(&stack, records);
+                if let Some(Level::Object { keys, key, expecting_key: true }) = stack.last_mut() {
+                    let raw = &contents[start..end];
+                    let decoded = serde_json::from_str::<String>(&format!("\"{raw}\"")).unwrap_or_else(|_| raw.to_string());
+                    match keys.get(&decoded) {
+                        Some(&first) => duplicates.push(Duplicate { path, key: decoded.clone(), line, column, first }),
+                        None => {
+                            keys.insert(decoded.clone(), (line, column));
+                        }
+                    }
+                    *key = Some(decoded);
+                }
+                root_done |= stack.is_empty();
+            }
+            _ => {}
+        }
+    }
+    duplicates
+}
+
+/// The dotted path of the innermost object or array; newline-delimited
+/// records are read as an array, so there paths start at the record's.
+fn path(stack: &[Level], records: usize) -> String {
+    let record = (records > 0).then(|| records.to_string());
+    let levels = stack[..stack.len().saturating_sub(1)].iter().filter_map(|level| match level {
+        Level::Object { key, .. } => key.clone(),
+        Level::Array(index) => Some(index.to_string()),
+    });
+    record.into_iter().chain(levels).collect::<Vec<_>>().join(".")
+}
+
+/// `find` for JSON input under `Warn`; nothing otherwise, YAML refusing
+/// duplicate keys itself.
+pub fn find_in_document(contents: &str, path: &Path, format: Format, policy: DuplicateKeys) -> Vec<Duplicate> {
+    if policy == DuplicateKeys::Warn && format.resolve(path, contents) == Format::Json {
+        find(contents)
+    } else {
+        Vec::new()
+    }
+}
+
+/// Reads a JSON value as `Value::deserialize` does, except that a repeated
+/// key keeps its first value rather than its last.
+pub struct KeepFirst;
+
+impl<'de> DeserializeSeed<'de> for KeepFirst {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeepFirst {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Value, E> {
+        Ok(Value::Bool(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Value, E> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Value, E> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Value, E> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Value, E> {
+        Ok(Value::String(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Value, E> {
+        Ok(Value::String(value))
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        KeepFirst.deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element_seed(KeepFirst)? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut object = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let value = map.next_value_seed(KeepFirst)?;
+            object.entry(key).or_insert(value);
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+/// Reads one JSON value, keeping the first of a repeated key's values under
+/// `First` and the last otherwise; `Error` is checked on the text, by `find`.
+pub fn read<'de, D: Deserializer<'de>>(deserializer: D, policy: DuplicateKeys) -> Result<Value, D::Error> {
+    match policy {
+        DuplicateKeys::First => KeepFirst.deserialize(deserializer),
+        _ => Value::deserialize(deserializer),
+    }
+}
@@ -0,0 +1,174 @@
+//! `--compare-with <pattern>=<command>`: equality decided by a command of
+//! the user's, for rules no option covers, such as two polygons being the
+//! same shape. Where values at a path matching the pattern (see `ignore`)
+//! differ, the command runs under the shell with
+//! `{"path": ..., "left": ..., "right": ...}` on its stdin; exiting 0 means
+//! equal and 1 different. Any other exit, a command that can't be started
+//! or one that runs past `TIMEOUT` leaves the values to the comparison
+//! they would otherwise have, and the failure is shown on the row.
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::compare::ValueComparator;
+use crate::ignore;
+
+/// How long a command has to answer for one pair of values.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Each pair's answer, as the diff asks more than once: by path and both
+/// values as JSON, whether they are equal or why the command didn't say.
+type Answers = BTreeMap<(String, String, String), Result<bool, String>>;
+
+pub struct ExternalRule {
+    pattern: String,
+    command: String,
+    answers: Mutex<Answers>,
+}
+
+impl ExternalRule {
+    /// Parses `pattern=command`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (pattern, command) = spec.split_once('=').ok_or_else(|| anyhow!("'{spec}' is not pattern=command"))?;
+        let (pattern, command) = (pattern.trim(), command.trim());
+        if pattern.is_empty() || pattern.split('.').any(str::is_empty) {
+            bail!("'{spec}' has an empty path segment");
+        }
+        if command.is_empty() {
+            bail!("'{spec}' has no command");
+        }
+        Ok(Self { pattern: pattern.to_string(), command: command.to_string(), answers: Mutex::default() })
+    }
+
+    fn answer(&self, path: &str, left: &Value, right: &Value) -> Result<bool, String> {
+        let key = (path.to_string(), left.to_string(), right.to_string());
+        let mut answers = self.answers.lock().expect("not poisoned");
+        answers.entry(key).or_insert_with(|| run(&self.command, path, left, right)).clone()
+    }
+}
+
+impl ValueComparator for ExternalRule {
+    fn equal(&self, path: &str, left: &Value, right: &Value) -> Option<bool> {
+        if !ignore::glob_matches_path(&self.pattern, path) {
+            return None;
+        }
+        self.answer(path, left, right).ok()
+    }
+
+    fn note(&self, _left: &Value, _right: &Value) -> Option<String> {
+        Some(format!("≈ equal per {}", self.command))
+    }
+
+    fn failure(&self, path: &str) -> Option<String> {
+        let answers = self.answers.lock().expect("not poisoned");
+        let mut at_path = answers.range((path.to_string(), String::new(), String::new())..).take_while(|((at, _, _), _)| at == path);
+        at_path.find_map(|(_, answer)| answer.as_ref().err().map(|reason| format!("{}: {reason}", self.command)))
+    }
+
+    fn failures(&self) -> Vec<(String, String)> {
+        let answers = self.answers.lock().expect("not poisoned");
+        answers
+            .iter()
+            .filter_map(|((path, _, _), answer)| answer.as_ref().err().map(|reason| (path.clone(), format!("{}: {reason}", self.command))))
+            .collect()
+    }
+}
+
+/// Runs `command` on the values at `path`: whether it found them equal, or
+/// why it didn't say.
+fn run(command: &str, path: &str, left: &Value, right: &Value) -> Result<bool, String> {
+    let mut shell = if cfg!(windows) { Command::new("cmd") } else { Command::new("sh") };
+    shell.args(if cfg!(windows) { ["/C", command] } else { ["-c", command] });
+    let mut child = shell
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("couldn't start: {err}"))?;
+    // Written and read on threads of their own, so that a command that
+    // reads nothing or writes a lot can't block the wait for it.
+    let input = json!({ "path": path, "left": left, "right": right }).to_string();
+    let mut stdin = child.stdin.take().expect("piped");
+    thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let mut stderr = child.stderr.take().expect("piped");
+    let errors = thread::spawn(move || {
+        let mut text = String::new();
+        stderr.read_to_string(&mut text).map(|_| text).unwrap_or_default()
+    });
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() >= TIMEOUT => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("no answer within {}s", TIMEOUT.as_secs()));
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(5)),
+            Err(err) => return Err(err.to_string()),
+        }
+    };
+    match status.code() {
+        Some(0) => Ok(true),
+        Some(1) => Ok(false),
+        code => {
+            let errors = errors.join().unwrap_or_default();
+            let exit = code.map_or_else(|| "was killed".to_string(), |code| format!("exited with {code}"));
+            match errors.lines().find(|line| !line.trim().is_empty()) {
+                Some(line) => Err(format!("{exit}: {}", line.trim())),
+                None => Err(exit),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn rule(command: &str) -> ExternalRule {
+        ExternalRule::parse(&format!("shape={command}")).unwrap()
+    }
+
+    #[test]
+    fn exit_codes_decide_equality() {
+        let (left, right) = (json!([0, 0]), json!([1, 1]));
+        assert_eq!(rule("exit 0").equal("shape", &left, &right), Some(true));
+        assert_eq!(rule("exit 1").equal("shape", &left, &right), Some(false));
+        assert_eq!(rule("exit 0").failure("shape"), None);
+        // Paths the pattern doesn't match are left alone.
+        assert_eq!(rule("exit 0").equal("other", &left, &right), None);
+    }
+
+    #[test]
+    fn values_arrive_on_stdin() {
+        let rule = rule(r#"grep -q '"left":\[0,0\]'"#);
+        assert_eq!(rule.equal("shape", &json!([0, 0]), &json!(1)), Some(true));
+        assert_eq!(rule.equal("shape", &json!([1, 1]), &json!(1)), Some(false));
+    }
+
+    #[test]
+    fn other_exits_are_failures() {
+        let rule = rule("echo 'bad shape' >&2; exit 2");
+        assert_eq!(rule.equal("shape", &json!(1), &json!(2)), None);
+        let failure = rule.failure("shape").unwrap();
+        assert!(failure.ends_with(": exited with 2: bad shape"), "{failure}");
+        assert_eq!(rule.failures(), [("shape".to_string(), failure)]);
+        assert_eq!(rule.failure("other"), None);
+    }
+
+    #[test]
+    fn slow_commands_time_out() {
+        let rule = rule("exec sleep 30");
+        let started = Instant::now();
+        assert_eq!(rule.equal("shape", &json!(1), &json!(2)), None);
+        assert!(started.elapsed() < TIMEOUT * 2);
+        assert_eq!(rule.failure("shape").unwrap(), format!("exec sleep 30: no answer within {}s", TIMEOUT.as_secs()));
+    }
+}
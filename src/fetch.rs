@@ -0,0 +1,163 @@
+//! `--fetch <left-url> <right-url>`: loads the two panes from HTTP responses,
+//! typically the same endpoint on two environments. Header values may refer
+//! to environment variables as `${NAME}` so tokens stay out of shell history.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine;
+use serde_json::Value;
+use std::time::Duration;
+use ureq::ResponseExt;
+
+/// Command-line options for fetching the inputs over HTTP.
+#[derive(Clone, Default)]
+pub struct FetchArgs {
+    pub urls: Option<(String, String)>,
+    /// `--header`, `--left-header`, `--right-header`: `Name: value` lines.
+    pub headers: Vec<String>,
+    pub left_headers: Vec<String>,
+    pub right_headers: Vec<String>,
+    /// `--auth bearer:<token>` or `--auth basic:<user>:<password>`.
+    pub auth: Option<String>,
+    /// `--response-select .data.items`: the part of each response to compare.
+    pub select: Option<String>,
+    /// `--insecure`: skip TLS certificate verification.
+    pub insecure: bool,
+}
+
+impl FetchArgs {
+    /// Consumes `arg` (and its values from `iter`) if it is a fetch option.
+    pub fn parse_arg<'a>(&mut self, arg: &str, iter: &mut impl Iterator<Item = &'a String>) -> Result<bool> {
+        let mut value = |what: &str| iter.next().cloned().ok_or_else(|| anyhow!("{arg} needs {what}"));
+        match arg {
+            "--fetch" => self.urls = Some((value("a left and a right URL")?, value("a left and a right URL")?)),
+            "--header" => self.headers.push(value("'Name: value'")?),
+            "--left-header" => self.left_headers.push(value("'Name: value'")?),
+            "--right-header" => self.right_headers.push(value("'Name: value'")?),
+            "--auth" => self.auth = Some(value("bearer:<token> or basic:<user>:<password>")?),
+            "--response-select" => self.select = Some(value("a path such as .data")?),
+            "--insecure" => self.insecure = true,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// A fetcher for the configured URLs, or `None` without `--fetch`.
+    pub fn fetcher(&self) -> Result<Option<Fetcher>> {
+        let Some((left, right)) = &self.urls else {
+            return Ok(None);
+        };
+        let mut shared = Vec::new();
+        if let Some(auth) = &self.auth {
+            shared.push(("Authorization".to_string(), auth_header(&expand_env(auth)?)?));
+        }
+        for header in &self.headers {
+            shared.push(parse_header(header)?);
+        }
+        let side = |extra: &[String]| -> Result<Vec<(String, String)>> {
+            let mut headers = shared.clone();
+            for header in extra {
+                headers.push(parse_header(header)?);
+            }
+            Ok(headers)
+        };
+        let tls = ureq::tls::TlsConfig::builder().disable_verification(self.insecure).build();
+        let agent = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .max_redirects(10)
+            .timeout_global(Some(Duration::from_secs(30)))
+            .tls_config(tls)
+            .build()
+            .into();
+        Ok(Some(Fetcher {
+            agent,
+            urls: [left.clone(), right.clone()],
+            headers: [side(&self.left_headers)?, side(&self.right_headers)?],
+            select: self.select.clone(),
+        }))
+    }
+}
+
+/// A single GET without extra headers, for URLs typed on the start screen.
+pub fn fetch_url(url: &str) -> Result<String> {
+    let args = FetchArgs { urls: Some((url.to_string(), url.to_string())), ..FetchArgs::default() };
+    args.fetcher()?.ok_or_else(|| anyhow!("no URL"))?.fetch(0)
+}
+
+pub struct Fetcher {
+    agent: ureq::Agent,
+    urls: [String; 2],
+    headers: [Vec<(String, String)>; 2],
+    select: Option<String>,
+}
+
+impl Fetcher {
+    /// The body for side `index` (0 left, 1 right), with `--response-select`
+    /// applied. Errors name the URL, the final URL after redirects and the
+    /// status, so an auth failure reads differently from a content change.
+    pub fn fetch(&self, index: usize) -> Result<String> {
+        let url = &self.urls[index];
+        let mut request = self.agent.get(url);
+        for (name, value) in &self.headers[index] {
+            request = request.header(name, value);
+        }
+        let mut response = request.call().with_context(|| format!("GET {url}"))?;
+        let final_url = response.get_uri().to_string();
+        let status = response.status();
+        let body = response
+            .body_mut()
+            .with_config()
+            .limit(u64::MAX)
+            .read_to_string()
+            .with_context(|| format!("GET {url}: reading the response"))?;
+        let redirected = if final_url != *url { format!(" (redirected to {final_url})") } else { String::new() };
+        if !status.is_success() {
+            let excerpt: String = body.chars().take(200).collect();
+            bail!("GET {url}{redirected}: HTTP {status}\n{excerpt}");
+        }
+        let Some(select) = &self.select else {
+            return Ok(body);
+        };
+        let json: Value = serde_json::from_str(&body)
+            .with_context(|| format!("GET {url}{redirected}: --response-select needs a JSON response"))?;
+        let selected = select
+            .split('.')
+            .filter(|key| !key.is_empty())
+            .try_fold(&json, |value, key| match value {
+                Value::Array(items) => key.parse::<usize>().ok().and_then(|index| items.get(index)),
+                _ => value.get(key),
+            })
+            .ok_or_else(|| anyhow!("GET {url}{redirected}: --response-select '{select}' matches nothing"))?;
+        Ok(serde_json::to_string_pretty(selected)?)
+    }
+}
+
+/// Replaces `${NAME}` with the environment variable's value.
+fn expand_env(text: &str) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..].find('}').ok_or_else(|| anyhow!("unclosed ${{ in '{text}'"))? + start;
+        let name = &rest[start + 2..end];
+        let value = std::env::var(name).map_err(|_| anyhow!("environment variable {name} is not set"))?;
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&value);
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn parse_header(header: &str) -> Result<(String, String)> {
+    let (name, value) = header.split_once(':').ok_or_else(|| anyhow!("header '{header}': expected 'Name: value'"))?;
+    Ok((name.trim().to_string(), expand_env(value.trim())?))
+}
+
+fn auth_header(auth: &str) -> Result<String> {
+    match auth.split_once(':') {
+        Some(("bearer", token)) => Ok(format!("Bearer {token}")),
+        Some(("basic", credentials)) => {
+            Ok(format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(credentials)))
+        }
+        _ => bail!("--auth: expected bearer:<token> or basic:<user>:<password>"),
+    }
+}
@@ -0,0 +1,72 @@
+//! The report view (**G**): the changes of the current diff in groups of
+//! added, removed and changed, then renamed and moved if any, each headed by its count and sorted by path,
+//! for reading through in a review rather than following the documents' key
+//! order. It lists what `--by-kind` prints. **p** narrows it to each
+//! change's kind and JSON Pointer, what `--changed-paths` prints.
+
+use crossterm::event::KeyCode;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Paragraph, Wrap},
+};
+
+use crate::display;
+use crate::report::{ReportLine, Summary};
+use crate::theme;
+
+pub struct ReportView {
+    lines: Vec<ReportLine>,
+    changes: usize,
+    scroll: u16,
+    /// Lists only the changes' kinds and paths.
+    paths: bool,
+}
+
+impl ReportView {
+    /// The changes of `summary`, grouped by kind or, with `paths`, one line
+    /// per change, its kind's marker and JSON Pointer: `~ /spec/replicas`.
+    /// Values are cut to `limit` columns, as in the diff.
+    pub fn new(summary: &Summary, paths: bool, limit: usize) -> Self {
+        let lines = match paths {
+            true => summary.changed_paths().into_iter().map(|(kind, pointer)| ReportLine::Change(kind, format!("{} {pointer}", kind.marker()))).collect(),
+            false => summary.by_kind(limit),
+        };
+        Self { lines, changes: summary.changes.len(), scroll: 0, paths }
+    }
+
+    pub fn lists_paths(&self) -> bool {
+        self.paths
+    }
+
+    /// Scrolls; returns whether the key was used.
+    pub fn handle_key(&mut self, code: KeyCode) -> bool {
+        let last = self.lines.len().saturating_sub(1) as u16;
+        self.scroll = match code {
+            KeyCode::Up | KeyCode::Char('k') => self.scroll.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll.saturating_add(1).min(last),
+            KeyCode::PageUp => self.scroll.saturating_sub(20),
+            KeyCode::PageDown => self.scroll.saturating_add(20).min(last),
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            _ => return false,
+        };
+        true
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect) {
+        let lines: Vec<Line> = self
+            .lines
+            .iter()
+            .map(|line| match line {
+                ReportLine::Group(kind, text) => Line::styled(text.clone(), theme::kind(*kind).add_modifier(Modifier::BOLD)),
+                ReportLine::Change(kind, text) => Line::styled(display::sanitize(text).into_owned(), theme::kind(*kind)),
+            })
+            .collect();
+        let title = match self.paths {
+            true => format!("Changed paths: {}", self.changes),
+            false => format!("Changes by kind: {}", self.changes),
+        };
+        let paragraph = Paragraph::new(lines).block(Block::bordered().title(title)).wrap(Wrap { trim: false }).scroll((self.scroll, 0));
+        f.render_widget(paragraph, area);
+    }
+}
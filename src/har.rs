@@ -0,0 +1,312 @@
+//! `--har`: compares the API responses captured in two HAR files. Entries are
+//! paired by method and URL (the nth occurrence on the left with the nth on
+//! the right) and compared by their response bodies.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine;
+use crossterm::event::KeyCode;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph, Wrap},
+};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+
+use crate::{centered_rect, display};
+
+/// How query strings take part in pairing.
+#[derive(Clone, Copy, PartialEq)]
+pub enum QueryMode {
+    /// Parameters compared in any order (the default).
+    Sorted,
+    Exact,
+    /// Query strings left out of the pairing key.
+    Ignore,
+}
+
+/// Command-line options for HAR comparison.
+#[derive(Clone)]
+pub struct HarArgs {
+    /// `--har <left> <right>`.
+    pub files: Option<(PathBuf, PathBuf)>,
+    /// `--har-query sorted|exact|ignore`.
+    pub query: QueryMode,
+    /// `--har-drop-param`: query parameters (cache busters, timestamps) left out of the pairing key.
+    pub drop_params: Vec<String>,
+}
+
+impl Default for HarArgs {
+    fn default() -> Self {
+        Self { files: None, query: QueryMode::Sorted, drop_params: Vec::new() }
+    }
+}
+
+impl HarArgs {
+    /// Consumes `arg` (and its values from `iter`) if it is a HAR option.
+    pub fn parse_arg<'a>(&mut self, arg: &str, iter: &mut impl Iterator<Item = &'a String>) -> Result<bool> {
+        match arg {
+            "--har" => {
+                let mut path = || iter.next().map(PathBuf::from).ok_or_else(|| anyhow!("--har needs a left and a right file"));
+                self.files = Some((path()?, path()?));
+            }
+            "--har-query" => {
+                self.query = match iter.next().map(String::as_str) {
+                    Some("sorted") => QueryMode::Sorted,
+                    Some("exact") => QueryMode::Exact,
+                    Some("ignore") => QueryMode::Ignore,
+                    Some(other) => bail!("--har-query: unknown mode '{other}' (expected sorted, exact or ignore)"),
+                    None => bail!("--har-query needs a mode"),
+                };
+            }
+            "--har-drop-param" => {
+                self.drop_params.push(iter.next().ok_or_else(|| anyhow!("--har-drop-param needs a parameter name"))?.clone());
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// The pairing key for a request URL: fragment removed and the query
+    /// normalized per `--har-query` and `--har-drop-param`.
+    fn url_key(&self, url: &str) -> String {
+        let url = url.split('#').next().unwrap_or(url);
+        let Some((base, query)) = url.split_once('?') else {
+            return url.to_string();
+        };
+        if self.query == QueryMode::Ignore {
+            return base.to_string();
+        }
+        let mut params: Vec<&str> = query
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .filter(|param| !self.drop_params.iter().any(|name| param.split('=').next() == Some(name.as_str())))
+            .collect();
+        if self.query == QueryMode::Sorted {
+            params.sort_unstable();
+        }
+        if params.is_empty() {
+            base.to_string()
+        } else {
+            format!("{base}?{}", params.join("&"))
+        }
+    }
+}
+
+/// One captured request and its response.
+pub struct Entry {
+    pub url: String,
+    pub status: i64,
+    pub request_headers: Vec<(String, String)>,
+    pub response_headers: Vec<(String, String)>,
+    /// The response body, decoded from base64 where the HAR says so, parsed
+    /// as JSON when it is JSON and kept as a string otherwise.
+    pub body: Value,
+}
+
+pub struct Pair {
+    pub method: String,
+    pub url: String,
+    pub left: Option<Entry>,
+    pub right: Option<Entry>,
+}
+
+impl Pair {
+    fn status(&self) -> (&'static str, Color) {
+        match (&self.left, &self.right) {
+            (Some(l), Some(r)) if l.body == r.body => ("identical", Color::Green),
+            (Some(_), Some(_)) => ("changed", Color::Red),
+            (Some(_), None) => ("only left", Color::Yellow),
+            (None, Some(_)) => ("only right", Color::Yellow),
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+pub struct Har {
+    pub files: (PathBuf, PathBuf),
+    pairs: Vec<Pair>,
+    selected: usize,
+    /// The request list is showing; otherwise the selected pair's bodies are in the panes.
+    pub list_open: bool,
+    headers_open: bool,
+}
+
+impl Har {
+    pub fn open(args: &HarArgs) -> Result<Option<Self>> {
+        let Some((left_path, right_path)) = &args.files else {
+            return Ok(None);
+        };
+        let (left, right) = (read_entries(left_path)?, read_entries(right_path)?);
+        let mut pairs: Vec<Pair> = Vec::new();
+        let mut keys: Vec<(String, String)> = Vec::new();
+        for (method, entry) in left {
+            let url = args.url_key(&entry.url);
+            keys.push((method.clone(), url.clone()));
+            pairs.push(Pair { method, url, left: Some(entry), right: None });
+        }
+        for (method, entry) in right {
+            let url = args.url_key(&entry.url);
+            let open = pairs.iter_mut().find(|pair| pair.right.is_none() && pair.method == method && pair.url == url);
+            match open {
+                Some(pair) if pair.left.is_some() => pair.right = Some(entry),
+                _ => pairs.push(Pair { method, url, left: None, right: Some(entry) }),
+            }
+        }
+        Ok(Some(Self {
+            files: (left_path.clone(), right_path.clone()),
+            pairs,
+            selected: 0,
+            list_open: true,
+            headers_open: false,
+        }))
+    }
+
+    pub fn selected(&self) -> Option<&Pair> {
+        self.pairs.get(self.selected)
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) {
+        let last = self.pairs.len().saturating_sub(1);
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(20),
+            KeyCode::PageDown => self.selected = (self.selected + 20).min(last),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = last,
+            KeyCode::Char('h') => self.headers_open = !self.headers_open,
+            KeyCode::Esc => self.headers_open = false,
+            _ => {}
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let count = |status: &str| self.pairs.iter().filter(|pair| pair.status().0 == status).count();
+        format!(
+            "{} requests: {} changed, {} identical, {} only left, {} only right",
+            self.pairs.len(),
+            count("changed"),
+            count("identical"),
+            count("only left"),
+            count("only right")
+        )
+    }
+
+    /// The request list, and the headers popup over it when open.
+    /// `redact` hides secret header values.
+    pub fn render(&self, f: &mut Frame, area: Rect, redact: impl Fn(&Value) -> Value) {
+        let items: Vec<ListItem> = self
+            .pairs
+            .iter()
+            .map(|pair| {
+                let (status, color) = pair.status();
+                let code = |entry: &Option<Entry>| entry.as_ref().map_or("-".to_string(), |entry| entry.status.to_string());
+                ListItem::new(format!(
+                    "{:<11} {:>3} / {:<3} {:<7} {}",
+                    status,
+                    code(&pair.left),
+                    code(&pair.right),
+                    display::sanitize(&pair.method),
+                    display::sanitize(&pair.url)
+                ))
+                .style(Style::default().fg(color))
+            })
+            .collect();
+        let title = format!("Requests — {} / {}", self.files.0.display(), self.files.1.display());
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(self.selected).filter(|_| !self.pairs.is_empty()));
+        f.render_stateful_widget(list, area, &mut state);
+
+        let Some(pair) = self.selected().filter(|_| self.headers_open) else {
+            return;
+        };
+        let popup = centered_rect(area, 90, 80);
+        f.render_widget(Clear, popup);
+        let [left_area, right_area] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(popup);
+        for (entry, area, side) in [(&pair.left, left_area, "Left"), (&pair.right, right_area, "Right")] {
+            let text = match entry {
+                Some(entry) => {
+                    let headers = |headers: &[(String, String)]| {
+                        let object: Map<String, Value> =
+                            headers.iter().map(|(name, value)| (name.clone(), Value::String(value.clone()))).collect();
+                        let redacted = redact(&Value::Object(object));
+                        redacted
+                            .as_object()
+                            .map(|object| {
+                                object
+                                    .iter()
+                                    .map(|(name, value)| format!("  {name}: {}\n", value.as_str().unwrap_or_default()))
+                                    .collect::<String>()
+                            })
+                            .unwrap_or_default()
+                    };
+                    format!(
+                        "{}\n\nrequest headers\n{}\nresponse headers ({})\n{}",
+                        entry.url,
+                        headers(&entry.request_headers),
+                        entry.status,
+                        headers(&entry.response_headers)
+                    )
+                }
+                None => "(no matching request)".to_string(),
+            };
+            f.render_widget(
+                Paragraph::new(display::sanitize_lines(&text))
+                    .wrap(Wrap { trim: false })
+                    .block(Block::bordered().title(format!("{side} headers (h/Esc close)"))),
+                area,
+            );
+        }
+    }
+}
+
+fn read_entries(path: &Path) -> Result<Vec<(String, Entry)>> {
+    let label = || path.display().to_string();
+    let contents = std::fs::read_to_string(path).with_context(label)?;
+    let har: Value = serde_json::from_str(&contents).with_context(label)?;
+    let entries = har
+        .pointer("/log/entries")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("{}: not a HAR file (no log.entries)", label()))?;
+    entries.iter().map(|entry| read_entry(entry).with_context(label)).collect()
+}
+
+fn read_entry(entry: &Value) -> Result<(String, Entry)> {
+    let text = |pointer: &str| entry.pointer(pointer).and_then(Value::as_str).unwrap_or_default().to_string();
+    let headers = |pointer: &str| -> Vec<(String, String)> {
+        entry
+            .pointer(pointer)
+            .and_then(Value::as_array)
+            .map(|headers| {
+                headers
+                    .iter()
+                    .map(|header| {
+                        let field = |name: &str| header.get(name).and_then(Value::as_str).unwrap_or_default().to_string();
+                        (field("name"), field("value"))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let mut body = text("/response/content/text");
+    if text("/response/content/encoding") == "base64" {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(body.trim())
+            .map_err(|err| anyhow!("{} {}: response body: {err}", text("/request/method"), text("/request/url")))?;
+        body = String::from_utf8_lossy(&bytes).into_owned();
+    }
+    let body = serde_json::from_str(&body).unwrap_or(Value::String(body));
+    Ok((
+        text("/request/method"),
+        Entry {
+            url: text("/request/url"),
+            status: entry.pointer("/response/status").and_then(Value::as_i64).unwrap_or_default(),
+            request_headers: headers("/request/headers"),
+            response_headers: headers("/response/headers"),
+            body,
+        },
+    ))
+}
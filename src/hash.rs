@@ -0,0 +1,169 @@
+//! Structural hashes of every object and array of the documents being
+//! diffed, computed once before the diff. Two subtrees whose hashes differ
+//! differ, so the diff tells them apart without walking them; only where the
+//! hashes match are the values compared, which confirms them equal despite a
+//! chance collision. Without the hashes, each level of the diff walked its
+//! subtree again down to the first difference, and aligning two arrays of
+//! objects walked their elements at every step.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+
+/// The hash of each object and array, by its address in the documents,
+/// which stay where they are while the diff borrows them.
+#[derive(Default)]
+pub struct Hashes {
+    by_node: HashMap<usize, u64, BuildHasherDefault<Mixer>>,
+}
+
+impl Hashes {
+    pub fn of(documents: &[&Value]) -> Self {
+        let mut hashes = Self::default();
+        for document in documents {
+            hashes.walk(document);
+        }
+        hashes
+    }
+
+    /// Equal values hash the same: numbers by their value as a float, which
+    /// numbers written alike share, and `1` and `1.0` hash apart.
+    fn walk(&mut self, value: &Value) -> u64 {
+        let mut hasher = Mixer::default();
+        match value {
+            Value::Null => 0u8.hash(&mut hasher),
+            Value::Bool(b) => (1u8, b).hash(&mut hasher),
+            Value::Number(n) => {
+                2u8.hash(&mut hasher);
+                match (n.as_u64(), n.as_i64(), n.as_f64()) {
+                    (Some(u), _, _) => (0u8, u).hash(&mut hasher),
+                    (None, Some(i), _) => (1u8, i).hash(&mut hasher),
+                    (None, None, f) => (2u8, f.filter(|f| *f != 0.0).unwrap_or(0.0).to_bits()).hash(&mut hasher),
+                }
+            }
+            Value::String(s) => (3u8, s).hash(&mut hasher),
+            Value::Array(items) => {
+                (4u8, items.len()).hash(&mut hasher);
+                for item in items {
+                    self.walk(item).hash(&mut hasher);
+                }
+            }
+            Value::Object(map) => {
+                (5u8, map.len()).hash(&mut hasher);
+                for (key, child) in map {
+                    key.hash(&mut hasher);
+                    self.walk(child).hash(&mut hasher);
+                }
+            }
+        }
+        let hash = hasher.finish();
+        if matches!(value, Value::Array(_) | Value::Object(_)) {
+            self.by_node.insert(address(value), hash);
+        }
+        hash
+    }
+
+    fn get(&self, value: &Value) -> Option<u64> {
+        self.by_node.get(&address(value)).copied()
+    }
+
+    /// Whether `left` and `right` are known to differ by their hashes; when
+    /// they aren't, only comparing them tells.
+    pub fn differ(&self, left: &Value, right: &Value) -> bool {
+        matches!((self.get(left), self.get(right)), (Some(l), Some(r)) if l != r)
+    }
+
+    /// `items` for comparing element by element, hashes first.
+    pub fn elements<'v>(&self, items: &'v [Value]) -> Vec<Hashed<'v>> {
+        items.iter().map(|item| Hashed(item, self.get(item))).collect()
+    }
+}
+
+fn address(value: &Value) -> usize {
+    value as *const Value as usize
+}
+
+/// A value with its hash, if it has one, equal to another only if their
+/// hashes don't tell them apart and then their values are equal.
+pub struct Hashed<'v>(pub &'v Value, Option<u64>);
+
+impl PartialEq for Hashed<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.1, other.1) {
+            (Some(l), Some(r)) if l != r => false,
+            _ => self.0 == other.0,
+        }
+    }
+}
+
+/// A multiply-and-rotate hasher, much quicker than the standard library's
+/// over many small writes; these hashes need no protection against crafted
+/// input, equal hashes being confirmed by comparing the values.
+#[derive(Default)]
+pub struct Mixer(u64);
+
+impl Hasher for Mixer {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.write_u64(u64::from_le_bytes(chunk.try_into().expect("8 bytes")));
+        }
+        for byte in chunks.remainder() {
+            self.write_u64(u64::from(*byte));
+        }
+    }
+
+    fn write_u64(&mut self, word: u64) {
+        self.0 = (self.0.rotate_left(5) ^ word).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+
+    fn write_u8(&mut self, byte: u8) {
+        self.write_u64(u64::from(byte));
+    }
+
+    fn write_usize(&mut self, word: usize) {
+        self.write_u64(word as u64);
+    }
+
+    /// The product's high bits are its best mixed, and the table takes the
+    /// low ones.
+    fn finish(&self) -> u64 {
+        self.0.rotate_left(26)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn equal_subtrees_hash_alike() {
+        let (left, right) = (json!({"a": {"x": [1, 2]}, "b": [{"y": null}]}), json!({"b": [{"y": null}], "a": {"x": [1, 2]}}));
+        let hashes = Hashes::of(&[&left, &right]);
+        assert!(!hashes.differ(&left, &right));
+        assert!(!hashes.differ(&left["a"], &right["a"]));
+        assert!(!hashes.differ(&left["b"][0], &right["b"][0]));
+    }
+
+    #[test]
+    fn differing_subtrees_hash_apart() {
+        let (left, right) = (json!({"a": [1, 2], "b": {"s": "x"}, "c": [1]}), json!({"a": [2, 1], "b": {"s": "y"}, "c": [1.0]}));
+        let hashes = Hashes::of(&[&left, &right]);
+        assert!(hashes.differ(&left, &right));
+        assert!(hashes.differ(&left["a"], &right["a"]));
+        assert!(hashes.differ(&left["b"], &right["b"]));
+        assert!(hashes.differ(&left["c"], &right["c"]));
+        // Scalars have no hash, so only comparing them tells.
+        assert!(!hashes.differ(&left["b"]["s"], &right["b"]["s"]));
+    }
+
+    #[test]
+    fn equal_hashes_are_confirmed_by_the_values() {
+        let (one, two) = (json!([1]), json!([2]));
+        assert!(Hashed(&one, Some(7)) != Hashed(&two, Some(7)));
+        assert!(Hashed(&one, Some(7)) == Hashed(&one, Some(7)));
+        assert!(Hashed(&one, Some(7)) != Hashed(&one, Some(8)));
+        assert!(Hashed(&one, None) == Hashed(&one, Some(8)));
+    }
+}
@@ -0,0 +1,278 @@
+//! A local log of past comparisons, and `diffrs history` to query it.
+//!
+//! Every completed comparison, in the TUI or headless, appends one JSON line
+//! to `history.jsonl` under the data directory (`$XDG_DATA_HOME/diffrs`,
+//! else `~/.local/share/diffrs`). The file keeps the newest `HISTORY_CAP`
+//! records. `--no-history` skips recording.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use crate::report::{ChangeKind, Summary};
+use crate::{atomic, check, timefmt};
+
+/// Records kept; older ones are dropped on append.
+const HISTORY_CAP: usize = 1000;
+
+/// Changed paths stored per record for `history show`.
+const STORED_CHANGES: usize = 200;
+
+#[derive(Serialize, Deserialize)]
+pub struct Record {
+    pub id: u64,
+    pub timestamp: String,
+    /// `tui`, `headless`, `check` or `hook`.
+    pub mode: String,
+    pub sources: [String; 2],
+    /// A hash of the options that shape the comparison, so runs with
+    /// different ignore rules or policies can be told apart.
+    pub options: String,
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    pub type_changed: usize,
+    /// The share of leaf values that are unchanged, from 0 to 1.
+    pub similarity: f64,
+    /// `+ path`, `- path`, ... for up to `STORED_CHANGES` changes.
+    pub changes: Vec<String>,
+    /// Arguments for `diffrs check` that repeat the comparison, when its
+    /// sources are files.
+    pub rerun: Option<Vec<String>>,
+}
+
+impl Record {
+    fn total(&self) -> usize {
+        self.added + self.removed + self.changed + self.type_changed
+    }
+
+    fn counts(&self) -> String {
+        format!("+{} -{} ~{} !{}", self.added, self.removed, self.changed, self.type_changed)
+    }
+}
+
+/// Where this session's comparisons are recorded.
+pub struct History {
+    file: PathBuf,
+    mode: &'static str,
+    options: String,
+}
+
+impl History {
+    /// `None` with `--no-history` or when there is no data directory.
+    pub fn open(disabled: bool, mode: &'static str, options: &[String]) -> Option<Self> {
+        if disabled {
+            return None;
+        }
+        Some(Self { file: history_file()?, mode, options: fingerprint(options) })
+    }
+
+    /// Appends a record of a comparison of `leaves` values (see
+    /// `leaf_total`). Failures to write are reported but never fail the
+    /// comparison itself.
+    pub fn record(&self, sources: [String; 2], summary: &Summary, leaves: usize, rerun: Option<Vec<String>>) {
+        let total = leaves.max(1);
+        let changes = summary
+            .changes
+            .iter()
+            .take(STORED_CHANGES)
+            .map(|change| format!("{} {}", change.kind.marker(), change.path))
+            .collect();
+        let record = Record {
+            id: 0,
+            timestamp: timefmt::format_rfc3339(timefmt::now_millis()),
+            mode: self.mode.to_string(),
+            sources,
+            options: self.options.clone(),
+            added: summary.count(ChangeKind::Added),
+            removed: summary.count(ChangeKind::Removed),
+            changed: summary.count(ChangeKind::Changed),
+            type_changed: summary.count(ChangeKind::TypeChanged),
+            similarity: (1.0 - summary.changes.len().min(total) as f64 / total as f64).clamp(0.0, 1.0),
+            changes,
+            rerun,
+        };
+        if let Err(err) = self.append(record) {
+            eprintln!("diffrs: could not record history in {}: {err:#}", self.file.display());
+        }
+    }
+
+    fn append(&self, mut record: Record) -> Result<()> {
+        let mut records = read_records(&self.file)?;
+        record.id = records.last().map_or(1, |last| last.id + 1);
+        records.push(record);
+        let keep = records.len().saturating_sub(HISTORY_CAP);
+        if let Some(dir) = self.file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut contents = Vec::new();
+        for record in &records[keep..] {
+            serde_json::to_writer(&mut contents, record)?;
+            contents.push(b'\n');
+        }
+        atomic::write(&self.file, contents)
+    }
+}
+
+pub fn history_file() -> Option<PathBuf> {
+    let data = match std::env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(data.join("diffrs").join("history.jsonl"))
+}
+
+fn read_records(file: &PathBuf) -> Result<Vec<Record>> {
+    let contents = match std::fs::read_to_string(file) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| file.display().to_string()),
+    };
+    // A line cut short by a crash shouldn't lose the rest of the history.
+    Ok(contents.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+/// Distinct source pairs of the newest comparisons that can be loaded again:
+/// files that still exist, or URLs.
+pub fn recent_pairs(limit: usize) -> Vec<[String; 2]> {
+    let Some(file) = history_file() else {
+        return Vec::new();
+    };
+    let loadable = |source: &String| source.starts_with("http://") || source.starts_with("https://") || Path::new(source).is_file();
+    let mut pairs: Vec<[String; 2]> = Vec::new();
+    for record in read_records(&file).unwrap_or_default().into_iter().rev() {
+        if pairs.len() == limit {
+            break;
+        }
+        if record.sources.iter().all(loadable) && !pairs.contains(&record.sources) {
+            pairs.push(record.sources);
+        }
+    }
+    pairs
+}
+
+/// A short, stable FNV-1a hash of the options.
+fn fingerprint(options: &[String]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in options.join("\0").bytes() {
+        hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:08x}", hash >> 32)
+}
+
+/// The size of a comparison for similarity: leaf values on the larger side.
+pub fn leaf_total(left: &Value, right: &Value) -> usize {
+    leaves(left).max(leaves(right))
+}
+
+/// Scalars and empty containers in `value`.
+pub fn leaves(value: &Value) -> usize {
+    match value {
+        Value::Object(map) if !map.is_empty() => map.values().map(leaves).sum(),
+        Value::Array(items) if !items.is_empty() => items.iter().map(leaves).sum(),
+        _ => 1,
+    }
+}
+
+/// `diffrs history [--source S] [--since WHEN] [--changed-only] [--limit N]`
+/// and `diffrs history show <id> [--rerun]`. Returns the exit code.
+pub fn run(args: &[String]) -> Result<i32> {
+    let file = history_file().ok_or_else(|| anyhow!("no data directory: set XDG_DATA_HOME or HOME"))?;
+    let records = read_records(&file)?;
+    if args.first().map(String::as_str) == Some("show") {
+        return show(&records, &args[1..]);
+    }
+
+    let (mut source, mut since, mut changed_only, mut limit) = (None, None, false, 20);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| anyhow!("{arg} needs a value"));
+        match arg.as_str() {
+            "--source" => source = Some(value()?.clone()),
+            "--since" => since = Some(parse_since(value()?)?),
+            "--changed-only" => changed_only = true,
+            "--limit" => {
+                let value = value()?;
+                limit = value.parse().map_err(|_| anyhow!("--limit: '{value}' is not a number"))?;
+            }
+            other => bail!("unknown history option '{other}'"),
+        }
+    }
+
+    let matching: Vec<&Record> = records
+        .iter()
+        .filter(|record| source.as_ref().is_none_or(|s| record.sources.iter().any(|source| source.contains(s.as_str()))))
+        .filter(|record| since.is_none_or(|since| timefmt::parse_rfc3339(&record.timestamp).is_some_and(|t| t >= since)))
+        .filter(|record| !changed_only || record.total() > 0)
+        .collect();
+    if matching.is_empty() {
+        println!("no recorded comparisons match ({})", file.display());
+        return Ok(0);
+    }
+    for record in &matching[matching.len().saturating_sub(limit)..] {
+        println!(
+            "{:>5}  {}  {:<5}  {:<18} {:>5.1}%  {} → {}",
+            record.id,
+            record.timestamp,
+            record.mode,
+            record.counts(),
+            record.similarity * 100.0,
+            record.sources[0],
+            record.sources[1]
+        );
+    }
+    Ok(0)
+}
+
+fn show(records: &[Record], args: &[String]) -> Result<i32> {
+    let usage = "usage: diffrs history show <id> [--rerun]";
+    let id: u64 = args.first().and_then(|id| id.parse().ok()).ok_or_else(|| anyhow!(usage))?;
+    let rerun = match args.get(1).map(String::as_str) {
+        None => false,
+        Some("--rerun") => true,
+        Some(_) => bail!(usage),
+    };
+    let record = records.iter().find(|record| record.id == id).ok_or_else(|| anyhow!("no comparison #{id} in history"))?;
+    if rerun {
+        let args = record
+            .rerun
+            .as_ref()
+            .ok_or_else(|| anyhow!("comparison #{id} wasn't between files and can't be re-run"))?;
+        return check::run(args);
+    }
+    println!("#{} {} ({}, options {})", record.id, record.timestamp, record.mode, record.options);
+    println!("{} → {}", record.sources[0], record.sources[1]);
+    println!(
+        "{} added, {} removed, {} changed, {} type-changed; {:.1}% similar",
+        record.added,
+        record.removed,
+        record.changed,
+        record.type_changed,
+        record.similarity * 100.0
+    );
+    for change in &record.changes {
+        println!("  {change}");
+    }
+    if record.total() > record.changes.len() {
+        println!("  … {} more not stored", record.total() - record.changes.len());
+    }
+    Ok(0)
+}
+
+/// `--since 2024-06-01`, a full RFC 3339 timestamp, or an age like `2h`/`3d`.
+fn parse_since(value: &str) -> Result<i64> {
+    if let Some(millis) = timefmt::parse_rfc3339(value).or_else(|| timefmt::parse_rfc3339(&format!("{value}T00:00:00Z"))) {
+        return Ok(millis);
+    }
+    let (number, unit) = value.split_at(value.len().saturating_sub(1));
+    let seconds = match unit {
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 7 * 86_400,
+        _ => bail!("--since: expected a date (2024-06-01) or an age (30m, 2h, 3d, 1w), got '{value}'"),
+    };
+    let count: i64 = number.parse().map_err(|_| anyhow!("--since: '{value}' is not a date or an age"))?;
+    Ok(timefmt::now_millis() - count * seconds * 1000)
+}
@@ -0,0 +1,171 @@
+//! `diffrs hook`: a non-interactive guard for pre-commit that compares staged
+//! JSON/YAML files against HEAD (or another revision, or the worktree) and
+//! prints a plain-text summary of the semantic changes per file.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::Path;
+use std::process::Command;
+
+use crate::cli::Cli;
+use crate::history::{self, History};
+use crate::ignore;
+use crate::normalize::Normalizers;
+use crate::repair;
+use crate::report::{ChangeKind, FailPolicy};
+use crate::{compare_inputs, report_limit, CancelToken, Comparison};
+
+/// What the staged blob is compared against. The target is always the left
+/// side and the staged content the right side.
+enum Target {
+    Revision(String),
+    Worktree,
+}
+
+struct HookArgs {
+    target: Target,
+    policy: FailPolicy,
+    /// The comparison's options, as the TUI takes them, and the paths named.
+    cli: Cli,
+}
+
+impl HookArgs {
+    fn parse(args: &[String]) -> Result<Self> {
+        let mut target = Target::Revision("HEAD".to_string());
+        let mut policy = FailPolicy::new(vec![ChangeKind::Removed]);
+        let mut rest = Vec::new();
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if policy.parse_arg(arg, &mut iter)? {
+                continue;
+            }
+            match arg.as_str() {
+                "--against" => {
+                    let value = iter.next().ok_or_else(|| anyhow!("--against needs a revision or 'worktree'"))?;
+                    target = if value == "worktree" {
+                        Target::Worktree
+                    } else {
+                        Target::Revision(value.clone())
+                    };
+                }
+                _ => rest.push(arg.clone()),
+            }
+        }
+
+        Ok(Self { target, policy, cli: Cli::parse(&rest)? })
+    }
+}
+
+/// Runs the hook and returns the process exit code.
+pub fn run(raw_args: &[String]) -> Result<i32> {
+    let args = HookArgs::parse(raw_args)?;
+    let cli = &args.cli;
+    let paths = match cli.paths.is_empty() {
+        true => staged_paths()?,
+        false => cli.paths.iter().map(|path| path.display().to_string()).collect(),
+    };
+    let options: Vec<String> = raw_args.iter().filter(|arg| !paths.contains(arg)).cloned().collect();
+    let history = History::open(cli.no_history, "hook", &options);
+    let normalizers = Normalizers::locale_numbers(&cli.locale_numbers)?;
+    let redactor = cli.redact.redactor()?;
+    let cancel = CancelToken::default();
+    cancel.cancel_on_interrupt()?;
+    let commit = match &args.target {
+        Target::Revision(rev) => commit(rev)?,
+        Target::Worktree => None,
+    };
+
+    let mut summaries = Vec::new();
+    for path in &paths {
+        let staged = git(&["show", &format!(":./{path}")])
+            .with_context(|| format!("{path}: not staged"))?;
+        // Only a file missing from the target is new; failing to read one
+        // that is there is an error.
+        let target = match (&args.target, &commit) {
+            (Target::Revision(_), Some(commit)) if in_commit(commit, path)? => Some(git(&["show", &format!("{commit}:./{path}")])?),
+            (Target::Revision(_), _) => None,
+            (Target::Worktree, _) => match std::fs::read_to_string(path) {
+                Ok(contents) => Some(contents),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                Err(err) => return Err(err).with_context(|| path.clone()),
+            },
+        };
+
+        // Each file gets the ignore rules discovered from its own directory.
+        let rules = cli.ignore.rules(&[ignore::input_dir(Path::new(path))])?;
+        if cli.ignore.show {
+            println!("{path}:");
+            print!("{}", rules.describe());
+            continue;
+        }
+        // A new file is compared against an empty document, so all it holds
+        // counts as added.
+        let title = match target {
+            Some(_) => path.clone(),
+            None => format!("{path} (new file)"),
+        };
+        let sources = [path.clone(), path.clone()];
+        let Some(Comparison { left, right, rows, identical, repairs, .. }) =
+            compare_inputs(cli, &sources, &[target.unwrap_or_default(), staged], &redactor, &rules, &normalizers, &cancel)?
+        else {
+            eprintln!("diffrs: hook cancelled");
+            return Ok(130);
+        };
+        if identical {
+            println!("{title}: documents are identical");
+        } else {
+            rows.summary.print(&title, report_limit(cli));
+        }
+        repair::print(&repairs);
+        if let Some(history) = &history {
+            let target = match &args.target {
+                Target::Revision(rev) => format!("{rev}:{path}"),
+                Target::Worktree => format!("worktree:{path}"),
+            };
+            history.record([target, format!("staged:{path}")], &rows.summary, history::leaf_total(&left, &right), None);
+        }
+        summaries.push(rows.summary);
+    }
+
+    if let Some(reason) = args.policy.evaluate(&summaries.iter().map(|summary| &**summary).collect::<Vec<_>>()) {
+        println!("diffrs: failed across {} files: {reason}", paths.len());
+        return Ok(1);
+    }
+    Ok(0)
+}
+
+/// Staged JSON/YAML files relative to the current directory, excluding deletions.
+fn staged_paths() -> Result<Vec<String>> {
+    let output = git(&["diff", "--cached", "--name-only", "--relative", "--diff-filter=ACMR"])?;
+    Ok(output
+        .lines()
+        .filter(|line| {
+            let ext = Path::new(line).extension().and_then(|ext| ext.to_str()).unwrap_or("");
+            matches!(ext, "json" | "yaml" | "yml")
+        })
+        .map(str::to_string)
+        .collect())
+}
+
+/// The commit `rev` names; `None` for `HEAD` on a branch with no commits
+/// yet, against which every file is new.
+fn commit(rev: &str) -> Result<Option<String>> {
+    match git(&["rev-parse", "--verify", "--quiet", &format!("{rev}^{{commit}}")]) {
+        Ok(id) => Ok(Some(id.trim().to_string())),
+        Err(_) if rev == "HEAD" && git(&["symbolic-ref", "--quiet", "HEAD"]).is_ok() => Ok(None),
+        Err(_) => bail!("--against {rev}: not a commit"),
+    }
+}
+
+/// Whether `path`, relative to the current directory, is in `commit`.
+fn in_commit(commit: &str, path: &str) -> Result<bool> {
+    Ok(!git(&["ls-tree", "--name-only", commit, "--", path])?.trim().is_empty())
+}
+
+fn git(args: &[&str]) -> Result<String> {
+    let output = Command::new("git").args(args).output().context("failed to run git")?;
+    if !output.status.success() {
+        bail!("git {}: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
@@ -0,0 +1,311 @@
+//! Ignore and mask rules, from `.diffrsignore` files and the command line.
+//!
+//! A `.diffrsignore` holds one rule per line (`#` starts a comment):
+//!
+//! ```text
+//! metadata.generated_at      # ignore: dropped from both sides
+//! mask build.*.checksum      # mask: presence compared, value hidden
+//! !metadata.owner            # cancel a rule from a farther file
+//! ```
+//!
+//! Files are collected from the input's directory up to the repository root
+//! (the first directory with a `.git`) or the filesystem root. Rules are keyed
+//! by pattern and the nearest file wins for a pattern; `--ignore`/`--mask`
+//! flags beat every file.
+//!
+//! A pattern is a dotted path where `*` matches any one key or index, `[*]`
+//! any array index, `[3]` (or `3`) the fourth element, and `**` any number of
+//! segments, none included: `metadata.**.timestamp`, `items[*].etag`. A rule
+//! matching an object or array drops all of it.
+//!
+//! `--ignore-key <name>` drops every member named `name`, at any depth, from
+//! both sides: keys like `updated_at` that differ on every comparison.
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+pub const FILE_NAME: &str = ".diffrsignore";
+
+/// Shown instead of a masked value on both sides.
+const MASKED: &str = "«masked»";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Action {
+    Ignore,
+    Mask,
+    /// `!pattern`: removes a rule inherited from a farther file.
+    Include,
+}
+
+/// Command-line options controlling ignore rules.
+#[derive(Clone, Default)]
+pub struct IgnoreArgs {
+    /// `--no-ignore-file`: skip `.diffrsignore` discovery.
+    pub no_file: bool,
+    pub ignores: Vec<String>,
+    pub masks: Vec<String>,
+    /// `--ignore-key`: key names ignored wherever they are.
+    pub keys: Vec<String>,
+    /// `--show-rules`: print the effective rules and exit.
+    pub show: bool,
+}
+
+impl IgnoreArgs {
+    /// Consumes `arg` (and its value from `iter`) if it is an ignore option.
+    pub fn parse_arg<'a>(&mut self, arg: &str, iter: &mut impl Iterator<Item = &'a String>) -> Result<bool> {
+        match arg {
+            "--no-ignore-file" => self.no_file = true,
+            "--show-rules" => self.show = true,
+            "--ignore" => self.ignores.push(iter.next().ok_or_else(|| anyhow!("--ignore needs a path pattern"))?.clone()),
+            "--mask" => self.masks.push(iter.next().ok_or_else(|| anyhow!("--mask needs a path pattern"))?.clone()),
+            "--ignore-key" => self.keys.push(iter.next().ok_or_else(|| anyhow!("--ignore-key needs a key name"))?.clone()),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// The effective rules for inputs in `dirs`.
+    pub fn rules(&self, dirs: &[&Path]) -> Result<IgnoreRules> {
+        let mut rules = IgnoreRules::default();
+        if !self.no_file {
+            for dir in dirs {
+                for file in discover(dir) {
+                    if !rules.files.contains(&file) {
+                        rules.files.push(file);
+                    }
+                }
+            }
+        }
+        // `files` is nearest first; apply farthest first so nearer files override.
+        for file in rules.files.clone().iter().rev() {
+            let contents = std::fs::read_to_string(file).with_context(|| file.display().to_string())?;
+            for (number, line) in contents.lines().enumerate() {
+                let line = line.split('#').next().unwrap_or("").trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let (action, pattern) = if let Some(pattern) = line.strip_prefix("mask ") {
+                    (Action::Mask, pattern.trim())
+                } else if let Some(pattern) = line.strip_prefix('!') {
+                    (Action::Include, pattern.trim())
+                } else {
+                    (Action::Ignore, line)
+                };
+                rules.set(pattern, action, format!("{}:{}", file.display(), number + 1));
+            }
+        }
+        for pattern in &self.ignores {
+            rules.set(pattern, Action::Ignore, "--ignore".to_string());
+        }
+        for pattern in &self.masks {
+            rules.set(pattern, Action::Mask, "--mask".to_string());
+        }
+        rules.keys.extend(self.keys.iter().cloned());
+        Ok(rules)
+    }
+}
+
+/// `.diffrsignore` files from `dir` upwards, nearest first.
+fn discover(dir: &Path) -> Vec<PathBuf> {
+    let start = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let mut files = Vec::new();
+    for ancestor in start.ancestors() {
+        let file = ancestor.join(FILE_NAME);
+        if file.is_file() {
+            files.push(file);
+        }
+        if ancestor.join(".git").exists() {
+            break;
+        }
+    }
+    files
+}
+
+#[derive(Default)]
+pub struct IgnoreRules {
+    /// Loaded `.diffrsignore` files, nearest first.
+    pub files: Vec<PathBuf>,
+    /// Pattern text to action and the rule's origin.
+    rules: BTreeMap<String, (Action, String)>,
+    /// `--ignore-key` names.
+    keys: BTreeSet<String>,
+}
+
+impl IgnoreRules {
+    fn set(&mut self, pattern: &str, action: Action, source: String) {
+        if action == Action::Include {
+            self.rules.remove(pattern);
+        } else {
+            self.rules.insert(pattern.to_string(), (action, source));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty() && self.keys.is_empty()
+    }
+
+    /// Drops ignored paths and keys and masks masked ones, returning the
+    /// paths dropped (`items[2].etag`). Ignored array elements become `null`
+    /// so the remaining elements keep their positions.
+    pub fn apply(&self, value: &mut Value) -> Vec<String> {
+        let mut dropped = Vec::new();
+        if self.is_empty() {
+            return dropped;
+        }
+        let patterns: Vec<(Vec<&str>, Action)> =
+            self.rules.iter().map(|(pattern, (action, _))| (segments(pattern), *action)).collect();
+        apply_in(value, &mut Vec::new(), &patterns, &self.keys, &mut dropped);
+        dropped
+    }
+
+    /// The `--show-rules` report.
+    pub fn describe(&self) -> String {
+        let mut report = String::new();
+        if self.files.is_empty() {
+            report.push_str("no .diffrsignore files loaded\n");
+        }
+        for file in &self.files {
+            report.push_str(&format!("loaded {}\n", file.display()));
+        }
+        for (pattern, (action, source)) in &self.rules {
+            let action = if *action == Action::Mask { "mask  " } else { "ignore" };
+            report.push_str(&format!("{action} {pattern}  ({source})\n"));
+        }
+        for key in &self.keys {
+            report.push_str(&format!("ignore key {key}  (--ignore-key)\n"));
+        }
+        report
+    }
+}
+
+/// `path`'s segments are keys, and array indexes in brackets (`[2]`).
+fn apply_in(
+    value: &mut Value,
+    path: &mut Vec<String>,
+    patterns: &[(Vec<&str>, Action)],
+    keys: &BTreeSet<String>,
+    dropped: &mut Vec<String>,
+) {
+    let action_at = |path: &[String]| {
+        patterns.iter().find(|(pattern, _)| glob_matches(pattern, path)).map(|(_, action)| *action)
+    };
+    match value {
+        Value::Object(map) => {
+            let names: Vec<String> = map.keys().cloned().collect();
+            for key in names {
+                path.push(key.clone());
+                if keys.contains(&key) || action_at(path) == Some(Action::Ignore) {
+                    map.remove(&key);
+                    dropped.push(display_path(path));
+                    path.pop();
+                    continue;
+                }
+                match action_at(path) {
+                    Some(Action::Mask) => {
+                        map.insert(key, Value::String(MASKED.to_string()));
+                    }
+                    _ => apply_in(map.get_mut(&key).expect("key just listed"), path, patterns, keys, dropped),
+                }
+                path.pop();
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                path.push(format!("[{index}]"));
+                match action_at(path) {
+                    Some(Action::Ignore) => {
+                        *item = Value::Null;
+                        dropped.push(display_path(path));
+                    }
+                    Some(Action::Mask) => *item = Value::String(MASKED.to_string()),
+                    _ => apply_in(item, path, patterns, keys, dropped),
+                }
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A rule's pattern split into segments: `a.b[*]` is `a`, `b` and `[*]`.
+fn segments(pattern: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    for part in pattern.split('.') {
+        let mut start = 0;
+        for (at, _) in part.match_indices('[').filter(|&(at, _)| at > 0) {
+            segments.push(&part[start..at]);
+            start = at;
+        }
+        segments.push(&part[start..]);
+    }
+    segments
+}
+
+/// Whether glob `pattern` matches a path as the diff rows name it
+/// (`items[2].etag`).
+pub fn glob_matches_path(pattern: &str, path: &str) -> bool {
+    let path: Vec<String> = segments(path).into_iter().map(str::to_string).collect();
+    glob_matches(&segments(pattern), &path)
+}
+
+/// A path as the diff rows name it split into segments, indices as plain
+/// numbers: `items[2].etag` is `items`, `2` and `etag`.
+pub fn path_segments(path: &str) -> Vec<String> {
+    segments(path)
+        .into_iter()
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| segment.strip_prefix('[').and_then(|index| index.strip_suffix(']')).unwrap_or(segment).to_string())
+        .collect()
+}
+
+/// Whether glob `pattern` matches `path`, as `apply_in` spells it.
+fn glob_matches(pattern: &[&str], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| glob_matches(rest, &path[skip..])),
+        Some((first, rest)) => {
+            path.split_first().is_some_and(|(segment, tail)| segment_matches(first, segment) && glob_matches(rest, tail))
+        }
+    }
+}
+
+fn segment_matches(pattern: &str, segment: &str) -> bool {
+    match segment.strip_prefix('[').and_then(|index| index.strip_suffix(']')) {
+        Some(index) => pattern == "*" || pattern == "[*]" || pattern == segment || pattern == index,
+        None => pattern == "*" || pattern == segment,
+    }
+}
+
+/// `items[2].etag`, from `apply_in`'s segments.
+fn display_path(path: &[String]) -> String {
+    let mut shown = String::new();
+    for segment in path {
+        if !shown.is_empty() && !segment.starts_with('[') {
+            shown.push('.');
+        }
+        shown.push_str(segment);
+    }
+    shown
+}
+
+/// The directory to start discovery from for an input file.
+pub fn input_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Whether a dotted pattern matches `path`; `*` matches any single key or
+/// array index.
+pub fn path_matches<S: AsRef<str>>(pattern: &[S], path: &[String]) -> bool {
+    pattern.len() == path.len() && pattern.iter().zip(path).all(|(p, segment)| p.as_ref() == "*" || p.as_ref() == segment)
+}
+
+/// Key names from a comma-separated list, as typed at the `i` prompt or in
+/// the `O` panel.
+pub fn key_names(input: &str) -> Vec<String> {
+    input.split(',').map(str::trim).filter(|name| !name.is_empty()).map(str::to_string).collect()
+}
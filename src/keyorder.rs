@@ -0,0 +1,305 @@
+//! `--key-order`: for documents whose key order means something, such as
+//! hand-kept configuration, the order keys were written in. Documents are
+//! held in maps sorted by key, so the order is read from the text again,
+//! into an `Order` mirroring the document. With it the panes show keys as
+//! written, and the diff reports a key present on both sides with an equal
+//! value but in another place among the keys they share as moved. Without
+//! the flag none of this is read and key order never matters.
+
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+use std::path::Path;
+
+use crate::cli::Format;
+use crate::display;
+use crate::hash::Mixer;
+
+/// The keys of each object of a document in the order they were written,
+/// and the same for the values inside them.
+#[derive(Default)]
+pub enum Order {
+    /// A scalar, or a value whose order isn't known.
+    #[default]
+    Unknown,
+    Object(Vec<(String, Order)>),
+    Array(Vec<Order>),
+}
+
+impl Order {
+    /// The order of the document `contents`, read as `parse_document` reads
+    /// it; unknown where it can't be read. Newline-delimited records are an
+    /// array of them.
+    pub fn read(contents: &str, path: &Path, format: Format) -> Self {
+        let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+        match format.resolve(path, contents) {
+            Format::Text => Self::Unknown,
+            Format::Yaml => Self::deserialize(serde_yaml::Deserializer::from_str(contents)).unwrap_or_default(),
+            _ => {
+                let mut deserializer = serde_json::Deserializer::from_str(contents);
+                deserializer.disable_recursion_limit();
+                let records: Result<Vec<Self>, _> = deserializer.into_iter().collect();
+                match records {
+                    Ok(mut records) if records.len() == 1 => records.remove(0),
+                    Ok(records) => Self::Array(records),
+                    Err(_) => Self::Unknown,
+                }
+            }
+        }
+    }
+
+    /// The order of the member `key`.
+    pub fn member(self, key: &str) -> Self {
+        match self {
+            Self::Object(members) => members.into_iter().find(|(member, _)| member == key).map(|(_, order)| order).unwrap_or_default(),
+            _ => Self::Unknown,
+        }
+    }
+
+    /// The order of the element at `index`.
+    pub fn element(self, index: usize) -> Self {
+        match self {
+            Self::Array(elements) => elements.into_iter().nth(index).unwrap_or_default(),
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Order {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(OrderVisitor)
+    }
+}
+
+struct OrderVisitor;
+
+impl<'de> Visitor<'de> for OrderVisitor {
+    type Value = Order;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Order, E> {
+        Ok(Order::Unknown)
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<Order, E> {
+        Ok(Order::Unknown)
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<Order, E> {
+        Ok(Order::Unknown)
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<Order, E> {
+        Ok(Order::Unknown)
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<Order, E> {
+        Ok(Order::Unknown)
+    }
+
+    fn visit_unit<E>(self) -> Result<Order, E> {
+        Ok(Order::Unknown)
+    }
+
+    fn visit_none<E>(self) -> Result<Order, E> {
+        Ok(Order::Unknown)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Order, D::Error> {
+        Order::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Order, A::Error> {
+        let mut elements = Vec::new();
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(Order::Array(elements))
+    }
+
+    /// A repeated key keeps its first place, as it would in an ordered map.
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Order, A::Error> {
+        let mut members: Vec<(String, Order)> = Vec::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let order = map.next_value()?;
+            match members.iter_mut().find(|(member, _)| *member == key) {
+                Some(member) => member.1 = order,
+                None => members.push((key, order)),
+            }
+        }
+        Ok(Order::Object(members))
+    }
+}
+
+struct Node {
+    /// An object's keys in the order written, those whose order isn't
+    /// known after them in sorted order; empty for an array.
+    keys: Vec<String>,
+    /// Equal for equal values written in the same order.
+    hash: u64,
+}
+
+/// The key order of every object of the documents being diffed, by its
+/// address, as `hash::Hashes` has their hashes. Empty without `--key-order`.
+#[derive(Default)]
+pub struct KeyOrders {
+    by_node: HashMap<usize, Node, BuildHasherDefault<Mixer>>,
+}
+
+impl KeyOrders {
+    /// The orders of `documents`, each read with its `Order`; the documents
+    /// may have lost or gained values since, whose order is then sorted.
+    pub fn of(documents: &[(&Value, &Order)]) -> Self {
+        let mut orders = Self::default();
+        for (document, order) in documents {
+            orders.walk(document, order);
+        }
+        orders
+    }
+
+    fn walk(&mut self, value: &Value, order: &Order) -> u64 {
+        let mut hasher = Mixer::default();
+        let (address, keys) = match value {
+            Value::Object(map) => {
+                let written: BTreeMap<&str, &Order> = match order {
+                    Order::Object(members) => members.iter().map(|(key, order)| (key.as_str(), order)).collect(),
+                    _ => BTreeMap::new(),
+                };
+                let mut keys: Vec<String> = match order {
+                    Order::Object(members) => members.iter().map(|(key, _)| key).filter(|key| map.contains_key(*key)).cloned().collect(),
+                    _ => Vec::new(),
+                };
+                keys.extend(map.keys().filter(|key| !written.contains_key(key.as_str())).cloned());
+                (5u8, keys.len()).hash(&mut hasher);
+                for key in &keys {
+                    let child = self.walk(&map[key], written.get(key.as_str()).copied().unwrap_or(&Order::Unknown));
+                    (key, child).hash(&mut hasher);
+                }
+                (map as *const Map<String, Value> as usize, keys)
+            }
+            // The elements' orders in any order, so arrays compared as sets
+            // don't count as reordered.
+            Value::Array(items) => {
+                let elements = match order {
+                    Order::Array(elements) => elements.as_slice(),
+                    _ => &[],
+                };
+                let sum = items.iter().enumerate().fold(0u64, |sum, (index, item)| {
+                    sum.wrapping_add(self.walk(item, elements.get(index).unwrap_or(&Order::Unknown)))
+                });
+                (4u8, items.len(), sum).hash(&mut hasher);
+                (items as *const Vec<Value> as usize, Vec::new())
+            }
+            _ => return 0,
+        };
+        let hash = hasher.finish();
+        self.by_node.insert(address, Node { keys, hash });
+        hash
+    }
+
+    fn node(&self, value: &Value) -> Option<&Node> {
+        let address = match value {
+            Value::Object(map) => map as *const Map<String, Value> as usize,
+            Value::Array(items) => items as *const Vec<Value> as usize,
+            _ => return None,
+        };
+        self.by_node.get(&address)
+    }
+
+    /// Whether equal `left` and `right` have keys written in another order
+    /// somewhere inside them.
+    pub fn differ(&self, left: &Value, right: &Value) -> bool {
+        matches!((self.node(left), self.node(right)), (Some(l), Some(r)) if l.hash != r.hash)
+    }
+
+    /// The keys of `map` in the order written.
+    pub fn keys(&self, map: &Map<String, Value>) -> Option<&[String]> {
+        self.by_node.get(&(map as *const Map<String, Value> as usize)).map(|node| node.keys.as_slice())
+    }
+
+    /// The keys both objects have whose place among those they share differs,
+    /// with where each is written on the left and on the right. The most
+    /// keys possible are taken as staying in place.
+    pub fn moved(&self, left: &Map<String, Value>, right: &Map<String, Value>) -> BTreeMap<String, [usize; 2]> {
+        let (Some(left_keys), Some(right_keys)) = (self.keys(left), self.keys(right)) else {
+            return BTreeMap::new();
+        };
+        let shared = |keys: &[String], other: &Map<String, Value>| -> Vec<(usize, String)> {
+            keys.iter().enumerate().filter(|(_, key)| other.contains_key(*key)).map(|(index, key)| (index, key.clone())).collect()
+        };
+        let (l, r) = (shared(left_keys, right), shared(right_keys, left));
+        // Too many to align; none is taken as moved.
+        if l.len().saturating_mul(r.len()) > 4_000_000 {
+            return BTreeMap::new();
+        }
+        // Longest common subsequence of the shared keys.
+        let mut lengths = vec![vec![0u32; r.len() + 1]; l.len() + 1];
+        for i in (0..l.len()).rev() {
+            for j in (0..r.len()).rev() {
+                lengths[i][j] = if l[i].1 == r[j].1 { lengths[i + 1][j + 1] + 1 } else { lengths[i + 1][j].max(lengths[i][j + 1]) };
+            }
+        }
+        let (mut i, mut j, mut staying) = (0, 0, Vec::new());
+        while i < l.len() && j < r.len() {
+            if l[i].1 == r[j].1 {
+                staying.push(&l[i].1);
+                (i, j) = (i + 1, j + 1);
+            } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        let right_index: BTreeMap<&String, usize> = r.iter().map(|(index, key)| (key, *index)).collect();
+        l.iter().filter(|(_, key)| !staying.contains(&key)).map(|(index, key)| (key.clone(), [*index, right_index[key]])).collect()
+    }
+
+    /// Compact JSON for display, keys in the order written, as
+    /// `display::render_value` renders it otherwise.
+    pub fn render_value(&self, value: &Value, decode: bool) -> String {
+        if self.by_node.is_empty() {
+            return display::render_value(value, decode);
+        }
+        let text = serde_json::to_string(&Ordered { value, orders: self, decode }).unwrap_or_default();
+        display::sanitize(&text).into_owned()
+    }
+
+    /// Pretty-printed JSON for display, keys in the order written, as
+    /// `display::render_pretty` renders it otherwise.
+    pub fn render_pretty(&self, value: &Value, decode: bool) -> String {
+        if self.by_node.is_empty() {
+            return display::render_pretty(value, decode);
+        }
+        display::sanitize_lines(&serde_json::to_string_pretty(&Ordered { value, orders: self, decode }).unwrap_or_default())
+    }
+}
+
+/// Serializes a value with its objects' keys in the order written, and with
+/// `decode` its strings' literal `\uXXXX` sequences decoded.
+#[derive(Clone, Copy)]
+struct Ordered<'a> {
+    value: &'a Value,
+    orders: &'a KeyOrders,
+    decode: bool,
+}
+
+impl Serialize for Ordered<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let child = |value| Ordered { value, ..*self };
+        match self.value {
+            Value::String(text) if self.decode => serializer.serialize_str(&display::decode_literal_escapes(text)),
+            Value::Array(items) => serializer.collect_seq(items.iter().map(child)),
+            Value::Object(map) => match self.orders.keys(map) {
+                Some(keys) => serializer.collect_map(keys.iter().map(|key| (key, child(&map[key])))),
+                None => serializer.collect_map(map.iter().map(|(key, value)| (key, child(value)))),
+            },
+            scalar => scalar.serialize(serializer),
+        }
+    }
+}
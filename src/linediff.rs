@@ -0,0 +1,216 @@
+//! Myers diff: line by line when the documents can't be compared
+//! structurally (scalar or mismatched roots, or `--format text`), element by
+//! element to align arrays, and word by word within a changed string.
+
+use ratatui::text::{Span, Text};
+use std::ops::Range;
+
+use crate::report::ChangeKind;
+use crate::{cancel::Checkpoint, display::sanitize, theme};
+
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Renders the line diff of `left` and `right` as two row-aligned texts:
+/// unchanged lines plain, removed lines on the left and added lines on the
+/// right styled as `theme` has them, with the kind of change on each row.
+/// Returns `None` if cancelled.
+pub fn diff_texts(left: &str, right: &str, checkpoint: &mut Checkpoint) -> Option<(Text<'static>, Text<'static>, Vec<Option<ChangeKind>>)> {
+    let left_lines: Vec<&str> = left.lines().collect();
+    let right_lines: Vec<&str> = right.lines().collect();
+    let ops = myers(&left_lines, &right_lines, checkpoint)?;
+
+    let mut left_text = Text::default();
+    let mut right_text = Text::default();
+    let mut kinds = Vec::new();
+    let mut deleted = Vec::new();
+    let mut inserted = Vec::new();
+    for op in ops.iter().chain([&Op::Equal(usize::MAX, usize::MAX)]) {
+        match *op {
+            Op::Delete(i) => deleted.push(left_lines[i]),
+            Op::Insert(j) => inserted.push(right_lines[j]),
+            Op::Equal(i, j) => {
+                // Pair up the pending run of removals and additions row by row.
+                for row in 0..deleted.len().max(inserted.len()) {
+                    let line = |lines: &[&str], kind| match lines.get(row) {
+                        Some(line) => Span::styled(sanitize(line).into_owned(), theme::kind(kind)),
+                        None => Span::raw(""),
+                    };
+                    left_text.extend([line(&deleted, ChangeKind::Removed)]);
+                    right_text.extend([line(&inserted, ChangeKind::Added)]);
+                    kinds.push(Some(match (row < deleted.len(), row < inserted.len()) {
+                        (true, true) => ChangeKind::Changed,
+                        (true, false) => ChangeKind::Removed,
+                        _ => ChangeKind::Added,
+                    }));
+                }
+                deleted.clear();
+                inserted.clear();
+                if i != usize::MAX {
+                    left_text.extend([Span::raw(sanitize(left_lines[i]).into_owned())]);
+                    right_text.extend([Span::raw(sanitize(right_lines[j]).into_owned())]);
+                    kinds.push(None);
+                }
+            }
+        }
+    }
+    Some((left_text, right_text, kinds))
+}
+
+/// A string as byte ranges, each marked whether it differs.
+pub type Words = Vec<(Range<usize>, bool)>;
+
+/// Splits `left` and `right` into words (runs of letters and digits) and
+/// single other characters, and diffs them like `git diff --word-diff`: each
+/// side as byte ranges, marked `true` where it differs from the other side.
+/// Returns `None` if cancelled.
+pub fn word_diff(left: &str, right: &str, checkpoint: &mut Checkpoint) -> Option<[Words; 2]> {
+    let words = |text: &str| {
+        let mut words: Vec<Range<usize>> = Vec::new();
+        let mut in_word = false;
+        for (start, c) in text.char_indices() {
+            let end = start + c.len_utf8();
+            match words.last_mut() {
+                Some(word) if in_word && c.is_alphanumeric() => word.end = end,
+                _ => words.push(start..end),
+            }
+            in_word = c.is_alphanumeric();
+        }
+        words
+    };
+    let words = [words(left), words(right)];
+    let texts = [left, right];
+    let tokens = [0, 1].map(|side| words[side].iter().map(|word| &texts[side][word.clone()]).collect::<Vec<_>>());
+    let mut parts: [Words; 2] = Default::default();
+    let mut push = |side: usize, index: usize, changed: bool| {
+        let word = words[side][index].clone();
+        match parts[side].last_mut() {
+            Some((range, last)) if *last == changed => range.end = word.end,
+            _ => parts[side].push((word, changed)),
+        }
+    };
+    for op in myers(&tokens[0], &tokens[1], checkpoint)? {
+        match op {
+            Op::Equal(i, j) => {
+                push(0, i, false);
+                push(1, j, false);
+            }
+            Op::Delete(i) => push(0, i, true),
+            Op::Insert(j) => push(1, j, true),
+        }
+    }
+    Some(parts)
+}
+
+/// Aligns `left` and `right` by their longest common subsequence: pairs of
+/// `(left index, right index)` in order, `None` on the side an element is
+/// missing from. Between two equal elements, each removed element is paired
+/// with the next added one it is `similar` to, as a modified element; the
+/// rest stay unpaired. Returns `None` if cancelled.
+pub fn align<T: PartialEq>(
+    left: &[T],
+    right: &[T],
+    similar: impl Fn(&T, &T) -> bool,
+    checkpoint: &mut Checkpoint,
+) -> Option<Vec<(Option<usize>, Option<usize>)>> {
+    let ops = myers(left, right, checkpoint)?;
+    let mut pairs = Vec::new();
+    let mut deleted = Vec::new();
+    let mut inserted = Vec::new();
+    for op in ops.iter().chain([&Op::Equal(usize::MAX, usize::MAX)]) {
+        match *op {
+            Op::Delete(i) => deleted.push(i),
+            Op::Insert(j) => inserted.push(j),
+            Op::Equal(i, j) => {
+                let mut next = 0;
+                let mut run = Vec::new();
+                for &i in &deleted {
+                    match inserted[next..].iter().position(|&j| similar(&left[i], &right[j])) {
+                        Some(offset) => {
+                            run.extend(inserted[next..next + offset].iter().map(|&j| (None, Some(j))));
+                            run.push((Some(i), Some(inserted[next + offset])));
+                            next += offset + 1;
+                        }
+                        None => run.push((Some(i), None)),
+                    }
+                }
+                run.extend(inserted[next..].iter().map(|&j| (None, Some(j))));
+                pairs.append(&mut run);
+                deleted.clear();
+                inserted.clear();
+                if i != usize::MAX {
+                    pairs.push((Some(i), Some(j)));
+                }
+            }
+        }
+    }
+    Some(pairs)
+}
+
+/// Myers' O(ND) shortest edit script. Each round keeps only the `2d + 1`
+/// live diagonals for backtracking, so memory grows with the edit distance
+/// rather than the input size.
+fn myers<T: PartialEq>(a: &[T], b: &[T], checkpoint: &mut Checkpoint) -> Option<Vec<Op>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    let offset = max + 1;
+    let mut v = vec![0isize; 2 * max as usize + 3];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            if checkpoint.tick() {
+                return None;
+            }
+            let mut x = if k == -d || (k != d && v[(offset + k - 1) as usize] < v[(offset + k + 1) as usize]) {
+                v[(offset + k + 1) as usize]
+            } else {
+                v[(offset + k - 1) as usize] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(offset + k) as usize] = x;
+            if x >= n && y >= m {
+                trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+                break 'search;
+            }
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+    }
+
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..trace.len() as isize).rev() {
+        let previous = &trace[(d - 1) as usize];
+        let get = |k: isize| previous[(k + d - 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) { k + 1 } else { k - 1 };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            ops.push(Op::Equal((x - 1) as usize, (y - 1) as usize));
+            x -= 1;
+            y -= 1;
+        }
+        if x == prev_x {
+            ops.push(Op::Insert((y - 1) as usize));
+        } else {
+            ops.push(Op::Delete((x - 1) as usize));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    while x > 0 && y > 0 {
+        ops.push(Op::Equal((x - 1) as usize, (y - 1) as usize));
+        x -= 1;
+        y -= 1;
+    }
+    ops.reverse();
+    Some(ops)
+}
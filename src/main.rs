@@ -1,80 +1,596 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{self, disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
 use ratatui::{
     prelude::*,
-    widgets::{Block, Paragraph, Wrap},
-    style::{Style, Color},
+    widgets::{Block, Clear, Paragraph, Wrap},
+    style::Style,
     text::{Span, Text},
 };
 use std::{
-    fs::File,
-    io::{self, Read},
-    path::Path,
+    borrow::Cow,
+    io,
+    path::{Path, PathBuf},
     process::Command,
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 use tempfile::NamedTempFile;
-use serde_json::{Value, json};
-use std::collections::BTreeSet;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
+use unicode_width::UnicodeWidthStr;
+
+mod annotations;
+mod arraykey;
+mod atomic;
+mod bookmarks;
+mod cancel;
+mod capabilities;
+mod check;
+mod cli;
+mod compare;
+mod context;
+mod coverage;
+mod decimal;
+mod decode;
+mod depth;
+mod diff;
+mod doctor;
+mod display;
+mod duplicates;
+mod external;
+mod fetch;
+mod grouped;
+mod har;
+mod hash;
+mod history;
+mod hook;
+mod ignore;
+mod keyorder;
+mod linediff;
+mod normalize;
+mod options;
+mod patch;
+mod redact;
+mod repair;
+mod report;
+mod schema;
+mod select;
+mod stamp;
+mod table;
+mod tail;
+mod theme;
+mod threeway;
+mod timefmt;
+mod timings;
+mod trace;
+mod wizard;
+
+use annotations::Annotations;
+use arraykey::ArrayKeys;
+use bookmarks::{Bookmarks, Mark, PopupAction};
+use cancel::{CancelToken, Checkpoint};
+use capabilities::Capabilities;
+use cli::{Cli, Format, OnChange, ReadOnly};
+use compare::{Comparators, Structure};
+use context::ContextFields;
+use ignore::IgnoreRules;
+use normalize::{Normalizers, Notes};
+use options::OptionsPanel;
+use coverage::{type_name, Coverage, Segment};
+use duplicates::{Duplicate, DuplicateKeys};
+use diff::{Counts, DiffEntry, DiffOptions, DiffResult, DiffView, Shape, Stats};
+use redact::Redactor;
+use select::Selection;
+use repair::Repair;
+use report::ChangeKind;
+use timings::{Timings, TrackingAllocator};
+
+#[global_allocator]
+static GLOBAL: TrackingAllocator = TrackingAllocator;
+
+use table::TableView;
+use threeway::Merges;
+use har::Har;
+use hash::Hashes;
+use keyorder::{KeyOrders, Order};
+use fetch::Fetcher;
+use grouped::ReportView;
+use history::History;
+use report::Summary;
+use stamp::Stamp;
+use tail::Tail;
+use timefmt::TimeAnnotator;
+use wizard::Wizard;
 
 struct DiffApp {
     left_file: NamedTempFile,
     right_file: NamedTempFile,
+    /// The current diff in full; the rows below are what is shown of it.
+    diff_rows: DiffText,
+    /// `D`: show only the rows that differ, and how many unchanged ones that hides.
+    only_differences: bool,
+    hidden_unchanged: usize,
+    /// Entries past `--max-entries`, counted in a footer instead of shown.
+    entries_left_out: usize,
     left_diff_result: Text<'static>,
     right_diff_result: Text<'static>,
+    diff_paths: Vec<Option<String>>,
+    /// The kind of difference each diff row shows, where it shows one.
+    diff_kinds: Vec<Option<ChangeKind>>,
+    /// Whether each diff row's object or array is expanded, for rows showing one.
+    diff_folds: Vec<Option<bool>>,
+    /// Expand/collapse choices made with Enter, by row path; they outlive
+    /// depth changes and re-diffs.
+    folds: BTreeMap<String, bool>,
+    /// Selected diff row, and the first row shown in the panes.
+    cursor: usize,
+    scroll_top: usize,
+    bookmarks: Bookmarks,
+    /// `m` or `'` waiting for the bookmark letter that follows it.
+    pending_mark: Option<char>,
+    annotations: Annotations,
+    history: Option<History>,
+    /// Where each pane's content came from, for the history log; `None` once
+    /// typed or edited in the app.
+    sources: [Option<String>; 2],
+    /// Both sources are files, so history can offer to re-run the comparison.
+    source_files: bool,
+    /// The panes' inner width, which values are cut at three rows of
+    /// unless `--truncate` is given.
+    pane_width: usize,
+    /// `--summarize-subtrees`: summarize subtrees over the threshold.
+    summarize_subtrees: bool,
+    /// Full values of the rows of the current diff whose values were cut.
+    diff_full: BTreeMap<String, [String; 2]>,
+    /// What `--lenient` repaired in the current diff and the keys repeated in
+    /// its inputs, and the `W` panel listing them.
+    repairs: [Vec<Repair>; 2],
+    duplicates: [Vec<Duplicate>; 2],
+    show_repairs: bool,
+    /// The displayed diff is of content that has since changed.
+    diff_stale: bool,
+    /// The documents of the current diff are equal under its options, so it
+    /// has no rows and a banner says so.
+    identical: bool,
+    /// The start screen, while nothing was given to compare.
+    wizard: Option<Wizard>,
+    /// Enter on a cut value: the popup showing it in full.
+    value_popup: Option<ValuePopup>,
+    /// `C`: the note being typed for a diff row.
+    note_prompt: Option<NotePrompt>,
+    /// `i` or `s`: an option being typed in the status bar.
+    option_prompt: Option<OptionPrompt>,
+    /// A question in the status bar; `y` runs the action, anything else cancels.
+    confirm: Option<Confirm>,
+    /// The files the panes were loaded from, which `r` re-reads.
+    stamps: [Option<Stamp>; 2],
+    /// Source files that didn't exist, compared as empty.
+    absent: [bool; 2],
+    error_on_missing: bool,
+    /// `d` found a source changed on disk; asking whether to reload first.
+    source_prompt: bool,
+    fetcher: Option<Fetcher>,
+    /// Left and right panes excluded from reloads.
+    pinned: [bool; 2],
+    read_only: ReadOnly,
+    tail: Option<Tail>,
+    har: Option<Har>,
+    /// The options the diff is shaped by: `O` edits them, and toggles such
+    /// as `1`–`9`, `P`, `S` and `T` flip them.
+    options: DiffOptions,
+    /// `options.ignores` and `options.array_keys`, compiled.
+    ignore_rules: Arc<IgnoreRules>,
+    array_keys: Arc<ArrayKeys>,
+    /// The `O` panel, and the options it resets to.
+    options_panel: Option<OptionsPanel>,
+    startup_options: DiffOptions,
+    normalizers: Arc<Normalizers>,
+    context: Arc<ContextFields>,
+    times: Arc<TimeAnnotator>,
     original_left_content: Text<'static>,
     original_right_content: Text<'static>,
     display_diff: bool,
+    tables: Vec<TableView>,
+    table_index: usize,
+    show_table: bool,
+    status_message: String,
+    show_all_arrays: bool,
+    pending_diff: Option<PendingDiff>,
+    timings: Timings,
+    render_pending: bool,
+    error_message: Option<String>,
+    /// Each pane's format as given, and as last resolved for its content.
+    formats: [Format; 2],
+    pane_formats: [Format; 2],
+    capabilities: Capabilities,
+    mouse_capture: bool,
+    coverage: Option<Coverage>,
+    show_coverage: bool,
+    /// The changes grouped by kind (**G**), while shown.
+    report: Option<ReportView>,
+    /// The documents as compared, for exporting a JSON Patch; `None` for text.
+    documents: Option<[Value; 2]>,
+    focus_path: Option<Vec<Segment>>,
+    /// `--base`: the common ancestor the panes are compared three ways with.
+    base: Option<PathBuf>,
+    redactor: Arc<Redactor>,
+    /// Values redacted in the left and right panes, and in the last diff.
+    pane_redactions: [usize; 2],
+    diff_redactions: usize,
+    /// The subtree the displayed diff compares, for the pane titles.
+    diff_selection: Option<String>,
+    /// Paths the ignore rules dropped from the last diff.
+    diff_suppressed: usize,
+}
+
+struct NotePrompt {
+    path: String,
+    kind: ChangeKind,
+    text: String,
+}
+
+struct OptionPrompt {
+    field: PromptField,
+    text: String,
+}
+
+#[derive(Clone, Copy)]
+enum PromptField {
+    /// `i`: `options.ignored_keys`, comma-separated.
+    IgnoredKeys,
+    /// `s`: `options.select`.
+    Select,
+}
+
+/// Actions that ask before running.
+enum Confirm {
+    UnpinAndEdit(FileSide),
+    Export(Export),
+}
+
+/// Files the UI can write.
+#[derive(Clone, Copy)]
+enum Export {
+    TableCsv,
+    CoverageCsv,
+    CoverageJson,
+    /// A JSON Patch from the left document to the right one.
+    Patch,
+    /// The `O` panel's options as command-line flags.
+    Flags,
+}
+
+impl Export {
+    fn path(self) -> &'static Path {
+        Path::new(match self {
+            Export::TableCsv => "diffrs-table.csv",
+            Export::CoverageCsv => "diffrs-coverage.csv",
+            Export::CoverageJson => "diffrs-coverage.json",
+            Export::Patch => "diffrs-patch.json",
+            Export::Flags => "diffrs-flags.txt",
+        })
+    }
+}
+
+/// A diff running on a background thread; Esc cancels it through `cancel`.
+struct PendingDiff {
+    handle: JoinHandle<Result<Option<DiffOutput>>>,
+    cancel: CancelToken,
+    started: Instant,
+    reset_view: bool,
+    /// Paths of the cursor row and the top visible row when the diff started,
+    /// to find the same place in the result.
+    anchors: [Option<String>; 2],
+}
+
+/// Everything a background diff needs, moved onto its thread.
+struct DiffJob {
+    left_path: PathBuf,
+    right_path: PathBuf,
+    formats: [Format; 2],
+    /// With the array summary threshold lifted while `A` shows all arrays.
+    options: DiffOptions,
+    folds: BTreeMap<String, bool>,
+    focus_path: Option<Vec<Segment>>,
+    base: Option<PathBuf>,
+    redactor: Arc<Redactor>,
+    ignore_rules: Arc<IgnoreRules>,
+    normalizers: Arc<Normalizers>,
+    annotations: Arc<Annotations>,
+    context: Arc<ContextFields>,
+    array_keys: Arc<ArrayKeys>,
+    truncate: usize,
+    /// Collect a change summary for the history log.
+    history: bool,
+    /// Set when relative-time annotations are shown.
+    times: Option<Arc<TimeAnnotator>>,
+    timings: bool,
+}
+
+struct DiffOutput {
+    diff: DiffText,
+    tables: Vec<TableView>,
+    prefers_table: bool,
+    coverage: Option<Coverage>,
+    documents: Option<[Value; 2]>,
+    /// The three-way counts, with `--base`.
+    merges: Option<String>,
+    redactions: usize,
+    /// Paths dropped by ignore rules, on either side.
+    suppressed: usize,
+    /// The `--select` path the documents were cut down to.
+    selection: Option<String>,
+    /// Which inputs were empty, for the status line.
+    empty: [bool; 2],
+    /// The comparison size, when history is recorded.
+    leaves: Option<usize>,
+    /// Escapes `--lenient` repaired on each side.
+    repairs: [Vec<Repair>; 2],
+    /// Keys repeated within an object on each side, under `--duplicate-keys warn`.
+    duplicates: [Vec<Duplicate>; 2],
+    /// The documents are equal under the options; `diff` has no rows.
+    identical: bool,
+    /// Where a `--compare-with` command didn't answer, and why.
+    comparator_failures: BTreeMap<String, String>,
+    timings: Timings,
 }
 
+#[derive(Clone, Copy)]
 enum FileSide {
     Left,
     Right,
 }
 
+impl FileSide {
+    fn index(self) -> usize {
+        match self {
+            FileSide::Left => 0,
+            FileSide::Right => 1,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            FileSide::Left => "left",
+            FileSide::Right => "right",
+        }
+    }
+}
+
 impl DiffApp {
-    fn new() -> Self {
+    fn new(cli: &Cli, redactor: Redactor, capabilities: Capabilities) -> Self {
         Self {
             left_file: NamedTempFile::new().expect("Failed to create temp file"),
             right_file: NamedTempFile::new().expect("Failed to create temp file"),
+            diff_rows: DiffText::default(),
+            only_differences: cli.only_differences,
+            hidden_unchanged: 0,
+            entries_left_out: 0,
             left_diff_result: Text::default(),
             right_diff_result: Text::default(),
+            diff_paths: Vec::new(),
+            diff_kinds: Vec::new(),
+            diff_folds: Vec::new(),
+            folds: BTreeMap::new(),
+            cursor: 0,
+            scroll_top: 0,
+            bookmarks: Bookmarks::default(),
+            pending_mark: None,
+            annotations: Annotations::default(),
+            history: None,
+            sources: [None, None],
+            source_files: false,
+            pane_width: 0,
+            summarize_subtrees: cli.summarize_subtrees,
+            diff_full: BTreeMap::new(),
+            identical: false,
+            repairs: Default::default(),
+            duplicates: Default::default(),
+            show_repairs: false,
+            diff_stale: false,
+            wizard: None,
+            value_popup: None,
+            note_prompt: None,
+            option_prompt: None,
+            confirm: None,
+            stamps: [None, None],
+            absent: [false, false],
+            error_on_missing: cli.error_on_missing,
+            source_prompt: false,
+            fetcher: None,
+            pinned: [false, false],
+            read_only: cli.read_only,
+            tail: None,
+            har: None,
+            options: DiffOptions::from_cli(cli),
+            ignore_rules: Arc::default(),
+            array_keys: Arc::new(cli.array_keys.clone()),
+            options_panel: None,
+            startup_options: DiffOptions::from_cli(cli),
+            context: Arc::new(cli.context.clone()),
+            normalizers: Arc::default(),
+            times: Arc::new(TimeAnnotator::new(&cli.epoch_seconds, &cli.epoch_millis)),
             original_left_content: Text::default(),
             original_right_content: Text::default(),
             display_diff: false,
+            tables: Vec::new(),
+            table_index: 0,
+            show_table: false,
+            status_message: String::new(),
+            show_all_arrays: false,
+            pending_diff: None,
+            timings: Timings::new(cli.timings),
+            render_pending: false,
+            error_message: None,
+            formats: cli.formats,
+            pane_formats: cli.formats,
+            mouse_capture: !cli.no_mouse && capabilities.mouse,
+            capabilities,
+            coverage: None,
+            documents: None,
+            show_coverage: false,
+            report: None,
+            focus_path: None,
+            base: cli.base.clone(),
+            redactor: Arc::new(redactor),
+            pane_redactions: [0, 0],
+            diff_redactions: 0,
+            diff_selection: None,
+            diff_suppressed: 0,
         }
     }
+
+    /// The length values are cut at: `--truncate`, else three rows of a pane.
+    fn truncate_limit(&self) -> usize {
+        self.options.truncate.unwrap_or(self.pane_width * 3)
+    }
 }
 
 fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let code = match args.first().map(String::as_str) {
+        Some("hook") => Some(hook::run(&args[1..])?),
+        Some("check") => Some(check::run(&args[1..])?),
+        Some("history") => Some(history::run(&args[1..])?),
+        Some("trace") => Some(trace::run(&args[1..])?),
+        Some("doctor") => Some(doctor::run(&args[1..])?),
+        Some("patch") => Some(patch::run(&args[1..])?),
+        _ => None,
+    };
+    if let Some(code) = code {
+        std::process::exit(code);
+    }
+    let cli = Cli::parse(&args)?;
+    if let Some(path) = cli.paths.first() {
+        bail!("unknown option '{}': the TUI compares ./left.json and ./right.json (-f); run `diffrs check <left> <right>` for two other files", path.display());
+    }
+    let redactor = cli.redact.redactor()?;
+    // The TUI's inputs are ./left.json and ./right.json, so discovery starts here.
+    let ignore_rules = cli.ignore.rules(&[Path::new(".")])?;
+    if cli.ignore.show {
+        print!("{}", ignore_rules.describe());
+        return Ok(());
+    }
+    let normalizers = Normalizers::locale_numbers(&cli.locale_numbers)?;
+    let annotations = Annotations::load(cli.annotations.clone())?;
+    let mut capabilities = Capabilities::detect();
+    if let Some(colors) = cli.color_depth {
+        capabilities.colors = colors;
+    }
+    if cli.debug {
+        eprint!("{}", capabilities.describe());
+    }
+    if cli.stats || cli.by_kind || cli.changed_paths {
+        let history = History::open(cli.no_history, "headless", &args);
+        return run_headless(&cli, &redactor, &ignore_rules, &normalizers, history);
+    }
+    if !capabilities.alternate_screen && !cli.force_tui {
+        eprintln!("diffrs: warning: this terminal can't show the TUI, printing a summary instead (--force-tui to start it anyway)");
+        let history = History::open(cli.no_history, "headless", &args);
+        return run_headless(&cli, &redactor, &ignore_rules, &normalizers, history);
+    }
+    let history = History::open(cli.no_history, "tui", &args);
+
+    thread::Builder::new()
+        .stack_size(depth::stack_size(cli.max_depth_parse))
+        .spawn(move || run_tui(cli, redactor, ignore_rules, normalizers, annotations, history, capabilities))?
+        .join()
+        .map_err(|_| anyhow!("UI thread panicked"))?
+}
+
+fn run_tui(
+    cli: Cli,
+    redactor: Redactor,
+    ignore_rules: IgnoreRules,
+    normalizers: Normalizers,
+    annotations: Annotations,
+    history: Option<History>,
+    capabilities: Capabilities,
+) -> Result<()> {
+    let tail = match &cli.tail {
+        Some((left, right)) => Some(Tail::open(left.clone(), right.clone(), cli.record_key.as_deref(), cli.tail_pairs)?),
+        None => None,
+    };
+    let har = Har::open(&cli.har)?;
+    let fetcher = cli.fetch.fetcher()?;
+    if cli.load_files && fetcher.is_none() {
+        // Missing inputs fail before the terminal is taken over.
+        read_inputs([Path::new("left.json"), Path::new("right.json")], cli.error_on_missing)?;
+    }
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen)?;
     let tui_backend = CrosstermBackend::new(stdout);
     let mut tui_terminal = Terminal::new(tui_backend)?;
 
-    let mut app = DiffApp::new();
+    let mut app = DiffApp::new(&cli, redactor, capabilities);
+    app.tail = tail;
+    if let Some(har) = &har {
+        app.status_message = har.summary();
+    }
+    app.har = har;
+    app.fetcher = fetcher;
+    if !ignore_rules.files.is_empty() {
+        let files: Vec<String> = ignore_rules.files.iter().map(|file| file.display().to_string()).collect();
+        app.status_message = format!("ignore rules from {}", files.join(", "));
+    }
+    app.ignore_rules = Arc::new(ignore_rules);
+    app.normalizers = Arc::new(normalizers);
+    app.annotations = annotations;
+    app.history = history;
+    if let Some((left, right)) = &cli.fetch.urls {
+        app.sources = [Some(left.clone()), Some(right.clone())];
+    } else if cli.load_files {
+        let absolute = |path: &str| std::path::absolute(path).map_or(path.to_string(), |path| path.display().to_string());
+        app.sources = [Some(absolute("left.json")), Some(absolute("right.json"))];
+        app.source_files = true;
+    }
+    apply_mouse_capture(&app)?;
 
-    if env::args().any(|arg| arg == "-f") {
-        app.original_left_content = read_json(Path::new("./left.json"))?;
-        app.original_right_content = read_json(Path::new("./right.json"))?;
-        let left_content = std::fs::read_to_string("./left.json")?;
-        let right_content = std::fs::read_to_string("./right.json")?;
-        std::fs::write(app.left_file.path(), left_content)?;
-        std::fs::write(app.right_file.path(), right_content)?;
+    if app.fetcher.is_some() {
+        for side in [FileSide::Left, FileSide::Right] {
+            match copy_source(&mut app, side) {
+                Ok(()) => load_pane(&mut app, side),
+                Err(err) => app.error_message = Some(format!("{}: {err:#}", side.label())),
+            }
+        }
+    } else if let Some((document, patch)) = &cli.apply_patch {
+        preview_patch(&mut app, document, patch);
+    } else if cli.load_files {
+        for side in [FileSide::Left, FileSide::Right] {
+            copy_source(&mut app, side)?;
+            load_pane(&mut app, side);
+        }
+    } else {
+        // Empty panes still show how to fill them.
+        for side in [FileSide::Left, FileSide::Right] {
+            load_pane(&mut app, side);
+        }
+        if !cli.no_wizard && app.tail.is_none() && app.har.is_none() {
+            app.wizard = Some(Wizard::new(history::recent_pairs(20)));
+        }
     }
 
-    let res = run_diff_app(&mut tui_terminal, app);
+    let res = run_diff_app(&mut tui_terminal, &mut app);
 
     disable_raw_mode()?;
-    execute!(tui_terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
+    execute!(tui_terminal.backend_mut(), LeaveAlternateScreen)?;
+    if app.capabilities.mouse {
+        execute!(tui_terminal.backend_mut(), DisableMouseCapture)?;
+    }
     tui_terminal.show_cursor()?;
 
+    if app.timings.enabled() {
+        eprint!("{}", app.timings.report());
+    }
+
     if let Err(err) = res {
         println!("{err:?}");
     }
@@ -82,34 +598,651 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn run_diff_app<B: Backend>(terminal: &mut Terminal<B>, mut app: DiffApp) -> io::Result<()> {
+/// Prints the change summary for `-f`, `--fetch` or `--apply-patch` inputs,
+/// on terminals that can't show the TUI, or with `--stats` the counts the
+/// TUI's status bar would show. `--by-kind` groups the changes by kind, and
+/// `--changed-paths` prints only their kinds and paths, nothing at all for
+/// documents equal under the options.
+fn run_headless(cli: &Cli, redactor: &Redactor, ignore_rules: &IgnoreRules, normalizers: &Normalizers, history: Option<History>) -> Result<()> {
+    if cli.by_kind && cli.changed_paths {
+        bail!("--by-kind and --changed-paths are different outputs: pass one");
+    }
+    let mut absent = [false, false];
+    let (sources, contents) = if let Some(fetcher) = cli.fetch.fetcher()? {
+        let (left, right) = cli.fetch.urls.clone().unwrap_or_default();
+        ([left, right], [fetcher.fetch(0)?, fetcher.fetch(1)?])
+    } else if let Some((document, patch_file)) = &cli.apply_patch {
+        let (contents, patched) = apply_patch_file(document, patch_file, cli.formats[0], cli.max_depth_parse)?;
+        let name = document.display().to_string();
+        let patched = format!("{:#}", patched.context("patch not applied")?);
+        ([name.clone(), format!("{name} with {} applied", patch_file.display())], [contents, patched])
+    } else if cli.load_files {
+        let contents = read_inputs([Path::new("left.json"), Path::new("right.json")], cli.error_on_missing)?;
+        absent = contents.each_ref().map(Option::is_none);
+        (["left.json".to_string(), "right.json".to_string()], contents.map(Option::unwrap_or_default))
+    } else {
+        bail!("nothing to compare without the TUI: pass -f, --fetch or --apply-patch, or run `diffrs check <left> <right>`");
+    };
+    let title = comparison_title([&sources[0], &sources[1]], absent);
+    let cancel = CancelToken::default();
+    cancel.cancel_on_interrupt()?;
+    let Some(Comparison { left, right, rows, identical, suppressed, redactions, repairs }) =
+        compare_inputs(cli, &sources, &contents, redactor, ignore_rules, normalizers, &cancel)?
+    else {
+        eprintln!("diffrs: diff cancelled");
+        std::process::exit(130);
+    };
+    if cli.stats {
+        match suppressed.len() {
+            0 => println!("{title}: {}", rows.stats),
+            n => println!("{title}: {}, {n} ignored", rows.stats),
+        }
+    }
+    if cli.changed_paths {
+        for (kind, pointer) in rows.summary.changed_paths() {
+            println!("{} {pointer}", kind.marker());
+        }
+        repair::warn(&repairs);
+    } else if !cli.stats || cli.by_kind {
+        if identical {
+            println!("{title}: documents are identical");
+        } else if cli.by_kind {
+            rows.summary.print_by_kind(&title, report_limit(cli));
+        } else {
+            rows.summary.print(&title, report_limit(cli));
+        }
+        if let Some(note) = redact::note(redactions) {
+            println!("  ({note})");
+        }
+    }
+    if !cli.changed_paths {
+        repair::print(&repairs);
+    }
+    if let Some(history) = history {
+        history.record(sources, &rows.summary, history::leaf_total(&left, &right), None);
+    }
+    Ok(())
+}
+
+/// The columns a value may take in printed reports: `--truncate`, else three
+/// rows of the terminal, or of 80 columns when there is none.
+fn report_limit(cli: &Cli) -> usize {
+    cli.truncate.unwrap_or_else(|| terminal::size().map_or(80, |(width, _)| usize::from(width)) * 3)
+}
+
+/// Two documents compared the way the TUI compares them, for the outputs
+/// that don't start it.
+struct Comparison {
+    left: Value,
+    right: Value,
+    rows: DiffText,
+    /// Whether the documents are equal under the options.
+    identical: bool,
+    /// The paths ignore rules dropped from either side.
+    suppressed: BTreeSet<String>,
+    /// How many secrets the outputs show as placeholders.
+    redactions: usize,
+    /// The escapes `--lenient` repaired on each side.
+    repairs: [Vec<Repair>; 2],
+}
+
+/// Parses `contents`, read from `sources`, and diffs them under `cli`'s
+/// options: ignore rules, normalizers, `--select`, comparators and array
+/// pairing as in the TUI, so every headless output counts the same changes.
+/// The real values are compared, and secrets redacted as in the TUI in the
+/// rows and reports built of them. `None` when `cancel` stopped the diff.
+fn compare_inputs(
+    cli: &Cli,
+    sources: &[String; 2],
+    contents: &[String; 2],
+    redactor: &Redactor,
+    ignore_rules: &IgnoreRules,
+    normalizers: &Normalizers,
+    cancel: &CancelToken,
+) -> Result<Option<Comparison>> {
+    let selection = cli.select.as_deref().map(Selection::parse).transpose()?;
+    // Under `--lenient`, bad escapes are repaired before anything reads the
+    // documents, as in the TUI.
+    let [(left_repaired, left_repairs), (right_repaired, right_repairs)] = [0, 1].map(|side| match cli.formats[side] {
+        Format::Text => (Cow::Borrowed(contents[side].as_str()), Vec::new()),
+        format => repair::repair_document(&contents[side], Path::new(&sources[side]), format, cli.lenient),
+    });
+    let repaired = [left_repaired, right_repaired];
+    let parse = |contents: &str, source: &str, format: Format, side: &str| -> Result<(Value, Notes, Vec<String>)> {
+        for duplicate in duplicates::find_in_document(contents, Path::new(source), format, cli.duplicate_keys) {
+            eprintln!("warning: {source}: {duplicate}");
+        }
+        let mut value = match format {
+            Format::Text => Value::Array(contents.lines().map(|line| Value::String(line.to_string())).collect()),
+            _ => parse_document(contents, Path::new(source), format, cli.max_depth_parse, cli.duplicate_keys)
+                .with_context(|| source.to_string())?,
+        };
+        let suppressed = ignore_rules.apply(&mut value);
+        let notes = normalizers.apply(&mut value);
+        if let Some(selection) = &selection {
+            value = selection.apply(value, side)?;
+        }
+        Ok((value, notes, suppressed))
+    };
+    let (mut left, left_notes, left_suppressed) = parse(&repaired[0], &sources[0], cli.formats[0], "left")?;
+    let (mut right, right_notes, right_suppressed) = parse(&repaired[1], &sources[1], cli.formats[1], "right")?;
+    fill_empty(&mut left, &mut right, [is_blank(&contents[0]), is_blank(&contents[1])]);
+    let decoded = decode::apply(&cli.decode_base64, &mut left, &mut right);
+    if cli.schema {
+        (left, right) = (schema::infer(&left), schema::infer(&right));
+    }
+    let options = DiffOptions::from_cli(cli);
+    let comparators = Comparators::new(&options);
+    // Equal under the options, tolerance and the like included: nothing to
+    // list, and no diff to run.
+    let identical = comparators.equal("", &left, &right);
+    let key_orders = match cli.key_order && !cli.schema {
+        true => {
+            let orders = [0, 1].map(|side| {
+                let order = Order::read(&repaired[side], Path::new(&sources[side]), cli.formats[side]);
+                match &selection {
+                    Some(selection) => selection.apply_order(order),
+                    None => order,
+                }
+            });
+            KeyOrders::of(&[(&left, &orders[0]), (&right, &orders[1])])
+        }
+        false => KeyOrders::default(),
+    };
+    // Only the counts and the changes tell of keys written in another order.
+    let identical = identical && !key_orders.differ(&left, &right);
+    let redaction = redactor.find(&[&left, &right]);
+    let repairs = [left_repairs, right_repairs];
+    let repaired_paths: BTreeSet<String> = repairs.iter().flatten().map(|repair| repair.path.clone()).collect();
+    let view = DiffView {
+        options: &options,
+        notes: &[left_notes, right_notes],
+        times: None,
+        folds: &BTreeMap::new(),
+        context: &cli.context,
+        array_keys: &cli.array_keys,
+        comparators: &comparators,
+        truncate: 0,
+        repaired: &repaired_paths,
+        decoded: &decoded,
+        hashes: &if identical { Hashes::default() } else { Hashes::of(&[&left, &right]) },
+        key_orders: &key_orders,
+        redaction: &redaction,
+        now: timefmt::now_millis(),
+    };
+    // The report lists the changes the diff finds, under the same rules.
+    let rows = match identical {
+        true => DiffText::default(),
+        false => match diff_json_values(&left, &right, &view, cancel) {
+            Some(rows) => rows,
+            None => return Ok(None),
+        },
+    };
+    for (path, reason) in comparators.failures() {
+        eprintln!("diffrs: {path}: {reason}; compared as JSON there");
+    }
+    let suppressed = left_suppressed.into_iter().chain(right_suppressed).collect();
+    let redactions = redaction.count;
+    Ok(Some(Comparison { left, right, rows, identical, suppressed, redactions, repairs }))
+}
+
+fn run_diff_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut DiffApp) -> io::Result<()> {
     loop {
-        terminal.draw(|f| render_ui(f, &app))?;
+        if let Some(tail) = &mut app.tail {
+            tail.poll();
+            app.status_message = match &tail.error {
+                Some(err) => format!("tail: {err}"),
+                None => tail.summary(),
+            };
+        }
+        if app.render_pending {
+            app.render_pending = false;
+            let mut timings = Timings::new(true);
+            timings.measure("render", || terminal.draw(|f| render_ui(f, app)))?;
+            app.timings.merge(timings);
+        } else {
+            terminal.draw(|f| render_ui(f, app))?;
+        }
+
+        if let Some(pending) = &app.pending_diff {
+            if pending.handle.is_finished() {
+                finish_diff(app);
+                continue;
+            }
+            app.status_message = format!("diffing… {:.1}s (Esc to cancel)", pending.started.elapsed().as_secs_f64());
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+        }
+        if app.tail.is_some() && !event::poll(Duration::from_millis(250))? {
+            continue;
+        }
+        if app.stamps.iter().any(Option::is_some) && !event::poll(Duration::from_secs(1))? {
+            changed_sources(app);
+            continue;
+        }
 
         if let Event::Key(key) = event::read()? {
-            match key.code {
-                KeyCode::Char('a') => {
-                    open_editor(&app, FileSide::Left, terminal)
-                        .map_err(|_| io::ErrorKind::BrokenPipe)?;
-                    app.original_left_content = read_json(app.left_file.path()).unwrap_or_default();
+            if app.error_message.is_some() {
+                app.error_message = None;
+                continue;
+            }
+            if let Some(wizard) = &mut app.wizard {
+                match wizard.handle_key(key.code, app.sources.each_ref().map(Option::is_some)) {
+                    wizard::Action::None => {}
+                    wizard::Action::Quit => return Ok(()),
+                    wizard::Action::Dismiss => app.wizard = None,
+                    wizard::Action::Load(side, source) => fill_side(app, side, source),
+                    wizard::Action::Paste(side) => {
+                        let side = if side == 0 { FileSide::Left } else { FileSide::Right };
+                        write_side(app, side, "clipboard".to_string(), wizard::read_clipboard());
+                    }
+                    wizard::Action::Pair(sources) => {
+                        for (side, source) in sources.into_iter().enumerate() {
+                            fill_side(app, side, source);
+                        }
+                    }
+                }
+                if app.sources.iter().all(Option::is_some) {
+                    app.wizard = None;
+                    app.status_message = "both sides loaded — press d to diff".to_string();
+                }
+                continue;
+            }
+            if let Some(pending) = &app.pending_diff {
+                match key.code {
+                    KeyCode::Esc => pending.cancel.cancel(),
+                    KeyCode::Char('q') => return Ok(()),
+                    _ => {}
+                }
+                continue;
+            }
+            if let Some(tail) = &mut app.tail {
+                if key.code == KeyCode::Char('q') {
+                    return Ok(());
+                }
+                tail.handle_key(key.code);
+                continue;
+            }
+            if let Some(har) = app.har.as_mut().filter(|har| har.list_open) {
+                match key.code {
+                    KeyCode::Char('q') => return Ok(()),
+                    KeyCode::Enter => open_har_pair(app)?,
+                    code => har.handle_key(code),
+                }
+                continue;
+            }
+            if app.source_prompt {
+                app.source_prompt = false;
+                app.status_message.clear();
+                match key.code {
+                    KeyCode::Char('r') => {
+                        reload(app);
+                        if !app.display_diff {
+                            start_diff(app, true);
+                        }
+                    }
+                    KeyCode::Char('o') => start_diff(app, true),
+                    _ => {}
+                }
+                continue;
+            }
+            if let Some(action) = app.confirm.take() {
+                app.status_message.clear();
+                if key.code == KeyCode::Char('y') {
+                    match action {
+                        Confirm::UnpinAndEdit(side) => {
+                            app.pinned[side.index()] = false;
+                            edit_pane(app, side, terminal)?;
+                        }
+                        Confirm::Export(export) => write_export(app, export),
+                    }
+                }
+                continue;
+            }
+            if let Some(mut prompt) = app.note_prompt.take() {
+                match key.code {
+                    KeyCode::Enter => {
+                        match app.annotations.set(&prompt.path, prompt.kind, &prompt.text) {
+                            Ok(()) => app.status_message = format!("notes saved to {}", app.annotations.file.display()),
+                            Err(err) => app.error_message = Some(format!("saving notes failed: {err:#}")),
+                        }
+                        start_diff(app, false);
+                    }
+                    KeyCode::Esc => app.status_message.clear(),
+                    code => {
+                        match code {
+                            KeyCode::Backspace => {
+                                prompt.text.pop();
+                            }
+                            KeyCode::Char(c) => prompt.text.push(c),
+                            _ => {}
+                        }
+                        show_note_prompt(app, &prompt);
+                        app.note_prompt = Some(prompt);
+                    }
+                }
+                continue;
+            }
+            if let Some(mut prompt) = app.option_prompt.take() {
+                match key.code {
+                    KeyCode::Enter => apply_option_prompt(app, prompt),
+                    KeyCode::Esc => app.status_message.clear(),
+                    code => {
+                        match code {
+                            KeyCode::Backspace => {
+                                prompt.text.pop();
+                            }
+                            KeyCode::Char(c) => prompt.text.push(c),
+                            _ => {}
+                        }
+                        show_option_prompt(app, &prompt);
+                        app.option_prompt = Some(prompt);
+                    }
+                }
+                continue;
+            }
+            if let Some(prefix) = app.pending_mark.take() {
+                match key.code {
+                    KeyCode::Char(letter) if letter.is_ascii_alphabetic() => {
+                        if prefix == 'm' {
+                            set_mark(app, letter);
+                        } else {
+                            jump_to_mark(app, letter);
+                        }
+                    }
+                    _ => app.status_message.clear(),
+                }
+                continue;
+            }
+            if app.bookmarks.popup_open() {
+                if let PopupAction::Jump(letter) = app.bookmarks.handle_popup_key(key.code) {
+                    jump_to_mark(app, letter);
+                }
+                continue;
+            }
+            if app.show_repairs {
+                if matches!(key.code, KeyCode::Esc | KeyCode::Char('W' | 'q')) {
+                    app.show_repairs = false;
+                }
+                continue;
+            }
+            if app.options_panel.is_some() {
+                let action = app.options_panel.as_mut().map_or(options::Action::None, |panel| panel.handle_key(key.code, &app.options));
+                match action {
+                    options::Action::Apply(options) => {
+                        if let Err(err) = apply_options(app, *options) {
+                            if let Some(panel) = &mut app.options_panel {
+                                panel.show_error(format!("{err:#}"));
+                            }
+                        }
+                    }
+                    options::Action::Export => request_export(app, Export::Flags),
+                    options::Action::Close => app.options_panel = None,
+                    options::Action::None => {}
+                }
+                continue;
+            }
+            if let Some(popup) = &mut app.value_popup {
+                match key.code {
+                    KeyCode::Down | KeyCode::Char('j') => popup.scroll = popup.scroll.saturating_add(1),
+                    KeyCode::Up | KeyCode::Char('k') => popup.scroll = popup.scroll.saturating_sub(1),
+                    KeyCode::PageDown => popup.scroll = popup.scroll.saturating_add(20),
+                    KeyCode::PageUp => popup.scroll = popup.scroll.saturating_sub(20),
+                    _ => app.value_popup = None,
+                }
+                continue;
+            }
+            // Nothing derived from a stale diff is reachable until it is redone.
+            if app.diff_stale && !matches!(key.code, KeyCode::Char('d' | 'q' | 'a' | 'b' | 'c' | 'r' | '[' | ']' | 'M' | 'O')) {
+                app.status_message = STALE_MESSAGE.to_string();
+                continue;
+            }
+            let in_diff_rows = app.display_diff && !app.show_table && !app.show_coverage && app.report.is_none();
+            if in_diff_rows {
+                let last = app.diff_paths.len().saturating_sub(1);
+                let moved = match key.code {
+                    KeyCode::Up | KeyCode::Char('k') => Some(app.cursor.saturating_sub(1)),
+                    KeyCode::Down | KeyCode::Char('j') => Some((app.cursor + 1).min(last)),
+                    KeyCode::PageUp => Some(app.cursor.saturating_sub(20)),
+                    KeyCode::PageDown => Some((app.cursor + 20).min(last)),
+                    KeyCode::Home => Some(0),
+                    KeyCode::End => Some(last),
+                    KeyCode::Char('C') => {
+                        open_note_prompt(app);
+                        None
+                    }
+                    KeyCode::Enter | KeyCode::Char(' ') => {
+                        open_row(app);
+                        None
+                    }
+                    KeyCode::Char(digit @ '1'..='9') => {
+                        app.options.expand_depth = digit.to_digit(10).unwrap_or(1) as usize;
+                        app.status_message = format!("expand depth {}", app.options.expand_depth);
+                        start_diff(app, false);
+                        None
+                    }
+                    KeyCode::Char(key @ ('-' | '+')) => {
+                        let depth = match (key, app.options.diff_depth) {
+                            ('-', Some(depth)) => Some(depth.saturating_sub(1).max(1)),
+                            ('-', None) => Some(app.diff_rows.depth.saturating_sub(1).max(1)),
+                            (_, depth) => depth.map(|depth| depth + 1),
+                        };
+                        app.options.diff_depth = depth;
+                        app.status_message = match depth {
+                            Some(depth) => format!("diff depth {depth}"),
+                            None => "no diff depth limit".to_string(),
+                        };
+                        start_diff(app, false);
+                        None
+                    }
+                    KeyCode::Char(prefix @ ('m' | '\'')) => {
+                        app.pending_mark = Some(prefix);
+                        app.status_message = if prefix == 'm' { "mark: press a letter" } else { "jump to mark: press a letter" }.to_string();
+                        None
+                    }
+                    _ => None,
+                };
+                if let Some(cursor) = moved {
+                    app.cursor = cursor;
+                    continue;
                 }
-                KeyCode::Char('b') => {
-                    open_editor(&app, FileSide::Right, terminal)
-                        .map_err(|_| io::ErrorKind::BrokenPipe)?;
-                    app.original_right_content = read_json(app.right_file.path()).unwrap_or_default();
+                if app.pending_mark.is_some() {
+                    continue;
                 }
+            }
+            if let Some(report) = &mut app.report {
+                if report.handle_key(key.code) {
+                    continue;
+                }
+            } else if app.show_coverage {
+                if let Some(coverage) = &mut app.coverage {
+                    if coverage.handle_key(key.code) {
+                        continue;
+                    }
+                }
+            } else if app.show_table {
+                if let Some(table) = app.tables.get_mut(app.table_index) {
+                    if table.handle_key(key.code) {
+                        continue;
+                    }
+                }
+            }
+            match key.code {
+                KeyCode::Char('a' | 'b' | 'c') if app.read_only != ReadOnly::Off => {
+                    app.status_message = "read-only: editing and clearing are disabled".to_string();
+                }
+                KeyCode::Char('a') => edit_pane(app, FileSide::Left, terminal)?,
+                KeyCode::Char('b') => edit_pane(app, FileSide::Right, terminal)?,
+                KeyCode::Char('[') => toggle_pin(app, FileSide::Left),
+                KeyCode::Char(']') => toggle_pin(app, FileSide::Right),
+                KeyCode::Char('r') => reload(app),
                 KeyCode::Char('c') => {
+                    app.sources = [None, None];
                     app.left_file.as_file().set_len(0)?;
                     app.right_file.as_file().set_len(0)?;
-                    app.original_left_content = Text::default();
-                    app.original_right_content = Text::default();
+                    for side in [FileSide::Left, FileSide::Right] {
+                        load_pane(app, side);
+                    }
+                    content_changed(app);
+                    app.tables.clear();
+                    app.show_table = false;
+                    app.coverage = None;
+                    app.show_coverage = false;
+                    app.documents = None;
+                    app.report = None;
+                    app.focus_path = None;
                 }
                 KeyCode::Char('d') => {
-                    let (left_diff, right_diff) = compare_json_files(&app).map_err(|_| io::ErrorKind::BrokenPipe)?;
-                    app.left_diff_result = left_diff;
-                    app.right_diff_result = right_diff;
-                    app.display_diff = true;
+                    let changed = changed_sources(app);
+                    if changed.is_empty() {
+                        start_diff(app, true);
+                    } else {
+                        app.source_prompt = true;
+                        app.status_message = format!(
+                            "{} changed on disk — [r] reload, [o] diff as loaded, [esc] cancel",
+                            changed.join(" and ")
+                        );
+                    }
+                }
+                KeyCode::Char('A') => {
+                    app.show_all_arrays = !app.show_all_arrays;
+                    if app.display_diff {
+                        start_diff(app, false);
+                    }
+                }
+                KeyCode::Char('D') => toggle_only_differences(app),
+                KeyCode::Char('L') => raise_entry_limit(app),
+                KeyCode::Char('P') => {
+                    app.options.full_paths = !app.options.full_paths;
+                    if app.display_diff {
+                        start_diff(app, false);
+                    }
+                }
+                KeyCode::Char('S') => {
+                    app.options.unordered_arrays = !app.options.unordered_arrays;
+                    app.status_message = if app.options.unordered_arrays {
+                        "arrays compared as unordered collections".to_string()
+                    } else {
+                        "arrays compared in order".to_string()
+                    };
+                    if app.display_diff {
+                        start_diff(app, false);
+                    }
+                }
+                KeyCode::Char('I') => {
+                    let on = toggle_comparator(app, "case-insensitive");
+                    app.status_message = format!("string case {}", if on { "ignored" } else { "compared" });
+                }
+                KeyCode::Char('i') => open_option_prompt(app, PromptField::IgnoredKeys),
+                KeyCode::Char('s') => open_option_prompt(app, PromptField::Select),
+                KeyCode::Char('Z') => {
+                    app.options.timestamps = match app.options.timestamps {
+                        Some(_) => None,
+                        None => Some(app.startup_options.timestamps.unwrap_or(0)),
+                    };
+                    app.status_message =
+                        format!("timestamps compared {}", if app.options.timestamps.is_some() { "as instants" } else { "as text" });
+                    if app.display_diff {
+                        start_diff(app, false);
+                    }
+                }
+                KeyCode::Char('w') => {
+                    let on = toggle_comparator(app, "whitespace");
+                    app.status_message = format!("whitespace in strings {}", if on { "ignored" } else { "compared" });
+                }
+                KeyCode::Char('N') => {
+                    let on = toggle_comparator(app, "numeric-strings");
+                    app.status_message = format!("numeric strings compared as {}", if on { "numbers" } else { "strings" });
+                }
+                KeyCode::Char('U') => {
+                    app.options.decode_escapes = !app.options.decode_escapes;
+                    load_pane(app, FileSide::Left);
+                    load_pane(app, FileSide::Right);
+                    if app.display_diff {
+                        start_diff(app, false);
+                    }
+                }
+                KeyCode::Char('Y') => {
+                    app.options.type_tags = !app.options.type_tags;
+                    app.status_message = format!("type tags {}", if app.options.type_tags { "on" } else { "off" });
+                    if app.display_diff {
+                        start_diff(app, false);
+                    }
                 }
+                KeyCode::Char('H') => match &mut app.har {
+                    Some(har) => {
+                        har.list_open = true;
+                        app.status_message = har.summary();
+                    }
+                    None => app.status_message = "no HAR files loaded (--har)".to_string(),
+                },
+                KeyCode::Char('T') => {
+                    app.options.relative_times = !app.options.relative_times;
+                    app.status_message =
+                        format!("relative times {}", if app.options.relative_times { "on" } else { "off" });
+                    if app.display_diff {
+                        start_diff(app, false);
+                    }
+                }
+                KeyCode::Char('M') if !app.capabilities.mouse => {
+                    app.status_message = "this terminal doesn't report the mouse".to_string();
+                }
+                KeyCode::Char('M') => {
+                    app.mouse_capture = !app.mouse_capture;
+                    apply_mouse_capture(app)?;
+                }
+                KeyCode::Char('t') => {
+                    if app.tables.is_empty() {
+                        app.status_message = "no arrays of objects to show as a table".to_string();
+                    } else {
+                        app.show_table = !app.show_table;
+                    }
+                }
+                KeyCode::Char('B') => app.bookmarks.toggle_popup(),
+                KeyCode::Char('O') => app.options_panel = Some(OptionsPanel::new(app.startup_options.clone())),
+                KeyCode::Char('W') => {
+                    if app.repairs.iter().all(Vec::is_empty) && app.duplicates.iter().all(Vec::is_empty) {
+                        app.status_message = "nothing was repaired (see --lenient) and no key is duplicated".to_string();
+                    } else {
+                        app.show_repairs = true;
+                    }
+                }
+                KeyCode::Char('K') => {
+                    if app.coverage.is_none() {
+                        app.status_message = "no arrays of records to compare key coverage".to_string();
+                    } else {
+                        app.show_coverage = !app.show_coverage;
+                    }
+                }
+                KeyCode::Char('G') if app.report.is_some() => app.report = None,
+                KeyCode::Char('p') if app.report.is_some() => {
+                    let paths = !app.report.as_ref().is_some_and(ReportView::lists_paths);
+                    app.report = Some(ReportView::new(&app.diff_rows.summary, paths, app.truncate_limit()));
+                }
+                KeyCode::Char('G') if app.display_diff && app.documents.is_some() => {
+                    app.report = Some(ReportView::new(&app.diff_rows.summary, false, app.truncate_limit()));
+                }
+                KeyCode::Char('G') => app.status_message = "no structured diff to group by kind: press d".to_string(),
+                KeyCode::Enter if app.show_coverage => {
+                    if let Some(path) = app.coverage.as_ref().and_then(Coverage::selected_path) {
+                        app.focus_path = Some(path.to_vec());
+                        app.show_coverage = false;
+                        app.show_table = false;
+                        start_diff(app, false);
+                    }
+                }
+                KeyCode::Char('F') if app.focus_path.is_some() => {
+                    app.focus_path = None;
+                    start_diff(app, false);
+                }
+                KeyCode::Char('x') if app.show_coverage => request_export(app, Export::CoverageCsv),
+                KeyCode::Char('J') if app.show_coverage => request_export(app, Export::CoverageJson),
+                KeyCode::Tab if app.show_table => {
+                    app.table_index = (app.table_index + 1) % app.tables.len();
+                }
+                KeyCode::Char('x') if app.show_table => request_export(app, Export::TableCsv),
+                KeyCode::Char('x') if app.display_diff => request_export(app, Export::Patch),
                 KeyCode::Char('q') => {
                     return Ok(());
                 }
@@ -119,70 +1252,704 @@ fn run_diff_app<B: Backend>(terminal: &mut Terminal<B>, mut app: DiffApp) -> io:
     }
 }
 
-fn render_ui(f: &mut Frame, app: &DiffApp) {
-    let vertical_layout = Layout::vertical([Constraint::Length(1), Constraint::Min(1)]);
-    let [help_section, content_section] = vertical_layout.areas(f.size());
+fn render_ui(f: &mut Frame, app: &mut DiffApp) {
+    let vertical_layout = Layout::vertical([Constraint::Length(1), Constraint::Min(1), Constraint::Length(1)]);
+    let [help_section, content_section, status_section] = vertical_layout.areas(f.size());
+    let mouse_mode = if app.mouse_capture { "mouse: on [M]" } else { "mouse: off, native selection [M]" };
+    let redactions = if app.display_diff { app.diff_redactions } else { app.pane_redactions.iter().sum() };
+    let mode = match app.read_only {
+        ReadOnly::Off => None,
+        ReadOnly::Hard => Some("read-only".to_string()),
+        ReadOnly::Soft => Some("read-only (soft)".to_string()),
+    };
+    let ignore_files = match app.ignore_rules.files.len() {
+        0 => None,
+        1 => Some("1 ignore file".to_string()),
+        n => Some(format!("{n} ignore files")),
+    };
+    let changed: Vec<String> = app.stamps.iter().flatten().filter(|stamp| stamp.changed()).map(Stamp::name).collect();
+    let changed = (!changed.is_empty()).then(|| format!("⚠ {} changed on disk [r]", changed.join(", ")));
+    let unordered = app.options.unordered_arrays.then(|| "unordered arrays [S]".to_string());
+    let comparator = |name: &str, shown: &str| app.options.comparators.iter().any(|enabled| enabled == name).then(|| shown.to_string());
+    let ignore_case = comparator("case-insensitive", "ignore case [I]");
+    let ignore_whitespace = comparator("whitespace", "ignore whitespace [w]");
+    let numeric_strings = comparator("numeric-strings", "numeric strings [N]");
+    let ignored_keys = (!app.options.ignored_keys.is_empty()).then(|| format!("ignoring {} [i]", app.options.ignored_keys.join(", ")));
+    let instants = app.options.timestamps.map(|_| "timestamps as instants [Z]".to_string());
+    let schemas = app.options.schema.then(|| "inferred schemas".to_string());
+    let structure = app.options.structure.map(|structure| match structure {
+        Structure::Types => "structure only".to_string(),
+        Structure::Lengths => "structure and array lengths only".to_string(),
+    });
+    let duplicates = match app.duplicates.iter().map(Vec::len).sum::<usize>() {
+        0 => None,
+        1 => Some("⚠ 1 duplicate key [W]".to_string()),
+        n => Some(format!("⚠ {n} duplicate keys [W]")),
+    };
+    // The scale of the diff at a glance, and what the ignore rules left out of it.
+    let stats = app.display_diff.then(|| match app.diff_suppressed {
+        0 => app.diff_rows.stats.to_string(),
+        n => format!("{}, {n} ignored", app.diff_rows.stats),
+    });
+    let mouse_mode = [stats, duplicates, changed, mode, ignore_files, ignored_keys, unordered, ignore_case, ignore_whitespace, numeric_strings, instants, structure, schemas, redact::note(redactions), Some(mouse_mode.to_string())]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" · ");
+    let [message_area, mode_area] =
+        Layout::horizontal([Constraint::Min(1), Constraint::Length(mouse_mode.width() as u16)]).areas(status_section);
+    f.render_widget(Paragraph::new(app.status_message.clone()).dim(), message_area);
+    f.render_widget(Paragraph::new(mouse_mode).dim(), mode_area);
+
+    match app.tables.get_mut(app.table_index) {
+        _ if app.wizard.is_some() => {
+            if let Some(wizard) = &app.wizard {
+                wizard.render(f, content_section, &app.sources);
+            }
+        }
+        _ if app.tail.is_some() => render_tail(f, app, help_section, content_section),
+        _ if app.har.as_ref().is_some_and(|har| har.list_open) => {
+            f.render_widget(Paragraph::new(Line::from(vec![
+                "[q]".green().bold(),
+                " Quit - ".into(),
+                "[↑/↓]".green().bold(),
+                " select request - ".into(),
+                "[enter]".green().bold(),
+                " diff response bodies - ".into(),
+                "[h]".green().bold(),
+                " headers".into(),
+            ])), help_section);
+            if let Some(har) = &app.har {
+                har.render(f, content_section, |value| app.redactor.redact(value).0);
+            }
+        }
+        _ if app.report.is_some() => {
+            f.render_widget(Paragraph::new(Line::from(vec![
+                "[G]".green().bold(),
+                " close - ".into(),
+                "[p]".green().bold(),
+                if app.report.as_ref().is_some_and(ReportView::lists_paths) { " by kind - ".into() } else { " paths only - ".into() },
+                "[↑/↓]".green().bold(),
+                " scroll".into(),
+            ])), help_section);
+            if let Some(report) = &app.report {
+                report.render(f, content_section);
+            }
+        }
+        _ if app.show_coverage && app.coverage.is_some() => {
+            f.render_widget(Paragraph::new(Line::from(vec![
+                "[K]".green().bold(),
+                " close - ".into(),
+                "[o]".green().bold(),
+                " sort - ".into(),
+                "[enter]".green().bold(),
+                " filter diff to path".into(),
+            ].into_iter().chain(export_help(app.read_only, &[("[x]", "CSV"), ("[J]", "JSON")])).collect::<Vec<_>>())), help_section);
+            if let Some(coverage) = &app.coverage {
+                coverage.render(f, content_section);
+            }
+        }
+        Some(table) if app.show_table => {
+            f.render_widget(Paragraph::new(Line::from(vec![
+                "[t]".green().bold(),
+                " text view - ".into(),
+                "[tab]".green().bold(),
+                " next table - ".into(),
+                "[p]".green().bold(),
+                " columns - ".into(),
+                "[enter]".green().bold(),
+                " detail".into(),
+            ].into_iter().chain(export_help(app.read_only, &[("[x]", "CSV")])).collect::<Vec<_>>())), help_section);
+            table.render(f, content_section);
+        }
+        _ => {
+            f.render_widget(render_help(app.read_only, app.display_diff), help_section);
+            render_panes(f, app, content_section);
+        }
+    }
+
+    if app.bookmarks.popup_open() {
+        app.bookmarks.render_popup(f, content_section);
+    }
+    if let Some(popup) = &app.value_popup {
+        render_value_popup(f, content_section, app, popup);
+    }
+    if app.show_repairs {
+        repair::render(f, content_section, &app.repairs, &app.duplicates);
+    }
+    if let Some(panel) = &app.options_panel {
+        panel.render(f, content_section, &app.options);
+    }
+    if let Some(message) = &app.error_message {
+        render_error(f, content_section, message);
+    }
+    app.capabilities.degrade(f.buffer_mut());
+}
+
+/// Tail mode: the list of record pairs on top, the selected pair's diff below.
+fn render_tail(f: &mut Frame, app: &DiffApp, help_section: Rect, content_section: Rect) {
+    let Some(tail) = &app.tail else {
+        return;
+    };
+    f.render_widget(Paragraph::new(Line::from(vec![
+        "[q]".green().bold(),
+        " Quit - ".into(),
+        "[↑/↓]".green().bold(),
+        " select pair - ".into(),
+        "[End]".green().bold(),
+        " follow newest".into(),
+    ])), help_section);
+    let [list_area, detail_area] =
+        Layout::vertical([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(content_section);
+    tail.render_list(f, list_area);
+
+    let show = |entry: &Option<tail::Entry>| -> Text<'static> {
+        match entry {
+            Some(tail::Entry { value: Ok(value), .. }) => {
+                Text::from(display::render_pretty(&app.redactor.redact(value).0, app.options.decode_escapes))
+            }
+            Some(tail::Entry { value: Err(err), line }) => {
+                Text::from(display::sanitize(&format!("line {line}: {err}")).into_owned()).red()
+            }
+            None => Text::from("(waiting for a record)").dim(),
+        }
+    };
+    let (left, right) = match tail.selected() {
+        Some(tail::Pair { left: Some(tail::Entry { value: Ok(l), .. }), right: Some(tail::Entry { value: Ok(r), .. }), .. }) => {
+            let redaction = app.redactor.find(&[l, r]);
+            let view = DiffView {
+                options: &app.options,
+                notes: &Default::default(),
+                times: app.options.relative_times.then_some(&*app.times),
+                folds: &app.folds,
+                context: &app.context,
+                array_keys: &app.array_keys,
+                comparators: &Comparators::new(&app.options),
+                truncate: app.truncate_limit(),
+                repaired: &BTreeSet::new(),
+                decoded: &Default::default(),
+                hashes: &Hashes::default(),
+                key_orders: &KeyOrders::default(),
+                redaction: &redaction,
+                now: timefmt::now_millis(),
+            };
+            diff_json_values(l, r, &view, &CancelToken::default())
+                .map(|diff| (diff.left, diff.right))
+                .unwrap_or_default()
+        }
+        Some(pair) => (show(&pair.left), show(&pair.right)),
+        None => (Text::default(), Text::default()),
+    };
+    let [left_area, right_area] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(detail_area);
+    f.render_widget(Paragraph::new(left).wrap(Wrap { trim: false }).block(Block::bordered().title("Left record")), left_area);
+    f.render_widget(Paragraph::new(right).wrap(Wrap { trim: false }).block(Block::bordered().title("Right record")), right_area);
+}
+
+fn pin_glyph(pinned: bool) -> &'static str {
+    if pinned {
+        " 📌 pinned"
+    } else {
+        ""
+    }
+}
+
+/// In place of the panes, when the documents are equal under the options.
+fn render_identical(f: &mut Frame, app: &DiffApp, area: Rect, filter: &str) {
+    let mut lines = vec![Line::default(); (area.height.saturating_sub(4) / 2) as usize];
+    lines.push(Line::from("✓ Documents are identical").bold().green());
+    let under = match app.diff_suppressed {
+        0 if app.options.flags().is_empty() => "under the default options".to_string(),
+        0 => format!("under {}", app.options.command_line()),
+        n => format!("under the current options, {n} path(s) ignored"),
+    };
+    lines.push(Line::from(display::sanitize(&under).into_owned()).dim());
+    let banner = Paragraph::new(lines).alignment(Alignment::Center).wrap(Wrap { trim: true });
+    f.render_widget(banner.block(Block::bordered().title(format!("Diff{filter}"))), area);
+}
+
+fn render_panes(f: &mut Frame, app: &mut DiffApp, content_section: Rect) {
     let horizontal_layout = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]);
     let [left_content_area, right_content_area] = horizontal_layout.areas(content_section);
+    app.pane_width = left_content_area.width.saturating_sub(2) as usize;
 
-    let help_message = render_help();
-    f.render_widget(help_message, help_section);
-
-    let left_content = if app.display_diff {
-        app.left_diff_result.clone()
+    let mut filter = match &app.diff_selection {
+        Some(selection) if app.display_diff => format!(" — {selection} only [s]"),
+        _ => String::new(),
+    };
+    if let Some(path) = app.focus_path.as_ref().filter(|_| app.display_diff) {
+        filter.push_str(&format!(" — {} only [F] clear", coverage::path_label(path)));
+    }
+    if app.display_diff && app.identical && !app.diff_stale {
+        render_identical(f, app, content_section, &filter);
+        return;
+    }
+    if app.display_diff && app.only_differences {
+        filter.push_str(&format!(" — differences only ({} unchanged keys hidden) [D]", display::group_thousands(app.hidden_unchanged)));
+    }
+    let (mut left_content, mut right_content) = if app.display_diff {
+        (app.left_diff_result.clone(), app.right_diff_result.clone())
     } else {
-        app.original_left_content.clone()
+        (app.original_left_content.clone(), app.original_right_content.clone())
     };
-    let left_paragraph = Paragraph::new(left_content)
-        .style(Style::default())
-        .wrap(Wrap { trim: false })
-        .block(Block::bordered().title("Left JSON"));
-    f.render_widget(left_paragraph, left_content_area);
 
-    let right_content = if app.display_diff {
-        app.right_diff_result.clone()
+    let stale = if app.diff_stale {
+        for text in [&mut left_content, &mut right_content] {
+            text.style = text.style.add_modifier(Modifier::DIM);
+        }
+        " — content changed, [d] refresh"
     } else {
-        app.original_right_content.clone()
+        ""
+    };
+
+    if app.display_diff {
+        for text in [&mut left_content, &mut right_content] {
+            if let Some(line) = text.lines.get_mut(app.cursor) {
+                line.style = line.style.add_modifier(Modifier::REVERSED);
+            }
+        }
+        // Rows are wrapped here by display width rather than by the
+        // paragraphs, and the shorter side of each padded, so every row starts
+        // level in both panes however wide its characters are.
+        let inner_width = left_content_area.width.saturating_sub(2).max(1) as usize;
+        // Each row starts with its change marker, or a space, so the diff
+        // reads without color too; the rows wrapped under it are indented to
+        // match.
+        let markers = app.options.markers;
+        let kinds = &app.diff_kinds;
+        let wrapped = |index: usize| {
+            let kind = kinds.get(index).copied().flatten();
+            let wrap = |text: &Text<'static>| {
+                let Some(line) = text.lines.get(index) else {
+                    return Vec::new();
+                };
+                if !markers {
+                    return display::wrap_line(line, inner_width);
+                }
+                // The marker takes the color of the row's text.
+                let style = line.spans.first().map_or(Style::default(), |span| span.style);
+                let mut lines = display::wrap_line(line, inner_width.saturating_sub(2).max(1));
+                for (row, line) in lines.iter_mut().enumerate() {
+                    let marker = match kind {
+                        Some(kind) if row == 0 => format!("{} ", kind.marker()),
+                        _ => "  ".to_string(),
+                    };
+                    line.spans.insert(0, Span::styled(marker, style));
+                }
+                lines
+            };
+            let (mut left, mut right) = (wrap(&left_content), wrap(&right_content));
+            let rows = left.len().max(right.len()).max(1);
+            for (lines, text) in [(&mut left, &left_content), (&mut right, &right_content)] {
+                let style = text.lines.get(index).map_or(Style::default(), |line| line.style);
+                lines.resize(rows, Line::default().style(style));
+            }
+            (left, right)
+        };
+        let height = |index: usize| wrapped(index).0.len();
+        let visible = content_section.height.saturating_sub(2) as usize;
+        // Scroll just far enough to show the cursor row.
+        app.scroll_top = app.scroll_top.min(app.cursor);
+        let (mut top, mut used) = (app.cursor, height(app.cursor));
+        while top > app.scroll_top && used + height(top - 1) <= visible {
+            top -= 1;
+            used += height(top);
+        }
+        app.scroll_top = top;
+        let rows = left_content.lines.len().max(right_content.lines.len());
+        let (mut left_lines, mut right_lines) = (Vec::new(), Vec::new());
+        for index in app.scroll_top..rows {
+            if left_lines.len() >= visible {
+                break;
+            }
+            let (left, right) = wrapped(index);
+            left_lines.extend(left);
+            right_lines.extend(right);
+        }
+        left_content = Text { lines: left_lines, ..left_content };
+        right_content = Text { lines: right_lines, ..right_content };
+    }
+
+    // Diff rows come wrapped already.
+    let paragraph = |text: Text<'static>| {
+        let paragraph = Paragraph::new(text).style(Style::default());
+        if app.display_diff {
+            paragraph
+        } else {
+            paragraph.wrap(Wrap { trim: false })
+        }
     };
-    let right_paragraph = Paragraph::new(right_content)
-        .style(Style::default())
-        .wrap(Wrap { trim: false })
-        .block(Block::bordered().title("Right JSON"));
+    let left_paragraph = paragraph(left_content)
+        .block(Block::bordered().title(format!("Left {}{}{filter}{stale}", app.pane_formats[0].name(), pin_glyph(app.pinned[0]))));
+    f.render_widget(left_paragraph, left_content_area);
+
+    let right_paragraph = paragraph(right_content)
+        .block(Block::bordered().title(format!("Right {}{}{filter}{stale}", app.pane_formats[1].name(), pin_glyph(app.pinned[1]))));
     f.render_widget(right_paragraph, right_content_area);
 }
 
-fn render_help() -> Paragraph<'static> {
-    let (msg, style) = (
+/// Errors are shown in a popup over the panes; any key dismisses it.
+fn render_error(f: &mut Frame, area: Rect, message: &str) {
+    let popup = centered_rect(area, 60, 30);
+    f.render_widget(Clear, popup);
+    f.render_widget(
+        Paragraph::new(message.to_string())
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title("Error (press any key)").red()),
+        popup,
+    );
+}
+
+pub fn centered_rect(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let [_, vertical, _] = Layout::vertical([
+        Constraint::Percentage((100 - percent_y) / 2),
+        Constraint::Percentage(percent_y),
+        Constraint::Percentage((100 - percent_y) / 2),
+    ])
+    .areas(area);
+    let [_, horizontal, _] = Layout::horizontal([
+        Constraint::Percentage((100 - percent_x) / 2),
+        Constraint::Percentage(percent_x),
+        Constraint::Percentage((100 - percent_x) / 2),
+    ])
+    .areas(vertical);
+    horizontal
+}
+
+/// ` - [x] export CSV` style entries, left out when `--read-only` forbids writing.
+fn export_help(read_only: ReadOnly, keys: &[(&'static str, &'static str)]) -> Vec<Span<'static>> {
+    if read_only == ReadOnly::Hard {
+        return Vec::new();
+    }
+    let confirm = if read_only == ReadOnly::Soft { " (confirm)" } else { "" };
+    keys.iter()
+        .flat_map(|(key, format)| [" - ".into(), key.green().bold(), format!(" export {format}{confirm}").into()])
+        .collect()
+}
+
+fn render_help(read_only: ReadOnly, diffed: bool) -> Paragraph<'static> {
+    let (mut msg, style) = (
         vec![
             "[q]".green().bold(),
             " Quit - ".into(),
+            "[d]".green().bold(),
+            " diff JSON - ".into(),
+            "[t]".green().bold(),
+            " table view - ".into(),
+            "[K]".green().bold(),
+            " key coverage".into(),
+        ],
+        Style::default().add_modifier(Modifier::RAPID_BLINK),
+    );
+    if read_only == ReadOnly::Off {
+        msg.splice(2..2, [
             "[a]".green().bold(),
             " edit left - ".into(),
             "[b]".green().bold(),
             " edit right - ".into(),
             "[c]".green().bold(),
             " clear input - ".into(),
-            "[d]".green().bold(),
-            " diff JSON".into(),
-        ],
-        Style::default().add_modifier(Modifier::RAPID_BLINK),
-    );
+        ]);
+    }
+    if diffed {
+        msg.extend(export_help(read_only, &[("[x]", "patch")]));
+    }
     let text = Text::from(Line::from(msg)).patch_style(style);
     Paragraph::new(text)
 }
 
-fn open_editor<B: Backend>(
-    app: &DiffApp,
-    side: FileSide,
-    terminal: &mut Terminal<B>,
-) -> Result<()> {
-    let mut stdout = io::stdout();
-
-    stdout.execute(LeaveAlternateScreen)?;
+/// Enables the comparator `name`, or disables it if it is enabled, and
+/// diffs again if a diff is shown. Returns whether it is now enabled.
+fn toggle_comparator(app: &mut DiffApp, name: &str) -> bool {
+    let comparators = &mut app.options.comparators;
+    let on = !comparators.iter().any(|enabled| enabled == name);
+    if on {
+        comparators.push(name.to_string());
+    } else {
+        comparators.retain(|enabled| enabled != name);
+    }
+    if app.display_diff {
+        start_diff(app, false);
+    }
+    on
+}
 
-    disable_raw_mode()?;
+/// Applies options changed in the `O` panel, diffing again if a diff is
+/// shown. Nothing changes if the ignore rules can't be loaded.
+fn apply_options(app: &mut DiffApp, options: DiffOptions) -> Result<()> {
+    let ignore_rules = options.ignore_args().rules(&[Path::new(".")])?;
+    app.array_keys = Arc::new(ArrayKeys::from_specs(&options.array_keys)?);
+    app.ignore_rules = Arc::new(ignore_rules);
+    let reload = app.options.decode_escapes != options.decode_escapes || app.options.key_order != options.key_order;
+    // How many entries are shown is settled without diffing again.
+    let shown_only = options.max_entries != app.options.max_entries
+        && DiffOptions { max_entries: app.options.max_entries, ..options.clone() } == app.options;
+    app.options = options;
+    if shown_only {
+        if app.display_diff {
+            show_diff_rows(app);
+            app.cursor = app.cursor.min(app.diff_paths.len().saturating_sub(1));
+        }
+        return Ok(());
+    }
+    if reload {
+        load_pane(app, FileSide::Left);
+        load_pane(app, FileSide::Right);
+    }
+    if app.display_diff {
+        start_diff(app, false);
+    }
+    Ok(())
+}
 
-    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vim".to_string());
+/// Writes `export` now, after a confirmation under `--read-only=soft`, or not
+/// at all under `--read-only`.
+fn request_export(app: &mut DiffApp, export: Export) {
+    match app.read_only {
+        ReadOnly::Off => write_export(app, export),
+        ReadOnly::Soft => {
+            app.status_message = format!("read-only: write {}? (y/n)", export.path().display());
+            app.confirm = Some(Confirm::Export(export));
+        }
+        ReadOnly::Hard => app.status_message = "read-only: exports are disabled".to_string(),
+    }
+}
+
+fn write_export(app: &mut DiffApp, export: Export) {
+    let path = export.path();
+    let written = match export {
+        Export::TableCsv => app.tables.get(app.table_index).map(|table| {
+            let absent = match app.absent {
+                [true, false] => Some("left input absent: every row is added".to_string()),
+                [false, true] => Some("right input absent: every row is removed".to_string()),
+                _ => None,
+            };
+            let note = [absent, redact::note(app.diff_redactions)].into_iter().flatten().collect::<Vec<_>>().join("; ");
+            let summarize = app.summarize_subtrees.then_some(app.options.subtree_threshold);
+            table
+                .write_csv(path, Some(&note).filter(|note| !note.is_empty()).map(String::as_str), summarize)
+                .map(|()| format!("table '{}'", table.label()))
+        }),
+        Export::CoverageCsv => app.coverage.as_ref().map(|coverage| coverage.write_csv(path).map(|()| "key coverage".to_string())),
+        Export::CoverageJson => app.coverage.as_ref().map(|coverage| coverage.write_json(path).map(|()| "key coverage".to_string())),
+        Export::Patch => Some(match &app.documents {
+            Some([left, right]) => {
+                let ops = patch::diff(left, right);
+                // Redacted values go into the patch as their placeholders,
+                // which applying it would write in place of the secrets.
+                let placeholders = ops.iter().filter(|op| op.get("value").is_some_and(redact::holds_placeholder)).count();
+                let what = match placeholders {
+                    0 => format!("a patch of {} operation(s)", ops.len()),
+                    n => format!("a patch of {} operation(s) (⚠ {n} with redacted placeholders)", ops.len()),
+                };
+                atomic::write(path, patch::to_string(ops)).map(|()| what)
+            }
+            None => Err(anyhow!("a text diff has no JSON Patch")),
+        }),
+        Export::Flags => {
+            let command = app.options.command_line();
+            Some(atomic::write(path, command + "\n").map(|()| "the options as flags".to_string()))
+        }
+    };
+    app.status_message = match written {
+        Some(Ok(what)) => format!("exported {what} to {}", path.display()),
+        Some(Err(err)) => format!("export failed: {err}"),
+        None => String::new(),
+    };
+}
+
+/// Opens a pane in the editor, asking to unpin it first if it is pinned.
+fn edit_pane<B: Backend>(app: &mut DiffApp, side: FileSide, terminal: &mut Terminal<B>) -> io::Result<()> {
+    if app.pinned[side.index()] {
+        app.status_message = format!("{} pane is pinned — unpin and edit? (y/n)", side.label());
+        app.confirm = Some(Confirm::UnpinAndEdit(side));
+        return Ok(());
+    }
+    open_editor(app, side, terminal).map_err(|_| io::ErrorKind::BrokenPipe)?;
+    app.sources[side.index()] = None;
+    app.absent[side.index()] = false;
+    load_pane(app, side);
+    content_changed(app);
+    Ok(())
+}
+
+const STALE_MESSAGE: &str = "content changed — press d to refresh the diff";
+
+/// After a pane's content changed: diffs again if a diff is displayed, or
+/// with `--on-change stale` marks it stale until `d`.
+fn content_changed(app: &mut DiffApp) {
+    if !app.display_diff {
+        return;
+    }
+    match app.options.on_change {
+        OnChange::Refresh => start_diff(app, false),
+        OnChange::Stale => {
+            app.diff_stale = true;
+            app.show_table = false;
+            app.show_coverage = false;
+            app.status_message = STALE_MESSAGE.to_string();
+        }
+    }
+}
+
+fn toggle_pin(app: &mut DiffApp, side: FileSide) {
+    let pinned = &mut app.pinned[side.index()];
+    *pinned = !*pinned;
+    app.status_message = if *pinned {
+        format!("{} pane pinned; [r] reloads only the other side", side.label())
+    } else {
+        format!("{} pane unpinned", side.label())
+    };
+}
+
+/// Re-reads the sources into every unpinned pane loaded from a file or URL,
+/// and re-diffs against whatever is pinned.
+fn reload(app: &mut DiffApp) {
+    if app.fetcher.is_none() && app.stamps.iter().all(Option::is_none) {
+        app.status_message = "nothing to reload: start with -f or --fetch, or open files".to_string();
+        return;
+    }
+    let mut reloaded = Vec::new();
+    for side in [FileSide::Left, FileSide::Right] {
+        if app.pinned[side.index()] || (app.fetcher.is_none() && app.stamps[side.index()].is_none()) {
+            continue;
+        }
+        if let Err(err) = copy_source(app, side) {
+            app.error_message = Some(format!("{}: {err:#}", side.label()));
+            return;
+        }
+        load_pane(app, side);
+        reloaded.push(side.label());
+    }
+    app.status_message = match reloaded.len() {
+        0 => "both panes are pinned; nothing reloaded".to_string(),
+        _ => format!("reloaded {}", reloaded.join(" and ")),
+    };
+    if !reloaded.is_empty() {
+        content_changed(app);
+    }
+}
+
+/// Copies the pane's source file (at first `./left.json` or `./right.json`),
+/// or the fetched response with `--fetch`, into the pane's working file.
+fn copy_source(app: &mut DiffApp, side: FileSide) -> Result<()> {
+    let target = match side {
+        FileSide::Left => app.left_file.path(),
+        FileSide::Right => app.right_file.path(),
+    };
+    if let Some(fetcher) = &app.fetcher {
+        std::fs::write(target, fetcher.fetch(side.index())?)?;
+        return Ok(());
+    }
+    let source = match (&app.stamps[side.index()], side) {
+        (Some(stamp), _) => stamp.path.clone(),
+        (None, FileSide::Left) => PathBuf::from("./left.json"),
+        (None, FileSide::Right) => PathBuf::from("./right.json"),
+    };
+    let contents = match std::fs::read(&source) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound && !app.error_on_missing => None,
+        read => Some(read.with_context(|| source.display().to_string())?),
+    };
+    std::fs::write(target, contents.as_deref().unwrap_or_default())?;
+    app.absent[side.index()] = contents.is_none();
+    app.stamps[side.index()] = Some(Stamp::new(source, contents.as_deref()));
+    Ok(())
+}
+
+/// Re-checks the panes' source files, returning the names of those changed
+/// on disk since they were loaded.
+fn changed_sources(app: &mut DiffApp) -> Vec<String> {
+    app.stamps.iter_mut().flatten().filter_map(|stamp| stamp.check().then(|| stamp.name())).collect()
+}
+
+/// Fills side 0 or 1 from the start screen with a file or, for `http(s)://`,
+/// a URL.
+fn fill_side(app: &mut DiffApp, side: usize, source: String) {
+    let side = if side == 0 { FileSide::Left } else { FileSide::Right };
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let contents = fetch::fetch_url(&source);
+        write_side(app, side, source, contents);
+    } else {
+        let path = std::path::absolute(&source).unwrap_or_else(|_| PathBuf::from(&source));
+        let contents = std::fs::read_to_string(&path).with_context(|| source.clone());
+        let stamp = contents.as_ref().ok().map(|contents| Stamp::new(path.clone(), Some(contents.as_bytes())));
+        write_side(app, side, path.display().to_string(), contents);
+        if app.sources[side.index()].is_some() {
+            app.stamps[side.index()] = stamp;
+        }
+    }
+}
+
+/// `--apply-patch`: `document` on the left, with `patch` applied on the
+/// right, diffed. A patch that fails to apply is explained, with the right
+/// pane left empty.
+fn preview_patch(app: &mut DiffApp, document: &Path, patch_file: &Path) {
+    let (contents, patched) = match apply_patch_file(document, patch_file, app.formats[0], app.options.max_depth) {
+        Ok(read) => read,
+        Err(err) => {
+            app.error_message = Some(format!("{err:#}"));
+            return;
+        }
+    };
+    let source = std::path::absolute(document).unwrap_or_else(|_| document.to_path_buf()).display().to_string();
+    write_side(app, FileSide::Left, source.clone(), Ok(contents));
+    match patched {
+        Ok(value) => {
+            write_side(app, FileSide::Right, format!("{source} with {} applied", patch_file.display()), Ok(format!("{value:#}\n")));
+            start_diff(app, true);
+        }
+        Err(err) => app.error_message = Some(format!("patch not applied: {err:#}")),
+    }
+}
+
+/// Reads `document` and applies the JSON Patch in `patch_file` to it: the
+/// document's contents, and the patched document or why that failed.
+fn apply_patch_file(document: &Path, patch_file: &Path, format: Format, max_depth: usize) -> Result<(String, Result<Value>)> {
+    let read = |path: &Path| std::fs::read_to_string(path).with_context(|| path.display().to_string());
+    let contents = read(document)?;
+    let patched = parse_document(&contents, document, format, max_depth, DuplicateKeys::Last)
+        .with_context(|| document.display().to_string())
+        .and_then(|value| {
+            let patch = read(patch_file)?;
+            let patch = parse_document(&patch, patch_file, Format::Json, max_depth, DuplicateKeys::Last).with_context(|| patch_file.display().to_string())?;
+            patch::apply(&value, &patch).with_context(|| patch_file.display().to_string())
+        });
+    Ok((contents, patched))
+}
+
+/// Puts `contents` in a pane, remembering `source` for history.
+fn write_side(app: &mut DiffApp, side: FileSide, source: String, contents: Result<String>) {
+    let target = match side {
+        FileSide::Left => app.left_file.path(),
+        FileSide::Right => app.right_file.path(),
+    };
+    match contents.and_then(|contents| Ok(std::fs::write(target, contents)?)) {
+        Ok(()) => {
+            app.stamps[side.index()] = None;
+            app.absent[side.index()] = false;
+            app.sources[side.index()] = Some(source);
+            app.source_files = app.sources.iter().all(|source| source.as_deref().is_some_and(|s| Path::new(s).is_file()));
+            load_pane(app, side);
+        }
+        Err(err) => app.error_message = Some(format!("{}: {err:#}", side.label())),
+    }
+}
+
+fn open_editor<B: Backend>(
+    app: &mut DiffApp,
+    side: FileSide,
+    terminal: &mut Terminal<B>,
+) -> Result<()> {
+    let mut stdout = io::stdout();
+
+    execute!(stdout, LeaveAlternateScreen)?;
+    if app.capabilities.mouse {
+        execute!(stdout, DisableMouseCapture)?;
+    }
+
+    disable_raw_mode()?;
+
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vim".to_string());
 
     let path = match side {
         FileSide::Left => app.left_file.path(),
@@ -193,59 +1960,1243 @@ fn open_editor<B: Backend>(
     enable_raw_mode()?;
 
     stdout.execute(EnterAlternateScreen)?;
+    apply_mouse_capture(app)?;
 
     terminal.clear()?;
     terminal.draw(|f| render_ui(f, app))?;
     Ok(())
 }
 
-fn compare_json_files(app: &DiffApp) -> Result<(Text<'static>, Text<'static>)> {
-    let left_json = parse_json(app.left_file.path())?;
-    let right_json = parse_json(app.right_file.path())?;
-    Ok(diff_json_values(&left_json, &right_json))
+/// Puts the selected HAR pair's response bodies in the panes and diffs them;
+/// a request missing on one side shows as `null` there.
+fn open_har_pair(app: &mut DiffApp) -> io::Result<()> {
+    let Some(pair) = app.har.as_ref().and_then(Har::selected) else {
+        return Ok(());
+    };
+    let body = |entry: &Option<har::Entry>| {
+        let body = entry.as_ref().map_or(&Value::Null, |entry| &entry.body);
+        serde_json::to_string_pretty(body).unwrap_or_default()
+    };
+    let (left, right) = (body(&pair.left), body(&pair.right));
+    let files = app.har.as_ref().map(|har| har.files.clone()).unwrap_or_default();
+    let source = |file: &Path| Some(format!("{} {} {}", file.display(), pair.method, pair.url));
+    let sources = [source(&files.0), source(&files.1)];
+    for (file, body) in [(&app.left_file, left), (&app.right_file, right)] {
+        file.as_file().set_len(0)?;
+        std::fs::write(file.path(), body)?;
+    }
+    for side in [FileSide::Left, FileSide::Right] {
+        load_pane(app, side);
+    }
+    if let Some(har) = &mut app.har {
+        har.list_open = false;
+    }
+    app.sources = sources;
+    app.focus_path = None;
+    start_diff(app, true);
+    Ok(())
+}
+
+/// Expands or collapses the object or array on the cursor row, or shows its
+/// values in full if they were cut.
+fn open_row(app: &mut DiffApp) {
+    let path = app.diff_paths.get(app.cursor).cloned().flatten();
+    let fold = app.diff_folds.get(app.cursor).copied().flatten();
+    match (path, fold) {
+        (Some(path), Some(expanded)) => {
+            app.folds.insert(path, !expanded);
+            start_diff(app, false);
+        }
+        (Some(path), None) if app.diff_full.contains_key(&path) => app.value_popup = Some(ValuePopup { path, scroll: 0 }),
+        _ => {}
+    }
+}
+
+/// The full values of a row whose values were cut in the diff.
+struct ValuePopup {
+    path: String,
+    scroll: u16,
+}
+
+fn render_value_popup(f: &mut Frame, area: Rect, app: &DiffApp, popup: &ValuePopup) {
+    let Some(values) = app.diff_full.get(&popup.path) else {
+        return;
+    };
+    let area = centered_rect(area, 90, 80);
+    f.render_widget(Clear, area);
+    let [left_area, right_area] = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(area);
+    for ((value, area), side) in values.iter().zip([left_area, right_area]).zip(["Left", "Right"]) {
+        let title = format!("{side} — {} (j/k scroll, Esc close)", display::sanitize(&popup.path));
+        f.render_widget(
+            Paragraph::new(value.clone()).wrap(Wrap { trim: false }).scroll((popup.scroll, 0)).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+}
+
+/// Starts typing a note for the difference under the cursor, prefilled
+/// with its current note.
+fn open_note_prompt(app: &mut DiffApp) {
+    if app.read_only == ReadOnly::Hard {
+        app.status_message = "read-only: notes are disabled".to_string();
+        return;
+    }
+    let path = app.diff_paths.get(app.cursor).cloned().flatten();
+    let kind = app.diff_kinds.get(app.cursor).copied().flatten();
+    let (Some(path), Some(kind)) = (path, kind) else {
+        app.status_message = "notes attach to changed entries; move the cursor to one".to_string();
+        return;
+    };
+    let text = app.annotations.get(&path, kind).unwrap_or_default().to_string();
+    let prompt = NotePrompt { path, kind, text };
+    show_note_prompt(app, &prompt);
+    app.note_prompt = Some(prompt);
+}
+
+fn show_note_prompt(app: &mut DiffApp, prompt: &NotePrompt) {
+    app.status_message = format!(
+        "note on {} ({}): {}▏ (Enter saves, empty removes, Esc cancels)",
+        display::sanitize(&prompt.path),
+        prompt.kind.name(),
+        display::sanitize(&prompt.text)
+    );
+}
+
+/// Starts typing `field` in the status bar, prefilled with its value.
+fn open_option_prompt(app: &mut DiffApp, field: PromptField) {
+    let text = match field {
+        PromptField::IgnoredKeys => app.options.ignored_keys.join(", "),
+        PromptField::Select => app.options.select.clone().unwrap_or_default(),
+    };
+    let prompt = OptionPrompt { field, text };
+    show_option_prompt(app, &prompt);
+    app.option_prompt = Some(prompt);
 }
 
-fn parse_json(path: &std::path::Path) -> Result<Value> {
-    let mut file = File::open(path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-    let json_value: Value = serde_json::from_str(&contents)?;
-    Ok(json_value)
+fn show_option_prompt(app: &mut DiffApp, prompt: &OptionPrompt) {
+    let (label, hint) = match prompt.field {
+        PromptField::IgnoredKeys => ("ignore keys named", "comma-separated; Enter applies, empty clears"),
+        PromptField::Select => ("compare only", "a path like $.spec.containers; Enter applies, empty compares everything"),
+    };
+    app.status_message = format!("{label}: {}▏ ({hint}, Esc cancels)", display::sanitize(&prompt.text));
 }
 
-fn read_json(path: &std::path::Path) -> Result<Text<'static>> {
-    let json_value = parse_json(path)?;
-    let json_string = serde_json::to_string_pretty(&json_value)?;
-    Ok(Text::from(json_string))
+/// Sets the prompt's option and diffs again, or says why it can't.
+fn apply_option_prompt(app: &mut DiffApp, prompt: OptionPrompt) {
+    let mut options = app.options.clone();
+    let text = prompt.text.trim();
+    match prompt.field {
+        PromptField::IgnoredKeys => options.ignored_keys = ignore::key_names(text),
+        PromptField::Select if text.is_empty() || text == "$" => options.select = None,
+        PromptField::Select => match Selection::parse(text) {
+            Ok(_) => options.select = Some(text.to_string()),
+            Err(err) => {
+                app.error_message = Some(format!("{err:#}"));
+                return;
+            }
+        },
+    }
+    app.status_message.clear();
+    if let Err(err) = apply_options(app, options) {
+        app.error_message = Some(format!("applying options failed: {err:#}"));
+    }
 }
 
-fn diff_json_values(left: &Value, right: &Value) -> (Text<'static>, Text<'static>) {
-    let mut left_diff = Text::default();
-    let mut right_diff = Text::default();
+/// Marks the cursor row with `letter`, replacing any previous mark of that letter.
+fn set_mark(app: &mut DiffApp, letter: char) {
+    let mark = Mark { path: app.diff_paths.get(app.cursor).cloned().flatten(), row: app.cursor };
+    let description = mark.describe();
+    app.status_message = match app.bookmarks.set(letter, mark) {
+        Some(previous) => format!("mark '{letter}' moved from {} to {description}", previous.describe()),
+        None => format!("mark '{letter}' set at {description}"),
+    };
+}
 
-    if let (Some(left_map), Some(right_map)) = (left.as_object(), right.as_object()) {
-        let all_keys: BTreeSet<_> = left_map.keys().chain(right_map.keys()).collect();
+fn jump_to_mark(app: &mut DiffApp, letter: char) {
+    let Some(mark) = app.bookmarks.get(letter) else {
+        app.status_message = format!("no mark '{letter}'");
+        return;
+    };
+    let (row, found) = mark.resolve(&app.diff_paths);
+    app.status_message = if found {
+        format!("mark '{letter}': {}", mark.describe())
+    } else {
+        format!("mark '{letter}': {} is no longer in the diff, jumped to line {}", mark.describe(), row + 1)
+    };
+    app.cursor = row;
+}
 
-        for key in all_keys {
-            let left_value = left_map.get(key).cloned().unwrap_or(json!(null));
-            let right_value = right_map.get(key).cloned().unwrap_or(json!(null));
-            if left_value == right_value {
-                let line = format!("{}: {}\n", key, left_value);
-                left_diff.extend(vec![Span::styled(line.clone(), Style::default().fg(Color::Green))]);
-                right_diff.extend(vec![Span::styled(line, Style::default().fg(Color::Green))]);
+/// Enables or disables mouse capture to match `app.mouse_capture`. With
+/// capture off the terminal's native text selection works in the panes.
+/// Terminals without mouse reporting are left alone.
+fn apply_mouse_capture(app: &DiffApp) -> io::Result<()> {
+    if !app.capabilities.mouse {
+        Ok(())
+    } else if app.mouse_capture {
+        execute!(io::stdout(), EnableMouseCapture)
+    } else {
+        execute!(io::stdout(), DisableMouseCapture)
+    }
+}
+
+/// Starts a diff of the current files on a background thread. `reset_view`
+/// re-picks between the text and table views once it completes, and marks a
+/// new comparison for the history log rather than a re-render of this one.
+fn start_diff(app: &mut DiffApp, reset_view: bool) {
+    let job = DiffJob {
+        left_path: app.left_file.path().to_path_buf(),
+        right_path: app.right_file.path().to_path_buf(),
+        formats: app.formats,
+        options: if app.show_all_arrays { app.options.clone().array_summary_threshold(usize::MAX) } else { app.options.clone() },
+        folds: app.folds.clone(),
+        focus_path: app.focus_path.clone(),
+        base: app.base.clone(),
+        redactor: Arc::clone(&app.redactor),
+        ignore_rules: Arc::clone(&app.ignore_rules),
+        normalizers: Arc::clone(&app.normalizers),
+        annotations: Arc::new(app.annotations.clone()),
+        context: Arc::clone(&app.context),
+        array_keys: Arc::clone(&app.array_keys),
+        truncate: app.truncate_limit(),
+        history: reset_view && app.history.is_some(),
+        times: app.options.relative_times.then(|| Arc::clone(&app.times)),
+        timings: app.timings.enabled(),
+    };
+    let cancel = CancelToken::default();
+    let token = cancel.clone();
+    let spawned = thread::Builder::new()
+        .stack_size(depth::stack_size(job.options.max_depth))
+        .spawn(move || compare_json_files(&job, &token));
+    let handle = match spawned {
+        Ok(handle) => handle,
+        Err(err) => {
+            app.error_message = Some(format!("diff failed: {err}"));
+            return;
+        }
+    };
+    let anchors = [app.cursor, app.scroll_top].map(|row| app.diff_paths.get(row).cloned().flatten());
+    app.pending_diff = Some(PendingDiff { handle, cancel, started: Instant::now(), reset_view, anchors });
+}
+
+/// Applies a finished background diff. A cancelled or failed diff leaves the
+/// previously displayed result untouched.
+fn finish_diff(app: &mut DiffApp) {
+    let Some(pending) = app.pending_diff.take() else {
+        return;
+    };
+    let elapsed = pending.started.elapsed().as_secs_f64();
+    match pending.handle.join() {
+        Ok(Ok(Some(output))) => {
+            app.diff_rows = output.diff;
+            app.identical = output.identical;
+            show_diff_rows(app);
+            app.display_diff = true;
+            app.diff_stale = false;
+            app.tables = output.tables;
+            app.table_index = 0;
+            app.coverage = output.coverage;
+            app.documents = output.documents;
+            if let Some(report) = &app.report {
+                app.report = app.documents.is_some().then(|| ReportView::new(&app.diff_rows.summary, report.lists_paths(), app.truncate_limit()));
+            }
+            app.diff_redactions = output.redactions;
+            app.diff_selection = output.selection;
+            app.diff_suppressed = output.suppressed;
+            if let (Some(history), Some(leaves)) = (&app.history, output.leaves) {
+                let describe = |source: &Option<String>| source.clone().unwrap_or_else(|| "(edited in diffrs)".to_string());
+                let rerun = app.sources.iter().flatten().cloned().collect::<Vec<_>>();
+                let rerun = (app.source_files && rerun.len() == 2).then_some(rerun);
+                history.record([describe(&app.sources[0]), describe(&app.sources[1])], &app.diff_rows.summary, leaves, rerun);
+            }
+            app.show_coverage &= app.coverage.is_some();
+            if pending.reset_view {
+                app.show_table = output.prefers_table;
             } else {
-                let left_line = format!("{}: {}\n", key, left_value);
-                let right_line = format!("{}: {}\n", key, right_value);
-                left_diff.extend(vec![Span::styled(left_line, Style::default().fg(Color::Green))]);
-                right_diff.extend(vec![Span::styled(right_line, Style::default().fg(Color::Red))]);
+                app.show_table &= !app.tables.is_empty();
+            }
+            let state = |side: usize| if app.absent[side] { "absent" } else { "empty" };
+            app.status_message = match output.empty {
+                [true, true] => "both sides are empty".to_string(),
+                [true, false] => format!("left is {}: everything on the right shows as added", state(0)),
+                [false, true] => format!("right is {}: everything on the left shows as removed", state(1)),
+                [false, false] => String::new(),
+            };
+            if output.suppressed > 0 {
+                let suppressed = format!("{} path(s) suppressed by ignore rules", output.suppressed);
+                app.status_message =
+                    if app.status_message.is_empty() { suppressed } else { format!("{}; {suppressed}", app.status_message) };
+            }
+            if let Some(merges) = output.merges {
+                app.status_message = merges;
+            }
+            restore_anchors(app, pending.anchors);
+            app.repairs = output.repairs;
+            app.duplicates = output.duplicates;
+            app.show_repairs &= app.repairs.iter().any(|repairs| !repairs.is_empty())
+                || app.duplicates.iter().any(|duplicates| !duplicates.is_empty());
+            let [left, right] = app.repairs.each_ref().map(Vec::len);
+            if left + right > 0 {
+                app.status_message = format!("⚠ repaired {left} escape(s) on the left, {right} on the right — [W] lists them");
+            }
+            let [left, right] = app.duplicates.each_ref().map(Vec::len);
+            app.status_message = match (left, right) {
+                (0, 0) => app.status_message.clone(),
+                (1, 0) => format!("⚠ left: {}", app.duplicates[0][0]),
+                (0, 1) => format!("⚠ right: {}", app.duplicates[1][0]),
+                _ => format!("⚠ {left} duplicate key(s) on the left, {right} on the right — [W] lists them"),
+            };
+            if let Some((path, reason)) = output.comparator_failures.first_key_value() {
+                let more = match output.comparator_failures.len() {
+                    1 => String::new(),
+                    n => format!(" (and {} more)", n - 1),
+                };
+                app.status_message = format!("⚠ {path}: {reason}{more}; compared as JSON there");
+            }
+            app.render_pending = app.timings.enabled();
+            app.timings.merge(output.timings);
+        }
+        Ok(Ok(None)) => app.status_message = format!("diff cancelled after {elapsed:.1}s"),
+        Ok(Err(err)) => {
+            app.status_message.clear();
+            app.error_message = Some(format!("diff failed: {err}"));
+        }
+        Err(_) => app.status_message = "diff failed: worker thread panicked".to_string(),
+    }
+}
+
+/// Shows the rows of the current diff, only those that differ with `D`, up
+/// to `--max-entries` of them and a footer counting the rest.
+fn show_diff_rows(app: &mut DiffApp) {
+    let (rows, hidden) = if app.only_differences { app.diff_rows.only_differences() } else { (app.diff_rows.clone(), 0) };
+    let (mut rows, [differing, unchanged]) = rows.first_entries(app.options.max_entries);
+    if differing + unchanged > 0 {
+        let more = match (differing, unchanged) {
+            (0, n) => format!("{} more unchanged entries", display::group_thousands(n)),
+            (n, 0) => format!("{} more differences", display::group_thousands(n)),
+            (n, m) => format!("{} more differences and {} unchanged entries", display::group_thousands(n), display::group_thousands(m)),
+        };
+        let export = if app.read_only == ReadOnly::Hard { "" } else { " / [x] export to see all" };
+        let footer = format!("… and {more} ([L] raise limit{export})");
+        let style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+        rows.push(Span::styled(footer.clone(), style), Span::styled(footer, style), None, None);
+    }
+    app.entries_left_out = differing + unchanged;
+    app.left_diff_result = rows.left;
+    app.right_diff_result = rows.right;
+    app.diff_paths = rows.paths;
+    app.diff_kinds = rows.kinds;
+    app.diff_folds = rows.folds;
+    app.diff_full = rows.full;
+    app.hidden_unchanged = hidden;
+}
+
+/// `D`: switches between all rows and only those that differ, from the diff
+/// already made, keeping the cursor on its path or the nearest ancestor shown.
+fn toggle_only_differences(app: &mut DiffApp) {
+    app.only_differences = !app.only_differences;
+    if !app.display_diff {
+        return;
+    }
+    let anchors = [app.cursor, app.scroll_top].map(|row| app.diff_paths.get(row).cloned().flatten());
+    show_diff_rows(app);
+    let [cursor, top] = anchors.map(|path| path.and_then(|path| find_anchor(&app.diff_paths, &path)).map_or(0, |(row, _)| row));
+    app.cursor = cursor.min(app.diff_paths.len().saturating_sub(1));
+    app.scroll_top = top;
+}
+
+/// `L`: shows ten times as many entries of the diff already made.
+fn raise_entry_limit(app: &mut DiffApp) {
+    if !app.display_diff || app.entries_left_out == 0 {
+        app.status_message = "every entry of the diff is shown".to_string();
+        return;
+    }
+    app.options.max_entries = app.options.max_entries.saturating_mul(10);
+    show_diff_rows(app);
+    app.status_message = format!("showing up to {} entries", display::group_thousands(app.options.max_entries));
+}
+
+/// Puts the cursor and the top of the view back on the paths they showed
+/// before a re-diff shifted the rows, or else on their nearest ancestors that
+/// still have rows. An ancestor that is only folded away by the expand depth
+/// is expanded again and the diff redone to reach the exact path.
+fn restore_anchors(app: &mut DiffApp, anchors: [Option<String>; 2]) {
+    let [cursor_path, top_path] = &anchors;
+    if let Some(path) = top_path {
+        if let Some((row, _)) = find_anchor(&app.diff_paths, path) {
+            app.scroll_top = row;
+        }
+    }
+    let Some(path) = cursor_path else {
+        app.cursor = app.cursor.min(app.diff_paths.len().saturating_sub(1));
+        return;
+    };
+    match find_anchor(&app.diff_paths, path) {
+        Some((row, true)) => app.cursor = row,
+        Some((row, false)) => {
+            app.cursor = row;
+            let ancestor = app.diff_paths[row].clone().unwrap_or_default();
+            let folded_by_depth = app.diff_folds.get(row).copied().flatten() == Some(false) && !app.folds.contains_key(&ancestor);
+            if folded_by_depth {
+                app.folds.insert(ancestor, true);
+                start_diff(app, false);
+                if let Some(pending) = &mut app.pending_diff {
+                    pending.anchors = anchors;
+                }
+                return;
+            }
+            app.status_message = format!("{} is no longer in the diff; showing {}", display::sanitize(path), display::sanitize(&ancestor));
+        }
+        None => {
+            app.cursor = app.cursor.min(app.diff_paths.len().saturating_sub(1));
+            app.status_message = format!("{} is no longer in the diff", display::sanitize(path));
+        }
+    }
+}
+
+/// The row showing `path`, or else its nearest ancestor that has a row, and
+/// whether the row is `path` itself.
+fn find_anchor(paths: &[Option<String>], path: &str) -> Option<(usize, bool)> {
+    let mut candidate = path;
+    loop {
+        if let Some(row) = paths.iter().position(|p| p.as_deref() == Some(candidate)) {
+            return Some((row, candidate == path));
+        }
+        candidate = &candidate[..candidate.rfind(['.', '['])?];
+    }
+}
+
+/// Returns `None` when `cancel` fired before the diff completed.
+fn compare_json_files(job: &DiffJob, cancel: &CancelToken) -> Result<Option<DiffOutput>> {
+    let (left_path, right_path, max_depth) = (job.left_path.as_path(), job.right_path.as_path(), job.options.max_depth);
+    let mut timings = Timings::new(job.timings);
+    let left_contents = timings.measure("load left", || std::fs::read_to_string(left_path))?;
+    timings.input_bytes(left_contents.len());
+    let right_contents = timings.measure("load right", || std::fs::read_to_string(right_path))?;
+    timings.input_bytes(right_contents.len());
+    if job.formats[0] == Format::Text {
+        let (left_contents, left_redacted) = job.redactor.redact_text(&left_contents);
+        let (right_contents, right_redacted) = job.redactor.redact_text(&right_contents);
+        let mut checkpoint = Checkpoint::new(cancel);
+        let diff = timings.measure("diff", || linediff::diff_texts(&left_contents, &right_contents, &mut checkpoint));
+        let Some(diff) = diff.map(DiffText::from_lines) else {
+            return Ok(None);
+        };
+        return Ok(Some(DiffOutput {
+            diff,
+            tables: Vec::new(),
+            prefers_table: false,
+            coverage: None,
+            documents: None,
+            merges: None,
+            redactions: left_redacted + right_redacted,
+            suppressed: 0,
+            selection: None,
+            empty: [false, false],
+            leaves: None,
+            repairs: Default::default(),
+            duplicates: Default::default(),
+            identical: false,
+            comparator_failures: BTreeMap::new(),
+            timings,
+        }));
+    }
+    let duplicates = [(&left_contents, left_path, job.formats[0]), (&right_contents, right_path, job.formats[1])]
+        .map(|(contents, path, format)| duplicates::find_in_document(contents, path, format, job.options.duplicate_keys));
+    let (left_repaired, left_repairs) = repair::repair_document(&left_contents, left_path, job.formats[0], job.options.lenient);
+    let (right_repaired, right_repairs) = repair::repair_document(&right_contents, right_path, job.formats[1], job.options.lenient);
+    let mut left_json = timings.measure("parse left", || parse_document(&left_repaired, left_path, job.formats[0], max_depth, job.options.duplicate_keys))
+        .with_context(|| "left")?;
+    timings.nodes(&left_json);
+    let mut right_json = timings.measure("parse right", || parse_document(&right_repaired, right_path, job.formats[1], max_depth, job.options.duplicate_keys))
+        .with_context(|| "right")?;
+    timings.nodes(&right_json);
+    let empty = [is_blank(&left_contents), is_blank(&right_contents)];
+    fill_empty(&mut left_json, &mut right_json, empty);
+    let suppressed: BTreeSet<String> =
+        job.ignore_rules.apply(&mut left_json).into_iter().chain(job.ignore_rules.apply(&mut right_json)).collect();
+    let notes = [job.normalizers.apply(&mut left_json), job.normalizers.apply(&mut right_json)];
+    let selection = job.options.select.as_deref().map(Selection::parse).transpose()?;
+    if let Some(selection) = &selection {
+        left_json = selection.apply(left_json, "left")?;
+        right_json = selection.apply(right_json, "right")?;
+    }
+    let decoded = decode::apply(&job.options.decode_base64, &mut left_json, &mut right_json);
+    if job.options.schema {
+        left_json = schema::infer(&left_json);
+        right_json = schema::infer(&right_json);
+    }
+    let leaves = job.history.then(|| history::leaf_total(&left_json, &right_json));
+    let coverage = Coverage::build(&left_json, &right_json);
+    let (left_json, right_json) = match &job.focus_path {
+        Some(path) => (coverage::project_records(&left_json, path), coverage::project_records(&right_json, path)),
+        None => (left_json, right_json),
+    };
+    let merges = match &job.base {
+        Some(base_path) => {
+            let contents = std::fs::read_to_string(base_path).with_context(|| base_path.display().to_string())?;
+            let mut base = parse_document(&contents, base_path, job.formats[0], max_depth, job.options.duplicate_keys).with_context(|| format!("base {}", base_path.display()))?;
+            job.ignore_rules.apply(&mut base);
+            job.normalizers.apply(&mut base);
+            if let Some(selection) = &selection {
+                base = selection.apply(base, "base")?;
+            }
+            // Against nothing, every string the patterns match is decoded.
+            decode::apply(&job.options.decode_base64, &mut base, &mut Value::Null);
+            if job.options.schema {
+                base = schema::infer(&base);
             }
+            if let Some(path) = &job.focus_path {
+                base = coverage::project_records(&base, path);
+            }
+            Some(Merges::classify(&base, &left_json, &right_json))
         }
+        None => None,
+    };
+    // Equal documents need no rows: one comparison under the same rules as
+    // the diff settles it, before anything is rendered.
+    let comparators = Comparators::new(&job.options);
+    let identical = merges.is_none() && timings.measure("equality", || comparators.equal("", &left_json, &right_json));
+    // The order keys were written in, read again from the text; a schema or
+    // a focused field's records are built anew and have none.
+    let key_orders = match job.options.key_order && !job.options.schema && job.focus_path.is_none() {
+        true => timings.measure("key order", || {
+            let orders = [(&left_repaired, left_path, job.formats[0]), (&right_repaired, right_path, job.formats[1])].map(|(contents, path, format)| {
+                let order = Order::read(contents, path, format);
+                match &selection {
+                    Some(selection) => selection.apply_order(order),
+                    None => order,
+                }
+            });
+            KeyOrders::of(&[(&left_json, &orders[0]), (&right_json, &orders[1])])
+        }),
+        false => KeyOrders::default(),
+    };
+    let identical = identical && !key_orders.differ(&left_json, &right_json);
+    let repaired: BTreeSet<String> = left_repairs.iter().chain(&right_repairs).map(|repair| repair.path.clone()).collect();
+    let hashes = if identical { Hashes::default() } else { timings.measure("hash", || Hashes::of(&[&left_json, &right_json])) };
+    // The diff compares the real values; the rows, tables and exported
+    // documents show secrets as their tags.
+    let redaction = job.redactor.find(&[&left_json, &right_json]);
+    let view = DiffView {
+        options: &job.options,
+        notes: &notes,
+        times: job.times.as_deref(),
+        folds: &job.folds,
+        context: &job.context,
+        array_keys: &job.array_keys,
+        comparators: &comparators,
+        truncate: job.truncate,
+        repaired: &repaired,
+        decoded: &decoded,
+        hashes: &hashes,
+        key_orders: &key_orders,
+        redaction: &redaction,
+        now: timefmt::now_millis(),
+    };
+    let diff = if identical {
+        Some(DiffText::default())
     } else {
-        let left_str = format!("{}", left);
-        let right_str = format!("{}", right);
-        left_diff.extend(vec![Span::styled(left_str, Style::default().fg(Color::Green))]);
-        right_diff.extend(vec![Span::styled(right_str, Style::default().fg(Color::Red))]);
+        timings.measure("diff", || diff_json_values(&left_json, &right_json, &view, cancel))
+    };
+    let Some(diff) = diff else {
+        return Ok(None);
+    };
+    let diff = diff.annotate(&job.annotations);
+    let mut tables = table::find_tables(&left_json, &right_json, &view);
+    let prefers_table = table::prefers_table(&tables);
+    if let Some(selection) = &selection {
+        for table in &mut tables {
+            table.under(&selection.to_string());
+        }
+    }
+    Ok(Some(DiffOutput {
+        diff: match &merges {
+            Some(merges) => diff.mark_merges(merges),
+            None => diff,
+        },
+        merges: merges.map(|merges| merges.describe()),
+        tables,
+        prefers_table,
+        coverage,
+        documents: Some([redaction.copy(&left_json), redaction.copy(&right_json)]),
+        redactions: redaction.count,
+        suppressed: suppressed.len(),
+        selection: selection.map(|selection| selection.to_string()),
+        empty,
+        leaves,
+        repairs: [left_repairs, right_repairs],
+        duplicates,
+        identical,
+        comparator_failures: comparators.failures(),
+        timings,
+    }))
+}
+
+/// Parses `contents` as YAML or JSON, per `format` resolved for `path`.
+/// JSON nested deeper than `max_depth` is rejected before serde sees it, and
+/// newline-delimited JSON is read as an array of its records. A key repeated
+/// in a JSON object is handled per `duplicate_keys`. Empty input is `null`;
+/// see `fill_empty`.
+fn parse_document(contents: &str, path: &Path, format: Format, max_depth: usize, duplicate_keys: DuplicateKeys) -> Result<Value> {
+    let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+    if is_blank(contents) {
+        return Ok(Value::Null);
+    }
+    let value = match format.resolve(path, contents) {
+        Format::Yaml => serde_yaml::from_str(contents)?,
+        _ => {
+            depth::check(contents, max_depth)?;
+            if duplicate_keys == DuplicateKeys::Error {
+                if let Some(duplicate) = duplicates::find(contents).first() {
+                    bail!("{duplicate}");
+                }
+            }
+            let mut deserializer = serde_json::Deserializer::from_str(contents);
+            deserializer.disable_recursion_limit();
+            let value = duplicates::read(&mut deserializer, duplicate_keys)?;
+            match deserializer.end() {
+                Ok(()) => value,
+                Err(err) => parse_ndjson(contents, duplicate_keys).ok_or(err)?,
+            }
+        }
+    };
+    Ok(value)
+}
+
+/// Reads the two input files; `None` for one that doesn't exist, unless
+/// `error_on_missing`, to be compared as an empty document. Both missing is
+/// an error.
+fn read_inputs(paths: [&Path; 2], error_on_missing: bool) -> Result<[Option<String>; 2]> {
+    let read = |path: &Path| match std::fs::read_to_string(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound && !error_on_missing => Ok(None),
+        read => read.map(Some).with_context(|| path.display().to_string()),
+    };
+    let contents = [read(paths[0])?, read(paths[1])?];
+    if contents.iter().all(Option::is_none) {
+        bail!("neither {} nor {} exists", paths[0].display(), paths[1].display());
+    }
+    Ok(contents)
+}
+
+/// What `summary.print` heads a comparison with, marking an absent side.
+fn comparison_title(sources: [&str; 2], absent: [bool; 2]) -> String {
+    let name = |side: usize| if absent[side] { format!("{} (absent)", sources[side]) } else { sources[side].to_string() };
+    format!("{} → {}", name(0), name(1))
+}
+
+/// Whether `contents` is empty apart from whitespace and byte order marks.
+fn is_blank(contents: &str) -> bool {
+    contents.chars().all(|c| c.is_whitespace() || c == '\u{feff}')
+}
+
+/// Stands an empty document of the other side's shape in for an empty input,
+/// so the other side's content all shows as added or removed. `empty` is
+/// `is_blank` of the left and right inputs.
+fn fill_empty(left: &mut Value, right: &mut Value, empty: [bool; 2]) {
+    let stand_in = |other: &Value| match other {
+        Value::Object(_) => Value::Object(serde_json::Map::new()),
+        Value::Array(_) => Value::Array(Vec::new()),
+        _ => Value::Null,
+    };
+    match empty {
+        [true, false] => *left = stand_in(right),
+        [false, true] => *right = stand_in(left),
+        _ => {}
+    }
+}
+
+/// One JSON value per non-empty line; `None` if any line isn't one.
+fn parse_ndjson(contents: &str, duplicate_keys: DuplicateKeys) -> Option<Value> {
+    let mut records = Vec::new();
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        let mut deserializer = serde_json::Deserializer::from_str(line);
+        deserializer.disable_recursion_limit();
+        records.push(duplicates::read(&mut deserializer, duplicate_keys).ok()?);
+        deserializer.end().ok()?;
+    }
+    Some(Value::Array(records))
+}
+
+/// Loads a pane's pretty-printed content, reporting failures in the error
+/// panel and leaving the pane blank.
+fn load_pane(app: &mut DiffApp, side: FileSide) {
+    let (path, label) = match side {
+        FileSide::Left => (app.left_file.path(), "left"),
+        FileSide::Right => (app.right_file.path(), "right"),
+    };
+    let format = app.formats[side.index()];
+    let placeholder = match (app.read_only, side) {
+        _ if app.absent[side.index()] => "(absent)",
+        (ReadOnly::Off, FileSide::Left) => "(empty — press a to edit)",
+        (ReadOnly::Off, FileSide::Right) => "(empty — press b to edit)",
+        _ => "(empty)",
+    };
+    let loaded = std::fs::read_to_string(path).map_err(Into::into).and_then(|contents| {
+        app.pane_formats[side.index()] = format.resolve(path, &contents);
+        if is_blank(&contents) {
+            Ok((Text::from(placeholder).dim(), 0))
+        } else if format == Format::Text {
+            let (contents, redacted) = app.redactor.redact_text(&contents);
+            Ok((Text::from(display::sanitize_lines(&contents)), redacted))
+        } else {
+            let (contents, _) = repair::repair_document(&contents, path, format, app.options.lenient);
+            read_document(&contents, path, format, &app.options, &app.redactor)
+        }
+    });
+    let (content, redacted) = loaded.unwrap_or_else(|err| {
+        app.error_message = Some(format!("{label}: {err}"));
+        (Text::default(), 0)
+    });
+    match side {
+        FileSide::Left => {
+            app.original_left_content = content;
+            app.pane_redactions[0] = redacted;
+        }
+        FileSide::Right => {
+            app.original_right_content = content;
+            app.pane_redactions[1] = redacted;
+        }
+    }
+}
+
+/// Pretty-printed, redacted content for a pane, with the number of values
+/// redacted; with `--key-order`, keys as they were written.
+fn read_document(contents: &str, path: &Path, format: Format, options: &DiffOptions, redactor: &Redactor) -> Result<(Text<'static>, usize)> {
+    let (json_value, redacted) = redactor.redact(&parse_document(contents, path, format, options.max_depth, options.duplicate_keys)?);
+    let key_orders = match options.key_order {
+        true => KeyOrders::of(&[(&json_value, &Order::read(contents, path, format))]),
+        false => KeyOrders::default(),
+    };
+    Ok((Text::from(key_orders.render_pretty(&json_value, options.decode_escapes)), redacted))
+}
+
+/// Row-aligned diff output: row `i` of `left` and `right` belong together,
+/// `paths[i]` is the document path that row shows, where known, `kinds[i]`
+/// the kind of difference it shows, if any, and `folds[i]` whether the
+/// object or array it shows is expanded. `full` has the left and right
+/// values in full for rows whose values were truncated, by path. `stats`
+/// counts the differences, however many rows are shown, and `depth` is how
+/// deep the entries nest. `summary` lists the differences, for the report
+/// views and the headless outputs.
+#[derive(Clone, Default)]
+struct DiffText {
+    left: Text<'static>,
+    right: Text<'static>,
+    paths: Vec<Option<String>>,
+    kinds: Vec<Option<ChangeKind>>,
+    folds: Vec<Option<bool>>,
+    full: BTreeMap<String, [String; 2]>,
+    stats: Stats,
+    depth: usize,
+    summary: Arc<Summary>,
+}
+
+impl DiffText {
+    fn push(&mut self, left: impl Into<Line<'static>>, right: impl Into<Line<'static>>, path: Option<String>, kind: Option<ChangeKind>) {
+        self.left.extend([left]);
+        self.right.extend([right]);
+        self.paths.push(path);
+        self.kinds.push(kind);
+        self.folds.push(None);
+    }
+
+    /// Marks the last row as showing an object or array that can be folded.
+    fn mark_container(&mut self, expanded: bool) {
+        if let Some(fold) = self.folds.last_mut() {
+            *fold = Some(expanded);
+        }
+    }
+
+    /// Wraps a line diff, whose rows carry no paths.
+    fn from_lines((left, right, kinds): (Text<'static>, Text<'static>, Vec<Option<ChangeKind>>)) -> Self {
+        let rows = left.lines.len();
+        let stats = Stats::count_lines(&kinds);
+        Self { left, right, paths: vec![None; rows], kinds, folds: vec![None; rows], full: BTreeMap::new(), stats, depth: 0, summary: Arc::default() }
+    }
+
+    /// The rows that show a difference, with the rows without a path that
+    /// belong to them (expanded subtrees, notes), and how many unchanged
+    /// entries that leaves out. A line diff is kept whole.
+    fn only_differences(&self) -> (Self, usize) {
+        if self.paths.iter().all(Option::is_none) {
+            return (self.clone(), 0);
+        }
+        let mut shown = DiffText { full: self.full.clone(), stats: self.stats, depth: self.depth, summary: self.summary.clone(), ..DiffText::default() };
+        let (mut hidden, mut keep) = (0, true);
+        for row in 0..self.paths.len() {
+            if self.paths[row].is_some() {
+                keep = self.kinds[row].is_some();
+                hidden += usize::from(!keep);
+            }
+            if keep {
+                shown.left.lines.push(self.left.lines[row].clone());
+                shown.right.lines.push(self.right.lines[row].clone());
+                shown.paths.push(self.paths[row].clone());
+                shown.kinds.push(self.kinds[row]);
+                shown.folds.push(self.folds[row]);
+            }
+        }
+        (shown, hidden)
+    }
+
+    /// The first `limit` entries, with the rows that belong to them, and how
+    /// many of the entries left out differ and how many don't; every entry
+    /// with `limit` 0. A line diff's rows aren't entries, and are all kept.
+    fn first_entries(mut self, limit: usize) -> (Self, [usize; 2]) {
+        let mut entries = 0;
+        let cut = self.paths.iter().position(|path| {
+            entries += usize::from(path.is_some());
+            entries > limit
+        });
+        let Some(cut) = cut.filter(|_| limit > 0) else {
+            return (self, [0, 0]);
+        };
+        let mut left_out = [0, 0];
+        for (path, kind) in self.paths[cut..].iter().zip(&self.kinds[cut..]) {
+            if path.is_some() {
+                left_out[usize::from(kind.is_none())] += 1;
+            }
+        }
+        self.left.lines.truncate(cut);
+        self.right.lines.truncate(cut);
+        self.paths.truncate(cut);
+        self.kinds.truncate(cut);
+        self.folds.truncate(cut);
+        (self, left_out)
+    }
+
+    /// Marks each row the three-way comparison classified with its class.
+    fn mark_merges(mut self, merges: &Merges) -> Self {
+        for (row, path) in self.paths.iter().enumerate() {
+            if let Some(merge) = path.as_deref().and_then(|path| merges.get(path)) {
+                for text in [&mut self.left, &mut self.right] {
+                    text.lines[row].spans.push(Span::styled(merge.marker(), merge.style()));
+                }
+            }
+        }
+        self
     }
 
-    (left_diff, right_diff)
+    /// Adds a dim row under each difference that has a note, and lists notes
+    /// whose difference is gone at the end.
+    fn annotate(self, annotations: &Annotations) -> Self {
+        if annotations.is_empty() {
+            return self;
+        }
+        let note_span = |text: String| Span::styled(text, Style::default().add_modifier(Modifier::DIM));
+        let mut live = Vec::new();
+        let mut annotated = DiffText { full: self.full, stats: self.stats, depth: self.depth, summary: self.summary, ..DiffText::default() };
+        let rows = self.left.lines.into_iter().zip(self.right.lines).zip(self.paths).zip(self.kinds).zip(self.folds);
+        for ((((left, right), path), kind), fold) in rows {
+            let note = match (&path, kind) {
+                (Some(path), Some(kind)) => {
+                    live.push((path.clone(), kind));
+                    annotations.get(path, kind).map(|note| format!("  ✎ {}\n", display::sanitize(note)))
+                }
+                _ => None,
+            };
+            annotated.left.lines.push(left);
+            annotated.right.lines.push(right);
+            annotated.paths.push(path);
+            annotated.kinds.push(kind);
+            annotated.folds.push(fold);
+            if let Some(note) = note {
+                annotated.push(note_span(note.clone()), note_span(note), None, None);
+            }
+        }
+        let orphaned = annotations.orphaned(&live);
+        if !orphaned.is_empty() {
+            let header = || Span::styled("orphaned notes (no longer differ)\n", Style::default().add_modifier(Modifier::BOLD));
+            annotated.push(header(), header(), None, None);
+            for orphan in orphaned {
+                let line = format!("  ✎ {} ({}): {}\n", display::sanitize(&orphan.path), orphan.kind, display::sanitize(&orphan.note));
+                // Keeps the note's path and kind so `C` can still edit or remove it.
+                let kind = ChangeKind::ALL.into_iter().find(|kind| kind.name() == orphan.kind);
+                annotated.push(note_span(line.clone()), note_span(line), Some(orphan.path.clone()), kind);
+            }
+        }
+        annotated
+    }
+}
+
+/// Compares the documents and lays out their rows; `None` if `cancel` fires.
+fn diff_json_values(left: &Value, right: &Value, view: &DiffView, cancel: &CancelToken) -> Option<DiffText> {
+    match diff::compare(left, right, view, cancel)? {
+        DiffResult::Entries(entries) => {
+            let depth = entries.iter().map(|entry| entry.segments.len()).max().unwrap_or(0);
+            let summary = Arc::new(Summary::of_entries(&entries, view.context, view.redaction));
+            let mut diff = DiffText { stats: Stats::count(&entries), depth, summary, ..DiffText::default() };
+            // The readable path of the last entry at each level, on each side,
+            // which the entries under it extend.
+            let mut shown_paths: Vec<[String; 2]> = Vec::new();
+            for entry in &entries {
+                render_entry(&mut diff, view, entry, &mut shown_paths);
+            }
+            Some(diff)
+        }
+        DiffResult::Lines(real_left, real_right) => {
+            let kind = diff::change_kind(view, "", Some(real_left), Some(real_right));
+            let (left, right) = (view.redaction.show(real_left), view.redaction.show(real_right));
+            let mut checkpoint = Checkpoint::new(cancel);
+            let (mut diff, shapes) = match view.options.structure {
+                Some(structure) => {
+                    let shapes = [structure.shape(&left), structure.shape(&right)];
+                    (diff_pretty_lines(&shapes[0], &shapes[1], &KeyOrders::default(), false, &mut checkpoint)?, Some(shapes))
+                }
+                None => (diff_pretty_lines(&left, &right, view.key_orders, view.options.decode_escapes, &mut checkpoint)?, None),
+            };
+            // The rows have no paths: the documents differ as a whole.
+            let (left, right) = shapes.as_ref().map_or((real_left, real_right), |[left, right]| (left, right));
+            diff.summary = Arc::new(Summary::of_documents(kind, left, right, view.redaction));
+            Some(diff)
+        }
+    }
+}
+
+/// Pushes the rows for one entry: a header for an expanded object or array
+/// or a summarized array, else the values on each side.
+fn render_entry(diff: &mut DiffText, view: &DiffView, entry: &DiffEntry, shown_paths: &mut Vec<[String; 2]>) {
+    let (segments, path, element, kind) = (&entry.segments, entry.path.as_str(), entry.element, entry.kind);
+    // Secrets are shown as their tags; only the kind, and comparators'
+    // notes, come of the real values.
+    let shown = [entry.left, entry.right].map(|value| value.map(|value| view.redaction.show(value)));
+    let (left, right) = (shown[0].as_deref(), shown[1].as_deref());
+    let (left_value, right_value) = (left.unwrap_or(&Value::Null), right.unwrap_or(&Value::Null));
+    let Some(level) = segments.len().checked_sub(1) else {
+        if let Shape::Summary(counts) = entry.shape {
+            render_summary(diff, "(root)", entry, counts);
+        }
+        return;
+    };
+    let indent = "  ".repeat(level);
+    let parents = if level == 0 { Default::default() } else { shown_paths[level - 1].clone() };
+    // With `--type-tags` each value is followed by its type.
+    let tag = |value: Option<&Value>| {
+        value.filter(|_| view.options.type_tags).map(|value| Span::styled(display::type_tag(value), theme::type_tag()))
+    };
+    if let Shape::SummaryElement = entry.shape {
+        let element = element.expect("a summarized array's entries are elements");
+        let key = view.array_keys.field_for(&segments[..level]);
+        let line = |side: usize, item: Option<&Value>, index: Option<usize>| {
+            let index = format!("[{}]", index.unwrap_or(element.index()));
+            let name = if view.options.full_paths { display::join_path(&parents[side], &index, true) } else { index };
+            let style = theme::side(kind, side);
+            let (value, note) = match item {
+                Some(value) => (shown_value(view, value), element.note(key)),
+                None => (display::MISSING.to_string(), String::new()),
+            };
+            let mut spans = vec![Span::styled(format!("{indent}{name}: {value}"), style)];
+            spans.extend(tag(item));
+            spans.push(Span::styled(format!("{note}\n"), style));
+            Line::from(spans)
+        };
+        diff.push(line(0, left, element.left), line(1, right, element.right), Some(path.to_string()), kind);
+        return;
+    }
+
+    let keys = match element {
+        Some(element) => [element.left.or(element.right), element.right.or(element.left)].map(|index| {
+            let index = index.expect("an element is on one side at least").to_string();
+            display::sanitize(&view.context.label(&segments[..level], &index, left, right)).into_owned()
+        }),
+        None => [segments.last(), entry.right_key.as_ref().or(segments.last())]
+            .map(|key| key.map_or_else(String::new, |key| display::sanitize(key).into_owned())),
+    };
+    let shown = [0, 1].map(|side| display::join_path(&parents[side], &keys[side], element.is_some()));
+    shown_paths.truncate(level);
+    shown_paths.push(shown.clone());
+    // With `--full-paths` each row is named by its whole path.
+    let names = if view.options.full_paths { &shown } else { &keys };
+    // How the element was paired, on the side it is on, or where a member
+    // written elsewhere among the keys moved.
+    let element_note = |side: usize| match (element, entry.key_moved) {
+        (Some(element), _) if [left, right][side].is_some() => element.note(view.array_keys.field_for(&segments[..level])),
+        (None, Some([from, to])) => format!(" ⇄ moved from position {} to {}", from + 1, to + 1),
+        _ => String::new(),
+    };
+    match entry.shape {
+        Shape::Summary(counts) => {
+            render_summary(diff, &format!("{indent}{}", names[1]), entry, counts);
+            return;
+        }
+        Shape::Expanded => {
+            // The header is structure, left uncolored: only the rows under it
+            // that differ are.
+            let decoded = decode::note(view.decoded, &segments.join("."));
+            let failed = view.comparators.failure(path).map_or_else(String::new, |reason| format!(" ⚠ compared as JSON: {reason}"));
+            let header = |side: usize| {
+                let mut spans = vec![Span::raw(format!("{indent}▾ {}", names[side]))];
+                spans.extend(tag([left, right][side]));
+                spans.push(Span::raw(format!("{}{failed}{decoded}\n", element_note(side))));
+                Line::from(spans)
+            };
+            diff.push(header(0), header(1), Some(path.to_string()), kind);
+            diff.mark_container(true);
+            return;
+        }
+        Shape::Value | Shape::SummaryElement => {}
+    }
+    let container =
+        matches!((left_value, right_value), (Value::Object(_), Value::Object(_)) | (Value::Array(_), Value::Array(_)));
+
+    let (left_time, right_time) = match view.times {
+        Some(times) => times.annotate(segments, left_value, right_value, view.now),
+        None => Default::default(),
+    };
+    let dotted = segments.join(".");
+    // A whole subtree on one side is one row summarizing it, its body shown
+    // under the row when expanded.
+    let subtree = match (left, right) {
+        (Some(value), None) | (None, Some(value)) => display::subtree_summary(value, view.options.subtree_threshold, kind),
+        _ => None,
+    };
+    let subtree_expanded = subtree.is_some() && view.folds.get(path).copied().unwrap_or(false);
+    let marker = match (container || subtree.is_some(), subtree_expanded) {
+        (_, true) => "▾ ",
+        (true, false) => "▸ ",
+        (false, false) => "",
+    };
+    let render = |value: Option<&Value>| match (value, &subtree) {
+        (None, _) => display::MISSING.to_string(),
+        (Some(_), Some(summary)) => summary.clone(),
+        (Some(value), None) => shown_value(view, value),
+    };
+    let rendered = [render(left), render(right)];
+    let truncated = rendered.each_ref().map(|text| display::truncate_middle(text, view.truncate));
+    // A change inside the part cut out would otherwise look like no change.
+    let hidden_change = match (&truncated[0], &truncated[1]) {
+        (Some(l), Some(r)) if kind == Some(ChangeKind::Changed) => {
+            let (start, left_end, right_end) = display::changed_region(&rendered[0], &rendered[1]);
+            l.hidden.start <= start && left_end <= l.hidden.end && r.hidden.start <= start && right_end <= r.hidden.end
+        }
+        _ => false,
+    };
+    let values = [0, 1].map(|side| truncated[side].as_ref().map_or(&rendered[side], |truncated| &truncated.text));
+    // A modified string has the words that differ highlighted within it.
+    let words = match (left, right) {
+        (Some(Value::String(_)), Some(Value::String(_))) if kind == Some(ChangeKind::Changed) => {
+            linediff::word_diff(values[0], values[1], &mut Checkpoint::new(&CancelToken::default()))
+        }
+        _ => None,
+    };
+    let line = |side: usize, notes: &Notes, time: &str| {
+        let note = display::sanitize(&normalize::describe_under(notes, &dotted)).into_owned();
+        let hidden = if hidden_change { " ◆ differs in the cut-out part" } else { "" };
+        let repaired = if repair::touches(view.repaired, &dotted) { " ⚠ repaired" } else { "" };
+        let failed = view.comparators.failure(path).map_or_else(String::new, |reason| format!(" ⚠ compared as JSON: {reason}"));
+        let decoded = decode::note(view.decoded, &dotted);
+        // A collapsed object or array says whether what it hides differs.
+        let collapsed = match entry.hidden_changes {
+            Some(0) => " (equal)".to_string(),
+            Some(1) => " (differs, 1 nested change)".to_string(),
+            Some(changes) => format!(" (differs, {changes} nested changes)"),
+            None => String::new(),
+        };
+        let style = theme::side(kind, side);
+        let mut spans = vec![Span::styled(format!("{indent}{marker}{}: ", names[side]), style)];
+        match &words {
+            Some(words) => spans.extend(words[side].iter().map(|(range, changed)| {
+                Span::styled(values[side][range.clone()].to_string(), if *changed { theme::changed_words(side) } else { style })
+            })),
+            None => spans.push(Span::styled(values[side].clone(), style)),
+        }
+        spans.extend(tag([left, right][side]));
+        spans.push(Span::styled(format!("{collapsed}{hidden}{repaired}{failed}{decoded}{}{note}{}", element_note(side), display::sanitize(time)), style));
+        spans
+    };
+    // Values a comparator found equal say which, if it tells.
+    let equal_note = match (entry.left, entry.right) {
+        (Some(l), Some(r)) if kind.is_none() && l != r => view.comparators.note(path, l, r),
+        _ => None,
+    };
+    let delta = match (display::numeric_delta(left_value, right_value), equal_note) {
+        _ if kind == Some(ChangeKind::TypeChanged) => format!("  {} → {}\n", type_name(left_value), type_name(right_value)),
+        _ if kind == Some(ChangeKind::Renamed) => format!("  {} → {} (value unchanged)\n", keys[0], keys[1]),
+        _ if kind == Some(ChangeKind::Moved) => "\n".to_string(),
+        (Some((absolute, _)), _) if kind.is_some() && view.options.percent_change => {
+            match display::percent_change(left_value, right_value) {
+                Some(percent) => format!("  {absolute} ({})\n", display::format_percent(percent)),
+                None => format!("  {absolute}\n"),
+            }
+        }
+        (Some((absolute, relative)), _) if kind.is_some() => format!("  {absolute} ({relative})\n"),
+        (_, Some(note)) => format!("  {note}\n"),
+        _ => "\n".to_string(),
+    };
+    let mut left_spans = line(0, &view.notes[0], &left_time);
+    left_spans.push(Span::styled("\n", theme::side(kind, 0)));
+    let mut right_spans = line(1, &view.notes[1], &right_time);
+    right_spans.push(Span::styled(delta, theme::side(kind, 1).add_modifier(Modifier::DIM)));
+    diff.push(
+        Line::from(left_spans),
+        Line::from(right_spans),
+        Some(path.to_string()),
+        kind,
+    );
+    if truncated.iter().any(Option::is_some) {
+        let full = |value: Option<&Value>| match value {
+            None => display::MISSING.to_string(),
+            Some(Value::String(s)) if view.options.decode_escapes => display::sanitize_lines(&display::decode_literal_escapes(s)),
+            Some(Value::String(s)) => display::sanitize_lines(s),
+            Some(value) => view.key_orders.render_pretty(value, view.options.decode_escapes),
+        };
+        diff.full.insert(path.to_string(), [full(left), full(right)]);
+    }
+    if container || subtree.is_some() {
+        diff.mark_container(subtree_expanded);
+    }
+    if subtree_expanded {
+        let (value, style) = match left {
+            Some(value) => (value, theme::side(kind, 0)),
+            None => (right_value, theme::side(kind, 1)),
+        };
+        let body = match view.options.structure {
+            Some(structure) => display::render_pretty(&structure.shape(value), view.options.decode_escapes),
+            None => view.key_orders.render_pretty(value, view.options.decode_escapes),
+        };
+        for body_line in body.lines() {
+            let body = Span::styled(format!("{indent}    {body_line}\n"), style);
+            if left.is_some() {
+                diff.push(body, "\n", None, kind);
+            } else {
+                diff.push("\n", body, None, kind);
+            }
+        }
+    }
+}
+
+/// `value` as a row shows it; under `--structure` its shape, a scalar as the
+/// name of its type.
+fn shown_value(view: &DiffView, value: &Value) -> String {
+    match view.options.structure {
+        Some(_) if !value.is_object() && !value.is_array() => type_name(value).to_string(),
+        Some(structure) => display::render_value(&structure.shape(value), view.options.decode_escapes),
+        None => view.key_orders.render_value(value, view.options.decode_escapes),
+    }
+}
+
+/// Falls back to a line diff of the pretty-printed documents when they can't be
+/// compared key by key.
+fn diff_pretty_lines(
+    left: &Value,
+    right: &Value,
+    key_orders: &KeyOrders,
+    decode_escapes: bool,
+    checkpoint: &mut Checkpoint,
+) -> Option<DiffText> {
+    let left_text = key_orders.render_pretty(left, decode_escapes);
+    let right_text = key_orders.render_pretty(right, decode_escapes);
+    linediff::diff_texts(&left_text, &right_text, checkpoint).map(DiffText::from_lines)
+}
+
+/// Renders a summarized array's header, `label: N elements — x changed, y
+/// added, z removed, w equal`; its differing elements follow as entries.
+fn render_summary(diff: &mut DiffText, label: &str, entry: &DiffEntry, counts: Counts) {
+    let mut parts = vec![format!("{} changed", display::group_thousands(counts.changed))];
+    for (count, name) in [(counts.added, "added"), (counts.removed, "removed"), (counts.moved, "moved")] {
+        if count > 0 {
+            parts.push(format!("{} {name}", display::group_thousands(count)));
+        }
+    }
+    parts.push(format!("{} equal", display::group_thousands(counts.equal)));
+    let header = |items: Option<&Value>| {
+        let len = items.and_then(Value::as_array).map_or(0, Vec::len);
+        Span::styled(
+            format!("{label}: {} elements — {} [A] show all\n", display::group_thousands(len), parts.join(", ")),
+            Style::default().add_modifier(Modifier::BOLD),
+        )
+    };
+    let path = Some(entry.path.clone()).filter(|path| !path.is_empty());
+    diff.push(header(entry.left), header(entry.right), path, entry.kind);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(contents: &str, max_depth: usize) -> Result<Value> {
+        parse_document(contents, Path::new("doc.json"), Format::Json, max_depth, DuplicateKeys::Last)
+    }
+
+    #[test]
+    fn documents_nest_up_to_the_maximum_depth() {
+        let nested = |depth: usize| format!("{}\n{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse(&nested(4), 4).is_ok());
+        let err = parse(&nested(5), 4).unwrap_err().to_string();
+        assert_eq!(err, "maximum nesting depth 4 exceeded at line 1, column 5");
+
+        // Brackets in strings don't nest, and lines count from 1.
+        assert!(parse(r#"{"a": "[[[[[["}"#, 1).is_ok());
+        let err = parse("{\n  \"a\": [\n    [1]\n  ]\n}", 2).unwrap_err().to_string();
+        assert_eq!(err, "maximum nesting depth 2 exceeded at line 3, column 5");
+
+        // Past serde_json's own limit of 128 levels.
+        assert!(parse(&nested(depth::DEFAULT_MAX_DEPTH), depth::DEFAULT_MAX_DEPTH).is_ok());
+    }
+
+    #[test]
+    fn blank_inputs_are_empty_documents() {
+        for blank in ["", "  \n\t", "\u{feff}", "\u{feff}\r\n"] {
+            assert!(is_blank(blank), "{blank:?}");
+            assert_eq!(parse(blank, 4).unwrap(), Value::Null);
+        }
+        assert!(!is_blank(" null "));
+        assert!(!is_blank("{}"));
+    }
+
+    #[test]
+    fn an_empty_side_takes_the_other_side_s_shape() {
+        let fill = |left: Value, right: Value, empty: [bool; 2]| {
+            let (mut left, mut right) = (left, right);
+            fill_empty(&mut left, &mut right, empty);
+            (left, right)
+        };
+        assert_eq!(fill(Value::Null, json!({"a": 1}), [true, false]), (json!({}), json!({"a": 1})));
+        assert_eq!(fill(json!([1]), Value::Null, [false, true]), (json!([1]), json!([])));
+        assert_eq!(fill(Value::Null, json!("text"), [true, false]), (Value::Null, json!("text")));
+        // Both empty, or neither, are left as they are.
+        assert_eq!(fill(Value::Null, Value::Null, [true, true]), (Value::Null, Value::Null));
+        assert_eq!(fill(json!(null), json!({}), [false, false]), (json!(null), json!({})));
+    }
+
+    #[test]
+    fn secrets_compare_as_their_real_values() {
+        let cli = Cli::parse(&["--ignore-case".to_string()]).unwrap();
+        let sources = ["a.json", "b.json"].map(str::to_string);
+        let contents = [
+            r#"{"password": "Hunter2", "pin": 1, "token": "a", "user": "x"}"#,
+            r#"{"password": "hunter2", "pin": 1.0, "token": "b", "user": "y"}"#,
+        ]
+        .map(str::to_string);
+        let redactor = Redactor::new(true, &["pin".to_string()], &[]).unwrap();
+        let compare = || {
+            compare_inputs(&cli, &sources, &contents, &redactor, &IgnoreRules::default(), &Normalizers::default(), &CancelToken::default())
+                .unwrap()
+                .expect("not cancelled")
+        };
+        let comparison = compare();
+        // Equal but for case, or for how the number is written, as with
+        // redaction off.
+        let paths: Vec<&str> = comparison.rows.summary.changes.iter().map(|change| change.pointer.as_str()).collect();
+        assert_eq!(paths, ["/token", "/user"]);
+        assert_eq!(comparison.redactions, 6);
+        // A secret that differs is reported by its tags.
+        let token = &comparison.rows.summary.changes[0];
+        assert!(token.values.iter().flatten().all(redact::holds_placeholder));
+    }
+
+    #[test]
+    fn lenient_inputs_are_repaired_and_their_repairs_kept() {
+        let sources = ["a.json", "b.json"].map(str::to_string);
+        let contents = [r#"{"s": "\ud83d x"}"#, r#"{"s": "� x"}"#].map(str::to_string);
+        let compare = |cli: &Cli| {
+            compare_inputs(cli, &sources, &contents, &Redactor::new(false, &[], &[]).unwrap(), &IgnoreRules::default(), &Normalizers::default(), &CancelToken::default())
+        };
+        assert!(compare(&Cli::parse(&[]).unwrap()).is_err());
+        let comparison = compare(&Cli::parse(&["--lenient".to_string()]).unwrap()).unwrap().expect("not cancelled");
+        assert!(comparison.identical);
+        assert_eq!(comparison.repairs[0].iter().map(|repair| repair.path.as_str()).collect::<Vec<_>>(), ["s"]);
+        assert!(comparison.repairs[1].is_empty());
+    }
 }
@@ -0,0 +1,175 @@
+//! Normalizers rewrite values before comparison so that differently encoded
+//! but equal data compares equal. Every rewrite is recorded with the original
+//! text so the diff can show both.
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Number, Value};
+use std::collections::BTreeMap;
+
+use crate::ignore;
+
+/// Original text of normalized values, keyed by dotted path.
+pub type Notes = BTreeMap<String, String>;
+
+pub trait Normalizer: Send + Sync {
+    /// The replacement for the value at `path`, or `None` to leave it alone.
+    fn normalize(&self, path: &[String], value: &Value) -> Option<Value>;
+}
+
+#[derive(Default)]
+pub struct Normalizers {
+    list: Vec<Box<dyn Normalizer>>,
+}
+
+impl Normalizers {
+    /// The normalizers `--locale-number` asks for.
+    pub fn locale_numbers(specs: &[String]) -> Result<Self> {
+        let mut normalizers = Self::default();
+        for spec in specs {
+            normalizers.push(LocaleNumber::parse(spec)?);
+        }
+        Ok(normalizers)
+    }
+
+    pub fn push(&mut self, normalizer: impl Normalizer + 'static) {
+        self.list.push(Box::new(normalizer));
+    }
+
+    /// Normalizes `value` in place and returns the originals of what changed.
+    pub fn apply(&self, value: &mut Value) -> Notes {
+        let mut notes = Notes::new();
+        if !self.list.is_empty() {
+            self.apply_in(value, &mut Vec::new(), &mut notes);
+        }
+        notes
+    }
+
+    fn apply_in(&self, value: &mut Value, path: &mut Vec<String>, notes: &mut Notes) {
+        if let Some(normalized) = self.list.iter().find_map(|normalizer| normalizer.normalize(path, value)) {
+            let original = match &*value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            notes.insert(path.join("."), original);
+            *value = normalized;
+            return;
+        }
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map.iter_mut() {
+                    path.push(key.clone());
+                    self.apply_in(child, path, notes);
+                    path.pop();
+                }
+            }
+            Value::Array(items) => {
+                for (index, child) in items.iter_mut().enumerate() {
+                    path.push(index.to_string());
+                    self.apply_in(child, path, notes);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parses string values at matching paths as numbers written with a
+/// locale's separators, e.g. `"1.234,56"` under `de`, or with separators
+/// given outright. Strings that don't parse under them are left as strings.
+pub struct LocaleNumber {
+    pattern: Vec<String>,
+    decimal: char,
+    grouping: Vec<char>,
+}
+
+impl LocaleNumber {
+    /// Parses a `--locale-number <path>=<locale>` argument, where the locale
+    /// may instead be the decimal separator followed by the grouping one, if
+    /// any: `price=,.` reads `"1.234,5"` and `price=,` only `"1234,5"`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (pattern, locale) = spec
+            .rsplit_once('=')
+            .ok_or_else(|| anyhow!("--locale-number: expected <path>=<locale>, got '{spec}'"))?;
+        let (decimal, grouping) = match locale.to_lowercase().replace('_', "-").as_str() {
+            "en" | "en-us" | "en-gb" | "ja" | "zh" | "ko" => ('.', vec![',']),
+            "de" | "de-de" | "es" | "it" | "nl" | "pt" | "pt-br" | "id" | "tr" | "da" => (',', vec!['.']),
+            "fr" | "ru" | "pl" | "cs" | "sv" | "fi" | "nb" | "uk" => (',', vec![' ', '\u{a0}', '\u{202f}']),
+            "de-ch" | "ch" => ('.', vec!['\'', '’']),
+            _ => separators(locale)?,
+        };
+        Ok(Self { pattern: pattern.split('.').map(str::to_string).collect(), decimal, grouping })
+    }
+
+    /// Strict parse: grouping separators only between groups of three integer
+    /// digits, at most one decimal separator, nothing else.
+    fn parse_number(&self, text: &str) -> Option<Number> {
+        let text = text.trim();
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (integer, fraction) = match unsigned.split_once(self.decimal) {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (unsigned, None),
+        };
+        let groups: Vec<&str> = integer.split(|c| self.grouping.contains(&c)).collect();
+        let well_grouped = groups.iter().all(|group| !group.is_empty() && group.bytes().all(|b| b.is_ascii_digit()))
+            && (groups.len() == 1 || (groups[0].len() <= 3 && groups[1..].iter().all(|group| group.len() == 3)));
+        if !well_grouped || fraction.is_some_and(|f| f.is_empty() || !f.bytes().all(|b| b.is_ascii_digit())) {
+            return None;
+        }
+        let digits = groups.concat();
+        let sign = if negative { "-" } else { "" };
+        match fraction.filter(|f| f.bytes().any(|b| b != b'0')) {
+            Some(fraction) => serde_json::from_str(&format!("{sign}{digits}.{fraction}")).ok(),
+            None if negative => format!("-{digits}").parse::<i64>().ok().map(Number::from),
+            None => digits.parse::<u64>().ok().map(Number::from),
+        }
+    }
+}
+
+/// The decimal and grouping separators of a `--locale-number` argument
+/// that gives them rather than a locale.
+fn separators(locale: &str) -> Result<(char, Vec<char>)> {
+    let chars: Vec<char> = locale.chars().collect();
+    let usable = |c: &char| !c.is_alphanumeric() && !matches!(c, '-' | '+');
+    match chars.as_slice() {
+        [decimal] if usable(decimal) => Ok((*decimal, Vec::new())),
+        [decimal, grouping] if usable(decimal) && usable(grouping) && decimal != grouping => Ok((*decimal, vec![*grouping])),
+        [decimal, grouping] if decimal == grouping => {
+            bail!("--locale-number: '{locale}' uses '{decimal}' for both the decimal and the grouping separator")
+        }
+        _ => bail!("--locale-number: unknown locale '{locale}' (expected a locale such as de, or separators such as ,.)"),
+    }
+}
+
+impl Normalizer for LocaleNumber {
+    fn normalize(&self, path: &[String], value: &Value) -> Option<Value> {
+        match value {
+            Value::String(s) if ignore::path_matches(&self.pattern, path) => self.parse_number(s).map(Value::Number),
+            _ => None,
+        }
+    }
+}
+
+/// `(path was "original")` notes for normalized values at or under `key`.
+pub fn describe_under(notes: &Notes, key: &str) -> String {
+    let nested = format!("{key}.");
+    let found: Vec<String> = notes
+        .iter()
+        .filter(|(path, _)| *path == key || path.starts_with(&nested))
+        .map(|(path, original)| {
+            if path == key {
+                format!("was {original:?}")
+            } else {
+                format!("{path} was {original:?}")
+            }
+        })
+        .collect();
+    if found.is_empty() {
+        String::new()
+    } else {
+        format!("  ({})", found.join(", "))
+    }
+}
@@ -0,0 +1,428 @@
+//! The `O` panel: the options that shape the diff, changed while the session
+//! runs. Each change is applied at once by diffing again, keeping the view
+//! where it was; `R` goes back to the options diffrs started with, and the
+//! panel shows the command-line flags that reproduce the current ones.
+
+use crossterm::event::KeyCode;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Clear, Paragraph, Wrap},
+};
+
+use crate::arraykey::ArrayKeys;
+use crate::cli::OnChange;
+use crate::compare::{MatchRule, Structure, UnicodeForm};
+use crate::diff::DiffOptions;
+use crate::duplicates::DuplicateKeys;
+use crate::external::ExternalRule;
+use crate::select::Selection;
+use crate::{centered_rect, compare, display, ignore};
+
+#[derive(Clone, Copy)]
+enum Field {
+    ArraySummaryThreshold,
+    ExpandDepth,
+    DiffDepth,
+    Truncate,
+    SubtreeThreshold,
+    MaxEntries,
+    DecodeEscapes,
+    RelativeTimes,
+    TypeTags,
+    Markers,
+    PercentChange,
+    Lenient,
+    DuplicateKeys,
+    OnChange,
+    IgnoreFiles,
+    Ignores,
+    IgnoredKeys,
+    Masks,
+    ArrayKeys,
+    UnorderedArrays,
+    KeyOrder,
+    Tolerance,
+    ExactNumbers,
+    UnicodeForm,
+    Timestamps,
+    Structure,
+    Schema,
+    DetectRenames,
+    DecodeBase64,
+    Comparators,
+    EqualRules,
+    ExternalRules,
+    UnitPaths,
+    UrlPaths,
+    Select,
+}
+
+const FIELDS: [Field; 35] = [
+    Field::ArraySummaryThreshold,
+    Field::ExpandDepth,
+    Field::DiffDepth,
+    Field::Truncate,
+    Field::SubtreeThreshold,
+    Field::MaxEntries,
+    Field::DecodeEscapes,
+    Field::RelativeTimes,
+    Field::TypeTags,
+    Field::Markers,
+    Field::PercentChange,
+    Field::Lenient,
+    Field::DuplicateKeys,
+    Field::OnChange,
+    Field::IgnoreFiles,
+    Field::Ignores,
+    Field::IgnoredKeys,
+    Field::Masks,
+    Field::ArrayKeys,
+    Field::UnorderedArrays,
+    Field::KeyOrder,
+    Field::Tolerance,
+    Field::ExactNumbers,
+    Field::UnicodeForm,
+    Field::Timestamps,
+    Field::Structure,
+    Field::Schema,
+    Field::DetectRenames,
+    Field::DecodeBase64,
+    Field::Comparators,
+    Field::EqualRules,
+    Field::ExternalRules,
+    Field::UnitPaths,
+    Field::UrlPaths,
+    Field::Select,
+];
+
+impl Field {
+    fn label(self) -> &'static str {
+        match self {
+            Self::ArraySummaryThreshold => "Summarize arrays longer than",
+            Self::ExpandDepth => "Expand depth",
+            Self::DiffDepth => "Diff depth",
+            Self::Truncate => "Cut values wider than",
+            Self::SubtreeThreshold => "Summarize subtrees over (bytes)",
+            Self::MaxEntries => "Show at most (entries)",
+            Self::DecodeEscapes => "Decode \\uXXXX in strings",
+            Self::RelativeTimes => "Relative times",
+            Self::TypeTags => "Tag values with their type",
+            Self::Markers => "Mark rows with + - ~",
+            Self::PercentChange => "Number changes in percent",
+            Self::Lenient => "Repair invalid escapes",
+            Self::DuplicateKeys => "Duplicate keys",
+            Self::OnChange => "After an edit",
+            Self::IgnoreFiles => "Read .diffrsignore files",
+            Self::Ignores => "Ignore paths",
+            Self::IgnoredKeys => "Ignore keys named",
+            Self::Masks => "Mask paths",
+            Self::ArrayKeys => "Array strategies",
+            Self::UnorderedArrays => "Ignore array order",
+            Self::KeyOrder => "Key order matters",
+            Self::Tolerance => "Numbers equal within",
+            Self::ExactNumbers => "Tell 1 from 1.0",
+            Self::UnicodeForm => "Normalize Unicode to",
+            Self::Timestamps => "Timestamps equal within",
+            Self::Structure => "Compare structure only",
+            Self::Schema => "Compare inferred schemas",
+            Self::DetectRenames => "Pair renamed keys",
+            Self::DecodeBase64 => "Decode base64 JSON at",
+            Self::Select => "Compare only",
+            Self::Comparators => "Compare values with",
+            Self::EqualRules => "Equal if both match",
+            Self::ExternalRules => "Equal if a command says",
+            Self::UnitPaths => "Compare units at",
+            Self::UrlPaths => "Compare URLs at",
+        }
+    }
+
+    fn value(self, options: &DiffOptions) -> String {
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+        let list = |patterns: &[String]| if patterns.is_empty() { "(none)".to_string() } else { patterns.join(", ") };
+        match self {
+            Self::ArraySummaryThreshold => options.array_summary_threshold.to_string(),
+            Self::ExpandDepth => options.expand_depth.to_string(),
+            Self::DiffDepth => options.diff_depth.map_or_else(|| "off".to_string(), |depth| depth.to_string()),
+            Self::Truncate => options.truncate.map_or_else(|| "auto (three rows)".to_string(), |limit| limit.to_string()),
+            Self::SubtreeThreshold => options.subtree_threshold.to_string(),
+            Self::MaxEntries if options.max_entries == 0 => "all".to_string(),
+            Self::MaxEntries => options.max_entries.to_string(),
+            Self::DecodeEscapes => on_off(options.decode_escapes),
+            Self::RelativeTimes => on_off(options.relative_times),
+            Self::TypeTags => on_off(options.type_tags),
+            Self::Markers => on_off(options.markers),
+            Self::PercentChange => on_off(options.percent_change),
+            Self::Lenient => on_off(options.lenient),
+            Self::DuplicateKeys => options.duplicate_keys.name().to_string(),
+            Self::OnChange => match options.on_change {
+                OnChange::Refresh => "diff again".to_string(),
+                OnChange::Stale => "mark the diff stale".to_string(),
+            },
+            Self::IgnoreFiles => on_off(options.ignore_files),
+            Self::Ignores => list(&options.ignores),
+            Self::IgnoredKeys => list(&options.ignored_keys),
+            Self::Masks => list(&options.masks),
+            Self::ArrayKeys => list(&options.array_keys),
+            Self::UnorderedArrays => on_off(options.unordered_arrays),
+            Self::KeyOrder => on_off(options.key_order),
+            Self::Tolerance => options.tolerance.map_or_else(|| "off".to_string(), |epsilon| epsilon.to_string()),
+            Self::ExactNumbers => on_off(options.exact_numbers),
+            Self::UnicodeForm => options.unicode_form.map_or("off", |form| form.name()).to_string(),
+            Self::Timestamps => options.timestamps.map_or_else(|| "off".to_string(), |skew| format!("{}s", skew as f64 / 1000.0)),
+            Self::Structure => options.structure.map_or("off", |structure| structure.name()).to_string(),
+            Self::Schema => on_off(options.schema),
+            Self::DetectRenames => on_off(options.detect_renames),
+            Self::DecodeBase64 => list(&options.decode_base64),
+            Self::Comparators => list(&options.comparators),
+            // Regexes have commas of their own.
+            Self::EqualRules if options.equal_rules.is_empty() => "(none)".to_string(),
+            Self::EqualRules => options.equal_rules.join("; "),
+            // Commands have commas of their own.
+            Self::ExternalRules if options.external_rules.is_empty() => "(none)".to_string(),
+            Self::ExternalRules => options.external_rules.join("; "),
+            Self::UnitPaths => list(&options.unit_paths),
+            Self::UrlPaths => list(&options.url_paths),
+            Self::Select => options.select.clone().unwrap_or_else(|| "the whole documents".to_string()),
+        }
+    }
+
+    /// What the field is edited from: `None` for toggles.
+    fn input(self, options: &DiffOptions) -> Option<String> {
+        match self {
+            Self::Truncate => Some(options.truncate.map_or_else(|| "auto".to_string(), |limit| limit.to_string())),
+            Self::Ignores => Some(options.ignores.join(", ")),
+            Self::IgnoredKeys => Some(options.ignored_keys.join(", ")),
+            Self::Masks => Some(options.masks.join(", ")),
+            Self::DecodeBase64 => Some(options.decode_base64.join(", ")),
+            Self::ArrayKeys => Some(options.array_keys.join(", ")),
+            Self::Tolerance => Some(options.tolerance.map_or_else(String::new, |epsilon| epsilon.to_string())),
+            Self::DiffDepth | Self::DuplicateKeys | Self::UnicodeForm | Self::Timestamps | Self::Structure => Some(self.value(options)),
+            Self::Comparators => Some(options.comparators.join(", ")),
+            Self::EqualRules => Some(options.equal_rules.join("; ")),
+            Self::ExternalRules => Some(options.external_rules.join("; ")),
+            Self::UnitPaths => Some(options.unit_paths.join(", ")),
+            Self::UrlPaths => Some(options.url_paths.join(", ")),
+            Self::Select => Some(options.select.clone().unwrap_or_default()),
+            Self::ArraySummaryThreshold | Self::ExpandDepth | Self::SubtreeThreshold | Self::MaxEntries => Some(self.value(options)),
+            _ => None,
+        }
+    }
+
+    /// `options` with the field toggled.
+    fn toggle(self, options: &mut DiffOptions) {
+        match self {
+            Self::DecodeEscapes => options.decode_escapes = !options.decode_escapes,
+            Self::RelativeTimes => options.relative_times = !options.relative_times,
+            Self::TypeTags => options.type_tags = !options.type_tags,
+            Self::Markers => options.markers = !options.markers,
+            Self::PercentChange => options.percent_change = !options.percent_change,
+            Self::Lenient => options.lenient = !options.lenient,
+            Self::IgnoreFiles => options.ignore_files = !options.ignore_files,
+            Self::UnorderedArrays => options.unordered_arrays = !options.unordered_arrays,
+            Self::KeyOrder => options.key_order = !options.key_order,
+            Self::ExactNumbers => options.exact_numbers = !options.exact_numbers,
+            Self::Schema => options.schema = !options.schema,
+            Self::DetectRenames => options.detect_renames = !options.detect_renames,
+            Self::OnChange => {
+                options.on_change = match options.on_change {
+                    OnChange::Refresh => OnChange::Stale,
+                    OnChange::Stale => OnChange::Refresh,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Sets the field from typed `input`, or says what is wrong with it.
+    fn set(self, options: &mut DiffOptions, input: &str) -> Result<(), String> {
+        let input = input.trim();
+        let number = || input.parse::<usize>().map_err(|_| format!("'{input}' is not a whole number"));
+        let patterns = || -> Result<Vec<String>, String> {
+            let patterns: Vec<String> = input.split(',').map(str::trim).filter(|p| !p.is_empty()).map(str::to_string).collect();
+            match patterns.iter().find(|pattern| pattern.split('.').any(str::is_empty)) {
+                Some(pattern) => Err(format!("'{pattern}' has an empty path segment")),
+                None => Ok(patterns),
+            }
+        };
+        match self {
+            Self::ArraySummaryThreshold => options.array_summary_threshold = number()?,
+            Self::ExpandDepth => options.expand_depth = number()?.max(1),
+            Self::DiffDepth if input.is_empty() || input == "off" => options.diff_depth = None,
+            Self::DiffDepth => options.diff_depth = Some(number()?.max(1)),
+            Self::Truncate if input.is_empty() || input == "auto" => options.truncate = None,
+            Self::Truncate => options.truncate = Some(number()?),
+            Self::SubtreeThreshold => options.subtree_threshold = number()?,
+            Self::MaxEntries if input == "all" => options.max_entries = 0,
+            Self::MaxEntries => options.max_entries = number()?,
+            Self::Ignores => options.ignores = patterns()?,
+            Self::IgnoredKeys => options.ignored_keys = ignore::key_names(input),
+            Self::Masks => options.masks = patterns()?,
+            Self::DecodeBase64 => options.decode_base64 = patterns()?,
+            Self::UnitPaths => options.unit_paths = patterns()?,
+            Self::UrlPaths => options.url_paths = patterns()?,
+            Self::ArrayKeys => {
+                let specs: Vec<String> = input.split(',').map(str::trim).filter(|spec| !spec.is_empty()).map(str::to_string).collect();
+                ArrayKeys::from_specs(&specs).map_err(|err| err.to_string())?;
+                options.array_keys = specs;
+            }
+            Self::Tolerance if input.is_empty() || input == "off" => options.tolerance = None,
+            Self::Tolerance => options.tolerance = Some(compare::parse_tolerance(input).map_err(|err| err.to_string())?),
+            Self::UnicodeForm if input.is_empty() || input == "off" => options.unicode_form = None,
+            Self::UnicodeForm => options.unicode_form = Some(UnicodeForm::parse(input).map_err(|err| err.to_string())?),
+            Self::Select if input.is_empty() || input == "$" => options.select = None,
+            Self::Select => {
+                Selection::parse(input).map_err(|err| err.to_string())?;
+                options.select = Some(input.to_string());
+            }
+            Self::DuplicateKeys => options.duplicate_keys = DuplicateKeys::parse(input).map_err(|err| err.to_string())?,
+            Self::Structure if input.is_empty() || input == "off" => options.structure = None,
+            Self::Structure => options.structure = Some(Structure::parse(input).map_err(|err| err.to_string())?),
+            Self::Timestamps if input.is_empty() || input == "off" => options.timestamps = None,
+            Self::Timestamps => options.timestamps = Some(compare::parse_skew(input).map_err(|err| err.to_string())?),
+            Self::Comparators => {
+                let names: Vec<String> = input.split(',').map(str::trim).filter(|name| !name.is_empty()).map(str::to_string).collect();
+                for name in &names {
+                    compare::check(name).map_err(|err| err.to_string())?;
+                }
+                options.comparators = names;
+            }
+            Self::EqualRules => {
+                let rules: Vec<String> = input.split("; ").map(str::trim).filter(|rule| !rule.is_empty()).map(str::to_string).collect();
+                for rule in &rules {
+                    MatchRule::parse(rule).map_err(|err| format!("{err:#}"))?;
+                }
+                options.equal_rules = rules;
+            }
+            Self::ExternalRules => {
+                let rules: Vec<String> = input.split("; ").map(str::trim).filter(|rule| !rule.is_empty()).map(str::to_string).collect();
+                for rule in &rules {
+                    ExternalRule::parse(rule).map_err(|err| format!("{err:#}"))?;
+                }
+                options.external_rules = rules;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+pub enum Action {
+    None,
+    Close,
+    /// Apply these options; report a failure with `show_error`.
+    Apply(Box<DiffOptions>),
+    /// Write `command_line` to a file.
+    Export,
+}
+
+pub struct OptionsPanel {
+    startup: DiffOptions,
+    selected: usize,
+    /// The text being typed into the selected field.
+    input: Option<String>,
+    error: Option<String>,
+}
+
+impl OptionsPanel {
+    pub fn new(startup: DiffOptions) -> Self {
+        Self { startup, selected: 0, input: None, error: None }
+    }
+
+    /// Shows why the last change couldn't be applied, e.g. an unreadable
+    /// `.diffrsignore`.
+    pub fn show_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode, current: &DiffOptions) -> Action {
+        let field = FIELDS[self.selected];
+        if let Some(input) = &mut self.input {
+            match code {
+                KeyCode::Enter => {
+                    let mut options = current.clone();
+                    return match field.set(&mut options, input) {
+                        Ok(()) => {
+                            self.input = None;
+                            self.error = None;
+                            Action::Apply(Box::new(options))
+                        }
+                        Err(error) => {
+                            self.error = Some(error);
+                            Action::None
+                        }
+                    };
+                }
+                KeyCode::Esc => {
+                    self.input = None;
+                    self.error = None;
+                }
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return Action::None;
+        }
+        self.error = None;
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(FIELDS.len() - 1),
+            KeyCode::Enter | KeyCode::Char(' ') => match field.input(current) {
+                Some(input) => self.input = Some(input),
+                None => {
+                    let mut options = current.clone();
+                    field.toggle(&mut options);
+                    return Action::Apply(Box::new(options));
+                }
+            },
+            KeyCode::Char('R') => return Action::Apply(Box::new(self.startup.clone())),
+            KeyCode::Char('x') => return Action::Export,
+            KeyCode::Esc | KeyCode::Char('O' | 'q') => return Action::Close,
+            _ => {}
+        }
+        Action::None
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, current: &DiffOptions) {
+        let popup = centered_rect(area, 80, 80);
+        f.render_widget(Clear, popup);
+        let width = FIELDS.iter().map(|field| field.label().len()).max().unwrap_or(0);
+        let mut lines = Vec::new();
+        for (index, field) in FIELDS.iter().enumerate() {
+            let selected = index == self.selected;
+            let value = match (&self.input, selected) {
+                (Some(input), true) => format!("{input}▏"),
+                _ => display::sanitize(&field.value(current)).into_owned(),
+            };
+            let changed = field.value(current) != field.value(&self.startup);
+            let mut line = Line::from(vec![
+                format!("{} {:<width$}  ", if selected { "›" } else { " " }, field.label()).into(),
+                if changed { value.yellow() } else { value.into() },
+            ]);
+            if selected {
+                line = line.add_modifier(Modifier::REVERSED);
+            }
+            lines.push(line);
+            if selected {
+                if let Some(error) = &self.error {
+                    lines.push(Line::from(format!("    ⚠ {error}").red()));
+                }
+            }
+        }
+        lines.push(Line::default());
+        let hint = if self.input.is_some() {
+            "[enter] apply - [esc] cancel; lists are comma-separated"
+        } else {
+            "[enter/space] edit or toggle - [R] reset to startup options - [x] export flags - [esc] close"
+        };
+        lines.push(Line::from(hint.dim()));
+        lines.push(Line::default());
+        lines.push(Line::from("Equivalent command line:".bold()));
+        lines.push(Line::from(display::sanitize(&current.command_line()).into_owned()));
+        f.render_widget(
+            Paragraph::new(lines).wrap(Wrap { trim: false }).block(Block::bordered().title("Diff options (changes apply at once)")),
+            popup,
+        );
+    }
+}
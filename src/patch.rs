@@ -0,0 +1,309 @@
+//! JSON Patch (RFC 6902): the `add`, `remove` and `replace` operations that
+//! turn the left document into the right one, applied in order. Exported with
+//! `x` in the diff view, and printed by `diffrs patch <left> <right>`.
+//!
+//! `diffrs patch --merge` prints a JSON Merge Patch (RFC 7386) instead: the
+//! right document's changed and added values, `null` for removed keys, and
+//! nothing for what is unchanged. A merge patch can't set a key to `null`,
+//! since that means removing it; those keys are listed as warnings.
+//!
+//! `--apply-patch <document> <patch>` goes the other way: it applies a JSON
+//! Patch to a document and diffs the result against it, to review the patch.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+use std::mem::discriminant;
+use std::path::{Path, PathBuf};
+
+use crate::cancel::{CancelToken, Checkpoint};
+use crate::cli::Format;
+use crate::coverage::type_name;
+use crate::duplicates::DuplicateKeys;
+use crate::redact::{self, RedactArgs};
+use crate::report::pointer_token;
+use crate::{atomic, depth, linediff, parse_document};
+
+const USAGE: &str = "usage: diffrs patch <left> <right> [--merge] [--out <file>] [--no-redact] [--redact-path P] [--redact-value R]";
+
+/// The operations that turn `left` into `right`; none if they are equal.
+pub fn diff(left: &Value, right: &Value) -> Vec<Value> {
+    let cancel = CancelToken::default();
+    let mut ops = Vec::new();
+    push_ops(left, right, "", &mut ops, &mut Checkpoint::new(&cancel));
+    ops
+}
+
+/// The merge patch that turns `left` into `right`, and the pointers of the
+/// keys it can't set to `null`. Equal objects give `{}`; other equal values
+/// can only be patched by themselves, as `{}` would turn them into an object.
+pub fn merge(left: &Value, right: &Value) -> (Value, Vec<String>) {
+    let mut nulls = Vec::new();
+    let patch = merge_value(left, right, "", &mut nulls).unwrap_or_else(|| if right.is_object() { json!({}) } else { right.clone() });
+    (patch, nulls)
+}
+
+/// `document` with `patch` applied: every operation, in order, or an error
+/// naming the first that failed and why.
+pub fn apply(document: &Value, patch: &Value) -> Result<Value> {
+    let ops = patch.as_array().ok_or_else(|| anyhow!("a JSON Patch is an array of operations"))?;
+    let mut document = document.clone();
+    for (index, op) in ops.iter().enumerate() {
+        apply_op(&mut document, op).map_err(|err| {
+            let name = op.get("op").and_then(Value::as_str).unwrap_or("?");
+            let path = op.get("path").and_then(Value::as_str).unwrap_or("?");
+            anyhow!("operation {} of {} ({name} \"{path}\") failed: {err}", index + 1, ops.len())
+        })?;
+    }
+    Ok(document)
+}
+
+/// The patch as the file `x` writes and `diffrs patch` prints.
+pub fn to_string(ops: Vec<Value>) -> String {
+    format!("{:#}\n", Value::Array(ops))
+}
+
+/// Returns the exit code.
+pub fn run(args: &[String]) -> Result<i32> {
+    let (mut out, mut merge_patch, mut paths) = (None, false, Vec::new());
+    let mut redact = RedactArgs::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if redact.parse_arg(arg, &mut iter)? {
+            continue;
+        }
+        match arg.as_str() {
+            "--merge" => merge_patch = true,
+            "--out" => out = Some(PathBuf::from(iter.next().ok_or_else(|| anyhow!("{arg} needs a file"))?)),
+            flag if flag.starts_with("--") => bail!("unknown patch option '{flag}'"),
+            path => paths.push(PathBuf::from(path)),
+        }
+    }
+    let [left_path, right_path] = paths.as_slice() else {
+        bail!(USAGE);
+    };
+    let parse = |path: &Path| -> Result<Value> {
+        let label = || path.display().to_string();
+        let contents = std::fs::read_to_string(path).with_context(label)?;
+        parse_document(&contents, path, Format::Auto, depth::DEFAULT_MAX_DEPTH, DuplicateKeys::Last).with_context(label)
+    };
+    // Secrets go into the patch as their placeholders, as in the TUI's export.
+    let redactor = redact.redactor()?;
+    let ((left, _), (right, _)) = (redactor.redact(&parse(left_path)?), redactor.redact(&parse(right_path)?));
+    let patch = if merge_patch {
+        let (patch, nulls) = merge(&left, &right);
+        for pointer in nulls {
+            eprintln!("diffrs: warning: the merge patch removes {pointer} instead of setting it to null");
+        }
+        if redact::holds_placeholder(&patch) {
+            eprintln!("diffrs: warning: the merge patch holds redacted placeholders, which applying it would write in place of the secrets (--no-redact to keep them)");
+        }
+        format!("{patch:#}\n")
+    } else {
+        let ops = diff(&left, &right);
+        match ops.iter().filter(|op| op.get("value").is_some_and(redact::holds_placeholder)).count() {
+            0 => {}
+            n => eprintln!("diffrs: warning: {n} operation(s) hold redacted placeholders, which applying the patch would write in place of the secrets (--no-redact to keep them)"),
+        }
+        to_string(ops)
+    };
+    match out {
+        Some(out) => atomic::write(&out, patch)?,
+        None => print!("{patch}"),
+    }
+    Ok(0)
+}
+
+fn push_ops(left: &Value, right: &Value, pointer: &str, ops: &mut Vec<Value>, checkpoint: &mut Checkpoint) {
+    let child = |token: &str| format!("{pointer}/{}", pointer_token(token));
+    match (left, right) {
+        (Value::Object(left_map), Value::Object(right_map)) => {
+            for (key, left_value) in left_map {
+                match right_map.get(key) {
+                    Some(right_value) => push_ops(left_value, right_value, &child(key), ops, checkpoint),
+                    None => ops.push(json!({ "op": "remove", "path": child(key) })),
+                }
+            }
+            for (key, right_value) in right_map.iter().filter(|(key, _)| !left_map.contains_key(*key)) {
+                ops.push(json!({ "op": "add", "path": child(key), "value": right_value }));
+            }
+        }
+        (Value::Array(left_items), Value::Array(right_items)) if left != right => {
+            // Elements of the same type are paired and patched in place.
+            let similar = |a: &Value, b: &Value| discriminant(a) == discriminant(b);
+            let pairs = linediff::align(left_items, right_items, similar, checkpoint).expect("never cancelled");
+            // Operations apply in order, so `index` is where the next element
+            // sits in the array as patched so far: every right element before
+            // it is in place, and every left element before it is gone.
+            let mut index = 0;
+            for pair in pairs {
+                let path = child(&index.to_string());
+                match pair {
+                    (Some(i), Some(j)) => {
+                        push_ops(&left_items[i], &right_items[j], &path, ops, checkpoint);
+                        index += 1;
+                    }
+                    (Some(_), None) => ops.push(json!({ "op": "remove", "path": path })),
+                    (None, Some(j)) => {
+                        ops.push(json!({ "op": "add", "path": path, "value": right_items[j] }));
+                        index += 1;
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+        _ if left == right => {}
+        _ => ops.push(json!({ "op": "replace", "path": pointer, "value": right })),
+    }
+}
+
+/// The merge patch for one value; `None` where nothing changed.
+fn merge_value(left: &Value, right: &Value, pointer: &str, nulls: &mut Vec<String>) -> Option<Value> {
+    let child = |key: &str| format!("{pointer}/{}", pointer_token(key));
+    match (left, right) {
+        _ if left == right => None,
+        (Value::Object(left_map), Value::Object(right_map)) => {
+            let mut patch = serde_json::Map::new();
+            for key in left_map.keys().filter(|key| !right_map.contains_key(*key)) {
+                patch.insert(key.clone(), Value::Null);
+            }
+            for (key, right_value) in right_map {
+                let value = match left_map.get(key) {
+                    Some(left_value) => merge_value(left_value, right_value, &child(key), nulls),
+                    None => {
+                        null_members(right_value, &child(key), nulls);
+                        Some(right_value.clone())
+                    }
+                };
+                if let Some(value) = value {
+                    patch.insert(key.clone(), value);
+                }
+            }
+            Some(Value::Object(patch))
+        }
+        _ => {
+            null_members(right, pointer, nulls);
+            Some(right.clone())
+        }
+    }
+}
+
+/// Collects the `null`s that `value`, patched in whole at `pointer`, would
+/// remove rather than set: itself, or members of objects within it.
+fn null_members(value: &Value, pointer: &str, nulls: &mut Vec<String>) {
+    match value {
+        Value::Null if !pointer.is_empty() => nulls.push(pointer.to_string()),
+        Value::Object(map) => {
+            for (key, member) in map {
+                null_members(member, &format!("{pointer}/{}", pointer_token(key)), nulls);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn apply_op(document: &mut Value, op: &Value) -> Result<()> {
+    let member = |name: &str| op.get(name).ok_or_else(|| anyhow!("it has no \"{name}\""));
+    let pointer = |name: &str| -> Result<Vec<String>> {
+        tokens(member(name)?.as_str().ok_or_else(|| anyhow!("its \"{name}\" is not a string"))?)
+    };
+    let path = pointer("path")?;
+    match member("op")?.as_str() {
+        Some("add") => add(document, &path, member("value")?.clone()),
+        Some("remove") => remove(document, &path).map(drop),
+        Some("replace") => {
+            let value = member("value")?.clone();
+            *lookup(document, &path)? = value;
+            Ok(())
+        }
+        Some("move") => {
+            let from = pointer("from")?;
+            if path.len() > from.len() && path.starts_with(&from) {
+                bail!("a value can't be moved into itself");
+            }
+            let value = remove(document, &from)?;
+            add(document, &path, value)
+        }
+        Some("copy") => {
+            let value = lookup(document, &pointer("from")?)?.clone();
+            add(document, &path, value)
+        }
+        Some("test") => {
+            let (expected, actual) = (member("value")?, &*lookup(document, &path)?);
+            if actual != expected {
+                bail!("the value is {actual}, not {expected}");
+            }
+            Ok(())
+        }
+        Some(other) => bail!("'{other}' is not an operation"),
+        None => bail!("its \"op\" is not a string"),
+    }
+}
+
+/// A JSON Pointer's unescaped reference tokens.
+fn tokens(pointer: &str) -> Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let rest = pointer.strip_prefix('/').ok_or_else(|| anyhow!("\"{pointer}\" doesn't start with /"))?;
+    rest.split('/')
+        .map(|token| {
+            if token.replace("~0", "").replace("~1", "").contains('~') {
+                bail!("\"{token}\" has a ~ that isn't ~0 or ~1");
+            }
+            Ok(token.replace("~1", "/").replace("~0", "~"))
+        })
+        .collect()
+}
+
+/// An array index token: digits without leading zeros, below `len` (or equal
+/// to it where an element can be appended).
+fn index(token: &str, len: usize, append: bool) -> Result<usize> {
+    let valid = !token.is_empty() && token.bytes().all(|b| b.is_ascii_digit()) && (token == "0" || !token.starts_with('0'));
+    let index = token.parse::<usize>().ok().filter(|_| valid).ok_or_else(|| anyhow!("\"{token}\" is not an array index"))?;
+    if index > len || (index == len && !append) {
+        bail!("index {index} is out of bounds: the array has {len} element(s)");
+    }
+    Ok(index)
+}
+
+fn lookup<'v>(document: &'v mut Value, path: &[String]) -> Result<&'v mut Value> {
+    path.iter().try_fold(document, |value, token| match value {
+        Value::Object(map) => map.get_mut(token).ok_or_else(|| anyhow!("there is no key \"{token}\"")),
+        Value::Array(items) => {
+            let index = index(token, items.len(), false)?;
+            Ok(&mut items[index])
+        }
+        _ => bail!("\"{token}\" is inside a {}, not an object or array", type_name(value)),
+    })
+}
+
+fn add(document: &mut Value, path: &[String], value: Value) -> Result<()> {
+    let Some((last, parent)) = path.split_last() else {
+        *document = value;
+        return Ok(());
+    };
+    match lookup(document, parent)? {
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+        }
+        Value::Array(items) if last == "-" => items.push(value),
+        Value::Array(items) => {
+            let index = index(last, items.len(), true)?;
+            items.insert(index, value);
+        }
+        other => bail!("\"{last}\" can't be added to a {}", type_name(other)),
+    }
+    Ok(())
+}
+
+fn remove(document: &mut Value, path: &[String]) -> Result<Value> {
+    let (last, parent) = path.split_last().ok_or_else(|| anyhow!("the whole document can't be removed"))?;
+    match lookup(document, parent)? {
+        Value::Object(map) => map.remove(last).ok_or_else(|| anyhow!("there is no key \"{last}\"")),
+        Value::Array(items) => {
+            let index = index(last, items.len(), false)?;
+            Ok(items.remove(index))
+        }
+        other => bail!("\"{last}\" is inside a {}, not an object or array", type_name(other)),
+    }
+}
@@ -0,0 +1,325 @@
+//! Redaction of secret values for display and export.
+//!
+//! Secrets are replaced by numbered tags such as `«redacted #1»`, equal
+//! secrets sharing a tag for the whole session. The diff compares the real
+//! values, so that comparators such as `--ignore-case` apply to secrets too;
+//! a `Redaction` of the documents then stands the tags in for them wherever
+//! a value is shown, reported or exported.
+
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+use std::sync::Mutex;
+
+use crate::hash::Mixer;
+use crate::ignore;
+
+/// Words a key's name ends in when its value is always redacted: `token`,
+/// `access_token`, `accessToken` and `X-Auth-Token`, but not `max_tokens`,
+/// `tokenizer` or `token_count`.
+const SECRET_KEYS: [&str; 8] = ["password", "passwd", "secret", "token", "api_key", "apikey", "authorization", "cookie"];
+
+/// `--no-redact`, `--redact-path` and `--redact-value`, for the TUI and the
+/// commands that print values.
+#[derive(Default)]
+pub struct RedactArgs {
+    /// `--no-redact`: show secret values instead of `«redacted #N»` tags.
+    pub off: bool,
+    /// `--redact-path`: extra dot-separated paths to redact, `*` for any segment.
+    pub paths: Vec<String>,
+    /// `--redact-value`: regexes; string values matching one are redacted.
+    pub values: Vec<String>,
+}
+
+impl RedactArgs {
+    /// Consumes `arg` (and its value from `iter`) if it is a redaction option.
+    pub fn parse_arg<'a>(&mut self, arg: &str, iter: &mut impl Iterator<Item = &'a String>) -> Result<bool> {
+        match arg {
+            "--no-redact" => self.off = true,
+            "--redact-path" => self.paths.push(iter.next().ok_or_else(|| anyhow!("{arg} needs a path pattern"))?.clone()),
+            "--redact-value" => self.values.push(iter.next().ok_or_else(|| anyhow!("{arg} needs a regex"))?.clone()),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn redactor(&self) -> Result<Redactor> {
+        Redactor::new(!self.off, &self.paths, &self.values)
+    }
+}
+
+pub struct Redactor {
+    enabled: bool,
+    /// Dot-separated path patterns; `*` matches any single key or array index.
+    paths: Vec<Vec<String>>,
+    values: Vec<Regex>,
+    tags: Mutex<HashMap<String, usize>>,
+}
+
+impl Redactor {
+    pub fn new(enabled: bool, paths: &[String], values: &[String]) -> Result<Self> {
+        let values = values
+            .iter()
+            .map(|pattern| Regex::new(pattern).with_context(|| format!("--redact-value '{pattern}'")))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            enabled,
+            paths: paths.iter().map(|path| path.split('.').map(str::to_string).collect()).collect(),
+            values,
+            tags: Mutex::default(),
+        })
+    }
+
+    /// Returns a display copy of `value` with secrets replaced, and how many were.
+    pub fn redact(&self, value: &Value) -> (Value, usize) {
+        let mut redacted = value.clone();
+        let mut count = 0;
+        if self.enabled {
+            self.redact_in(&mut redacted, &mut Vec::new(), &mut count);
+        }
+        (redacted, count)
+    }
+
+    /// Where the secrets of `documents` are, for showing them redacted while
+    /// the diff compares the real values. The documents must stay where they
+    /// are while the redaction is used.
+    pub fn find(&self, documents: &[&Value]) -> Redaction {
+        let mut redaction = Redaction::default();
+        if self.enabled {
+            for document in documents {
+                self.find_in(document, &mut Vec::new(), &mut redaction);
+            }
+        }
+        redaction
+    }
+
+    /// Records the secrets in `value`; whether it holds any.
+    fn find_in(&self, value: &Value, path: &mut Vec<String>, redaction: &mut Redaction) -> bool {
+        if self.is_secret(value, path) {
+            self.hide(value, redaction);
+            redaction.count += 1;
+            return true;
+        }
+        let mut holds = false;
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map {
+                    path.push(key.clone());
+                    holds |= self.find_in(child, path, redaction);
+                    path.pop();
+                }
+            }
+            Value::Array(items) => {
+                for (index, child) in items.iter().enumerate() {
+                    path.push(index.to_string());
+                    holds |= self.find_in(child, path, redaction);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+        if holds {
+            redaction.nodes.insert(address(value), Node::Holder);
+        }
+        holds
+    }
+
+    /// Tags `value` and everything in it, each by its own value, as the
+    /// diff may show the values inside a secret object that differs.
+    fn hide(&self, value: &Value, redaction: &mut Redaction) {
+        redaction.nodes.insert(address(value), Node::Secret(Value::String(self.tag(&value.to_string()))));
+        match value {
+            Value::Object(map) => map.values().for_each(|child| self.hide(child, redaction)),
+            Value::Array(items) => items.iter().for_each(|child| self.hide(child, redaction)),
+            _ => {}
+        }
+    }
+
+    fn is_secret(&self, value: &Value, path: &[String]) -> bool {
+        path.last().is_some_and(|key| is_secret_key(key))
+            || self.paths.iter().any(|pattern| ignore::path_matches(pattern, path))
+            || matches!(value, Value::String(s) if self.values.iter().any(|re| re.is_match(s)))
+    }
+
+    fn redact_in(&self, value: &mut Value, path: &mut Vec<String>, count: &mut usize) {
+        if self.is_secret(value, path) {
+            *value = Value::String(self.tag(&value.to_string()));
+            *count += 1;
+            return;
+        }
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map.iter_mut() {
+                    path.push(key.clone());
+                    self.redact_in(child, path, count);
+                    path.pop();
+                }
+            }
+            Value::Array(items) => {
+                for (index, child) in items.iter_mut().enumerate() {
+                    path.push(index.to_string());
+                    self.redact_in(child, path, count);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Line-based redaction for `--format text`: values after a secret-looking
+    /// `key:` or `key=` and substrings matching a value pattern are replaced.
+    pub fn redact_text(&self, text: &str) -> (String, usize) {
+        if !self.enabled {
+            return (text.to_string(), 0);
+        }
+        let assignment = Regex::new(r#"^(\s*"?([\w.-]+)"?\s*[:=]\s*)(.*?)(,?\s*)$"#).expect("valid regex");
+        let mut count = 0;
+        let mut lines = Vec::new();
+        for line in text.lines() {
+            let mut line = line.to_string();
+            if let Some(captures) = assignment.captures(&line) {
+                if is_secret_key(&captures[2]) && !captures[3].is_empty() {
+                    line = format!("{}{}{}", &captures[1], self.tag(&captures[3]), &captures[4]);
+                    count += 1;
+                }
+            }
+            for re in &self.values {
+                let replaced = re.replace_all(&line, |captures: &regex::Captures| {
+                    count += 1;
+                    self.tag(&captures[0])
+                });
+                line = replaced.into_owned();
+            }
+            lines.push(line);
+        }
+        (lines.join("\n"), count)
+    }
+
+    fn tag(&self, secret: &str) -> String {
+        let mut tags = self.tags.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let next = tags.len() + 1;
+        let number = *tags.entry(secret.to_string()).or_insert(next);
+        format!("{TAG}{number}»")
+    }
+}
+
+/// The secrets of documents being diffed, by their address in them, as
+/// `hash::Hashes` has their hashes. Empty with redaction off.
+#[derive(Default)]
+pub struct Redaction {
+    nodes: HashMap<usize, Node, BuildHasherDefault<Mixer>>,
+    /// How many secrets were found; a secret object counts once.
+    pub count: usize,
+}
+
+enum Node {
+    /// A secret, or a value inside one, and its tag.
+    Secret(Value),
+    /// An object or array holding secrets.
+    Holder,
+}
+
+impl Redaction {
+    /// `value`, from the documents, as it may be shown: its tag if it is a
+    /// secret, a copy with its secrets tagged if it holds any, else itself.
+    pub fn show<'a>(&'a self, value: &'a Value) -> Cow<'a, Value> {
+        match self.nodes.get(&address(value)) {
+            Some(Node::Secret(tag)) => Cow::Borrowed(tag),
+            Some(Node::Holder) => Cow::Owned(self.copy(value)),
+            None => Cow::Borrowed(value),
+        }
+    }
+
+    /// A copy of `value`, from the documents, with its secrets tagged: what
+    /// exports are made from.
+    pub fn copy(&self, value: &Value) -> Value {
+        match (self.nodes.get(&address(value)), value) {
+            (Some(Node::Secret(tag)), _) => tag.clone(),
+            (Some(Node::Holder), Value::Object(map)) => Value::Object(map.iter().map(|(key, child)| (key.clone(), self.copy(child))).collect()),
+            (Some(Node::Holder), Value::Array(items)) => Value::Array(items.iter().map(|child| self.copy(child)).collect()),
+            _ => value.clone(),
+        }
+    }
+}
+
+fn address(value: &Value) -> usize {
+    value as *const Value as usize
+}
+
+/// How every placeholder starts.
+const TAG: &str = "«redacted #";
+
+/// Whether `value` is or holds a placeholder, as a patch operation built
+/// from redacted copies can.
+pub fn holds_placeholder(value: &Value) -> bool {
+    match value {
+        Value::String(s) => s.contains(TAG),
+        Value::Array(items) => items.iter().any(holds_placeholder),
+        Value::Object(map) => map.values().any(holds_placeholder),
+        _ => false,
+    }
+}
+
+/// Whether `key`'s last words, split at `_`, `-`, `.` and lower-to-upper
+/// case changes and read case-insensitively, are one of `SECRET_KEYS`.
+fn is_secret_key(key: &str) -> bool {
+    let mut words = String::new();
+    let mut previous = None;
+    for c in key.chars() {
+        match c {
+            '_' | '-' | '.' => words.push('_'),
+            _ if c.is_uppercase() && previous.is_some_and(char::is_lowercase) => {
+                words.push('_');
+                words.extend(c.to_lowercase());
+            }
+            _ => words.extend(c.to_lowercase()),
+        }
+        previous = Some(c);
+    }
+    SECRET_KEYS.iter().any(|secret| words == *secret || words.strip_suffix(secret).is_some_and(|rest| rest.ends_with('_')))
+}
+
+/// The "N values redacted" note for the status bar and export headers.
+pub fn note(count: usize) -> Option<String> {
+    match count {
+        0 => None,
+        1 => Some("1 value redacted".to_string()),
+        n => Some(format!("{n} values redacted")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn secret_keys_end_in_a_secret_word() {
+        for key in ["password", "DB_PASSWORD", "token", "access_token", "accessToken", "X-Auth-Token", "api_key", "apiKey", "x-api-key", "Authorization", "Set-Cookie", "client.secret"] {
+            assert!(is_secret_key(key), "{key}");
+        }
+    }
+
+    #[test]
+    fn words_containing_a_secret_are_not_secret() {
+        for key in ["max_tokens", "maxTokens", "token_count", "tokenizer", "cookie_domain", "secretary", "secret_name", "passwords_enabled", "keyboard"] {
+            assert!(!is_secret_key(key), "{key}");
+        }
+    }
+
+    #[test]
+    fn redaction_shows_secrets_as_tags() {
+        let redactor = Redactor::new(true, &["servers.*.host".to_string()], &["^AKIA".to_string()]).unwrap();
+        let document = json!({"max_tokens": 5, "password": "p", "servers": [{"host": "h", "port": 1}], "keys": ["AKIA1", "other"]});
+        let redaction = redactor.find(&[&document]);
+        assert_eq!(redaction.count, 3);
+        assert_eq!(redaction.show(&document["max_tokens"]).as_ref(), &json!(5));
+        assert!(holds_placeholder(&redaction.show(&document["password"])));
+        assert_eq!(redaction.show(&document["servers"]).as_ref(), &json!([{"host": "«redacted #3»", "port": 1}]));
+        assert_eq!(redaction.copy(&document), redactor.redact(&document).0);
+        assert_eq!(Redactor::new(false, &[], &[]).unwrap().find(&[&document]).copy(&document), document);
+    }
+}
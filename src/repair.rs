@@ -0,0 +1,279 @@
+//! `--lenient`: repairs string escapes serde_json rejects, so documents from
+//! sloppy producers still load. Lone UTF-16 surrogates (`\uD83D` without its
+//! pair) and truncated `\u` escapes become U+FFFD; unknown escapes such as
+//! `\'` become the escaped character. Each repair is recorded with its
+//! document path and position, so the diff can flag values compared on
+//! repaired data. Without `--lenient` such input fails to parse, with serde's
+//! line and column.
+
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Clear, Paragraph},
+};
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::cli::Format;
+use crate::duplicates::Duplicate;
+use crate::{centered_rect, display};
+
+pub struct Repair {
+    /// Dotted document path of the repaired string, or of the object whose
+    /// key it was.
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    /// The escape as written, e.g. `\uD83D`, and what replaced it.
+    pub original: String,
+    pub replacement: String,
+}
+
+impl Repair {
+    /// `s  line 1, column 7: \uD83D → \u{fffd}`, as the `W` panel and the
+    /// printed reports list it.
+    pub fn describe(&self) -> String {
+        let path = if self.path.is_empty() { "(root)" } else { &self.path };
+        format!(
+            "{}  line {}, column {}: {} → {}",
+            display::sanitize(path),
+            self.line,
+            self.column,
+            display::sanitize(&self.original),
+            display::sanitize(&self.replacement)
+        )
+    }
+}
+
+enum Level {
+    Object { key: Option<String>, expecting_key: bool },
+    Array(usize),
+}
+
+/// `contents` with its invalid string escapes repaired, and the repairs.
+/// Borrows `contents` when there is nothing to repair.
+pub fn repair_json(contents: &str) -> (Cow<'_, str>, Vec<Repair>) {
+    let mut out = String::with_capacity(contents.len());
+    let mut repairs = Vec::new();
+    let mut stack: Vec<Level> = Vec::new();
+    // Top-level values, for newline-delimited input.
+    let (mut records, mut root_done) = (0, false);
+    let (mut line, mut line_start) = (1, 0);
+    let mut chars = contents.char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        out.push(c);
+        match c {
+            '\n' => {
+                line += 1;
+                line_start = offset + 1;
+            }
+            '{' | '[' => {
+                if stack.is_empty() && root_done {
+                    records += 1;
+                }
+                stack.push(if c == '{' { Level::Object { key: None, expecting_key: true } } else { Level::Array(0) });
+            }
+            '}' | ']' => {
+                stack.pop();
+                root_done |= stack.is_empty();
+            }
+            ',' => match stack.last_mut() {
+                Some(Level::Array(index)) => *index += 1,
+                Some(Level::Object { expecting_key, .. }) => *expecting_key = true,
+                None => {}
+            },
+            ':' => {
+                if let Some(Level::Object { expecting_key, .. }) = stack.last_mut() {
+                    *expecting_key = false;
+                }
+            }
+            '"' => {
+                if stack.is_empty() && root_done {
+                    records += 1;
+                }
+                let start = out.len();
+                let mut found: Vec<(usize, usize, String, String)> = Vec::new();
+                while let Some((offset, c)) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => {
+                            let column = offset - line_start + 1;
+                            let (written, repaired) = escape(&mut chars);
+                            if let Some((original, replacement)) = repaired {
+                                found.push((line, column, original, replacement));
+                            }
+                            out.push_str(&written);
+                        }
+                        c => out.push(c),
+                    }
+                }
+                let raw = out[start..].to_string();
+                out.push('"');
+                let mut segments = path(&stack);
+                if let Some(Level::Object { key, expecting_key: true }) = stack.last_mut() {
+                    let decoded = serde_json::from_str::<String>(&format!("\"{raw}\"")).unwrap_or(raw);
+                    segments.push(decoded.clone());
+                    *key = Some(decoded);
+                }
+                root_done |= stack.is_empty();
+                for (line, column, original, replacement) in found {
+                    repairs.push((records, Repair { path: segments.join("."), line, column, original, replacement }));
+                }
+            }
+            _ => {}
+        }
+    }
+    // Newline-delimited records are read as an array, so paths start at theirs.
+    let repairs: Vec<Repair> = repairs
+        .into_iter()
+        .map(|(record, mut repair)| {
+            if records > 0 {
+                repair.path = [record.to_string(), repair.path].join(".").trim_end_matches('.').to_string();
+            }
+            repair
+        })
+        .collect();
+    if repairs.is_empty() {
+        return (Cow::Borrowed(contents), repairs);
+    }
+    (Cow::Owned(out), repairs)
+}
+
+fn path(stack: &[Level]) -> Vec<String> {
+    stack
+        .iter()
+        .filter_map(|frame| match frame {
+            Level::Object { key, .. } => key.clone(),
+            Level::Array(index) => Some(index.to_string()),
+        })
+        .collect()
+}
+
+/// Reads the escape after a backslash: what to write in its place and, if
+/// it had to be repaired, the escape as written and what replaced it.
+fn escape(chars: &mut std::iter::Peekable<std::str::CharIndices>) -> (String, Option<(String, String)>) {
+    let replaced = |original: String| Some((original, "U+FFFD".to_string()));
+    let Some((_, c)) = chars.next() else {
+        return ("\\\\".to_string(), Some(("\\".to_string(), "\\".to_string())));
+    };
+    match c {
+        '"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' => (format!("\\{c}"), None),
+        'u' => {
+            let hex = take_hex(chars);
+            match u32::from_str_radix(&hex, 16).ok().filter(|_| hex.len() == 4) {
+                Some(0xD800..=0xDBFF) => {
+                    // A high surrogate is only valid followed by a low one.
+                    let mut lookahead = chars.clone();
+                    let low = match (lookahead.next(), lookahead.next()) {
+                        (Some((_, '\\')), Some((_, 'u'))) => Some(take_hex(&mut lookahead)),
+                        _ => None,
+                    };
+                    match low.as_deref().and_then(|low| u32::from_str_radix(low, 16).ok().filter(|_| low.len() == 4)) {
+                        Some(low @ 0xDC00..=0xDFFF) => {
+                            *chars = lookahead;
+                            (format!("\\u{hex}\\u{low:04X}"), None)
+                        }
+                        _ => ("\\uFFFD".to_string(), replaced(format!("\\u{hex}"))),
+                    }
+                }
+                Some(0xDC00..=0xDFFF) | None => ("\\uFFFD".to_string(), replaced(format!("\\u{hex}"))),
+                Some(_) => (format!("\\u{hex}"), None),
+            }
+        }
+        // Unknown escapes stand for the character itself.
+        c if c.is_control() => (format!("\\u{:04X}", c as u32), Some((format!("\\{}", c.escape_default()), format!("{:?}", c)))),
+        '\'' => ("'".to_string(), Some(("\\'".to_string(), "'".to_string()))),
+        c => (c.to_string(), Some((format!("\\{c}"), format!("'{c}'")))),
+    }
+}
+
+/// Up to four hex digits.
+fn take_hex(chars: &mut std::iter::Peekable<std::str::CharIndices>) -> String {
+    let mut hex = String::new();
+    while hex.len() < 4 {
+        match chars.peek() {
+            Some((_, c)) if c.is_ascii_hexdigit() => {
+                hex.push(*c);
+                chars.next();
+            }
+            _ => break,
+        }
+    }
+    hex
+}
+
+/// The repairs of both sides, and the keys repeated in them, for the `W`
+/// panel.
+pub fn render(f: &mut Frame, area: Rect, repairs: &[Vec<Repair>; 2], duplicates: &[Vec<Duplicate>; 2]) {
+    let popup = centered_rect(area, 80, 60);
+    f.render_widget(Clear, popup);
+    let mut lines = Vec::new();
+    let repaired = repairs.iter().any(|repairs| !repairs.is_empty());
+    let duplicated = duplicates.iter().any(|duplicates| !duplicates.is_empty());
+    for (side, repairs) in ["Left", "Right"].into_iter().zip(repairs).filter(|_| repaired || !duplicated) {
+        lines.push(Line::from(format!("{side}: {} repaired", repairs.len())).bold());
+        for repair in repairs {
+            lines.push(Line::from(format!("  ⚠ {}", repair.describe())));
+        }
+    }
+    for (side, duplicates) in ["Left", "Right"].into_iter().zip(duplicates).filter(|_| duplicated) {
+        lines.push(Line::from(format!("{side}: {} duplicate key(s), the last value kept", duplicates.len())).bold());
+        for duplicate in duplicates {
+            lines.push(Line::from(format!("  ⚠ {}", display::sanitize(&duplicate.to_string()))));
+        }
+    }
+    let title = match (repaired, duplicated) {
+        (true, true) => "Repaired escapes and duplicate keys",
+        (false, true) => "Duplicate keys",
+        _ => "Repaired escapes",
+    };
+    f.render_widget(Paragraph::new(lines).block(Block::bordered().title(format!("{title} ([W]/[esc] close)"))), popup);
+}
+
+/// The repairs of both sides under a printed report, so that values
+/// compared on repaired data never pass as equal unremarked.
+pub fn print(repairs: &[Vec<Repair>; 2]) {
+    for (side, repairs) in ["left", "right"].into_iter().zip(repairs).filter(|(_, repairs)| !repairs.is_empty()) {
+        println!("  ⚠ {} escape(s) repaired on the {side} (--lenient):", repairs.len());
+        for repair in repairs {
+            println!("    {}", repair.describe());
+        }
+    }
+}
+
+/// `print`'s count on stderr, for outputs scripts read.
+pub fn warn(repairs: &[Vec<Repair>; 2]) {
+    let [left, right] = repairs.each_ref().map(Vec::len);
+    if left + right > 0 {
+        eprintln!("diffrs: warning: repaired {left} escape(s) on the left, {right} on the right (--lenient)");
+    }
+}
+
+/// The repairs of both sides for `--json`.
+pub fn to_json(repairs: &[Vec<Repair>; 2]) -> Value {
+    let sides = ["left", "right"].into_iter().zip(repairs);
+    sides
+        .flat_map(|(side, repairs)| {
+            repairs.iter().map(move |repair| {
+                json!({ "side": side, "path": repair.path, "line": repair.line, "column": repair.column, "original": repair.original, "replacement": repair.replacement })
+            })
+        })
+        .collect()
+}
+
+/// `repair_json` for JSON input under `--lenient`; anything else as is.
+pub fn repair_document<'c>(contents: &'c str, path: &Path, format: Format, lenient: bool) -> (Cow<'c, str>, Vec<Repair>) {
+    if lenient && format.resolve(path, contents) == Format::Json {
+        repair_json(contents)
+    } else {
+        (Cow::Borrowed(contents), Vec::new())
+    }
+}
+
+/// Whether the row at dotted `path` is, or contains, a repaired value.
+pub fn touches(repaired: &BTreeSet<String>, path: &str) -> bool {
+    let nested = format!("{path}.");
+    repaired.contains(path) || repaired.range(nested.clone()..).next().is_some_and(|p| p.starts_with(&nested))
+}
@@ -0,0 +1,422 @@
+//! Plain-text change reports and the failure policy shared by the headless
+//! commands (`diffrs hook`, `diffrs check`).
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value};
+
+use crate::context::ContextFields;
+use crate::diff::{DiffEntry, Shape};
+use crate::display;
+use crate::redact::Redaction;
+use crate::coverage::type_name;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+    /// A value replaced by one of a different JSON type.
+    TypeChanged,
+    /// A key removed and another added with an equal value, with
+    /// `--detect-renames`. Only the diff view pairs them.
+    Renamed,
+    /// An array element removed in one place and added, unchanged, in
+    /// another. Only the diff view pairs them.
+    Moved,
+}
+
+impl ChangeKind {
+    pub const ALL: [ChangeKind; 4] = [ChangeKind::Added, ChangeKind::Removed, ChangeKind::Changed, ChangeKind::TypeChanged];
+
+    fn parse(name: &str) -> Result<Vec<ChangeKind>> {
+        match name {
+            "added" => Ok(vec![ChangeKind::Added]),
+            "removed" => Ok(vec![ChangeKind::Removed]),
+            "changed" => Ok(vec![ChangeKind::Changed]),
+            "type-changed" => Ok(vec![ChangeKind::TypeChanged]),
+            "any" => Ok(ChangeKind::ALL.to_vec()),
+            "none" => Ok(Vec::new()),
+            other => bail!("unknown change kind '{other}' (expected added, removed, changed, type-changed, any or none)"),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Changed => "changed",
+            ChangeKind::TypeChanged => "type-changed",
+            ChangeKind::Renamed => "renamed",
+            ChangeKind::Moved => "moved",
+        }
+    }
+
+    /// The kind of difference between two optional values, `None` if equal.
+    pub fn between(left: Option<&Value>, right: Option<&Value>) -> Option<ChangeKind> {
+        match (left, right) {
+            (Some(l), Some(r)) if l == r => None,
+            (Some(l), Some(r)) if std::mem::discriminant(l) != std::mem::discriminant(r) => Some(ChangeKind::TypeChanged),
+            (Some(_), Some(_)) => Some(ChangeKind::Changed),
+            (Some(_), None) => Some(ChangeKind::Removed),
+            (None, Some(_)) => Some(ChangeKind::Added),
+            (None, None) => None,
+        }
+    }
+
+    pub fn marker(self) -> char {
+        match self {
+            ChangeKind::Added => '+',
+            ChangeKind::Removed => '-',
+            ChangeKind::Changed => '~',
+            ChangeKind::TypeChanged => '!',
+            ChangeKind::Renamed => '>',
+            ChangeKind::Moved => '^',
+        }
+    }
+}
+
+/// One differing location.
+pub struct Change {
+    pub kind: ChangeKind,
+    /// As people read it: `orders[id=A-1009] › total`.
+    pub path: String,
+    /// As tools address it, an RFC 6901 JSON Pointer: `/orders/3/total`.
+    pub pointer: String,
+    /// For a type change, the JSON types before and after: `number`, `string`.
+    pub types: Option<[&'static str; 2]>,
+    /// For a changed number, the difference (`+60`) and the change as a
+    /// percentage of the old value, `None` from zero.
+    pub delta: Option<(String, Option<f64>)>,
+    /// For an array compared as a multiset, the value added or removed and
+    /// how many times.
+    pub element: Option<(Value, usize)>,
+    /// The value on each side, where there is one; `None` for a multiset's
+    /// changes, which have `element` instead.
+    pub values: [Option<Value>; 2],
+}
+
+impl Change {
+    /// A change of `kind` between `values`; for an element of an array
+    /// compared as a multiset, `count` is how many times the value on one
+    /// side only was added or removed. Values are kept as `redaction` shows
+    /// them, with no delta for a secret.
+    fn new(kind: ChangeKind, path: String, pointer: String, values: [Option<&Value>; 2], redaction: &Redaction, count: Option<usize>) -> Self {
+        let shown = values.map(|value| value.map(|value| redaction.show(value)));
+        let mut change = Change { kind, path, pointer, types: None, delta: None, element: None, values: shown.each_ref().map(|value| value.as_deref().cloned()) };
+        match (kind, shown.each_ref().map(|value| value.as_deref()), count) {
+            (ChangeKind::Added | ChangeKind::Removed, [Some(value), None] | [None, Some(value)], Some(count)) => {
+                change.element = Some((value.clone(), count));
+                change.values = [None, None];
+            }
+            (ChangeKind::TypeChanged, _, _) => change.types = values[0].zip(values[1]).map(|(left, right)| [type_name(left), type_name(right)]),
+            (ChangeKind::Changed, [Some(left), Some(right)], _) => {
+                change.delta = display::numeric_delta(left, right).map(|(absolute, _)| (absolute, display::percent_change(left, right)));
+            }
+            _ => {}
+        }
+        change
+    }
+}
+
+/// A flat list of the differing paths between two documents.
+#[derive(Default)]
+pub struct Summary {
+    pub changes: Vec<Change>,
+}
+
+impl Summary {
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.changes.iter().filter(|change| change.kind == kind).count()
+    }
+
+    /// The changes the entries of a diff show: only what differs under its
+    /// comparators, with array elements paired as the diff view pairs them.
+    /// An object or array shown collapsed is one change, at its path, and a
+    /// key renamed or written elsewhere, or an element moved, one more of
+    /// its kind. Elements of arrays with context fields are named by them:
+    /// `orders[id=A-1009] › total`.
+    pub fn of_entries(entries: &[DiffEntry], context: &ContextFields, redaction: &Redaction) -> Self {
+        let mut summary = Self::default();
+        // The readable path of the last entry at each level, and whether it
+        // ends in an element named by context fields.
+        let mut paths: Vec<(String, bool)> = Vec::new();
+        for entry in entries {
+            let Some(level) = entry.segments.len().checked_sub(1) else {
+                continue;
+            };
+            let key = &entry.segments[level];
+            let (parent, labelled) = match level {
+                0 => ("", false),
+                _ => (paths[level - 1].0.as_str(), paths[level - 1].1),
+            };
+            let (path, named) = match entry.element {
+                Some(_) => {
+                    let [left, right] = [entry.left, entry.right].map(|value| value.map(|value| redaction.show(value)));
+                    let label = context.label(&entry.segments[..level], key, left.as_deref(), right.as_deref());
+                    let named = label != format!("[{key}]");
+                    (format!("{parent}{label}"), named)
+                }
+                None if labelled => (format!("{parent} › {key}"), false),
+                None => (display::join_path(parent, key, false), false),
+            };
+            paths.truncate(level);
+            paths.push((path.clone(), named));
+            let (Some(kind), Shape::Value | Shape::SummaryElement) = (entry.kind, entry.shape) else {
+                continue;
+            };
+            let path = match &entry.right_key {
+                Some(right_key) if kind == ChangeKind::Renamed => format!("{path} → {right_key}"),
+                _ => path,
+            };
+            summary.changes.push(Change::new(kind, path, json_pointer(&entry.segments), [entry.left, entry.right], redaction, entry.element.and_then(|element| element.count)));
+        }
+        summary
+    }
+
+    /// The change between two documents the diff view compares line by line,
+    /// as a whole, if they differ as `kind` says.
+    pub fn of_documents(kind: Option<ChangeKind>, left: &Value, right: &Value, redaction: &Redaction) -> Self {
+        let changes = kind.map(|kind| Change::new(kind, display_path(""), String::new(), [Some(left), Some(right)], redaction, None));
+        Self { changes: changes.into_iter().collect() }
+    }
+
+    /// `name: X added, ...`, or that there are no changes.
+    fn headline(&self, name: &str) -> String {
+        if self.changes.is_empty() {
+            return format!("{name}: no semantic changes");
+        }
+        let counts: Vec<String> = self.counts().map(|(kind, count)| format!("{count} {}", kind.name())).collect();
+        format!("{name}: {}", counts.join(", "))
+    }
+
+    /// How many changes there are of each kind; renames and moves only if
+    /// there are any.
+    fn counts(&self) -> impl Iterator<Item = (ChangeKind, usize)> + '_ {
+        let counts = ChangeKind::ALL.into_iter().map(|kind| (kind, self.count(kind)));
+        let rare = [ChangeKind::Renamed, ChangeKind::Moved].into_iter().map(|kind| (kind, self.count(kind))).filter(|(_, count)| *count > 0);
+        counts.chain(rare)
+    }
+
+    /// Each change's kind and JSON Pointer, sorted by path as `by_kind` sorts
+    /// and then by kind, each once: what `--changed-paths` lists. The same
+    /// changes always give the same list, so it can itself be diffed.
+    pub fn changed_paths(&self) -> Vec<(ChangeKind, String)> {
+        let mut changes: Vec<(ChangeKind, String)> = self.changes.iter().map(|change| (change.kind, change.pointer.clone())).collect();
+        changes.sort_by_cached_key(|(kind, pointer)| (path_order(pointer), kind.marker()));
+        changes.dedup();
+        changes
+    }
+
+    /// Prints `name: X added, ...` and one marked line per change, values
+    /// cut to `limit` columns as `display::truncate_middle` cuts them.
+    pub fn print(&self, name: &str, limit: usize) {
+        println!("{}", self.headline(name));
+        for change in &self.changes {
+            println!("  {} {}", change.kind.marker(), describe(change, limit));
+        }
+    }
+
+    /// The changes in three groups, added, removed and changed (type changes
+    /// included), each headed by its count and sorted by path, then renamed
+    /// keys and moved values if there are any. An added or removed value
+    /// follows its path; a changed one's old and new values are on the two
+    /// lines after it. Values longer than `limit` columns have their middle
+    /// cut out, `start…end [1,842 more chars]`; 0 never cuts.
+    pub fn by_kind(&self, limit: usize) -> Vec<ReportLine> {
+        let groups = [
+            ("Added", ChangeKind::Added, &[ChangeKind::Added][..]),
+            ("Removed", ChangeKind::Removed, &[ChangeKind::Removed][..]),
+            ("Changed", ChangeKind::Changed, &[ChangeKind::Changed, ChangeKind::TypeChanged][..]),
+            ("Renamed", ChangeKind::Renamed, &[ChangeKind::Renamed][..]),
+            ("Moved", ChangeKind::Moved, &[ChangeKind::Moved][..]),
+        ];
+        let mut lines = Vec::new();
+        for (title, group, kinds) in groups {
+            let mut changes: Vec<&Change> = self.changes.iter().filter(|change| kinds.contains(&change.kind)).collect();
+            if changes.is_empty() && matches!(group, ChangeKind::Renamed | ChangeKind::Moved) {
+                continue;
+            }
+            changes.sort_by_cached_key(|change| path_order(&change.pointer));
+            lines.push(ReportLine::Group(group, format!("{title} ({})", changes.len())));
+            for change in changes {
+                let value = |side: usize| change.values[side].as_ref().map(|value| shown(value, limit));
+                if matches!(change.kind, ChangeKind::Renamed | ChangeKind::Moved) {
+                    lines.push(ReportLine::Change(change.kind, format!("  {} {}", change.kind.marker(), change.path)));
+                    continue;
+                }
+                match (value(0), value(1)) {
+                    (Some(old), Some(new)) => {
+                        lines.push(ReportLine::Change(change.kind, format!("  {} {}", change.kind.marker(), describe(change, limit))));
+                        lines.push(ReportLine::Change(ChangeKind::Removed, format!("      - {old}")));
+                        lines.push(ReportLine::Change(ChangeKind::Added, format!("      + {new}")));
+                    }
+                    (Some(value), None) | (None, Some(value)) => {
+                        lines.push(ReportLine::Change(change.kind, format!("  {} {}: {value}", change.kind.marker(), change.path)));
+                    }
+                    (None, None) => lines.push(ReportLine::Change(change.kind, format!("  {} {}", change.kind.marker(), describe(change, limit)))),
+                }
+            }
+        }
+        lines
+    }
+
+    /// Prints `name: X added, ...` and the changes grouped by kind, as
+    /// `by_kind` lays them out.
+    pub fn print_by_kind(&self, name: &str, limit: usize) {
+        println!("{}", self.headline(name));
+        if self.changes.is_empty() {
+            return;
+        }
+        for line in self.by_kind(limit) {
+            match line {
+                ReportLine::Group(_, text) | ReportLine::Change(_, text) => println!("{text}"),
+            }
+        }
+    }
+
+    /// The changes as JSON, each addressed by its JSON Pointer, for scripts.
+    pub fn to_json(&self, left: &str, right: &str) -> Value {
+        let changes: Vec<Value> = self
+            .changes
+            .iter()
+            .map(|change| {
+                let mut entry = json!({ "kind": change.kind.name(), "pointer": change.pointer, "path": change.path });
+                if let Some([from, to]) = change.types {
+                    entry["from_type"] = json!(from);
+                    entry["to_type"] = json!(to);
+                }
+                if let Some((value, count)) = &change.element {
+                    entry["value"] = value.clone();
+                    entry["count"] = json!(count);
+                }
+                // A side the value is missing from has no member, so a
+                // present `null` stays distinct from no value at all.
+                for (side, value) in ["left", "right"].iter().zip(&change.values) {
+                    if let Some(value) = value {
+                        entry[*side] = value.clone();
+                    }
+                }
+                if let Some((absolute, percent)) = &change.delta {
+                    entry["delta"] = display::delta_value(absolute).unwrap_or(Value::Null);
+                    if let Some(percent) = percent {
+                        // Two decimals, unless that leaves a change at 0 to sort.
+                        let rounded = (percent * 100.0).round() / 100.0;
+                        entry["percent"] = json!(if rounded == 0.0 { *percent } else { rounded });
+                    }
+                }
+                entry
+            })
+            .collect();
+        let counts: serde_json::Map<String, Value> = self.counts().map(|(kind, count)| (kind.name().to_string(), Value::from(count))).collect();
+        json!({ "left": left, "right": right, "counts": counts, "changes": changes })
+    }
+}
+
+/// A line of the report grouped by kind, with the kind it is styled as.
+pub enum ReportLine {
+    /// `Changed (3)`.
+    Group(ChangeKind, String),
+    Change(ChangeKind, String),
+}
+
+/// `orders › total (+60, +12.5%)`: the path, with the types of a type
+/// change, the difference of a changed number or a multiset's value and
+/// how many times it was added or removed.
+fn describe(change: &Change, limit: usize) -> String {
+    if let Some((value, count)) = &change.element {
+        return format!("{} {} (×{count})", change.path, shown(value, limit));
+    }
+    match (change.types, &change.delta) {
+        (Some([from, to]), _) => format!("{} ({from} → {to})", change.path),
+        (None, Some((absolute, Some(percent)))) => format!("{} ({absolute}, {})", change.path, display::format_percent(*percent)),
+        (None, Some((absolute, None))) => format!("{} ({absolute})", change.path),
+        (None, None) => change.path.clone(),
+    }
+}
+
+/// `value` as the report shows it, cut to `limit` columns.
+fn shown(value: &Value, limit: usize) -> String {
+    let text = display::render_value(value, false);
+    display::truncate_middle(&text, limit).map_or(text, |cut| cut.text)
+}
+
+/// Sorts paths as their documents order them: by key, and array elements
+/// by index, so `items[10]` comes after `items[9]`.
+fn path_order(pointer: &str) -> Vec<(Option<usize>, String)> {
+    pointer.split('/').skip(1).map(|token| (token.parse().ok(), token.to_string())).collect()
+}
+
+
+/// `segments` as an RFC 6901 JSON Pointer: `/users/3/email`, with `~` and
+/// `/` in keys escaped as `~0` and `~1`; the whole document is `""`.
+pub fn json_pointer(segments: &[String]) -> String {
+    segments.iter().map(|segment| format!("/{}", pointer_token(segment))).collect()
+}
+
+/// One key or index escaped for a JSON Pointer.
+pub fn pointer_token(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+fn display_path(path: &str) -> String {
+    if path.is_empty() {
+        "(root)".to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+/// `--fail-on` and `--max-changes`: which change kinds count against a run,
+/// and how many of them are tolerated before it fails.
+pub struct FailPolicy {
+    kinds: Vec<ChangeKind>,
+    max_changes: usize,
+}
+
+impl FailPolicy {
+    pub fn new(default_kinds: Vec<ChangeKind>) -> Self {
+        Self { kinds: default_kinds, max_changes: 0 }
+    }
+
+    /// Consumes `arg` (and its value from `iter`) if it is a policy option.
+    pub fn parse_arg<'a>(&mut self, arg: &str, iter: &mut impl Iterator<Item = &'a String>) -> Result<bool> {
+        match arg {
+            "--fail-on" => {
+                let value = iter.next().ok_or_else(|| anyhow!("--fail-on needs a list of change kinds"))?;
+                self.kinds = Vec::new();
+                for name in value.split(',') {
+                    self.kinds.extend(ChangeKind::parse(name.trim())?);
+                }
+            }
+            "--max-changes" => {
+                let value = iter.next().ok_or_else(|| anyhow!("--max-changes needs a number"))?;
+                self.max_changes = value.parse().map_err(|_| anyhow!("--max-changes: '{value}' is not a number"))?;
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Describes why the run fails, given the summaries of every compared
+    /// file, or `None` if it passes.
+    pub fn evaluate(&self, summaries: &[&Summary]) -> Option<String> {
+        let counted: Vec<(ChangeKind, usize)> = self
+            .kinds
+            .iter()
+            .map(|kind| (*kind, summaries.iter().map(|summary| summary.count(*kind)).sum()))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        let total: usize = counted.iter().map(|(_, count)| count).sum();
+        if total <= self.max_changes {
+            return None;
+        }
+        let found: Vec<String> = counted.iter().map(|(kind, count)| format!("{count} {}", kind.name())).collect();
+        let rule: Vec<&str> = self.kinds.iter().map(|kind| kind.name()).collect();
+        Some(format!(
+            "{} (fail-on: {}, max-changes: {})",
+            found.join(", "),
+            rule.join(","),
+            self.max_changes
+        ))
+    }
+}
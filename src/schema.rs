@@ -0,0 +1,71 @@
+//! `--schema`: compares a schema inferred from each document rather than the
+//! documents. The schema lists every field path (`items[].sku`, `[]` for the
+//! elements of an array) with the types seen there, `number | string` when
+//! there were several, and `(optional)` when some object on that path lacks
+//! the field. Diffing two of them shows the fields only one side has, those
+//! whose types changed or that became optional, and arrays whose elements
+//! changed type, which is what matters when comparing two exports or API
+//! samples record by record.
+
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::coverage::type_name;
+
+#[derive(Default)]
+struct Field {
+    types: BTreeSet<&'static str>,
+    /// The path of the object the field is a member of, and how many of
+    /// those objects have it; `None` for the root and array elements.
+    parent: Option<(String, usize)>,
+}
+
+#[derive(Default)]
+struct Inference {
+    fields: BTreeMap<String, Field>,
+    /// How many objects were seen on each path.
+    objects: BTreeMap<String, usize>,
+}
+
+impl Inference {
+    fn walk(&mut self, value: &Value, path: &str) {
+        self.fields.entry(path.to_string()).or_default().types.insert(type_name(value));
+        match value {
+            Value::Object(map) => {
+                *self.objects.entry(path.to_string()).or_default() += 1;
+                for (key, child) in map {
+                    let child_path = if path.is_empty() { key.clone() } else { format!("{path}.{key}") };
+                    let field = self.fields.entry(child_path.clone()).or_default();
+                    field.parent.get_or_insert_with(|| (path.to_string(), 0)).1 += 1;
+                    self.walk(child, &child_path);
+                }
+            }
+            Value::Array(items) => {
+                let element_path = format!("{path}[]");
+                for item in items {
+                    self.walk(item, &element_path);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The schema of `value`, as an object from each field path (the root is
+/// `(root)`) to its description: `{"id": "number", "tags[]": "string"}`.
+pub fn infer(value: &Value) -> Value {
+    let mut inference = Inference::default();
+    inference.walk(value, "");
+    let mut schema = Map::new();
+    for (path, field) in &inference.fields {
+        let mut description = field.types.iter().copied().collect::<Vec<_>>().join(" | ");
+        if let Some((parent, present)) = &field.parent {
+            if *present < inference.objects.get(parent).copied().unwrap_or(0) {
+                description.push_str(" (optional)");
+            }
+        }
+        let path = if path.is_empty() { "(root)".to_string() } else { path.clone() };
+        schema.insert(path, Value::String(description));
+    }
+    Value::Object(schema)
+}
@@ -0,0 +1,139 @@
+//! `--select <path>`: compares one subtree of both documents, such as
+//! `$.spec.containers`, instead of the whole of them. The path is JSONPath
+//! without wildcards, filters or recursive descent (`$`, `.key`, `['key']`
+//! and `[index]`), or a plain dotted path like `spec.containers[0]`.
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+use std::fmt;
+
+use crate::coverage::type_name;
+use crate::keyorder::Order;
+
+#[derive(Clone)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+pub struct Selection {
+    steps: Vec<Step>,
+}
+
+impl Selection {
+    pub fn parse(path: &str) -> Result<Self> {
+        let path = path.trim();
+        let rest = path.strip_prefix('$').unwrap_or(path);
+        let mut chars = rest.chars().peekable();
+        let mut steps = Vec::new();
+        let mut first = true;
+        while let Some(&c) = chars.peek() {
+            match c {
+                '[' => {
+                    chars.next();
+                    match chars.next() {
+                        Some(quote @ ('\'' | '"')) => {
+                            let mut key = String::new();
+                            loop {
+                                match chars.next() {
+                                    Some('\\') => key.extend(chars.next()),
+                                    Some(c) if c == quote => break,
+                                    Some(c) => key.push(c),
+                                    None => bail!("'{path}' has an unclosed quote"),
+                                }
+                            }
+                            if chars.next() != Some(']') {
+                                bail!("'{path}' has a quoted key not followed by ]");
+                            }
+                            steps.push(Step::Key(key));
+                        }
+                        Some(c) => {
+                            let mut index = c.to_string();
+                            loop {
+                                match chars.next() {
+                                    Some(']') => break,
+                                    Some(c) => index.push(c),
+                                    None => bail!("'{path}' has an unclosed ["),
+                                }
+                            }
+                            match index.trim().parse() {
+                                Ok(index) => steps.push(Step::Index(index)),
+                                Err(_) if index.trim() == "*" => bail!("'{path}' has a wildcard; select one subtree"),
+                                Err(_) => bail!("'{path}' has '[{index}]', which is not an array index"),
+                            }
+                        }
+                        None => bail!("'{path}' ends in ["),
+                    }
+                }
+                '.' if chars.clone().nth(1) == Some('.') => bail!("'{path}' uses recursive descent (..), which isn't supported"),
+                _ => {
+                    if c == '.' {
+                        chars.next();
+                    } else if !first {
+                        bail!("'{path}' has '{c}' where . or [ should be");
+                    }
+                    let mut key = String::new();
+                    while let Some(&c) = chars.peek().filter(|&&c| c != '.' && c != '[') {
+                        key.push(c);
+                        chars.next();
+                    }
+                    match key.as_str() {
+                        "" => bail!("'{path}' has an empty key"),
+                        "*" => bail!("'{path}' has a wildcard; select one subtree"),
+                        _ => steps.push(Step::Key(key)),
+                    }
+                }
+            }
+            first = false;
+        }
+        Ok(Self { steps })
+    }
+
+    /// The selected subtree of the `side` document, or an error saying it
+    /// has none and where the path leaves it.
+    pub fn apply(&self, value: Value, side: &str) -> Result<Value> {
+        self.lookup(value).map_err(|err| anyhow!("{self} is missing on the {side}: {err}"))
+    }
+
+    fn lookup(&self, value: Value) -> Result<Value> {
+        let mut value = value;
+        for (depth, step) in self.steps.iter().enumerate() {
+            let parent = Self { steps: self.steps[..depth].to_vec() };
+            value = match (step, value) {
+                (Step::Key(key), Value::Object(mut map)) => {
+                    map.remove(key).ok_or_else(|| anyhow!("{parent} has no key \"{key}\""))?
+                }
+                (Step::Index(index), Value::Array(mut items)) if *index < items.len() => items.swap_remove(*index),
+                (Step::Index(index), Value::Array(items)) => bail!("{parent} has {} element(s), not [{index}]", items.len()),
+                (Step::Key(_), other) => bail!("{parent} is a {}, not an object", type_name(&other)),
+                (Step::Index(_), other) => bail!("{parent} is a {}, not an array", type_name(&other)),
+            };
+        }
+        Ok(value)
+    }
+
+    /// The key order of the selected subtree, from that of the document.
+    pub fn apply_order(&self, order: Order) -> Order {
+        self.steps.iter().fold(order, |order, step| match step {
+            Step::Key(key) => order.member(key),
+            Step::Index(index) => order.element(*index),
+        })
+    }
+}
+
+/// `$.spec.containers[0]`, with keys that aren't plain names quoted.
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("$")?;
+        for step in &self.steps {
+            match step {
+                Step::Key(key) if !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') => {
+                    write!(f, ".{key}")?
+                }
+                Step::Key(key) => write!(f, "['{}']", key.replace('\\', "\\\\").replace('\'', "\\'"))?,
+                Step::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+//! Notices when a file a pane was loaded from changes on disk during the
+//! session, so a diff isn't silently of data that is no longer current.
+//! Checks are cheap: a stat, and a read and hash only when the mtime moved.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+pub struct Stamp {
+    pub path: PathBuf,
+    modified: Option<SystemTime>,
+    /// `None` for a file that didn't exist.
+    hash: Option<u64>,
+    changed: bool,
+}
+
+impl Stamp {
+    /// Stamps `path` as loaded with `contents`, or as absent.
+    pub fn new(path: PathBuf, contents: Option<&[u8]>) -> Self {
+        Self { modified: modified(&path), hash: contents.map(hash), changed: false, path }
+    }
+
+    /// Whether the file's content differs from when it was loaded. A file
+    /// that was only touched, or changed and changed back, hasn't changed; a
+    /// deleted or newly created one has.
+    pub fn check(&mut self) -> bool {
+        let modified = modified(&self.path);
+        if modified != self.modified {
+            self.modified = modified;
+            self.changed = std::fs::read(&self.path).ok().map(|contents| hash(&contents)) != self.hash;
+        }
+        self.changed
+    }
+
+    /// Whether the last `check` found the file changed.
+    pub fn changed(&self) -> bool {
+        self.changed
+    }
+
+    pub fn name(&self) -> String {
+        self.path.file_name().map_or_else(|| self.path.display().to_string(), |name| name.to_string_lossy().into_owned())
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn hash(contents: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(contents);
+    hasher.finish()
+}
@@ -0,0 +1,446 @@
+//! Table view for arrays of flat objects: one row per element pair, one column
+//! per key from the union of both sides, with differing cells highlighted.
+//! Elements are paired as the diff pairs them under `--array`, and cells
+//! differ as the diff's comparators say.
+
+use anyhow::Result;
+use crossterm::event::KeyCode;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Cell, Clear, List, ListItem, Paragraph, Row, Table, TableState, Wrap},
+};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use unicode_width::UnicodeWidthStr;
+
+use crate::arraykey::{self, Strategy};
+use crate::diff::{self, DiffView};
+use crate::report::ChangeKind;
+use crate::{atomic, centered_rect, display, theme};
+
+const MAX_COLUMN_WIDTH: usize = 30;
+
+pub struct TableView {
+    label: String,
+    /// Whether the table is of the documents' roots.
+    root: bool,
+    columns: Vec<String>,
+    hidden: BTreeSet<String>,
+    rows: Vec<TableRow>,
+    selected_row: usize,
+    selected_col: usize,
+    col_offset: usize,
+    picker: Option<usize>,
+    detail: bool,
+}
+
+struct TableRow {
+    /// The element's index on the right, or on the left if it was removed.
+    index: usize,
+    left: Option<Map<String, Value>>,
+    right: Option<Map<String, Value>>,
+    /// How the cells of elements on both sides changed, by column, under the
+    /// diff's comparators.
+    changes: BTreeMap<String, ChangeKind>,
+}
+
+impl TableRow {
+    fn cell(&self, column: &str) -> (Option<&Value>, Option<&Value>) {
+        (
+            self.left.as_ref().and_then(|map| map.get(column)),
+            self.right.as_ref().and_then(|map| map.get(column)),
+        )
+    }
+
+    fn cell_text(&self, column: &str) -> String {
+        self.cell_text_with(column, cell_value)
+    }
+
+    /// `cell_text`, with each value rendered by `render`.
+    fn cell_text_with(&self, column: &str, render: impl Fn(&Value) -> String) -> String {
+        match self.cell(column) {
+            (l, r) if l == r => l.map(&render).unwrap_or_default(),
+            (l, r) if self.left.is_some() && self.right.is_some() => {
+                let render = |value: Option<&Value>| value.map_or_else(|| display::MISSING.to_string(), &render);
+                format!("{} → {}", render(l), render(r))
+            }
+            (l, r) => l.or(r).map(&render).unwrap_or_default(),
+        }
+    }
+
+    /// The difference and ratio of a number that changed in `column`.
+    fn delta(&self, column: &str) -> Option<(String, String)> {
+        match self.cell(column) {
+            (Some(l), Some(r)) if self.changes.contains_key(column) => display::numeric_delta(l, r),
+            _ => None,
+        }
+    }
+}
+
+/// Finds every array of objects that can be shown as a table: the roots
+/// themselves, or top-level keys holding such arrays on both sides.
+pub fn find_tables(left: &Value, right: &Value, view: &DiffView) -> Vec<TableView> {
+    let mut tables = Vec::new();
+    if let Some(table) = TableView::build(None, left, right, view) {
+        tables.push(table);
+    }
+    if let (Some(left_map), Some(right_map)) = (left.as_object(), right.as_object()) {
+        for (key, left_value) in left_map {
+            if let Some(table) = right_map.get(key).and_then(|right_value| TableView::build(Some(key), left_value, right_value, view)) {
+                tables.push(table);
+            }
+        }
+    }
+    tables
+}
+
+/// The roots are arrays of objects, so the table is the more useful view.
+pub fn prefers_table(tables: &[TableView]) -> bool {
+    tables.iter().any(|table| table.root)
+}
+
+impl TableView {
+    /// The table of the arrays under `key`, or of the roots.
+    fn build(key: Option<&str>, left: &Value, right: &Value, view: &DiffView) -> Option<Self> {
+        let (left_items, right_items) = (left.as_array()?, right.as_array()?);
+        if left_items.is_empty() && right_items.is_empty() {
+            return None;
+        }
+        let objects = |items: &Vec<Value>| -> Option<Vec<Map<String, Value>>> {
+            items.iter().map(|item| item.as_object().cloned()).collect()
+        };
+        let (left_rows, right_rows) = (objects(left_items)?, objects(right_items)?);
+
+        let mut columns: Vec<String> = Vec::new();
+        for key in left_rows.iter().chain(&right_rows).flat_map(|row| row.keys()) {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+
+        let segments: Vec<String> = key.map(str::to_string).into_iter().collect();
+        let pairs = match view.array_keys.strategy(&segments, view.options.unordered_arrays) {
+            Strategy::Key(field) => arraykey::pair(left_items, right_items, |element| arraykey::field_key(element, &field)),
+            Strategy::Set | Strategy::Multiset => arraykey::pair(left_items, right_items, |element| Some(arraykey::canonical(element))),
+            Strategy::Ordered => {
+                let rows = 0..left_items.len().max(right_items.len());
+                rows.map(|index| (Some(index).filter(|&i| i < left_items.len()), Some(index).filter(|&j| j < right_items.len()), false)).collect()
+            }
+        };
+        let base = key.map_or_else(String::new, |key| display::join_path("", key, false));
+        let rows = pairs
+            .into_iter()
+            .map(|(i, j, _)| {
+                let index = j.or(i).expect("an element is on one side at least");
+                let (left, right) = (i.map(|i| &left_rows[i]), j.map(|j| &right_rows[j]));
+                let mut changes = BTreeMap::new();
+                if let (Some(left), Some(right)) = (left, right) {
+                    for column in &columns {
+                        let path = display::join_path(&format!("{base}[{index}]"), column, false);
+                        if let Some(kind) = diff::change_kind(view, &path, left.get(column), right.get(column)) {
+                            changes.insert(column.clone(), kind);
+                        }
+                    }
+                }
+                // Cells differ as the real values do, and show secrets as
+                // tags; a secret element has its tag in every cell.
+                let shown = |item: &Value| match view.redaction.show(item).into_owned() {
+                    Value::Object(map) => map,
+                    tag => item.as_object().map(|map| map.keys().map(|key| (key.clone(), tag.clone())).collect()).unwrap_or_default(),
+                };
+                TableRow { index, left: i.map(|i| shown(&left_items[i])), right: j.map(|j| shown(&right_items[j])), changes }
+            })
+            .collect();
+
+        Some(Self {
+            label: key.unwrap_or("(root)").to_string(),
+            root: key.is_none(),
+            columns,
+            hidden: BTreeSet::new(),
+            rows,
+            selected_row: 0,
+            selected_col: 0,
+            col_offset: 0,
+            picker: None,
+            detail: false,
+        })
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Names the table from `root`, the subtree `--select` compared.
+    pub fn under(&mut self, root: &str) {
+        self.label = if self.label == "(root)" { root.to_string() } else { format!("{root}.{}", self.label) };
+    }
+
+    fn visible_columns(&self) -> Vec<&String> {
+        self.columns.iter().filter(|column| !self.hidden.contains(*column)).collect()
+    }
+
+    /// Returns whether the key was consumed by the table view.
+    pub fn handle_key(&mut self, code: KeyCode) -> bool {
+        if let Some(cursor) = self.picker {
+            match code {
+                KeyCode::Up | KeyCode::Char('k') => self.picker = Some(cursor.saturating_sub(1)),
+                KeyCode::Down | KeyCode::Char('j') => {
+                    self.picker = Some((cursor + 1).min(self.columns.len().saturating_sub(1)))
+                }
+                KeyCode::Char(' ') => {
+                    let column = &self.columns[cursor];
+                    if !self.hidden.remove(column) {
+                        self.hidden.insert(column.clone());
+                    }
+                    self.selected_col = self.selected_col.min(self.visible_columns().len().saturating_sub(1));
+                    self.col_offset = self.col_offset.min(self.selected_col);
+                }
+                KeyCode::Esc | KeyCode::Char('p') => self.picker = None,
+                _ => {}
+            }
+            return true;
+        }
+        if self.detail {
+            if matches!(code, KeyCode::Esc | KeyCode::Enter) {
+                self.detail = false;
+            }
+            return true;
+        }
+
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected_row = self.selected_row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected_row = (self.selected_row + 1).min(self.rows.len().saturating_sub(1))
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                self.selected_col = self.selected_col.saturating_sub(1);
+                self.col_offset = self.col_offset.min(self.selected_col);
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                self.selected_col = (self.selected_col + 1).min(self.visible_columns().len().saturating_sub(1))
+            }
+            KeyCode::Enter => self.detail = !self.visible_columns().is_empty(),
+            KeyCode::Char('p') => self.picker = Some(0),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Writes the visible columns of every row as CSV, preceded by a `# note`
+    /// line if given. Cells read as rendered, except that nested values are
+    /// written out in full, or with `summarize` summarized as in the diff when
+    /// over that many bytes. Columns with changed numbers are followed by
+    /// `<column> delta` and `<column> ratio` columns.
+    pub fn write_csv(&self, path: &Path, note: Option<&str>, summarize: Option<usize>) -> Result<()> {
+        let render = |value: &Value| match value {
+            Value::Object(_) | Value::Array(_) => summarize
+                .and_then(|threshold| display::subtree_summary(value, threshold, None))
+                .unwrap_or_else(|| display::render_value(value, false)),
+            _ => cell_value(value),
+        };
+        let columns = self.visible_columns();
+        let numeric: Vec<bool> =
+            columns.iter().map(|column| self.rows.iter().any(|row| row.delta(column).is_some())).collect();
+        let mut csv = note.map(|note| format!("# {note}\n")).unwrap_or_default();
+        csv.push_str("row,status");
+        for (column, numeric) in columns.iter().zip(&numeric) {
+            csv.push(',');
+            csv.push_str(&csv_field(column));
+            if *numeric {
+                csv.push_str(&format!(",{},{}", csv_field(&format!("{column} delta")), csv_field(&format!("{column} ratio"))));
+            }
+        }
+        csv.push('\n');
+        for row in &self.rows {
+            csv.push_str(&format!("{},{}", row.index, row_status(row)));
+            for (column, numeric) in columns.iter().zip(&numeric) {
+                csv.push(',');
+                csv.push_str(&csv_field(&row.cell_text_with(column, render)));
+                if *numeric {
+                    let (absolute, relative) = row.delta(column).unwrap_or_default();
+                    csv.push_str(&format!(",{},{}", csv_field(&absolute), csv_field(&relative)));
+                }
+            }
+            csv.push('\n');
+        }
+        atomic::write(path, csv)
+    }
+
+    pub fn render(&mut self, f: &mut Frame, area: Rect) {
+        let widths: Vec<usize> = self
+            .visible_columns()
+            .iter()
+            .map(|column| {
+                self.rows
+                    .iter()
+                    .map(|row| row.cell_text(column).width())
+                    .chain([display::sanitize(column).width()])
+                    .max()
+                    .unwrap_or(0)
+                    .min(MAX_COLUMN_WIDTH)
+            })
+            .collect();
+
+        // Scroll horizontally just far enough to keep the selected column visible.
+        let available = area.width.saturating_sub(2 + 8) as usize;
+        if self.selected_col < self.col_offset {
+            self.col_offset = self.selected_col;
+        }
+        while self.col_offset < self.selected_col
+            && widths[self.col_offset..=self.selected_col].iter().map(|w| w + 1).sum::<usize>() > available
+        {
+            self.col_offset += 1;
+        }
+        let columns = self.visible_columns();
+        let shown: Vec<usize> = (self.col_offset..columns.len()).collect();
+
+        let header = Row::new(
+            ["#".to_string()]
+                .into_iter()
+                .chain(shown.iter().map(|&i| display::sanitize(columns[i]).into_owned()))
+                .map(|title| Cell::from(title).bold()),
+        );
+        let rows = self.rows.iter().enumerate().map(|(row_number, row)| {
+            let row_style = match (&row.left, &row.right) {
+                (Some(_), None) => theme::kind(ChangeKind::Removed),
+                (None, Some(_)) => theme::kind(ChangeKind::Added),
+                _ => Style::default(),
+            };
+            let cells = shown.iter().map(|&i| {
+                let mut style = match row.changes.get(columns[i].as_str()) {
+                    Some(&kind) => theme::kind(kind),
+                    None => Style::default(),
+                };
+                if row_number == self.selected_row && i == self.selected_col {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                Cell::from(row.cell_text(columns[i])).style(style)
+            });
+            Row::new([Cell::from(format!("{}{}", row_status(row), row.index))].into_iter().chain(cells)).style(row_style)
+        });
+
+        let constraints: Vec<Constraint> = [Constraint::Length(7)]
+            .into_iter()
+            .chain(shown.iter().map(|&i| Constraint::Length(widths[i] as u16)))
+            .collect();
+        let hidden = if self.hidden.is_empty() { String::new() } else { format!(", {} hidden", self.hidden.len()) };
+        let title = format!("Table: {} ({} rows, {} columns{hidden})", self.label, self.rows.len(), columns.len());
+        let table = Table::new(rows, constraints)
+            .header(header)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::BOLD));
+        let mut state = TableState::default().with_selected(Some(self.selected_row));
+        f.render_stateful_widget(table, area, &mut state);
+
+        if let Some(cursor) = self.picker {
+            self.render_picker(f, area, cursor);
+        } else if self.detail {
+            self.render_detail(f, area);
+        }
+    }
+
+    fn render_picker(&self, f: &mut Frame, area: Rect, cursor: usize) {
+        let items: Vec<ListItem> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let mark = if self.hidden.contains(column) { "[ ]" } else { "[x]" };
+                let item = ListItem::new(format!("{mark} {}", display::sanitize(column)));
+                if i == cursor {
+                    item.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    item
+                }
+            })
+            .collect();
+        let popup = centered_rect(area, 40, 60);
+        f.render_widget(Clear, popup);
+        f.render_widget(List::new(items).block(Block::bordered().title("Columns ([space] toggle, [p] close)")), popup);
+    }
+
+    fn render_detail(&self, f: &mut Frame, area: Rect) {
+        let (Some(row), Some(column)) = (self.rows.get(self.selected_row), self.visible_columns().get(self.selected_col).copied()) else {
+            return;
+        };
+        let (left, right) = row.cell(column);
+        let pretty = |value: Option<&Value>| match value {
+            Some(Value::String(s)) if display::is_binaryish(s) => format!("(hex)\n{}", display::hex_dump(s)),
+            Some(value) => display::render_pretty(value, false),
+            None => display::MISSING.to_string(),
+        };
+        let text = format!("Left:\n{}\n\nRight:\n{}", pretty(left), pretty(right));
+        let popup = centered_rect(area, 70, 70);
+        f.render_widget(Clear, popup);
+        f.render_widget(
+            Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered().title(format!("[{}].{}", row.index, display::sanitize(column)))),
+            popup,
+        );
+    }
+}
+
+fn row_status(row: &TableRow) -> &'static str {
+    match (&row.left, &row.right) {
+        (Some(_), None) => "-",
+        (None, Some(_)) => "+",
+        (Some(_), Some(_)) if !row.changes.is_empty() => "~",
+        _ => " ",
+    }
+}
+
+/// Scalars render as-is (strings without quotes); nested values as placeholders.
+fn cell_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => display::sanitize(s).into_owned(),
+        Value::Object(_) => "{…}".to_string(),
+        Value::Array(_) => "[…]".to_string(),
+        other => other.to_string(),
+    }
+}
+
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::{tests::with_view, DiffOptions};
+    use serde_json::json;
+
+    fn table(options: &DiffOptions, arrays: &[&str], left: &Value, right: &Value) -> TableView {
+        with_view(options, arrays, [left, right], |view| find_tables(left, right, view)).remove(0)
+    }
+
+    #[test]
+    fn rows_pair_by_the_array_key() {
+        let left = json!([{"id": 1, "v": "a"}, {"id": 2, "v": "b"}]);
+        let right = json!([{"id": 2, "v": "b"}, {"id": 1, "v": "c"}]);
+        let by_key = table(&DiffOptions::default(), &["key:id"], &left, &right);
+        let rows: Vec<(usize, &str)> = by_key.rows.iter().map(|row| (row.index, row_status(row))).collect();
+        assert_eq!(rows, [(0, " "), (1, "~")]);
+        assert_eq!(by_key.rows[1].changes.keys().collect::<Vec<_>>(), ["v"]);
+
+        // By position, every row differs.
+        let by_position = table(&DiffOptions::default(), &[], &left, &right);
+        assert!(by_position.rows.iter().all(|row| row_status(row) == "~"));
+    }
+
+    #[test]
+    fn cells_differ_as_the_comparators_say() {
+        let left = json!([{"price": 1.0, "name": "a"}]);
+        let right = json!([{"price": 1.004, "name": "b"}]);
+        let options = DiffOptions::default().tolerance(Some(0.01));
+        let table = table(&options, &[], &left, &right);
+        assert_eq!(table.rows[0].changes.keys().collect::<Vec<_>>(), ["name"]);
+        assert!(table.rows[0].delta("price").is_none());
+        assert!(prefers_table(&[table]));
+    }
+}
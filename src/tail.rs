@@ -0,0 +1,232 @@
+//! `--tail`: follows two growing NDJSON files and pairs up the records that
+//! arrive on each side, either by arrival order or by a key field.
+
+use anyhow::{Context, Result};
+use crossterm::event::KeyCode;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, List, ListItem, ListState},
+};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use crate::display;
+
+/// Pairs kept for scrollback unless `--tail-pairs` says otherwise.
+pub const DEFAULT_TAIL_PAIRS: usize = 200;
+
+/// One appended line: its line number since tailing began, and the record or
+/// the parse error.
+pub struct Entry {
+    pub line: usize,
+    pub value: Result<Value, String>,
+}
+
+pub struct Pair {
+    key: String,
+    pub left: Option<Entry>,
+    pub right: Option<Entry>,
+}
+
+impl Pair {
+    fn status(&self) -> (&'static str, Color) {
+        match (&self.left, &self.right) {
+            (Some(Entry { value: Err(_), .. }), _) | (_, Some(Entry { value: Err(_), .. })) => ("parse error", Color::Magenta),
+            (Some(_), None) => ("waiting for right", Color::DarkGray),
+            (None, Some(_)) => ("waiting for left", Color::DarkGray),
+            (Some(l), Some(r)) if l.value == r.value => ("equal", Color::Green),
+            _ => ("differs", Color::Red),
+        }
+    }
+}
+
+/// A file being followed from the end it had when tailing began.
+struct Follower {
+    path: PathBuf,
+    offset: u64,
+    partial: String,
+    lines: usize,
+}
+
+impl Follower {
+    fn open(path: PathBuf) -> Result<Self> {
+        let offset = std::fs::metadata(&path).with_context(|| path.display().to_string())?.len();
+        Ok(Self { path, offset, partial: String::new(), lines: 0 })
+    }
+
+    /// Complete lines appended since the last call. A file that shrank was
+    /// truncated or rotated and is read again from the start.
+    fn read_new_lines(&mut self) -> Result<Vec<(usize, String)>> {
+        let mut file = File::open(&self.path).with_context(|| self.path.display().to_string())?;
+        let len = file.metadata()?.len();
+        if len < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut appended = Vec::new();
+        file.read_to_end(&mut appended)?;
+        self.offset += appended.len() as u64;
+        self.partial.push_str(&String::from_utf8_lossy(&appended));
+
+        let mut lines = Vec::new();
+        while let Some(end) = self.partial.find('\n') {
+            let line: String = self.partial.drain(..=end).collect();
+            self.lines += 1;
+            if !line.trim().is_empty() {
+                lines.push((self.lines, line.trim_end().to_string()));
+            }
+        }
+        Ok(lines)
+    }
+}
+
+pub struct Tail {
+    followers: [Follower; 2],
+    record_key: Option<Vec<String>>,
+    capacity: usize,
+    pairs: VecDeque<Pair>,
+    /// Per-side record counters, used as the pairing key in arrival order.
+    arrivals: [usize; 2],
+    selected: usize,
+    /// Keep the newest pair selected until the user scrolls up.
+    follow: bool,
+    pub error: Option<String>,
+}
+
+impl Tail {
+    pub fn open(left: PathBuf, right: PathBuf, record_key: Option<&str>, capacity: usize) -> Result<Self> {
+        Ok(Self {
+            followers: [Follower::open(left)?, Follower::open(right)?],
+            record_key: record_key.map(|key| key.split('.').map(str::to_string).collect()),
+            capacity: capacity.max(1),
+            pairs: VecDeque::new(),
+            arrivals: [0, 0],
+            selected: 0,
+            follow: true,
+            error: None,
+        })
+    }
+
+    /// Reads whatever was appended to either file. Returns whether anything arrived.
+    pub fn poll(&mut self) -> bool {
+        let mut arrived = false;
+        for side in 0..2 {
+            match self.followers[side].read_new_lines() {
+                Ok(lines) => {
+                    for (line, text) in lines {
+                        let value = serde_json::from_str::<Value>(&text).map_err(|err| format!("{err}: {text}"));
+                        self.add(side, Entry { line, value });
+                        arrived = true;
+                    }
+                    self.error = None;
+                }
+                Err(err) => self.error = Some(format!("{err:#}")),
+            }
+        }
+        if self.follow {
+            self.selected = self.pairs.len().saturating_sub(1);
+        }
+        arrived
+    }
+
+    fn add(&mut self, side: usize, entry: Entry) {
+        let key = match (&self.record_key, &entry.value) {
+            (Some(path), Ok(value)) => match lookup(value, path) {
+                Some(key) => key,
+                None => format!("line {} (no key)", entry.line),
+            },
+            (Some(_), Err(_)) => format!("line {} (unparsed)", entry.line),
+            (None, _) => {
+                self.arrivals[side] += 1;
+                format!("#{}", self.arrivals[side])
+            }
+        };
+        // The most recent pair with this key still waiting for this side.
+        let open = self.pairs.iter_mut().rev().find(|pair| {
+            pair.key == key && if side == 0 { pair.left.is_none() } else { pair.right.is_none() }
+        });
+        match open {
+            Some(pair) if side == 0 => pair.left = Some(entry),
+            Some(pair) => pair.right = Some(entry),
+            None => {
+                let (left, right) = if side == 0 { (Some(entry), None) } else { (None, Some(entry)) };
+                self.pairs.push_back(Pair { key, left, right });
+                if self.pairs.len() > self.capacity {
+                    self.pairs.pop_front();
+                    self.selected = self.selected.saturating_sub(1);
+                }
+            }
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Pair> {
+        self.pairs.get(self.selected)
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) -> bool {
+        let last = self.pairs.len().saturating_sub(1);
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(20),
+            KeyCode::PageDown => self.selected = (self.selected + 20).min(last),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = last,
+            _ => return false,
+        }
+        self.follow = self.selected == last;
+        true
+    }
+
+    pub fn summary(&self) -> String {
+        let differing = self.pairs.iter().filter(|pair| pair.status().0 == "differs").count();
+        let follow = if self.follow { "following" } else { "paused, [End] to follow" };
+        format!(
+            "tailing {} / {}: {} pairs, {differing} differ ({follow})",
+            self.followers[0].path.display(),
+            self.followers[1].path.display(),
+            self.pairs.len()
+        )
+    }
+
+    pub fn render_list(&self, f: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .pairs
+            .iter()
+            .map(|pair| {
+                let (status, color) = pair.status();
+                let line = |entry: &Option<Entry>| entry.as_ref().map_or("-".to_string(), |entry| entry.line.to_string());
+                ListItem::new(format!(
+                    "{:<24} {:<18} lines {} / {}",
+                    display::sanitize(&pair.key),
+                    status,
+                    line(&pair.left),
+                    line(&pair.right)
+                ))
+                .style(Style::default().fg(color))
+            })
+            .collect();
+        let title = match &self.record_key {
+            Some(key) => format!("Pairs by {}", key.join(".")),
+            None => "Pairs by arrival order".to_string(),
+        };
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(self.selected).filter(|_| !self.pairs.is_empty()));
+        f.render_stateful_widget(list, area, &mut state);
+    }
+}
+
+/// The key field of a record as text; string keys without their quotes.
+fn lookup(value: &Value, path: &[String]) -> Option<String> {
+    let found = path.iter().try_fold(value, |value, key| value.get(key))?;
+    Some(match found {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    })
+}
@@ -0,0 +1,51 @@
+//! How differences are colored, by change kind: removed values red and
+//! added ones green, modified values yellow, type changes bold magenta, as
+//! the likeliest to be a bug, renamed keys cyan and moved array elements
+//! blue, and unchanged values plain. The diff panes, the line diff and the table view
+//! all style rows from here.
+
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::report::ChangeKind;
+
+/// The style of a change of `kind`.
+pub fn kind(kind: ChangeKind) -> Style {
+    let color = match kind {
+        ChangeKind::Removed => Color::Red,
+        ChangeKind::Added => Color::Green,
+        ChangeKind::Changed => Color::Yellow,
+        ChangeKind::TypeChanged => Color::Magenta,
+        ChangeKind::Renamed => Color::Cyan,
+        ChangeKind::Moved => Color::Blue,
+    };
+    let style = Style::default().fg(color);
+    if kind == ChangeKind::TypeChanged {
+        style.add_modifier(Modifier::BOLD)
+    } else {
+        style
+    }
+}
+
+/// The style of a row in the left (`side` 0) or right pane. A removed value
+/// is colored on the left only and an added one on the right only; the side
+/// it is missing from is dimmed.
+pub fn side(change: Option<ChangeKind>, side: usize) -> Style {
+    match (change, side) {
+        (None, _) => Style::default(),
+        (Some(ChangeKind::Removed), 0) | (Some(ChangeKind::Added), 1) => kind(change.expect("matched")),
+        (Some(ChangeKind::Removed | ChangeKind::Added), _) => Style::default().add_modifier(Modifier::DIM),
+        (Some(change), _) => kind(change),
+    }
+}
+
+/// A value's type tag, dim beside the diff's colors.
+pub fn type_tag() -> Style {
+    Style::default().add_modifier(Modifier::DIM)
+}
+
+/// The words of a modified string that differ from the other side's: in bold,
+/// as removed on the left and as added on the right.
+pub fn changed_words(side: usize) -> Style {
+    let change = if side == 0 { ChangeKind::Removed } else { ChangeKind::Added };
+    kind(change).add_modifier(Modifier::BOLD)
+}
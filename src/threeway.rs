@@ -0,0 +1,127 @@
+//! `--base <file>`: a three-way comparison against the documents' common
+//! ancestor. Each difference is classified by who made it: the left side
+//! only, the right side only, both the same way, or both differently — a
+//! conflict. A key deleted on one side and modified on the other is a
+//! conflict too. The classes mark the rows of the two-pane diff.
+//!
+//! Arrays are compared by position where the three have the same length;
+//! otherwise an array changed on both sides is one conflict.
+
+use ratatui::style::{Color, Modifier, Style};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Merge {
+    LeftOnly,
+    RightOnly,
+    /// Changed the same way on both sides.
+    Both,
+    Conflict,
+    /// An object or array with a conflict somewhere in it.
+    ConflictInside,
+}
+
+impl Merge {
+    /// Appended to the rows it marks.
+    pub fn marker(self) -> &'static str {
+        match self {
+            Merge::LeftOnly => " ◀ left only",
+            Merge::RightOnly => " ▶ right only",
+            Merge::Both => " ═ same on both",
+            Merge::Conflict => " ⚡ conflict",
+            Merge::ConflictInside => " ⚡ conflicts inside",
+        }
+    }
+
+    /// Conflicts stand out from the one-sided changes, which stay quiet.
+    pub fn style(self) -> Style {
+        match self {
+            Merge::Conflict | Merge::ConflictInside => {
+                Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD | Modifier::REVERSED)
+            }
+            _ => Style::default().add_modifier(Modifier::DIM),
+        }
+    }
+}
+
+/// The class of each difference, by its path as the diff rows record it
+/// (`servers[2].port`).
+#[derive(Default)]
+pub struct Merges {
+    by_path: BTreeMap<String, Merge>,
+}
+
+impl Merges {
+    pub fn classify(base: &Value, left: &Value, right: &Value) -> Self {
+        let mut merges = Self::default();
+        merges.walk(String::new(), Some(base), Some(left), Some(right));
+        merges
+    }
+
+    pub fn get(&self, path: &str) -> Option<Merge> {
+        self.by_path.get(path).copied()
+    }
+
+    /// `three-way: 2 conflicts, 3 left only, 1 right only, 0 same on both`.
+    pub fn describe(&self) -> String {
+        let count = |merge: Merge| self.by_path.values().filter(|&&found| found == merge).count();
+        format!(
+            "three-way: {} conflict(s), {} left only, {} right only, {} same on both",
+            count(Merge::Conflict),
+            count(Merge::LeftOnly),
+            count(Merge::RightOnly),
+            count(Merge::Both)
+        )
+    }
+
+    /// Classifies `path`, or the entries under it where both sides changed
+    /// it differently; returns whether there is a conflict.
+    fn walk(&mut self, path: String, base: Option<&Value>, left: Option<&Value>, right: Option<&Value>) -> bool {
+        let merge = if left == right {
+            if left == base {
+                return false;
+            }
+            Merge::Both
+        } else if left == base {
+            Merge::RightOnly
+        } else if right == base {
+            Merge::LeftOnly
+        } else {
+            let empty = Map::new();
+            let conflict = match (base, left, right) {
+                (None | Some(Value::Object(_)), Some(Value::Object(left_map)), Some(Value::Object(right_map))) => {
+                    let base_map = base.and_then(Value::as_object).unwrap_or(&empty);
+                    let mut keys: Vec<&String> = base_map.keys().chain(left_map.keys()).chain(right_map.keys()).collect();
+                    keys.sort();
+                    keys.dedup();
+                    let mut conflict = false;
+                    for key in keys {
+                        let child = if path.is_empty() { key.clone() } else { format!("{path}.{key}") };
+                        conflict |= self.walk(child, base_map.get(key), left_map.get(key), right_map.get(key));
+                    }
+                    conflict
+                }
+                (Some(Value::Array(base_items)), Some(Value::Array(left_items)), Some(Value::Array(right_items)))
+                    if base_items.len() == left_items.len() && left_items.len() == right_items.len() =>
+                {
+                    let mut conflict = false;
+                    for (index, base_item) in base_items.iter().enumerate() {
+                        conflict |= self.walk(format!("{path}[{index}]"), Some(base_item), left_items.get(index), right_items.get(index));
+                    }
+                    conflict
+                }
+                _ => {
+                    self.by_path.insert(path, Merge::Conflict);
+                    return true;
+                }
+            };
+            if conflict {
+                self.by_path.insert(path, Merge::ConflictInside);
+            }
+            return conflict;
+        };
+        self.by_path.insert(path, merge);
+        false
+    }
+}
@@ -0,0 +1,200 @@
+//! Relative-time annotations for timestamp values: how long ago a single
+//! value was, and how far apart the left and right values of a change are.
+//! Display only; comparisons never see these.
+
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ignore;
+
+pub struct TimeAnnotator {
+    /// Paths whose numeric values are Unix timestamps in seconds or milliseconds.
+    epoch_seconds: Vec<Vec<String>>,
+    epoch_millis: Vec<Vec<String>>,
+}
+
+impl TimeAnnotator {
+    pub fn new(epoch_seconds: &[String], epoch_millis: &[String]) -> Self {
+        let split = |patterns: &[String]| patterns.iter().map(|p| p.split('.').map(str::to_string).collect()).collect();
+        Self { epoch_seconds: split(epoch_seconds), epoch_millis: split(epoch_millis) }
+    }
+
+    /// Milliseconds since the epoch if `value` at `path` is a recognized timestamp.
+    fn timestamp(&self, path: &[String], value: &Value) -> Option<i64> {
+        match value {
+            Value::String(s) => parse_rfc3339(s),
+            Value::Number(n) if self.epoch_seconds.iter().any(|p| ignore::path_matches(p, path)) => {
+                Some((n.as_f64()? * 1000.0) as i64)
+            }
+            Value::Number(n) if self.epoch_millis.iter().any(|p| ignore::path_matches(p, path)) => Some(n.as_f64()? as i64),
+            _ => None,
+        }
+    }
+
+    fn collect(&self, value: &Value, path: &mut Vec<String>, found: &mut Vec<(String, i64)>) {
+        if let Some(millis) = self.timestamp(path, value) {
+            found.push((path.join("."), millis));
+            return;
+        }
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map {
+                    path.push(key.clone());
+                    self.collect(child, path, found);
+                    path.pop();
+                }
+            }
+            Value::Array(items) => {
+                for (index, child) in items.iter().enumerate() {
+                    path.push(index.to_string());
+                    self.collect(child, path, found);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Annotations for the left and right values at `path`: relative to `now`,
+    /// except that a right timestamp whose left counterpart differs is
+    /// described relative to it.
+    pub fn annotate(&self, path: &[String], left: &Value, right: &Value, now: i64) -> (String, String) {
+        let (mut left_found, mut right_found) = (Vec::new(), Vec::new());
+        self.collect(left, &mut path.to_vec(), &mut left_found);
+        self.collect(right, &mut path.to_vec(), &mut right_found);
+
+        let here = path.join(".");
+        let label = |found: &str, text: String| if found == here { text } else { format!("{found}: {text}") };
+        let left_notes: Vec<String> =
+            left_found.iter().map(|(path, millis)| label(path, relative(millis - now))).collect();
+        let right_notes: Vec<String> = right_found
+            .iter()
+            .map(|(path, millis)| {
+                let text = match left_found.iter().find(|(left_path, _)| left_path == path) {
+                    Some((_, left_millis)) if left_millis != millis => delta(millis - left_millis),
+                    _ => relative(millis - now),
+                };
+                label(path, text)
+            })
+            .collect();
+        let wrap = |notes: Vec<String>| if notes.is_empty() { String::new() } else { format!("  ({})", notes.join(", ")) };
+        (wrap(left_notes), wrap(right_notes))
+    }
+}
+
+/// The current time in milliseconds since the epoch.
+pub fn now_millis() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as i64)
+}
+
+/// `3 days ago` / `in 2h` for an offset from now in milliseconds.
+fn relative(offset_millis: i64) -> String {
+    if offset_millis.abs() < 1000 {
+        return "now".to_string();
+    }
+    let amount = duration(offset_millis.abs());
+    if offset_millis < 0 {
+        format!("{amount} ago")
+    } else {
+        format!("in {amount}")
+    }
+}
+
+/// `right is 2h later` for the right value minus the left, in milliseconds.
+fn delta(millis: i64) -> String {
+    if millis.abs() < 1000 {
+        return format!("right is {}ms {}", millis.abs(), if millis < 0 { "earlier" } else { "later" });
+    }
+    format!("right is {} {}", duration(millis.abs()), if millis < 0 { "earlier" } else { "later" })
+}
+
+/// The largest whole unit: `45s`, `12m`, `5h`, `3 days`.
+fn duration(millis: i64) -> String {
+    let seconds = millis / 1000;
+    match seconds {
+        0..=59 => format!("{seconds}s"),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86_399 => format!("{}h", seconds / 3600),
+        86_400..=172_799 => "1 day".to_string(),
+        _ => format!("{} days", seconds / 86_400),
+    }
+}
+
+/// Parses `2024-06-01T03:12:45Z`, with optional fractional seconds and a
+/// numeric offset, to milliseconds since the epoch.
+pub fn parse_rfc3339(s: &str) -> Option<i64> {
+    let b = s.as_bytes();
+    if b.len() < 20 || b[4] != b'-' || b[7] != b'-' || !matches!(b[10], b'T' | b't' | b' ') || b[13] != b':' || b[16] != b':' {
+        return None;
+    }
+    let num = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = s.get(range)?;
+        digits.bytes().all(|b| b.is_ascii_digit()).then(|| digits.parse().ok())?
+    };
+    let (year, month, day) = (num(0..4)?, num(5..7)?, num(8..10)?);
+    let (hour, minute, second) = (num(11..13)?, num(14..16)?, num(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut rest = &s[19..];
+    let mut millis = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        let padded = format!("{:0<3}", &fraction[..digits.min(3)]);
+        millis = padded.parse::<i64>().ok()?;
+        rest = &fraction[digits..];
+    }
+    let offset_minutes = match rest {
+        "Z" | "z" => 0,
+        _ if rest.len() == 6 && matches!(rest.as_bytes()[0], b'+' | b'-') && rest.as_bytes()[3] == b':' => {
+            let minutes = rest[1..3].parse::<i64>().ok()? * 60 + rest[4..6].parse::<i64>().ok()?;
+            if rest.starts_with('-') {
+                -minutes
+            } else {
+                minutes
+            }
+        }
+        _ => return None,
+    };
+
+    let days = days_from_civil(year, month, day);
+    let seconds = days * 86_400 + hour * 3600 + minute * 60 + second - offset_minutes * 60;
+    Some(seconds * 1000 + millis)
+}
+
+/// Formats milliseconds since the epoch as `2024-06-01T03:12:45Z`.
+pub fn format_rfc3339(millis: i64) -> String {
+    let seconds = millis.div_euclid(1000);
+    let (days, time) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    let (year, month, day) = civil_from_days(days);
+    format!("{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z", time / 3600, time % 3600 / 60, time % 60)
+}
+
+/// The inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
@@ -0,0 +1,154 @@
+//! `--timings`: per-phase wall time and allocation estimates for a diff.
+//!
+//! Allocation tracking goes through [`TrackingAllocator`], which only touches
+//! its counters while a phase is being measured, so it costs a single
+//! thread-local read per allocation when the flag is off. The counters are
+//! per thread: a phase counts what its own thread allocates, not what the UI
+//! thread allocates while a diff runs in the background.
+
+use serde_json::Value;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+thread_local! {
+    // Const-initialized and without destructors, so the allocator can use
+    // them at any point in a thread's life without allocating.
+    static TRACKING: Cell<bool> = const { Cell::new(false) };
+    static CURRENT: Cell<isize> = const { Cell::new(0) };
+    static PEAK: Cell<isize> = const { Cell::new(0) };
+}
+
+pub struct TrackingAllocator;
+
+impl TrackingAllocator {
+    fn record(delta: isize) {
+        if TRACKING.get() {
+            let current = CURRENT.get() + delta;
+            CURRENT.set(current);
+            PEAK.set(PEAK.get().max(current));
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::record(layout.size() as isize);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        Self::record(-(layout.size() as isize));
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::record(new_size as isize - layout.size() as isize);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+struct Phase {
+    name: &'static str,
+    elapsed: Duration,
+    peak_alloc: usize,
+    input_bytes: Option<usize>,
+    nodes: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct Timings {
+    enabled: bool,
+    phases: Vec<Phase>,
+}
+
+impl Timings {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, phases: Vec::new() }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Runs `f` as the named phase, recording its duration and the peak
+    /// number of bytes this thread allocated on top of what it had live
+    /// when the phase started.
+    pub fn measure<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        if !self.enabled {
+            return f();
+        }
+        let baseline = CURRENT.get();
+        PEAK.set(baseline);
+        TRACKING.set(true);
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed();
+        TRACKING.set(false);
+        let peak_alloc = (PEAK.get() - baseline).max(0) as usize;
+        self.phases.push(Phase { name, elapsed, peak_alloc, input_bytes: None, nodes: None });
+        result
+    }
+
+    /// Annotates the most recent phase with the size of its input.
+    pub fn input_bytes(&mut self, bytes: usize) {
+        if let Some(phase) = self.phases.last_mut() {
+            phase.input_bytes = Some(bytes);
+        }
+    }
+
+    /// Annotates the most recent phase with the number of nodes in `value`.
+    pub fn nodes(&mut self, value: &Value) {
+        if let Some(phase) = self.phases.last_mut() {
+            phase.nodes = Some(count_nodes(value));
+        }
+    }
+
+    /// Replaces phases with the same name, so a re-diff reports its latest run.
+    pub fn merge(&mut self, other: Timings) {
+        for phase in other.phases {
+            self.phases.retain(|existing| existing.name != phase.name);
+            self.phases.push(phase);
+        }
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = format!("{:<12} {:>10} {:>12} {:>12} {:>10}\n", "phase", "time", "peak alloc", "input", "nodes");
+        for phase in &self.phases {
+            report.push_str(&format!(
+                "{:<12} {:>10} {:>12} {:>12} {:>10}\n",
+                phase.name,
+                format!("{:.2?}", phase.elapsed),
+                format_bytes(phase.peak_alloc),
+                phase.input_bytes.map(format_bytes).unwrap_or_default(),
+                phase.nodes.map(|n| n.to_string()).unwrap_or_default(),
+            ));
+        }
+        let total: Duration = self.phases.iter().map(|phase| phase.elapsed).sum();
+        report.push_str(&format!("{:<12} {:>10}\n", "total", format!("{total:.2?}")));
+        report
+    }
+}
+
+fn count_nodes(value: &Value) -> usize {
+    1 + match value {
+        Value::Array(items) => items.iter().map(count_nodes).sum(),
+        Value::Object(map) => map.values().map(count_nodes).sum(),
+        _ => 0,
+    }
+}
+
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
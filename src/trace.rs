@@ -0,0 +1,134 @@
+//! `diffrs trace <path> <snapshot>...`: one value's history across several
+//! snapshots of a document, to find when it changed. Snapshots are ordered by
+//! the date in their file names (`config-2024-06-01.json`,
+//! `dump_20240601T1200.yaml`), or by `--order`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use unicode_width::UnicodeWidthStr;
+
+use crate::cli::Format;
+use crate::duplicates::DuplicateKeys;
+use crate::table::csv_field;
+use crate::{atomic, depth, display, parse_document};
+
+const USAGE: &str = "usage: diffrs trace <path> <snapshot> <snapshot>... [--order name,name,...] [--csv <file>]";
+
+struct Entry {
+    name: String,
+    /// `None` where the snapshot doesn't have the path.
+    value: Option<Value>,
+}
+
+impl Entry {
+    fn text(&self) -> String {
+        self.value.as_ref().map_or_else(|| "(absent)".to_string(), |value| display::render_value(value, false))
+    }
+}
+
+/// Returns the exit code.
+pub fn run(args: &[String]) -> Result<i32> {
+    let (mut order, mut csv, mut positional) = (None, None, Vec::new());
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| anyhow!("{arg} needs a value"));
+        match arg.as_str() {
+            "--order" => order = Some(value()?.split(',').map(|name| name.trim().to_string()).collect::<Vec<_>>()),
+            "--csv" => csv = Some(PathBuf::from(value()?)),
+            flag if flag.starts_with("--") => bail!("unknown trace option '{flag}'"),
+            other => positional.push(other.to_string()),
+        }
+    }
+    if positional.len() < 3 {
+        bail!(USAGE);
+    }
+    let path = positional.remove(0);
+    let snapshots = match order {
+        Some(order) => order_explicitly(positional, &order)?,
+        None => order_by_name(positional)?,
+    };
+
+    let segments: Vec<&str> = if path.is_empty() { Vec::new() } else { path.split('.').collect() };
+    let mut entries = Vec::new();
+    for snapshot in &snapshots {
+        let label = || snapshot.clone();
+        let contents = std::fs::read_to_string(snapshot).with_context(label)?;
+        let document = parse_document(&contents, Path::new(snapshot), Format::Auto, depth::DEFAULT_MAX_DEPTH, DuplicateKeys::Last).with_context(label)?;
+        entries.push(Entry { name: snapshot.clone(), value: lookup(&document, &segments).cloned() });
+    }
+
+    let width = entries.iter().map(|entry| display::sanitize(&entry.name).width()).max().unwrap_or(0);
+    let mut changes = 0;
+    println!("{path} across {} snapshots:", entries.len());
+    for (index, entry) in entries.iter().enumerate() {
+        let changed = index > 0 && entries[index - 1].value != entry.value;
+        changes += usize::from(changed);
+        let marker = if changed { "~" } else { " " };
+        // Padded by display width, so wide characters in names keep the column.
+        let name = display::sanitize(&entry.name);
+        println!("{marker} {name}{}  {}", " ".repeat(width - name.width()), entry.text());
+    }
+    println!("{changes} change(s)");
+
+    if let Some(csv_path) = csv {
+        let mut out = String::from("snapshot,value,changed\n");
+        for (index, entry) in entries.iter().enumerate() {
+            let changed = index > 0 && entries[index - 1].value != entry.value;
+            out.push_str(&format!("{},{},{}\n", csv_field(&entry.name), csv_field(&entry.text()), changed));
+        }
+        atomic::write(&csv_path, out)?;
+    }
+    Ok(0)
+}
+
+/// The value at dotted `segments`, indices as numbers.
+fn lookup<'v>(value: &'v Value, segments: &[&str]) -> Option<&'v Value> {
+    segments.iter().try_fold(value, |value, segment| match value {
+        Value::Object(map) => map.get(*segment),
+        Value::Array(items) => segment.parse::<usize>().ok().and_then(|index| items.get(index)),
+        _ => None,
+    })
+}
+
+/// `snapshots` in the order `--order` names them, by path or file name.
+fn order_explicitly(snapshots: Vec<String>, order: &[String]) -> Result<Vec<String>> {
+    let matches = |snapshot: &String, name: &String| snapshot == name || file_name(snapshot) == name.as_str();
+    let mut ordered = Vec::new();
+    for name in order {
+        let snapshot = snapshots
+            .iter()
+            .find(|snapshot| matches(snapshot, name))
+            .ok_or_else(|| anyhow!("--order: '{name}' is not one of the snapshots"))?;
+        ordered.push(snapshot.clone());
+    }
+    if let Some(missing) = snapshots.iter().find(|snapshot| !ordered.contains(snapshot)) {
+        bail!("--order doesn't place {missing}");
+    }
+    Ok(ordered)
+}
+
+/// `snapshots` sorted by the date and time in their file names; in the order
+/// given when none has one.
+fn order_by_name(mut snapshots: Vec<String>) -> Result<Vec<String>> {
+    let pattern = Regex::new(r"(\d{4})-?(\d{2})-?(\d{2})(?:[T_ -]?(\d{2})[:-]?(\d{2})(?:[:-]?(\d{2}))?)?").expect("valid regex");
+    let stamp = |snapshot: &String| {
+        pattern.captures(file_name(snapshot)).map(|captures| {
+            (1..=6).map(|group| captures.get(group).map_or("00", |digits| digits.as_str())).collect::<String>()
+        })
+    };
+    let undated: Vec<&String> = snapshots.iter().filter(|snapshot| stamp(snapshot).is_none()).collect();
+    if undated.len() == snapshots.len() {
+        return Ok(snapshots);
+    }
+    if let Some(snapshot) = undated.first() {
+        bail!("no date in the name of {snapshot}: name snapshots like config-2024-06-01.json, or pass --order");
+    }
+    snapshots.sort_by_cached_key(stamp);
+    Ok(snapshots)
+}
+
+fn file_name(snapshot: &str) -> &str {
+    Path::new(snapshot).file_name().and_then(|name| name.to_str()).unwrap_or(snapshot)
+}
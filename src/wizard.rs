@@ -0,0 +1,167 @@
+//! The start screen shown when diffrs is launched with nothing to compare:
+//! keys to fill each side from a file, the clipboard, a URL or a recent
+//! comparison, in place of two empty panes. `--no-wizard` skips it.
+
+use anyhow::{bail, Result};
+use crossterm::event::KeyCode;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Clear, List, ListState, Paragraph},
+};
+use std::process::Command;
+
+use crate::{centered_rect, display};
+
+pub enum Action {
+    None,
+    /// Fill side 0 (left) or 1 (right) from a file path or URL.
+    Load(usize, String),
+    /// Fill a side from the clipboard.
+    Paste(usize),
+    /// Fill both sides from a recent comparison's sources.
+    Pair([String; 2]),
+    /// Close the screen and edit the empty panes.
+    Dismiss,
+    Quit,
+}
+
+enum Step {
+    Menu,
+    /// Typing a file path, or a URL, for a side.
+    Prompt { side: usize, url: bool, input: String },
+    /// Choosing from the recent comparisons.
+    Recent(usize),
+}
+
+pub struct Wizard {
+    step: Step,
+    recent: Vec<[String; 2]>,
+}
+
+impl Wizard {
+    /// `recent`: source pairs of past comparisons, newest first.
+    pub fn new(recent: Vec<[String; 2]>) -> Self {
+        Self { step: Step::Menu, recent }
+    }
+
+    /// `filled`: which sides already have content. URLs and pastes go to the
+    /// first side that doesn't.
+    pub fn handle_key(&mut self, code: KeyCode, filled: [bool; 2]) -> Action {
+        let next = usize::from(filled[0]);
+        match &mut self.step {
+            Step::Menu => match code {
+                KeyCode::Char('l') => self.step = Step::Prompt { side: 0, url: false, input: String::new() },
+                KeyCode::Char('r') => self.step = Step::Prompt { side: 1, url: false, input: String::new() },
+                KeyCode::Char('u') => self.step = Step::Prompt { side: next, url: true, input: String::new() },
+                KeyCode::Char('v') => return Action::Paste(next),
+                KeyCode::Char('h') if !self.recent.is_empty() => self.step = Step::Recent(0),
+                KeyCode::Char('e') | KeyCode::Esc => return Action::Dismiss,
+                KeyCode::Char('q') => return Action::Quit,
+                _ => {}
+            },
+            Step::Prompt { side, input, .. } => match code {
+                KeyCode::Enter => {
+                    let (side, input) = (*side, input.trim().to_string());
+                    self.step = Step::Menu;
+                    if !input.is_empty() {
+                        return Action::Load(side, input);
+                    }
+                }
+                KeyCode::Esc => self.step = Step::Menu,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            },
+            Step::Recent(selected) => match code {
+                KeyCode::Up | KeyCode::Char('k') => *selected = selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => *selected = (*selected + 1).min(self.recent.len() - 1),
+                KeyCode::Enter => {
+                    let pair = self.recent[*selected].clone();
+                    self.step = Step::Menu;
+                    return Action::Pair(pair);
+                }
+                KeyCode::Esc | KeyCode::Char('h') => self.step = Step::Menu,
+                _ => {}
+            },
+        }
+        Action::None
+    }
+
+    /// `sources`: what each side was filled from so far.
+    pub fn render(&self, f: &mut Frame, area: Rect, sources: &[Option<String>; 2]) {
+        let side_line = |label: &'static str, source: &Option<String>| {
+            Line::from(vec![
+                format!("  {label}  ").bold(),
+                match source {
+                    Some(source) => format!("✓ {}", display::sanitize(source)).green(),
+                    None => "(empty)".dim(),
+                },
+            ])
+        };
+        let next = if sources[0].is_some() { "right" } else { "left" };
+        let option = |key: &'static str, text: String| Line::from(vec![format!("  [{key}]").green().bold(), format!(" {text}").into()]);
+        let mut lines = vec![
+            Line::from(" Compare two JSON or YAML documents.".bold()),
+            Line::default(),
+            side_line("Left: ", &sources[0]),
+            side_line("Right:", &sources[1]),
+            Line::default(),
+            option("l", "open a file on the left".to_string()),
+            option("r", "open a file on the right".to_string()),
+            option("u", format!("fetch a URL into the {next} side")),
+            option("v", format!("paste the clipboard into the {next} side")),
+        ];
+        if !self.recent.is_empty() {
+            lines.push(option("h", format!("recent comparisons ({})", self.recent.len())));
+        }
+        lines.push(option("e", "start with empty panes and edit them".to_string()));
+        lines.push(option("q", "quit".to_string()));
+        if let Step::Prompt { side, url, input } = &self.step {
+            let what = if *url { "URL" } else { "file" };
+            let side = if *side == 0 { "Left" } else { "Right" };
+            lines.push(Line::default());
+            lines.push(Line::from(vec![format!("  {side} {what}: ").bold(), format!("{input}▏").into()]));
+            lines.push(Line::from("  [enter] load - [esc] back".dim()));
+        }
+
+        let screen = centered_rect(area, 70, 70);
+        f.render_widget(Clear, screen);
+        f.render_widget(Paragraph::new(lines).block(Block::bordered().title("diffrs")), screen);
+
+        if let Step::Recent(selected) = self.step {
+            let popup = centered_rect(area, 80, 50);
+            f.render_widget(Clear, popup);
+            let items: Vec<String> = self
+                .recent
+                .iter()
+                .map(|[left, right]| format!("{} → {}", display::sanitize(left), display::sanitize(right)))
+                .collect();
+            let list = List::new(items)
+                .block(Block::bordered().title("Recent comparisons ([enter] load, [esc] back)"))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+            f.render_stateful_widget(list, popup, &mut ListState::default().with_selected(Some(selected)));
+        }
+    }
+}
+
+/// Tools that print the clipboard, with their arguments, in order of preference.
+pub const CLIPBOARD_TOOLS: [(&str, &[&str]); 4] = [
+    ("wl-paste", &["--no-newline"]),
+    ("xclip", &["-selection", "clipboard", "-o"]),
+    ("xsel", &["--clipboard", "--output"]),
+    ("pbpaste", &[]),
+];
+
+/// The clipboard's text, from the first clipboard tool that is installed.
+pub fn read_clipboard() -> Result<String> {
+    for (tool, args) in CLIPBOARD_TOOLS {
+        if let Ok(output) = Command::new(tool).args(args).output() {
+            if output.status.success() {
+                return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+            }
+        }
+    }
+    bail!("no clipboard tool found (wl-paste, xclip, xsel or pbpaste)")
+}